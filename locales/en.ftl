@@ -0,0 +1,142 @@
+# Messages pdl shows people, in English. Every other locale has the same
+# messages, in the same order; ones it lacks are shown in English.
+
+## Downloading
+
+fetching-feed = Fetching feed...
+fetching-url = Fetching { $url }...
+no-episodes-in-feed = No episodes found in the feed.
+no-episodes-match-filter = No episodes in the feed match the filter.
+feed-no-matching-episodes = { $show }: no matching episodes
+downloading-all = Downloading all { $count } episodes
+no-episodes-selected = No episodes selected.
+download-cancelled = Download cancelled.
+nothing-new = Nothing new to download (use --force to download again).
+already-downloaded = ⏭ Already downloaded: { $title }
+queued = ✓ Queued: { $title }
+already-queued = ⏭ Already queued: { $title }
+streaming = Streaming: { $title }
+downloading = Downloading: { $title }
+downloading-nth = [{ $n }/{ $total }] Downloading: { $title }
+saved-to = Saved to: { $path } ({ $throughput })
+download-complete = ✓ Download complete!
+episode-interrupted = ✗ Interrupted: { $title }
+episode-failed = ✗ Failed: { $title }: { $error }
+batch-summary = ✓ { $downloaded } downloaded, { $skipped } already present, { $failed } failed
+removed-stale-part = Removed stale partial download: { $path }
+checksums-written = Checksums written to: { $path }
+playlist-updated = Playlist updated: { $path }
+playing-with = Playing with { $player }...
+transcoded-to = Transcoded to: { $path }
+split-into-chapters = Split into { $count } chapters in: { $dir }
+no-chapters-to-split = No chapters to split { $path } by
+uploaded-to = Uploaded to: { $remote }
+saved-cover = Saved cover art to: { $path }
+artwork-failed = Failed to fetch artwork { $url }: { $error }
+cannot-tag = Can't tag this file type: { $path }
+tag-failed = Failed to tag { $path }: { $error }
+interrupted = Interrupted
+
+## Prompts
+
+select-episode = Select an episode to download:
+select-episodes = Select episodes to download:
+select-help = ↑↓ to move, type to filter, enter to select
+multi-select-help = ↑↓ to move, type to filter, space to toggle, → all, ← none, enter to confirm
+choose-version = Choose a version of "{ $title }":
+file-exists = { $path } already exists:
+if-exists-skip = Skip
+if-exists-overwrite = Overwrite
+if-exists-rename = Keep both (save under a new name)
+total-size = Total: { $size }
+total-size-at-least = Total: at least { $size }
+confirm-download =
+    { $count ->
+        [1] Download this episode?
+       *[other] Download these { $count } episodes?
+    }
+select-feed = Select a podcast feed:
+select-linked-feed = This page links to several feeds:
+confirm-moved = "{ $name }" has moved to { $url }. Update the subscription?
+select-podcast = Select a podcast:
+confirm-subscribe = Subscribe to { $title }?
+select-played-episode = Play which episode?
+prompt-password = Password:
+prompt-token = Token:
+
+## Subscriptions and the library
+
+subscribed = ✓ Subscribed to { $name }
+unsubscribed = ✓ Unsubscribed from { $name }
+already-subscribed = Already subscribed.
+feed-moved = ✓ "{ $name }" moved to { $url }; subscription updated
+no-feeds = No subscribed feeds.
+no-podcasts-found = No podcasts found for "{ $terms }".
+podcast-author = Author:   { $author }
+podcast-artwork = Artwork:  { $url }
+podcast-feed-url = Feed URL: { $url }
+no-history = No downloads recorded yet.
+no-matching-episodes = No matching episodes.
+queue-empty = The queue is empty.
+playing = Playing { $title } with { $player }...
+man-written = Man pages written to: { $dir }
+
+## Statistics
+
+stats-show = Show
+stats-episodes = Episodes
+stats-on-disk = On disk
+stats-size = Size
+stats-listening = Listening
+stats-oldest = Oldest
+stats-newest = Newest
+stats-total = { $shows } show(s), { $downloaded } episode(s) downloaded, { $on_disk } on disk ({ $size }), { $listening } of listening
+stats-per-month = Downloads per month:
+
+## Feed snapshots
+
+no-snapshots = No snapshots of { $feed }.
+no-episodes-changed = No episodes changed.
+snapshot-was = was: { $title }
+
+## Checking for new episodes
+
+update-no-episodes = { $name }: no episodes
+update-now-tracking = { $name }: now tracking, new episodes are listed from the next update
+update-nothing-new = { $name }: nothing new
+update-new = { $name }: { $count } new
+episode-replaced = { $name }: "{ $title }" has been replaced by the publisher ({ $sizes })
+watching = Watching subscriptions every { $interval }
+watch-new = { $name }: { $count } new episode(s)
+watch-checked = Checked { $feeds } feed(s), { $count } new episode(s)
+outside-window = Outside the download window ({ $window }); downloads wait
+
+## Syncing
+
+syncing-with = Syncing with { $server }...
+sync-subscribed = ✓ Subscribed: { $name }
+sync-unsubscribed = ✓ Unsubscribed: { $name }
+sync-summary = ✓ Synced: { $subscribed } subscribed, { $unsubscribed } unsubscribed, { $changes } feed change(s) and { $actions } episode action(s) uploaded, { $marked } episode(s) updated
+
+## Terminal UI
+
+tui-help = Enter opens a feed, Tab switches panes, / searches, q quits
+tui-keys = ↑↓ move  Tab pane  Enter open/download  / search  r reload  q quit
+tui-filter-help = Enter to keep, Esc to clear
+tui-subscriptions = Subscriptions
+tui-episodes = Episodes
+tui-episodes-loading = Episodes (loading...)
+tui-details = Details
+tui-downloads = Downloads
+tui-no-downloads = Press Enter on an episode to download it
+tui-queued = queued
+tui-feed-loaded = { $show }: { $count } episodes
+tui-downloading-again = Downloading again: { $title }
+tui-saved = ✓ Saved to: { $path }
+
+## Desktop notifications
+
+notify-downloaded = Downloaded { $title }
+notify-downloaded-count = { $count } episodes downloaded
+notify-some-failed = { $downloaded } downloaded, { $failed } failed: { $titles }
+notify-failed = pdl failed
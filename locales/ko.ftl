@@ -0,0 +1,142 @@
+# pdl이 보여 주는 메시지의 한국어 번역. en.ftl과 같은 메시지를 같은 순서로
+# 둡니다. 빠진 메시지는 영어로 나옵니다.
+
+## 다운로드
+
+fetching-feed = 피드를 가져오는 중...
+fetching-url = { $url } 가져오는 중...
+no-episodes-in-feed = 피드에 에피소드가 없습니다.
+no-episodes-match-filter = 필터에 맞는 에피소드가 없습니다.
+feed-no-matching-episodes = { $show }: 맞는 에피소드가 없습니다
+downloading-all = 에피소드 { $count }개를 모두 다운로드합니다
+no-episodes-selected = 선택한 에피소드가 없습니다.
+download-cancelled = 다운로드를 취소했습니다.
+nothing-new = 새로 받을 에피소드가 없습니다 (다시 받으려면 --force를 쓰세요).
+already-downloaded = ⏭ 이미 받음: { $title }
+queued = ✓ 대기열에 추가: { $title }
+already-queued = ⏭ 이미 대기열에 있음: { $title }
+streaming = 스트리밍: { $title }
+downloading = 다운로드 중: { $title }
+downloading-nth = [{ $n }/{ $total }] 다운로드 중: { $title }
+saved-to = 저장함: { $path } ({ $throughput })
+download-complete = ✓ 다운로드 완료!
+episode-interrupted = ✗ 중단됨: { $title }
+episode-failed = ✗ 실패: { $title }: { $error }
+batch-summary = ✓ { $downloaded }개 받음, { $skipped }개 이미 있음, { $failed }개 실패
+removed-stale-part = 오래된 미완성 다운로드를 지웠습니다: { $path }
+checksums-written = 체크섬을 기록했습니다: { $path }
+playlist-updated = 재생 목록을 업데이트했습니다: { $path }
+playing-with = { $player }(으)로 재생하는 중...
+transcoded-to = 변환했습니다: { $path }
+split-into-chapters = 챕터 { $count }개로 나눴습니다: { $dir }
+no-chapters-to-split = { $path }을(를) 나눌 챕터가 없습니다
+uploaded-to = 업로드했습니다: { $remote }
+saved-cover = 커버 아트를 저장했습니다: { $path }
+artwork-failed = 아트워크 { $url }을(를) 가져오지 못했습니다: { $error }
+cannot-tag = 태그를 쓸 수 없는 파일 형식입니다: { $path }
+tag-failed = { $path }에 태그를 쓰지 못했습니다: { $error }
+interrupted = 중단됨
+
+## 프롬프트
+
+select-episode = 다운로드할 에피소드를 고르세요:
+select-episodes = 다운로드할 에피소드들을 고르세요:
+select-help = ↑↓ 이동, 입력해서 거르기, enter 선택
+multi-select-help = ↑↓ 이동, 입력해서 거르기, space 선택/해제, → 전체, ← 해제, enter 확인
+choose-version = "{ $title }"의 버전을 고르세요:
+file-exists = { $path } 파일이 이미 있습니다:
+if-exists-skip = 건너뛰기
+if-exists-overwrite = 덮어쓰기
+if-exists-rename = 둘 다 두기 (새 이름으로 저장)
+total-size = 합계: { $size }
+total-size-at-least = 합계: { $size } 이상
+confirm-download =
+    { $count ->
+        [1] 이 에피소드를 다운로드할까요?
+       *[other] 에피소드 { $count }개를 다운로드할까요?
+    }
+select-feed = 팟캐스트 피드를 고르세요:
+select-linked-feed = 이 페이지에는 피드가 여러 개 있습니다:
+confirm-moved = "{ $name }"이(가) { $url }(으)로 옮겨졌습니다. 구독을 바꿀까요?
+select-podcast = 팟캐스트를 고르세요:
+confirm-subscribe = { $title }을(를) 구독할까요?
+select-played-episode = 어떤 에피소드를 재생할까요?
+prompt-password = 비밀번호:
+prompt-token = 토큰:
+
+## 구독과 라이브러리
+
+subscribed = ✓ { $name } 구독함
+unsubscribed = ✓ { $name } 구독 해지함
+already-subscribed = 이미 구독 중입니다.
+feed-moved = ✓ "{ $name }"이(가) { $url }(으)로 옮겨져 구독을 바꿨습니다
+no-feeds = 구독한 피드가 없습니다.
+no-podcasts-found = "{ $terms }"에 해당하는 팟캐스트가 없습니다.
+podcast-author = 제작자:   { $author }
+podcast-artwork = 아트워크: { $url }
+podcast-feed-url = 피드 URL: { $url }
+no-history = 아직 다운로드 기록이 없습니다.
+no-matching-episodes = 맞는 에피소드가 없습니다.
+queue-empty = 대기열이 비어 있습니다.
+playing = { $player }(으)로 { $title } 재생 중...
+man-written = man 페이지를 저장했습니다: { $dir }
+
+## 통계
+
+stats-show = 프로그램
+stats-episodes = 에피소드
+stats-on-disk = 디스크
+stats-size = 크기
+stats-listening = 재생 시간
+stats-oldest = 최초
+stats-newest = 최신
+stats-total = 프로그램 { $shows }개, 에피소드 { $downloaded }개 다운로드, 디스크에 { $on_disk }개({ $size }), 재생 시간 { $listening }
+stats-per-month = 월별 다운로드:
+
+## 피드 스냅숏
+
+no-snapshots = { $feed }의 스냅숏이 없습니다.
+no-episodes-changed = 바뀐 에피소드가 없습니다.
+snapshot-was = 이전: { $title }
+
+## 새 에피소드 확인
+
+update-no-episodes = { $name }: 에피소드가 없습니다
+update-now-tracking = { $name }: 추적을 시작했습니다. 새 에피소드는 다음 업데이트부터 보여 줍니다
+update-nothing-new = { $name }: 새 에피소드 없음
+update-new = { $name }: 새 에피소드 { $count }개
+episode-replaced = { $name }: 게시자가 "{ $title }"을(를) 바꿨습니다 ({ $sizes })
+watching = { $interval }마다 구독을 확인합니다
+watch-new = { $name }: 새 에피소드 { $count }개
+watch-checked = 피드 { $feeds }개를 확인했습니다. 새 에피소드 { $count }개
+outside-window = 다운로드 시간대({ $window })가 아닙니다. 다운로드는 기다립니다
+
+## 동기화
+
+syncing-with = { $server }와(과) 동기화하는 중...
+sync-subscribed = ✓ 구독했습니다: { $name }
+sync-unsubscribed = ✓ 구독을 취소했습니다: { $name }
+sync-summary = ✓ 동기화했습니다: 구독 { $subscribed }개, 구독 취소 { $unsubscribed }개, 피드 변경 { $changes }개와 에피소드 기록 { $actions }개 업로드, 에피소드 { $marked }개 업데이트
+
+## 터미널 UI
+
+tui-help = Enter 피드 열기, Tab 창 바꾸기, / 검색, q 종료
+tui-keys = ↑↓ 이동  Tab 창  Enter 열기/다운로드  / 검색  r 새로 고침  q 종료
+tui-filter-help = Enter 유지, Esc 지우기
+tui-subscriptions = 구독
+tui-episodes = 에피소드
+tui-episodes-loading = 에피소드 (불러오는 중...)
+tui-details = 자세히
+tui-downloads = 다운로드
+tui-no-downloads = 에피소드에서 Enter를 누르면 다운로드합니다
+tui-queued = 대기 중
+tui-feed-loaded = { $show }: 에피소드 { $count }개
+tui-downloading-again = 다시 다운로드: { $title }
+tui-saved = ✓ 저장했습니다: { $path }
+
+## 데스크톱 알림
+
+notify-downloaded = 다운로드했습니다: { $title }
+notify-downloaded-count = 에피소드 { $count }개를 다운로드했습니다
+notify-some-failed = { $downloaded }개 다운로드, { $failed }개 실패: { $titles }
+notify-failed = pdl 실패
@@ -0,0 +1,258 @@
+use crate::player::on_path;
+use anyhow::{Context, Result, bail};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// What fetches episode media: pdl itself, or an external program
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Downloader {
+    /// pdl's own HTTP client
+    #[default]
+    Builtin,
+    /// aria2c, which also splits files over several connections (`--segments`)
+    Aria2,
+    /// curl
+    Curl,
+}
+
+impl Downloader {
+    /// The program this downloader runs, if it isn't pdl's own
+    pub fn program(self) -> Option<&'static str> {
+        match self {
+            Downloader::Builtin => None,
+            Downloader::Aria2 => Some("aria2c"),
+            Downloader::Curl => Some("curl"),
+        }
+    }
+}
+
+/// A file transfer handed to a [`Backend`]
+#[derive(Debug, Clone, Copy)]
+pub struct Fetch<'a> {
+    /// Where the media is, after redirects
+    pub url: &'a str,
+    /// File to download into, carrying on from any bytes already in it
+    pub path: &'a Path,
+    /// Headers the request needs, e.g. `Authorization` for a private feed
+    pub headers: &'a [(String, String)],
+    /// Speed cap in bytes per second
+    pub rate_limit: Option<u64>,
+    /// Connections to use, for backends that can use several
+    pub segments: u16,
+}
+
+/// Fetches episode media in place of pdl's own HTTP client. pdl still makes
+/// the first request, to name the file and catch web pages sent instead of
+/// media, and checks the finished file before moving it into place.
+pub trait Backend: fmt::Debug + Send + Sync {
+    /// Download `fetch.url` into `fetch.path`
+    fn fetch<'a>(&'a self, fetch: Fetch<'a>) -> BoxFuture<'a, Result<()>>;
+}
+
+/// aria2c or curl, run once per download
+#[derive(Debug, Clone)]
+pub struct External {
+    downloader: Downloader,
+    program: PathBuf,
+    user_agent: Option<String>,
+    /// Sent with every request, like `--header` is by pdl's own client
+    headers: Vec<(String, String)>,
+    proxy: Option<String>,
+}
+
+impl External {
+    /// `downloader`'s program, if it's installed
+    pub fn find(downloader: Downloader) -> Option<Self> {
+        Some(Self {
+            downloader,
+            program: on_path(downloader.program()?)?,
+            user_agent: None,
+            headers: Vec::new(),
+            proxy: None,
+        })
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_proxy(mut self, proxy: &str) -> Self {
+        self.proxy = Some(proxy.to_string());
+        self
+    }
+
+    fn args(&self, fetch: &Fetch) -> Vec<OsString> {
+        let headers = self.headers.iter().chain(fetch.headers);
+        let mut args: Vec<OsString> = Vec::new();
+        match self.downloader {
+            Downloader::Aria2 => {
+                let dir = fetch.path.parent().unwrap_or(Path::new("."));
+                let name = fetch.path.file_name().unwrap_or_default();
+                args.extend(
+                    [
+                        "--continue=true",
+                        "--allow-overwrite=true",
+                        "--auto-file-renaming=false",
+                        "--file-allocation=none",
+                        "--console-log-level=error",
+                        "--summary-interval=0",
+                        "--download-result=hide",
+                    ]
+                    .map(Into::into),
+                );
+                let connections = fetch.segments.max(1);
+                args.push(format!("--max-connection-per-server={}", connections).into());
+                args.push(format!("--split={}", connections).into());
+                if let Some(rate) = fetch.rate_limit {
+                    args.push(format!("--max-overall-download-limit={}", rate).into());
+                }
+                if let Some(user_agent) = &self.user_agent {
+                    args.push(format!("--user-agent={}", user_agent).into());
+                }
+                for (name, value) in headers {
+                    args.push(format!("--header={}: {}", name, value).into());
+                }
+                if let Some(proxy) = &self.proxy {
+                    args.push(format!("--all-proxy={}", proxy).into());
+                }
+                args.push("--dir".into());
+                args.push(dir.into());
+                args.push("--out".into());
+                args.push(name.into());
+            }
+            Downloader::Curl | Downloader::Builtin => {
+                args.extend(
+                    [
+                        "--fail",
+                        "--location",
+                        "--silent",
+                        "--show-error",
+                        "--continue-at",
+                        "-",
+                    ]
+                    .map(Into::into),
+                );
+                if let Some(rate) = fetch.rate_limit {
+                    args.extend(["--limit-rate".into(), rate.to_string().into()]);
+                }
+                if let Some(user_agent) = &self.user_agent {
+                    args.extend(["--user-agent".into(), user_agent.into()]);
+                }
+                for (name, value) in headers {
+                    args.extend(["--header".into(), format!("{}: {}", name, value).into()]);
+                }
+                if let Some(proxy) = &self.proxy {
+                    args.extend(["--proxy".into(), proxy.into()]);
+                }
+                args.push("--output".into());
+                args.push(fetch.path.into());
+            }
+        }
+        args.push(fetch.url.into());
+        args
+    }
+}
+
+impl Backend for External {
+    fn fetch<'a>(&'a self, fetch: Fetch<'a>) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let output = Command::new(&self.program)
+                .args(self.args(&fetch))
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output()
+                .await
+                .with_context(|| format!("Failed to run {}", self.program.display()))?;
+            if !output.status.success() {
+                // aria2c reports on stdout, curl on stderr
+                let message = [output.stderr, output.stdout]
+                    .iter()
+                    .map(|out| String::from_utf8_lossy(out).trim().to_string())
+                    .find(|out| !out.is_empty())
+                    .unwrap_or_default();
+                bail!(
+                    "{} exited with {}: {}",
+                    self.downloader.program().unwrap_or_default(),
+                    output.status,
+                    message
+                );
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn external(downloader: Downloader) -> External {
+        External {
+            downloader,
+            program: PathBuf::from(downloader.program().unwrap()),
+            user_agent: Some("pdl/1".to_string()),
+            headers: vec![("Referer".to_string(), "https://example.com".to_string())],
+            proxy: None,
+        }
+    }
+
+    fn args(external: &External, fetch: &Fetch) -> Vec<String> {
+        external
+            .args(fetch)
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_external_args() {
+        let auth = [("authorization".to_string(), "Bearer t".to_string())];
+        let fetch = Fetch {
+            url: "https://cdn.example.com/ep.mp3",
+            path: Path::new("Show/Ep 1.mp3.part"),
+            headers: &auth,
+            rate_limit: Some(512000),
+            segments: 4,
+        };
+
+        let aria2 = args(&external(Downloader::Aria2), &fetch);
+        for arg in [
+            "--continue=true",
+            "--split=4",
+            "--max-overall-download-limit=512000",
+            "--user-agent=pdl/1",
+            "--header=Referer: https://example.com",
+            "--header=authorization: Bearer t",
+        ] {
+            assert!(aria2.contains(&arg.to_string()), "{}", arg);
+        }
+        assert_eq!(
+            aria2[aria2.len() - 5..],
+            [
+                "--dir",
+                "Show",
+                "--out",
+                "Ep 1.mp3.part",
+                "https://cdn.example.com/ep.mp3"
+            ]
+        );
+
+        let curl = args(&external(Downloader::Curl), &fetch).join(" ");
+        assert!(curl.starts_with("--fail --location --silent --show-error --continue-at -"));
+        assert!(curl.contains("--limit-rate 512000"));
+        assert!(curl.contains("--header authorization: Bearer t"));
+        assert!(curl.ends_with("--output Show/Ep 1.mp3.part https://cdn.example.com/ep.mp3"));
+    }
+}
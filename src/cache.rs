@@ -0,0 +1,113 @@
+use crate::config;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// A feed document as last fetched, with the validators the server sent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedFeed {
+    #[serde(skip)]
+    pub content: Vec<u8>,
+    pub content_type: Option<String>,
+    /// `ETag` response header, sent back as `If-None-Match`
+    pub etag: Option<String>,
+    /// `Last-Modified` response header, sent back as `If-Modified-Since`
+    pub last_modified: Option<String>,
+}
+
+impl CachedFeed {
+    /// Whether the server gave anything to revalidate the copy with
+    pub fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+/// On-disk copies of fetched feeds, so unchanged feeds can be refreshed
+/// with a conditional GET and a `304 Not Modified` instead of a full download
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedCache {
+    dir: PathBuf,
+}
+
+impl FeedCache {
+    /// Keep cached feeds in `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The cache under the data directory (`<data dir>/pdl/feeds`)
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(config::data_dir()?.join("feeds")))
+    }
+
+    /// Files holding the feed body and its validators, named by a hash of the URL
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = url_key(url);
+        (
+            self.dir.join(format!("{}.feed", key)),
+            self.dir.join(format!("{}.toml", key)),
+        )
+    }
+
+    /// The cached copy of the feed at `url`, if there is a readable one
+    pub async fn load(&self, url: &str) -> Option<CachedFeed> {
+        let (body, meta) = self.paths(url);
+        let meta = tokio::fs::read_to_string(meta).await.ok()?;
+        let mut cached: CachedFeed = toml::from_str(&meta).ok()?;
+        cached.content = tokio::fs::read(body).await.ok()?;
+        Some(cached)
+    }
+
+    /// Save `cached` as the copy of the feed at `url`
+    pub async fn store(&self, url: &str, cached: &CachedFeed) -> Result<()> {
+        let (body, meta) = self.paths(url);
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .context("Failed to create feed cache directory")?;
+        let content = toml::to_string(cached).context("Failed to serialize feed cache entry")?;
+        // Body first: validators without their body are never trusted
+        write(&body, &cached.content).await?;
+        write(&meta, content.as_bytes()).await
+    }
+}
+
+/// File name safe stand-in for a feed URL: the start of its SHA-256
+pub(crate) fn url_key(url: &str) -> String {
+    let mut key = format!("{:x}", Sha256::digest(url.as_bytes()));
+    key.truncate(32);
+    key
+}
+
+async fn write(path: &Path, content: &[u8]) -> Result<()> {
+    tokio::fs::write(path, content)
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_store_and_load() {
+        let dir = std::env::temp_dir().join(format!("pdl-feed-cache-{}", std::process::id()));
+        let cache = FeedCache::new(&dir);
+        let url = "https://example.com/feed.rss";
+        assert_eq!(cache.load(url).await, None);
+
+        let cached = CachedFeed {
+            content: b"<rss/>".to_vec(),
+            content_type: Some("application/rss+xml".to_string()),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+        };
+        cache.store(url, &cached).await.unwrap();
+        let loaded = cache.load(url).await;
+        let other = cache.load("https://example.com/other.rss").await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded, Some(cached));
+        assert_eq!(other, None);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::client::Http;
+use reqwest::header::{CONTENT_LENGTH, ETAG};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::debug;
+
+/// How much a downloaded episode's size may change, in percent, before it
+/// counts as replaced: dynamically inserted ads make it vary a little
+/// without the episode itself changing
+pub const DEFAULT_TOLERANCE: u8 = 10;
+
+/// How long to wait for a server to answer an ETag check before moving on
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What `pdl update` does about a downloaded episode whose publisher has
+/// since replaced its audio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RefreshChanged {
+    /// Don't check
+    Ignore,
+    /// Report it
+    #[default]
+    Flag,
+    /// Download it again over the old file
+    Redownload,
+}
+
+/// What the history knows of a downloaded episode's media, to tell whether
+/// it has been replaced since
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordedMedia {
+    /// Size in bytes the feed (or, failing that, the server) gave for it
+    pub length: Option<u64>,
+    /// The server's ETag for it, once checked
+    pub etag: Option<String>,
+}
+
+/// What a server says about an episode's media when asked with `HEAD`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Probe {
+    pub length: Option<u64>,
+    pub etag: Option<String>,
+}
+
+/// Whether a size of `now` bytes differs from `was` by more than
+/// `tolerance` percent
+pub fn size_changed(was: u64, now: u64, tolerance: u8) -> bool {
+    u128::from(was.abs_diff(now)) * 100 > u128::from(was) * u128::from(tolerance)
+}
+
+/// The size and ETag the server gives for `url`, if it answers
+pub(crate) async fn probe(client: &Http, url: &str) -> Option<Probe> {
+    let response = match client.head(url).timeout(HEAD_TIMEOUT).send().await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            debug!("HEAD {} returned {}", url, response.status());
+            return None;
+        }
+        Err(e) => {
+            debug!("HEAD {} failed: {}", url, e);
+            return None;
+        }
+    };
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    Some(Probe {
+        // `Response::content_length` is the body's length, which is 0 for HEAD
+        length: header(CONTENT_LENGTH).and_then(|len| len.parse().ok()),
+        etag: header(ETAG),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_changed() {
+        let mb = 1_000_000;
+        // A minute of ads more or less in an hour-long episode
+        assert!(!size_changed(57 * mb, 58 * mb, DEFAULT_TOLERANCE));
+        assert!(!size_changed(57 * mb, 57 * mb, 0));
+        assert!(size_changed(57 * mb, 30 * mb, DEFAULT_TOLERANCE));
+        assert!(size_changed(57 * mb, 80 * mb, DEFAULT_TOLERANCE));
+        assert!(size_changed(0, 1, DEFAULT_TOLERANCE));
+        assert!(!size_changed(u64::MAX, u64::MAX - 1, 1));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::client::Http;
+use crate::download::sanitize_filename;
+use crate::retry::check_response;
+use crate::transcode::check_ffmpeg;
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::fs;
+use tokio::process::Command;
+
+/// A titled section of an episode
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub start: Duration,
+    /// Where the chapter ends; the next chapter's start (or the end of the
+    /// file) if not given
+    pub end: Option<Duration>,
+    pub title: String,
+}
+
+/// A `podcast:chapters` JSON document
+#[derive(Deserialize)]
+struct ChaptersDocument {
+    chapters: Vec<JsonChapter>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonChapter {
+    start_time: f64,
+    end_time: Option<f64>,
+    title: Option<String>,
+    /// `false` marks a chapter kept out of the table of contents, e.g. one
+    /// only there to change the artwork
+    toc: Option<bool>,
+}
+
+/// Parse a `podcast:chapters` JSON document
+pub fn parse_json(json: &[u8]) -> Result<Vec<Chapter>> {
+    let document: ChaptersDocument =
+        serde_json::from_slice(json).context("Failed to parse chapters")?;
+    let seconds = |s: f64| Duration::try_from_secs_f64(s).ok();
+    Ok(document
+        .chapters
+        .into_iter()
+        .filter(|chapter| chapter.toc != Some(false))
+        .filter_map(|chapter| {
+            Some(Chapter {
+                start: seconds(chapter.start_time)?,
+                end: chapter.end_time.and_then(seconds),
+                title: chapter.title.unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+/// Chapters embedded in an mp3's ID3 tag (`CHAP` frames), in order
+pub fn read_id3(path: &Path) -> Result<Vec<Chapter>> {
+    let tag = match id3::Tag::read_from_path(path) {
+        Ok(tag) => tag,
+        Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let mut chapters: Vec<Chapter> = tag
+        .chapters()
+        .map(|chapter| Chapter {
+            start: Duration::from_millis(chapter.start_time.into()),
+            end: Some(Duration::from_millis(chapter.end_time.into())),
+            title: chapter
+                .frames
+                .iter()
+                .find(|frame| frame.id() == "TIT2")
+                .and_then(|frame| frame.content().text())
+                .unwrap_or_default()
+                .to_string(),
+        })
+        .collect();
+    chapters.sort_by_key(|chapter| chapter.start);
+    Ok(chapters)
+}
+
+/// Fetch the `podcast:chapters` document at `url`
+pub(crate) async fn fetch_chapters(client: &Http, url: &str) -> Result<Vec<Chapter>> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .context("Failed to fetch chapters")?;
+    let bytes = check_response(response)
+        .context("Failed to fetch chapters")?
+        .bytes()
+        .await
+        .context("Failed to read chapters")?;
+    parse_json(&bytes)
+}
+
+/// Folder the chapters of `audio` are split into: next to it, named after it
+pub fn split_dir(audio: &Path) -> PathBuf {
+    audio.with_extension("")
+}
+
+/// File name for chapter `index` (from 0) of `count`, e.g. `01 - Intro.mp3`
+fn chapter_file_name(index: usize, count: usize, title: &str, extension: &str) -> String {
+    let width = count.to_string().len().max(2);
+    let title = sanitize_filename(title);
+    if title.is_empty() {
+        format!("{:0width$}.{}", index + 1, extension)
+    } else {
+        format!("{:0width$} - {}.{}", index + 1, title, extension)
+    }
+}
+
+/// Cut `audio` into one file per chapter with ffmpeg, without re-encoding,
+/// into [`split_dir`]. Returns the files written.
+pub async fn split(audio: &Path, chapters: &[Chapter]) -> Result<Vec<PathBuf>> {
+    if chapters.is_empty() {
+        bail!("{} has no chapters", audio.display());
+    }
+    check_ffmpeg("--split-chapters").await?;
+    let dir = split_dir(audio);
+    fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    let extension = audio
+        .extension()
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut files = Vec::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        let end = chapter.end.or_else(|| chapters.get(i + 1).map(|c| c.start));
+        let output = dir.join(chapter_file_name(
+            i,
+            chapters.len(),
+            &chapter.title,
+            &extension,
+        ));
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-ss"])
+            .arg(format!("{:.3}", chapter.start.as_secs_f64()))
+            .arg("-i")
+            .arg(audio);
+        if let Some(end) = end.filter(|end| *end > chapter.start) {
+            command
+                .arg("-t")
+                .arg(format!("{:.3}", (end - chapter.start).as_secs_f64()));
+        }
+        let result = command
+            .args(["-map", "0:a", "-c", "copy", "-map_metadata", "0"])
+            .arg("-metadata")
+            .arg(format!("title={}", chapter.title))
+            .arg("-metadata")
+            .arg(format!("track={}/{}", i + 1, chapters.len()))
+            .arg(&output)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .context("Failed to run ffmpeg")?;
+        if !result.status.success() {
+            let _ = fs::remove_file(&output).await;
+            bail!(
+                "ffmpeg failed to cut chapter {:?} from {}: {}",
+                chapter.title,
+                audio.display(),
+                String::from_utf8_lossy(&result.stderr).trim()
+            );
+        }
+        files.push(output);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_chapters() {
+        let json = br#"{
+            "version": "1.2.0",
+            "chapters": [
+                {"startTime": 0, "title": "Intro"},
+                {"startTime": 65.5, "title": "Artwork only", "toc": false},
+                {"startTime": 90, "endTime": 1800, "title": "Interview: Part 1"}
+            ]
+        }"#;
+        let chapters = parse_json(json).unwrap();
+        assert_eq!(
+            chapters,
+            vec![
+                Chapter {
+                    start: Duration::ZERO,
+                    end: None,
+                    title: "Intro".to_string(),
+                },
+                Chapter {
+                    start: Duration::from_secs(90),
+                    end: Some(Duration::from_secs(1800)),
+                    title: "Interview: Part 1".to_string(),
+                },
+            ]
+        );
+        assert!(parse_json(b"{}").is_err());
+
+        assert_eq!(
+            chapter_file_name(1, 2, &chapters[1].title, "mp3"),
+            "02 - Interview- Part 1.mp3"
+        );
+        assert_eq!(chapter_file_name(0, 120, "", "m4a"), "001.m4a");
+        assert_eq!(
+            split_dir(Path::new("Show/Ep 1.mp3")),
+            Path::new("Show/Ep 1")
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File};
+use tokio::io::AsyncReadExt;
+
+/// Name of the per-show file written by `--checksums`, in `sha256sum` format
+pub const SUMS_FILE: &str = "sha256sums";
+
+/// Digest algorithms a feed can publish for an enclosure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Parse a `<media:hash algo="...">` value (Media RSS defaults to md5)
+    pub fn from_media_algo(algo: &str) -> Option<Self> {
+        match algo.trim().to_ascii_lowercase().as_str() {
+            "" | "md5" => Some(Self::Md5),
+            "sha-1" | "sha1" => Some(Self::Sha1),
+            "sha-256" | "sha256" => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+}
+
+/// Expected digest of an episode's media file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: HashAlgorithm,
+    /// Lowercase hex digest
+    pub hex: String,
+}
+
+/// A downloaded file didn't match the checksum published in the feed
+#[derive(Debug)]
+pub struct ChecksumMismatch {
+    pub path: PathBuf,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Checksum mismatch for {}: expected {}, got {}",
+            self.path.display(),
+            self.expected,
+            self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+enum Hasher {
+    Md5(Md5),
+    Sha1(sha1_smol::Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Md5 => Self::Md5(Md5::new()),
+            HashAlgorithm::Sha1 => Self::Sha1(sha1_smol::Sha1::new()),
+            HashAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Md5(h) => h.update(data),
+            Self::Sha1(h) => h.update(data),
+            Self::Sha256(h) => h.update(data),
+        }
+    }
+
+    fn hex(self) -> String {
+        match self {
+            Self::Md5(h) => format!("{:x}", h.finalize()),
+            Self::Sha1(h) => h.digest().to_string(),
+            Self::Sha256(h) => format!("{:x}", h.finalize()),
+        }
+    }
+}
+
+/// Hex digest of the file at `path`
+pub async fn file_digest(path: &Path, algorithm: HashAlgorithm) -> Result<String> {
+    let mut file = File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.hex())
+}
+
+/// Check the file at `path` against `expected`
+pub async fn verify(path: &Path, expected: &Checksum) -> Result<()> {
+    let actual = file_digest(path, expected.algorithm).await?;
+    if !actual.eq_ignore_ascii_case(&expected.hex) {
+        return Err(ChecksumMismatch {
+            path: path.to_path_buf(),
+            expected: expected.hex.clone(),
+            actual,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Add or refresh the SHA-256 lines for `files` in `dir`'s `sha256sums`
+pub async fn update_sums_file(dir: &Path, files: &[PathBuf]) -> Result<PathBuf> {
+    let sums_path = dir.join(SUMS_FILE);
+    let existing = match fs::read_to_string(&sums_path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context("Failed to read checksums file"),
+    };
+
+    let mut sums = parse_sums(&existing);
+    for file in files {
+        let Some(name) = file.file_name() else {
+            continue;
+        };
+        let digest = file_digest(file, HashAlgorithm::Sha256).await?;
+        sums.insert(name.to_string_lossy().into_owned(), digest);
+    }
+
+    fs::write(&sums_path, format_sums(&sums))
+        .await
+        .context("Failed to write checksums file")?;
+    Ok(sums_path)
+}
+
+/// Parse `sha256sum` output into file name → digest
+fn parse_sums(content: &str) -> BTreeMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (digest, name) = line.split_once("  ")?;
+            Some((name.to_string(), digest.to_string()))
+        })
+        .collect()
+}
+
+fn format_sums(sums: &BTreeMap<String, String>) -> String {
+    sums.iter()
+        .map(|(name, digest)| format!("{}  {}\n", digest, name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_media_algo() {
+        assert_eq!(HashAlgorithm::from_media_algo(""), Some(HashAlgorithm::Md5));
+        assert_eq!(
+            HashAlgorithm::from_media_algo("SHA-1"),
+            Some(HashAlgorithm::Sha1)
+        );
+        assert_eq!(
+            HashAlgorithm::from_media_algo("sha256"),
+            Some(HashAlgorithm::Sha256)
+        );
+        assert_eq!(HashAlgorithm::from_media_algo("crc32"), None);
+    }
+
+    #[tokio::test]
+    async fn test_file_digest_and_verify() {
+        let path = std::env::temp_dir().join(format!("pdl-checksum-{}", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+
+        assert_eq!(
+            file_digest(&path, HashAlgorithm::Md5).await.unwrap(),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            file_digest(&path, HashAlgorithm::Sha1).await.unwrap(),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(
+            file_digest(&path, HashAlgorithm::Sha256).await.unwrap(),
+            sha256
+        );
+
+        let good = Checksum {
+            algorithm: HashAlgorithm::Sha256,
+            hex: sha256.to_uppercase(),
+        };
+        assert!(verify(&path, &good).await.is_ok());
+        let bad = Checksum {
+            algorithm: HashAlgorithm::Sha256,
+            hex: "00".repeat(32),
+        };
+        assert!(
+            verify(&path, &bad)
+                .await
+                .unwrap_err()
+                .is::<ChecksumMismatch>()
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sums_roundtrip_sorted_by_name() {
+        let sums = parse_sums("bbb  b.mp3\naaa  a file.mp3\nnot a sum line\n");
+
+        assert_eq!(format_sums(&sums), "aaa  a file.mp3\nbbb  b.mp3\n");
+    }
+}
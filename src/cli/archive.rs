@@ -0,0 +1,56 @@
+use super::download::download_archive;
+use super::feeds;
+use super::output;
+use anyhow::Result;
+use chrono::Utc;
+use pdl::config::Config;
+use pdl::download::show_dir;
+use pdl::snapshot::Snapshots;
+use pdl::{Episode, PodcastClient};
+use std::path::PathBuf;
+use tracing::info;
+
+/// Folder in the archive holding the feed snapshots
+const SNAPSHOT_DIR: &str = "feed";
+
+/// Bring the archive of the show at `url` up to date: snapshot the feed if
+/// it changed since the last run, then download every episode not in the
+/// archive yet. Paged feeds are followed to their oldest episode, though
+/// only the first page is snapshotted.
+pub async fn archive(
+    client: &PodcastClient,
+    config: &Config,
+    url: &str,
+    output_dir: Option<PathBuf>,
+    jobs: Option<u16>,
+) -> Result<()> {
+    let interactive = output::interactive();
+    info!("Fetching feed...");
+    let (url, document, feed) =
+        feeds::fetch_feed_document(client, url, usize::MAX, interactive).await?;
+
+    let dir = match output_dir {
+        Some(dir) => dir,
+        None => show_dir(&config.download_dir(), &feed.title),
+    };
+    let snapshots = Snapshots::new(dir.join(SNAPSHOT_DIR));
+    match snapshots.save(&document.content, Utc::now()).await? {
+        Some(path) => info!("Saved feed snapshot: {}", path.display()),
+        None => info!("Feed unchanged since the last snapshot"),
+    }
+
+    // Files are never replaced, so what is already there is done
+    let pending: Vec<&Episode> = feed
+        .episodes
+        .iter()
+        .filter(|episode| !client.planned_path(episode, &dir).exists())
+        .collect();
+    info!(
+        "{} episodes in the feed, {} not archived yet",
+        feed.episodes.len(),
+        pending.len()
+    );
+
+    let jobs = jobs.or(config.jobs).unwrap_or(1);
+    download_archive(client, &url, &feed, dir, &pending, jobs).await
+}
@@ -0,0 +1,99 @@
+use super::output;
+use anyhow::{Context, Result, bail};
+use inquire::{Password, PasswordDisplayMode};
+use pdl::auth::Credentials;
+use pdl::credentials::{CredentialStore, LoginKind};
+use pdl::tr;
+use serde_json::json;
+use std::io::{self, BufRead, IsTerminal};
+
+/// Save a login for `url` in the keyring: a user name and password, or a
+/// bearer token with `token`
+pub async fn add(url: &str, user: Option<String>, token: bool) -> Result<()> {
+    let credentials = match user {
+        Some(username) if !token => Credentials::Basic {
+            username,
+            password: Some(read_secret(&tr!("prompt-password"))?),
+        },
+        _ => Credentials::Bearer(read_secret(&tr!("prompt-token"))?),
+    };
+
+    let owned = url.to_string();
+    keyring(move || {
+        let mut store = CredentialStore::load()?;
+        store.add(&owned, &credentials)?;
+        store.save()
+    })
+    .await?;
+
+    if output::json() {
+        return output::emit(&json!({ "added": url }));
+    }
+    println!("✓ Saved credentials for {} in the system keyring", url);
+    Ok(())
+}
+
+pub async fn remove(url: &str) -> Result<()> {
+    let owned = url.to_string();
+    keyring(move || {
+        let mut store = CredentialStore::load()?;
+        store.remove(&owned)?;
+        store.save()
+    })
+    .await?;
+
+    if output::json() {
+        return output::emit(&json!({ "removed": url }));
+    }
+    println!("✓ Removed credentials for {}", url);
+    Ok(())
+}
+
+/// List the feeds with stored logins; secrets stay in the keyring
+pub fn list() -> Result<()> {
+    let store = CredentialStore::load()?;
+    if output::json() {
+        return output::emit(&store.feeds);
+    }
+    if store.feeds.is_empty() {
+        println!("No stored credentials.");
+        return Ok(());
+    }
+
+    for login in &store.feeds {
+        match (login.kind, &login.username) {
+            (LoginKind::Basic, Some(username)) => println!("{}\n  user {}", login.url, username),
+            _ => println!("{}\n  bearer token", login.url),
+        }
+    }
+    Ok(())
+}
+
+/// Run keyring work off the async runtime; some backends start their own
+async fn keyring(work: impl FnOnce() -> Result<()> + Send + 'static) -> Result<()> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .context("Keyring access failed")?
+}
+
+/// Prompt for a secret without echoing it, or read it from stdin when piped
+fn read_secret(prompt: &str) -> Result<String> {
+    let secret = if io::stdin().is_terminal() && !output::json() {
+        Password::new(prompt)
+            .with_display_mode(PasswordDisplayMode::Masked)
+            .without_confirmation()
+            .prompt()
+            .context("Failed to read secret")?
+    } else {
+        let mut line = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .context("Failed to read secret from stdin")?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    };
+    if secret.is_empty() {
+        bail!("No secret given");
+    }
+    Ok(secret)
+}
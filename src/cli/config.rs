@@ -0,0 +1,95 @@
+use super::output;
+use anyhow::{Context, Result, bail};
+use pdl::config::Config;
+use serde_json::json;
+use std::env;
+use std::fs;
+use std::process::Command;
+
+/// Written by `pdl config edit` when there's no config file yet
+const CONFIG_TEMPLATE: &str = r#"# pdl configuration. Command-line flags override these settings, and
+# PDL_* environment variables (e.g. PDL_JOBS) sit in between.
+
+# download_dir = "~/Podcasts"
+# default_feed = "https://example.com/podcast.rss"
+# episodes = 10
+# jobs = 1
+# proxy = "http://proxy.example.com:8080"
+# filename_template = "{date} {title}"
+
+# Settings for `pdl --profile car`, in place of the ones above
+# [profiles.car]
+# download_dir = "/media/usb/Podcasts"
+# transcode = "mp3:96k"
+# keep_latest = 3
+"#;
+
+pub fn show_path() -> Result<()> {
+    let path = Config::path()?;
+    if output::json() {
+        return output::emit(&json!({ "path": path }));
+    }
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Print one setting as pdl sees it (config file, `profile`'s settings and
+/// `PDL_*` variables)
+pub fn get(key: &str, profile: Option<&str>) -> Result<()> {
+    let Some(value) = Config::load(profile)?.get(key)? else {
+        bail!("{} is not set", key);
+    };
+    if output::json() {
+        return output::emit(&json!({ "key": key, "value": value }));
+    }
+    println!("{}", value);
+    Ok(())
+}
+
+/// Change one setting in the config file, or in `profile`'s section of it
+pub fn set(key: &str, value: &str, profile: Option<&str>) -> Result<()> {
+    Config::set(key, value, profile)?;
+    if output::json() {
+        return output::emit(&json!({ "key": key, "value": value }));
+    }
+    println!("✓ Set {} = {}", key, value);
+    Ok(())
+}
+
+/// Open the config file in `$VISUAL`/`$EDITOR`, creating it first if needed
+pub fn edit(profile: Option<&str>) -> Result<()> {
+    let path = Config::path()?;
+    if !path.exists() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Failed to create config directory")?;
+        }
+        fs::write(&path, CONFIG_TEMPLATE)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| default_editor().to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().context("$EDITOR is empty")?;
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to start editor {:?}", program))?;
+    if !status.success() {
+        bail!("Editor exited with {}", status);
+    }
+
+    // Catch mistakes now rather than on the next download
+    Config::load(profile).context("The config file has errors")?;
+    if output::json() {
+        output::emit(&json!({ "path": path }))?;
+    }
+    Ok(())
+}
+
+fn default_editor() -> &'static str {
+    if cfg!(windows) { "notepad" } else { "vi" }
+}
@@ -0,0 +1,117 @@
+use super::output;
+use anyhow::Result;
+use indicatif::HumanBytes;
+use pdl::dedupe::{self, IdenticalFiles, LibraryFile};
+use pdl::history::History;
+use pdl::library::{EpisodeState, LibraryQuery};
+use serde::Serialize;
+use tracing::{info, warn};
+
+/// What `pdl dedupe` finds, as printed with `--json`
+#[derive(Serialize)]
+struct Report {
+    identical: Vec<IdenticalFiles>,
+    similar: Vec<Vec<LibraryFile>>,
+    /// Files replaced by hard links with `--link`
+    linked: usize,
+    /// Bytes that freed
+    freed: u64,
+}
+
+/// Report downloads with identical files and episodes cross-posted to
+/// several shows, and with `link`, replace identical copies with hard links
+/// to one of them
+pub async fn dedupe(link: bool) -> Result<()> {
+    let episodes = History::open_default()?.query(&LibraryQuery {
+        state: Some(EpisodeState::Downloaded),
+        ..Default::default()
+    })?;
+    let mut report = Report {
+        identical: dedupe::identical_files(&episodes).await?,
+        similar: dedupe::similar_titles(&episodes),
+        linked: 0,
+        freed: 0,
+    };
+    if link {
+        for group in &report.identical {
+            match dedupe::link_group(group) {
+                Ok(freed) => {
+                    report.linked += group.files.len() - 1;
+                    report.freed += freed;
+                }
+                Err(e) => warn!("{:#}", e),
+            }
+        }
+    }
+
+    if output::json() {
+        return output::emit(&report);
+    }
+    if output::porcelain() {
+        for group in &report.identical {
+            for file in &group.files {
+                output::row(&[
+                    "identical",
+                    &group.hash,
+                    &file.show,
+                    &file.title,
+                    &path(file),
+                ]);
+            }
+        }
+        for (n, group) in report.similar.iter().enumerate() {
+            for file in group {
+                let n = (n + 1).to_string();
+                output::row(&["similar", &n, &file.show, &file.title, &path(file)]);
+            }
+        }
+        return Ok(());
+    }
+
+    if !report.identical.is_empty() {
+        info!("Identical files:");
+        for group in &report.identical {
+            info!(
+                "\n  {} × {} (SHA-256 {})",
+                HumanBytes(group.size),
+                group.files.len(),
+                &group.hash[..12]
+            );
+            for file in &group.files {
+                info!("    {} › {}\n      {}", file.show, file.title, path(file));
+            }
+        }
+    }
+    if !report.similar.is_empty() {
+        if !report.identical.is_empty() {
+            info!("");
+        }
+        info!("Similar episodes in different shows:");
+        for group in &report.similar {
+            info!("");
+            for file in group {
+                info!("  {} › {}\n    {}", file.show, file.title, path(file));
+            }
+        }
+    }
+
+    let wasted: u64 = report.identical.iter().map(IdenticalFiles::wasted).sum();
+    match (report.identical.len() + report.similar.len(), link) {
+        (0, _) => info!("No duplicates found."),
+        (_, true) => info!(
+            "\n✓ Linked {} duplicate file(s), freeing {}",
+            report.linked,
+            HumanBytes(report.freed)
+        ),
+        _ if wasted > 0 => info!(
+            "\n{} could be freed by linking identical files with --link",
+            HumanBytes(wasted)
+        ),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn path(file: &LibraryFile) -> String {
+    file.path.display().to_string()
+}
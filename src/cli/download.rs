@@ -0,0 +1,1810 @@
+use super::output;
+use super::{FilterArgs, display_banner, feeds, load_played, notify, progress, transfers};
+use anyhow::{Context, Result, bail};
+use futures_util::stream;
+use futures_util::{StreamExt, future};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use inquire::{Confirm, MultiSelect, Select};
+use pdl::chapters::{self, Chapter};
+use pdl::checksum;
+use pdl::config::Config;
+use pdl::download::{IfExists, part_path, remove_stale_parts, set_file_date, show_dir};
+use pdl::enclosure::{self, BitratePreference, EnclosurePolicy};
+use pdl::error::{BatchFailed, NoEpisodes};
+use pdl::extras;
+use pdl::history::History;
+use pdl::hook::PostCommand;
+use pdl::interrupt::{self, Interrupted};
+use pdl::listing;
+use pdl::metrics;
+use pdl::naming::NameScheme;
+use pdl::notes::{self, NotesFormat};
+use pdl::player::Player;
+use pdl::playlist::{self, NEW_EPISODES_PLAYLIST, Playlist, PlaylistEntry};
+use pdl::prune;
+use pdl::select::{EpisodeFilter, Selector};
+use pdl::site;
+use pdl::space;
+use pdl::subscriptions::{Subscription, Subscriptions};
+use pdl::tag::{self, Artwork};
+use pdl::torrent::TorrentClient;
+use pdl::tr;
+use pdl::transcode::{self, Transcode};
+use pdl::upload::UploadTarget;
+use pdl::webhook::{Webhook, WebhookEvent, WebhookEventKind};
+use pdl::{DownloadOutcome, Episode, Feed, PodcastClient};
+use regex::Regex;
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Options for picking and downloading episodes (the default command)
+#[derive(clap::Args, Debug)]
+pub struct DownloadArgs {
+    /// Number of episodes to display [default: 10]
+    #[arg(short, long)]
+    n: Option<usize>,
+
+    /// Feed URL to fetch (skips the subscription picker; repeat it for several feeds)
+    #[arg(short, long, value_name = "URL")]
+    feed: Vec<String>,
+
+    /// Also fetch every feed URL in this file, one per line (`-` reads them from stdin)
+    #[arg(long, value_name = "FILE")]
+    feed_list: Option<PathBuf>,
+
+    /// Select several episodes and download them one after another
+    #[arg(short, long, conflicts_with_all = ["all", "selector"])]
+    multi: bool,
+
+    /// Download every episode in the feed, skipping ones already downloaded
+    #[arg(short, long, conflicts_with = "selector")]
+    all: bool,
+
+    /// Don't ask for confirmation after picking episodes
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Never prompt; requires --feed and a choice of episodes (--all, --latest, --season, ...)
+    #[arg(long)]
+    non_interactive: bool,
+
+    /// Download the newest episode
+    #[arg(long, group = "selector")]
+    latest: bool,
+
+    /// Download the episode at this position in the list, or with this GUID
+    #[arg(long, value_name = "INDEX|GUID", group = "selector")]
+    episode: Option<String>,
+
+    /// Download every listed episode whose title matches this pattern
+    #[arg(long = "match", value_name = "REGEX", value_parser = Regex::new, group = "selector")]
+    pattern: Option<Regex>,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    /// Directory to save episodes in (a subfolder is created per show)
+    #[arg(short, long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// Write the episode to FILE instead of the download directory; `-` streams it to stdout
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["output_dir", "flat", "multi", "all", "jobs", "checksums", "tag", "save_cover", "save_notes", "extras", "preserve_dates", "playlist", "post_cmd", "transcode", "split_chapters", "upload", "name_scheme"]
+    )]
+    output: Option<PathBuf>,
+
+    /// Play the episodes once downloaded, with `player` from config (or mpv, vlc, ...)
+    #[arg(long)]
+    play: bool,
+
+    /// Add the episodes to the download queue (`pdl queue start`) instead of downloading them
+    #[arg(long, conflicts_with_all = ["output", "play"])]
+    pub queue: bool,
+
+    /// Update an .m3u8 playlist of the show's episodes, and one of new episodes across shows
+    #[arg(long)]
+    playlist: bool,
+
+    /// Run this command on each downloaded file; `{file}`, `{title}` and `{show}` are filled in
+    #[arg(long, value_name = "COMMAND")]
+    post_cmd: Option<String>,
+
+    /// Convert downloads with ffmpeg, e.g. `opus:64k`, `mp3:96k`, `aac` or `flac`
+    #[arg(long, value_name = "CODEC[:BITRATE]")]
+    transcode: Option<String>,
+
+    /// Keep the downloaded file next to the transcoded copy
+    #[arg(long, requires = "transcode")]
+    keep_original: bool,
+
+    /// Also cut each download into one file per chapter, from the feed's chapters or the file's own
+    #[arg(long)]
+    split_chapters: bool,
+
+    /// Copy each download and its sidecar files to `s3://bucket/prefix` (with the AWS CLI) or an rclone `remote:path`
+    #[arg(long, value_name = "TARGET")]
+    upload: Option<String>,
+
+    /// Download episodes the feed offers as torrents with `torrent_cmd` from config (or aria2c)
+    #[arg(long, conflicts_with = "output")]
+    prefer_torrent: bool,
+
+    /// Start downloading even if the episodes may not fit on the disk
+    #[arg(long)]
+    no_space_check: bool,
+
+    /// Save episodes directly in the download directory, without a folder per show
+    #[arg(long)]
+    flat: bool,
+
+    /// Set each downloaded file's modification time to the episode's publish date
+    #[arg(long)]
+    preserve_dates: bool,
+
+    /// Start file names with the publish date (YYYY-MM-DD)
+    #[arg(long)]
+    date_prefix: bool,
+
+    /// Start file names with the zero-padded episode number, or the position in the feed
+    #[arg(long)]
+    number_prefix: bool,
+
+    /// Name files after the title (through filename_template), the GUID, or a SHA-256 of their content; GUID and hash names are listed in a names.json per folder
+    #[arg(long, value_enum, value_name = "SCHEME")]
+    name_scheme: Option<NameScheme>,
+
+    /// What to do when an episode's file already exists: skip, overwrite, rename or ask
+    #[arg(long, value_enum, default_value_t, value_name = "POLICY")]
+    if_exists: IfExistsArg,
+
+    /// Number of episodes to download in parallel [default: 1]
+    #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: Option<u16>,
+
+    /// Download episodes even if the history says they were already fetched
+    #[arg(long)]
+    force: bool,
+
+    /// Record SHA-256 sums of downloaded files in a `sha256sums` file per show
+    #[arg(long)]
+    checksums: bool,
+
+    /// Write episode metadata and cover art into downloaded files (ID3 for mp3, MP4 atoms for m4a)
+    #[arg(long)]
+    tag: bool,
+
+    /// Save the show's artwork as cover.jpg (or cover.png) in the show folder
+    #[arg(long)]
+    save_cover: bool,
+
+    /// Save each episode's show notes next to the audio file
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "md")]
+    save_notes: Option<NotesFormat>,
+
+    /// Also download chapters and transcripts (Podcasting 2.0) next to the audio
+    #[arg(long)]
+    extras: bool,
+
+    /// When a feed offers alternate versions, prefer this codec (e.g. opus, aac, mp3)
+    #[arg(long, value_name = "CODEC")]
+    prefer_codec: Option<String>,
+
+    /// When a feed offers alternate versions, prefer this bitrate: highest, lowest or kbps
+    #[arg(long, value_name = "BITRATE")]
+    prefer_bitrate: Option<BitratePreference>,
+}
+
+/// `--if-exists` choices; `ask` decides per file before downloading
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum IfExistsArg {
+    #[default]
+    Skip,
+    Overwrite,
+    Rename,
+    Ask,
+}
+
+pub async fn run_download(
+    client: &PodcastClient,
+    config: &Config,
+    mut args: DownloadArgs,
+) -> Result<()> {
+    let mut client = client.clone();
+    match args.if_exists {
+        IfExistsArg::Skip | IfExistsArg::Ask => {}
+        IfExistsArg::Overwrite => client = client.with_if_exists(IfExists::Overwrite),
+        IfExistsArg::Rename => client = client.with_if_exists(IfExists::Rename),
+    }
+    if args.prefer_torrent {
+        client = client.with_torrent_client(TorrentClient::find(config.torrent_cmd.as_deref())?);
+    }
+    let client = &client;
+
+    let to_stdout = args.output.as_deref() == Some(Path::new("-"));
+    if to_stdout {
+        if io::stdout().is_terminal() {
+            bail!("Refusing to write audio to the terminal; pipe it somewhere, e.g. `| mpv -`");
+        }
+        if output::json() {
+            bail!("--output - and --json both need stdout");
+        }
+        if output::porcelain() {
+            bail!("--output - and --porcelain both need stdout");
+        }
+        if args.play {
+            bail!("--play needs a file; pipe --output - into the player instead");
+        }
+        output::set_streaming();
+    }
+
+    let transcode = match &args.transcode {
+        Some(spec) => Some(spec.parse().context("Invalid --transcode")?),
+        None if args.output.is_none() => config.transcode()?,
+        None => None,
+    };
+    if transcode.is_some() {
+        transcode::check_ffmpeg("--transcode").await?;
+    }
+
+    if args.split_chapters {
+        transcode::check_ffmpeg("--split-chapters").await?;
+    }
+
+    let upload = match &args.upload {
+        Some(target) => Some(target.parse().context("Invalid --upload")?),
+        None if args.output.is_none() => config.upload()?,
+        None => None,
+    };
+    if let Some(target) = &upload {
+        target.check()?;
+    }
+
+    // Find the player before downloading, so a missing one fails fast
+    let play = if args.play {
+        Some(Player::find(config.player.as_deref())?)
+    } else {
+        None
+    };
+
+    // Prompts need a terminal, so scripted runs must say what to download
+    let interactive = !args.non_interactive && output::interactive() && io::stdout().is_terminal();
+    let selector = if args.latest {
+        Some(Selector::Latest)
+    } else if let Some(key) = args.episode.take() {
+        Some(Selector::Episode(key))
+    } else {
+        args.pattern.take().map(Selector::Match)
+    };
+
+    // Command-line flags win over config and environment settings
+    let mut feed_urls = std::mem::take(&mut args.feed);
+    if let Some(path) = &args.feed_list {
+        feed_urls.extend(feeds::read_feed_list(path)?);
+    }
+    if feed_urls.is_empty() {
+        feed_urls.extend(config.default_feed.clone());
+    }
+    let n = args.n.or(config.episodes).unwrap_or(10);
+    let jobs = args.jobs.or(config.jobs).unwrap_or(1).max(1);
+
+    // --season and --episodes pick every episode they match unless told otherwise
+    let filter = args.filter.filter();
+    let all = args.all || (filter.by_number() && selector.is_none() && !args.multi);
+
+    if !interactive {
+        if feed_urls.is_empty() {
+            bail!("--feed is required when running non-interactively");
+        }
+        if selector.is_none() && !all {
+            bail!(
+                "Choose episodes with --all, --latest, --episode, --match, --season or --episodes when running non-interactively"
+            );
+        }
+        if args.if_exists == IfExistsArg::Ask {
+            bail!("--if-exists ask needs a terminal; use skip, overwrite or rename");
+        }
+    }
+    if feed_urls.len() > 1 && args.output.is_some() {
+        bail!("--output writes a single episode, so it takes a single feed");
+    }
+
+    // Display banner
+    if interactive && !output::quiet() {
+        display_banner();
+    }
+
+    let run = Run {
+        client,
+        config,
+        args: &args,
+        interactive,
+        all,
+        selector,
+        n,
+        jobs,
+        play,
+        transcode,
+        upload,
+    };
+    match feed_urls.as_slice() {
+        // Use the feed given on the command line, or select one of the subscriptions
+        [] => run.single(&feeds::select_feed()?, filter).await,
+        [url] => run.single(url, filter).await,
+        urls => run.several(urls, filter).await,
+    }
+}
+
+/// Settings for one `pdl` download run, shared by every feed in it
+struct Run<'a> {
+    client: &'a PodcastClient,
+    config: &'a Config,
+    args: &'a DownloadArgs,
+    interactive: bool,
+    /// Download every episode that passes the filter
+    all: bool,
+    selector: Option<Selector>,
+    n: usize,
+    jobs: u16,
+    play: Option<Player>,
+    transcode: Option<Transcode>,
+    upload: Option<UploadTarget>,
+}
+
+impl Run<'_> {
+    /// Fetch the feed at `url` and narrow its episodes down to the ones on
+    /// offer; with a filter, -n counts the episodes that pass it
+    async fn fetch(&self, url: &str, filter: &mut EpisodeFilter) -> Result<(String, Feed)> {
+        let limit = if self.all || !filter.is_empty() {
+            usize::MAX
+        } else {
+            self.n
+        };
+        let (feed_url, mut feed) =
+            feeds::fetch_feed(self.client, url, limit, self.interactive).await?;
+        load_played(filter, &feed_url)?;
+        filter.apply(&mut feed.episodes);
+        if !self.all {
+            feed.episodes.truncate(self.n);
+        }
+        Ok((feed_url, feed))
+    }
+
+    /// Whether episodes are picked in a menu rather than by the options
+    fn picker(&self) -> bool {
+        self.interactive && self.selector.is_none() && !self.all
+    }
+
+    /// The episodes the selector picks, or all of them
+    fn chosen<'e>(&self, episodes: &'e [Episode]) -> Vec<&'e Episode> {
+        match &self.selector {
+            Some(selector) => selector.select(episodes),
+            None => episodes.iter().collect(),
+        }
+    }
+
+    async fn single(&self, url: &str, mut filter: EpisodeFilter) -> Result<()> {
+        info!("\n{}\n", tr!("fetching-feed"));
+        let (feed_url, feed) = self.fetch(url, &mut filter).await?;
+        let episodes = &feed.episodes;
+
+        if episodes.is_empty() {
+            if filter.is_empty() {
+                info!("{}", tr!("no-episodes-in-feed"));
+            } else {
+                info!("{}", tr!("no-episodes-match-filter"));
+            }
+            return emit_report(&feed_url, &feed, Vec::new(), None);
+        }
+
+        // Create interactive selection menu
+        let selected = if !self.picker() {
+            let selected = self.chosen(episodes);
+            if selected.is_empty() {
+                return Err(NoEpisodes.into());
+            }
+            if self.all {
+                info!("{}", tr!("downloading-all", count = episodes.len()));
+            }
+            selected
+        } else if self.args.multi {
+            select_episodes(episodes)?
+        } else {
+            vec![select_episode(episodes)?]
+        };
+
+        if selected.is_empty() {
+            info!("{}", tr!("no-episodes-selected"));
+            return Ok(());
+        }
+
+        // Show what was picked before committing to the download
+        if self.picker() && !self.args.yes && !confirm_download(&selected)? {
+            info!("{}", tr!("download-cancelled"));
+            return Ok(());
+        }
+        self.download(&feed_url, &feed, selected).await
+    }
+
+    /// Download from each of `urls` in turn. A menu offers the episodes of
+    /// every feed at once, grouped by show; otherwise the options pick from
+    /// each feed on its own. A feed that fails doesn't stop the others.
+    async fn several(&self, urls: &[String], mut filter: EpisodeFilter) -> Result<()> {
+        let mut fetched = Vec::new();
+        let mut failed = 0;
+        for url in urls {
+            info!("\n{}", tr!("fetching-url", url = url.as_str()));
+            match self.fetch(url, &mut filter).await {
+                Ok(feed) => fetched.push(feed),
+                Err(e) if e.is::<Interrupted>() => return Err(e),
+                Err(e) => {
+                    warn!("{}: {:#}", url, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        let selected: Vec<Vec<&Episode>> = if self.picker() {
+            let selected = select_across(&fetched, self.args.multi)?;
+            let picked: Vec<&Episode> = selected.iter().flatten().copied().collect();
+            if picked.is_empty() {
+                info!("{}", tr!("no-episodes-selected"));
+                return Ok(());
+            }
+            if !self.args.yes && !confirm_download(&picked)? {
+                info!("{}", tr!("download-cancelled"));
+                return Ok(());
+            }
+            selected
+        } else {
+            let selected: Vec<_> = fetched
+                .iter()
+                .map(|(_, feed)| self.chosen(&feed.episodes))
+                .collect();
+            if self.selector.is_some() && !fetched.is_empty() && selected.iter().all(Vec::is_empty)
+            {
+                return Err(NoEpisodes.into());
+            }
+            selected
+        };
+
+        for ((feed_url, feed), selected) in fetched.iter().zip(selected) {
+            if selected.is_empty() {
+                if !self.picker() {
+                    info!("\n{}", tr!("feed-no-matching-episodes", show = &feed.title));
+                    emit_report(feed_url, feed, Vec::new(), None)?;
+                }
+                continue;
+            }
+            info!("\n{}", feed.title);
+            match self.download(feed_url, feed, selected).await {
+                Ok(()) => {}
+                Err(e) if e.is::<Interrupted>() => return Err(e),
+                Err(e) => {
+                    warn!("{}: {:#}", feed.title, e);
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            bail!("{} of {} feeds failed", failed, urls.len());
+        }
+        Ok(())
+    }
+
+    /// Download the `selected` episodes of `feed`, less the ones the
+    /// history already has
+    async fn download(&self, feed_url: &str, feed: &Feed, selected: Vec<&Episode>) -> Result<()> {
+        let args = self.args;
+        let subscriptions = Subscriptions::load()?;
+        let subscription = subscriptions.find_url(feed_url);
+        let mut template = self
+            .config
+            .filename_template_for(subscription)?
+            .with_prefixes(args.date_prefix, args.number_prefix);
+        if let Some(scheme) = args.name_scheme {
+            template = template.with_scheme(scheme);
+        }
+        let client = &self.client.clone().with_filename_template(template);
+        let mut report = Vec::new();
+
+        // Skip episodes the history says were already downloaded; --output
+        // writes outside the library, so it doesn't count
+        let history = History::open_default()?;
+        let selected = if args.force || args.output.is_some() {
+            selected
+        } else {
+            let mut pending = Vec::new();
+            for episode in selected {
+                if history.contains(episode)? {
+                    info!("{}", tr!("already-downloaded", title = &episode.title));
+                    report.push(EpisodeReport::new(episode, "in_history"));
+                } else {
+                    pending.push(episode);
+                }
+            }
+            pending
+        };
+
+        if selected.is_empty() {
+            info!("\n{}", tr!("nothing-new"));
+            return emit_report(feed_url, feed, report, None);
+        }
+
+        // Pick which version of each episode to fetch when the feed offers several
+        let policy = EnclosurePolicy {
+            codec: args.prefer_codec.clone(),
+            bitrate: args.prefer_bitrate,
+        };
+        let prompt = self.interactive && !self.all && policy.is_empty();
+        let selected = selected
+            .into_iter()
+            .map(|episode| choose_version(episode, &policy, prompt))
+            .collect::<Result<Vec<_>>>()?;
+        let selected: Vec<&Episode> = selected.iter().collect();
+
+        if args.queue {
+            for episode in selected {
+                let status = if history.enqueue(feed_url, &feed.title, episode)? {
+                    info!("{}", tr!("queued", title = &episode.title));
+                    "queued"
+                } else {
+                    info!("{}", tr!("already-queued", title = &episode.title));
+                    "already_queued"
+                };
+                report.push(EpisodeReport::new(episode, status));
+            }
+            return emit_report(feed_url, feed, report, None);
+        }
+
+        if let Some(path) = &args.output {
+            let [episode] = selected.as_slice() else {
+                bail!(
+                    "--output writes a single episode, but {} were selected",
+                    selected.len()
+                );
+            };
+            write_episode(client, episode, path).await?;
+            if let Some(player) = &self.play {
+                player.play(&[path])?;
+                if let Err(e) = history.mark_episode_played(feed_url, &feed.title, episode) {
+                    warn!("{:#}", e);
+                }
+            }
+            return Ok(());
+        }
+
+        // Save into a folder named after the show under the download directory
+        let root = match &args.output_dir {
+            Some(dir) => dir.clone(),
+            None => self.config.download_dir_for(subscription),
+        };
+        let post_cmd = match &args.post_cmd {
+            Some(command) => Some(command.parse().context("Invalid --post-cmd")?),
+            None => self.config.post_cmd(subscription)?,
+        };
+        // The playlist of new episodes spans shows, so it stays in the main
+        // download directory when a feed has its own
+        let playlists = (args.playlist || self.config.playlists == Some(true)).then(|| {
+            args.output_dir
+                .clone()
+                .unwrap_or_else(|| self.config.download_dir())
+        });
+        let dir = if args.flat {
+            root
+        } else {
+            show_dir(&root, &feed.title)
+        };
+
+        // Settle clashes with existing files up front rather than mid-download
+        let mut if_exists = HashMap::new();
+        if args.if_exists == IfExistsArg::Ask {
+            for episode in &selected {
+                let path = client.planned_path(episode, &dir);
+                if path.exists() {
+                    if_exists.insert(episode.url.clone(), ask_if_exists(&path)?);
+                }
+            }
+        }
+
+        let batch = Batch {
+            client,
+            feed_url,
+            dir,
+            if_exists,
+            jobs: self.jobs.into(),
+            history,
+            checksums: args.checksums,
+            tag: args.tag,
+            save_cover: args.save_cover,
+            save_notes: args.save_notes,
+            extras: args.extras,
+            preserve_dates: args.preserve_dates,
+            play: self.play.clone(),
+            webhook: None,
+            playlists,
+            post_cmd,
+            transcode: self.transcode.clone(),
+            keep_original: args.keep_original,
+            split_chapters: args.split_chapters,
+            upload: self.upload.clone(),
+            cover_uploaded: Cell::default(),
+            space_check: !args.no_space_check,
+            feed,
+            saved: RefCell::default(),
+            artwork: RefCell::default(),
+            report: RefCell::new(report),
+        };
+        batch.run(&selected).await
+    }
+}
+
+/// Write `episode` to `path` as it downloads, or to stdout for `-`. The
+/// file isn't part of the library, so it isn't recorded in the history.
+async fn write_episode(client: &PodcastClient, episode: &Episode, path: &Path) -> Result<()> {
+    if path == Path::new("-") {
+        info!("{}", tr!("streaming", title = &episode.title));
+        // The player reading stdout likely shares the terminal; keep it clean
+        let pb = ProgressBar::hidden();
+        client
+            .stream_episode(episode, &mut tokio::io::stdout(), &pb)
+            .await?;
+        return Ok(());
+    }
+
+    info!("\n{}", tr!("downloading", title = &episode.title));
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let pb = new_progress_bar()?;
+    client.stream_episode(episode, &mut file, &pb).await?;
+    info!(
+        "{}",
+        tr!(
+            "saved-to",
+            path = path.display().to_string(),
+            throughput = Throughput::of(&pb).to_string()
+        )
+    );
+    Ok(())
+}
+
+/// Queue the episodes a subscription's watch rule picks that the history
+/// doesn't have yet, returning how many weren't queued before
+pub async fn queue_new(client: &PodcastClient, subscription: &Subscription) -> Result<usize> {
+    // The rule's count applies after its filter, as with `-n` and `--filter`
+    let filter = subscription.watch_filter()?;
+    let limit = if filter.is_empty() {
+        subscription.watch_latest()
+    } else {
+        usize::MAX
+    };
+    let mut feed = client.fetch_feed(&subscription.url, limit).await?;
+    filter.apply(&mut feed.episodes);
+    feed.episodes.truncate(subscription.watch_latest());
+    // Nobody's there to ask while watching
+    let feed_url = feeds::follow_moved(client, &subscription.url, false)?
+        .unwrap_or_else(|| subscription.url.clone());
+
+    let history = History::open_default()?;
+    let mut queued = 0;
+    for episode in &feed.episodes {
+        if !history.contains(episode)? && history.enqueue(&feed_url, &feed.title, episode)? {
+            queued += 1;
+        }
+    }
+    Ok(queued)
+}
+
+/// Download the given episodes of `feed` that the history doesn't have yet,
+/// with the configured settings, returning how many that was
+pub async fn download_pending(
+    client: &PodcastClient,
+    config: &Config,
+    subscription: &Subscription,
+    feed: &Feed,
+    episodes: &[&Episode],
+) -> Result<usize> {
+    let history = History::open_default()?;
+    let mut pending = Vec::new();
+    for &episode in episodes {
+        if !history.contains(episode)? {
+            pending.push(episode);
+        }
+    }
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    download_configured(client, config, &subscription.url, feed, &pending).await?;
+    Ok(pending.len())
+}
+
+/// Download `episodes` of the feed at `feed_url` with the configured
+/// settings, and those of the feed's subscription if there is one
+pub async fn download_configured(
+    client: &PodcastClient,
+    config: &Config,
+    feed_url: &str,
+    feed: &Feed,
+    episodes: &[&Episode],
+) -> Result<()> {
+    let subscriptions = Subscriptions::load()?;
+    let subscription = subscriptions.find_url(feed_url);
+    let client = &client
+        .clone()
+        .with_filename_template(config.filename_template_for(subscription)?);
+    let batch = Batch {
+        client,
+        feed_url,
+        dir: show_dir(&config.download_dir_for(subscription), &feed.title),
+        if_exists: HashMap::new(),
+        jobs: config.jobs.unwrap_or(1).max(1).into(),
+        history: History::open_default()?,
+        checksums: false,
+        tag: false,
+        save_cover: false,
+        save_notes: None,
+        extras: false,
+        preserve_dates: false,
+        play: None,
+        webhook: config.webhook(),
+        playlists: (config.playlists == Some(true)).then(|| config.download_dir()),
+        post_cmd: config.post_cmd(subscription)?,
+        transcode: config.transcode()?,
+        keep_original: false,
+        split_chapters: false,
+        upload: config.upload()?,
+        cover_uploaded: Cell::default(),
+        space_check: true,
+        feed,
+        saved: RefCell::default(),
+        artwork: RefCell::default(),
+        report: RefCell::default(),
+    };
+    batch.run(episodes).await
+}
+
+/// Download `episodes` into the show archive at `dir`, keeping everything
+/// that goes with them: cover art, show notes as HTML, chapters, transcripts
+/// and SHA-256 sums. Files are left as published (no tags), dated by the
+/// episode's publish date.
+pub async fn download_archive(
+    client: &PodcastClient,
+    feed_url: &str,
+    feed: &Feed,
+    dir: PathBuf,
+    episodes: &[&Episode],
+    jobs: u16,
+) -> Result<()> {
+    let batch = Batch {
+        client,
+        feed_url,
+        dir,
+        if_exists: HashMap::new(),
+        jobs: jobs.max(1).into(),
+        history: History::open_default()?,
+        checksums: true,
+        tag: false,
+        save_cover: true,
+        save_notes: Some(NotesFormat::Html),
+        extras: true,
+        preserve_dates: true,
+        play: None,
+        webhook: None,
+        playlists: None,
+        post_cmd: None,
+        transcode: None,
+        keep_original: false,
+        split_chapters: false,
+        upload: None,
+        cover_uploaded: Cell::default(),
+        space_check: true,
+        feed,
+        saved: RefCell::default(),
+        artwork: RefCell::default(),
+        report: RefCell::default(),
+    };
+    batch.run(episodes).await
+}
+
+/// The version of `episode` to download: asked for when `prompt` is set,
+/// picked by `policy` otherwise (the main enclosure if the policy is empty)
+fn choose_version(episode: &Episode, policy: &EnclosurePolicy, prompt: bool) -> Result<Episode> {
+    let options = enclosure::options(episode);
+    if options.len() < 2 || (!prompt && policy.is_empty()) {
+        return Ok(episode.clone());
+    }
+
+    let chosen = if prompt {
+        let labels: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+        let selection = Select::new(&tr!("choose-version", title = &episode.title), labels)
+            .raw_prompt()
+            .context("Failed to get user selection")?;
+        &options[selection.index]
+    } else {
+        policy.choose(&options).unwrap_or(&options[0])
+    };
+    Ok(enclosure::with_enclosure(episode, chosen))
+}
+
+/// Ask what to do about an episode whose file is already at `path`
+fn ask_if_exists(path: &Path) -> Result<IfExists> {
+    let options = [
+        (tr!("if-exists-skip"), IfExists::Skip),
+        (tr!("if-exists-overwrite"), IfExists::Overwrite),
+        (tr!("if-exists-rename"), IfExists::Rename),
+    ];
+    let labels: Vec<&str> = options.iter().map(|(label, _)| label.as_str()).collect();
+    let prompt = tr!("file-exists", path = path.display().to_string());
+    let selection = Select::new(&prompt, labels)
+        .raw_prompt()
+        .context("Failed to get user selection")?;
+    Ok(options[selection.index].1)
+}
+
+/// Rows shown at once by the episode pickers; typing filters the whole list
+const PICKER_PAGE_SIZE: usize = 15;
+
+/// Picker labels: position, title and date, so any of them can be typed to filter
+fn episode_labels(episodes: &[Episode]) -> Vec<String> {
+    episodes
+        .iter()
+        .enumerate()
+        .map(|(i, ep)| match ep.published {
+            Some(date) => format!("{}. {} ({})", i + 1, ep.title, date.format("%Y-%m-%d")),
+            None => format!("{}. {}", i + 1, ep.title),
+        })
+        .collect()
+}
+
+pub fn select_episode(episodes: &[Episode]) -> Result<&Episode> {
+    let selection = Select::new(&tr!("select-episode"), episode_labels(episodes))
+        .with_page_size(PICKER_PAGE_SIZE)
+        .with_help_message(&tr!("select-help"))
+        .raw_prompt()
+        .context("Failed to get user selection")?;
+
+    Ok(&episodes[selection.index])
+}
+
+/// Pick episodes from several feeds in one menu, listed show by show with
+/// the show's title in front of each so typing it narrows the list.
+/// Returns the picks for each feed, in the order of `feeds`.
+fn select_across(feeds: &[(String, Feed)], multi: bool) -> Result<Vec<Vec<&Episode>>> {
+    let mut labels = Vec::new();
+    let mut positions = Vec::new();
+    for (i, (_, feed)) in feeds.iter().enumerate() {
+        for (j, label) in episode_labels(&feed.episodes).into_iter().enumerate() {
+            labels.push(format!("{} › {}", feed.title, label));
+            positions.push((i, j));
+        }
+    }
+    let picked: Vec<usize> = if multi {
+        MultiSelect::new(&tr!("select-episodes"), labels)
+            .with_page_size(PICKER_PAGE_SIZE)
+            .with_help_message(&tr!("multi-select-help"))
+            .raw_prompt()
+            .context("Failed to get user selection")?
+            .iter()
+            .map(|s| s.index)
+            .collect()
+    } else {
+        let selection = Select::new(&tr!("select-episode"), labels)
+            .with_page_size(PICKER_PAGE_SIZE)
+            .with_help_message(&tr!("select-help"))
+            .raw_prompt()
+            .context("Failed to get user selection")?;
+        vec![selection.index]
+    };
+
+    let mut selected = vec![Vec::new(); feeds.len()];
+    for index in picked {
+        let (i, j) = positions[index];
+        selected[i].push(&feeds[i].1.episodes[j]);
+    }
+    Ok(selected)
+}
+
+fn select_episodes(episodes: &[Episode]) -> Result<Vec<&Episode>> {
+    let selections = MultiSelect::new(&tr!("select-episodes"), episode_labels(episodes))
+        .with_page_size(PICKER_PAGE_SIZE)
+        .with_help_message(&tr!("multi-select-help"))
+        .raw_prompt()
+        .context("Failed to get user selection")?;
+
+    Ok(selections.iter().map(|s| &episodes[s.index]).collect())
+}
+
+/// Show details of the picked episodes and ask whether to go ahead
+fn confirm_download(episodes: &[&Episode]) -> Result<bool> {
+    println!();
+    match episodes {
+        [episode] => print!("{}", listing::details(episode)),
+        _ => {
+            for episode in episodes {
+                println!("  • {}", listing::summary(episode));
+            }
+            let known: Vec<u64> = episodes.iter().filter_map(|e| e.size).collect();
+            if !known.is_empty() {
+                let total = HumanBytes(known.iter().sum());
+                if known.len() == episodes.len() {
+                    println!("\n  {}", tr!("total-size", size = total.to_string()));
+                } else {
+                    println!(
+                        "\n  {}",
+                        tr!("total-size-at-least", size = total.to_string())
+                    );
+                }
+            }
+        }
+    }
+    println!();
+
+    let prompt = tr!("confirm-download", count = episodes.len());
+    Confirm::new(&prompt)
+        .with_default(true)
+        .prompt()
+        .context("Failed to get confirmation")
+}
+
+/// Where and how a set of selected episodes gets downloaded
+struct Batch<'a> {
+    client: &'a PodcastClient,
+    feed_url: &'a str,
+    dir: PathBuf,
+    /// Answers to `--if-exists ask` by episode URL; others use the client's policy
+    if_exists: HashMap<String, IfExists>,
+    jobs: usize,
+    history: History,
+    /// Write a `sha256sums` file covering the files saved
+    checksums: bool,
+    /// Tag downloaded files with episode metadata
+    tag: bool,
+    /// Save the show artwork next to the episodes
+    save_cover: bool,
+    /// Write show notes sidecars in this format
+    save_notes: Option<NotesFormat>,
+    /// Fetch chapters and transcripts
+    extras: bool,
+    /// Date downloaded files by the episode's publish date
+    preserve_dates: bool,
+    /// Play the files saved once the batch is done
+    play: Option<Player>,
+    /// Post each download and failure here
+    webhook: Option<Webhook>,
+    /// Download root holding the playlist of new episodes, if playlists are kept
+    playlists: Option<PathBuf>,
+    /// Run on each file downloaded
+    post_cmd: Option<PostCommand>,
+    /// Convert each file downloaded, replacing it unless `keep_original`
+    transcode: Option<Transcode>,
+    keep_original: bool,
+    /// Cut each file downloaded into its chapters
+    split_chapters: bool,
+    /// Copy each file downloaded, with its sidecars, here
+    upload: Option<UploadTarget>,
+    /// Whether the show's artwork has been uploaded in this batch
+    cover_uploaded: Cell<bool>,
+    /// Make sure the episodes fit on the disk before downloading any
+    space_check: bool,
+    feed: &'a Feed,
+    /// Files downloaded or found in place so far
+    saved: RefCell<Vec<PathBuf>>,
+    /// Artwork fetched so far by URL; `None` if fetching it failed
+    artwork: RefCell<HashMap<String, Option<Arc<Artwork>>>>,
+    /// What happened to each episode, for `--json`
+    report: RefCell<Vec<EpisodeReport>>,
+}
+
+/// Size checks sent to servers at once before a batch
+const SIZE_CHECKS: usize = 8;
+
+/// Partial downloads untouched for this long are deleted rather than resumed
+const STALE_PART_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+impl Batch<'_> {
+    async fn download(&self, episode: &Episode, pb: &ProgressBar) -> Result<DownloadOutcome> {
+        let _tracked = transfers::track(&self.feed.title, episode, pb);
+        let download = async {
+            match self.if_exists.get(&episode.url) {
+                Some(&policy) => {
+                    let client = self.client.clone().with_if_exists(policy);
+                    client.download_episode(episode, &self.dir, pb).await
+                }
+                None => self.client.download_episode(episode, &self.dir, pb).await,
+            }
+        };
+        if output::progress_json() {
+            let file = self.client.planned_path(episode, &self.dir);
+            progress::report(&self.feed.title, episode, &file, pb, download).await
+        } else {
+            download.await
+        }
+    }
+
+    async fn run(&self, episodes: &[&Episode]) -> Result<()> {
+        for path in remove_stale_parts(&self.dir, STALE_PART_AGE).await? {
+            info!(
+                "{}",
+                tr!("removed-stale-part", path = path.display().to_string())
+            );
+        }
+
+        if self.space_check {
+            self.check_space(episodes).await?;
+        }
+
+        if let Err(e) =
+            self.history
+                .remember_episodes(self.feed_url, &self.feed.title, &self.feed.episodes)
+        {
+            warn!("{:#}", e);
+        }
+
+        if self.save_cover {
+            self.save_cover().await;
+        }
+
+        let result = self.download_all(episodes).await;
+
+        let saved = self.saved.take();
+        let mut sums = None;
+        if self.checksums && !saved.is_empty() {
+            let path = checksum::update_sums_file(&self.dir, &saved).await?;
+            info!(
+                "{}",
+                tr!("checksums-written", path = path.display().to_string())
+            );
+            sums = Some(path);
+        }
+        let report = self.report.take();
+        if let Some(message) = batch_message(&report) {
+            if report.iter().any(|e| e.status == "failed") {
+                notify::send_failure(&self.feed.title, &message);
+            } else {
+                notify::send(&self.feed.title, &message);
+            }
+        }
+        if let Some(webhook) = &self.webhook {
+            self.post_webhook(webhook, &report).await;
+        }
+        if let Some(root) = &self.playlists {
+            match self.update_playlists(root, episodes, &report) {
+                Ok(path) => info!(
+                    "{}",
+                    tr!("playlist-updated", path = path.display().to_string())
+                ),
+                Err(e) => warn!("{:#}", e),
+            }
+        }
+        emit_report(self.feed_url, self.feed, report, sums)?;
+        if let Some(player) = &self.play
+            && !saved.is_empty()
+        {
+            info!("\n{}", tr!("playing-with", player = player.program()));
+            player.play(&saved)?;
+            for path in &saved {
+                if let Err(e) = self.history.mark_file_played(path) {
+                    warn!("{:#}", e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Fail if the episodes still to download won't fit in the download
+    /// directory. Episodes whose size can't be found out aren't counted.
+    async fn check_space(&self, episodes: &[&Episode]) -> Result<()> {
+        let sizes: Vec<(&Episode, Option<u64>)> = stream::iter(episodes.iter().copied())
+            .map(|episode| async move { (episode, self.client.episode_size(episode).await) })
+            .buffer_unordered(SIZE_CHECKS)
+            .collect()
+            .await;
+        let mut needed = 0u64;
+        for (episode, size) in sizes {
+            let path = self.client.planned_path(episode, &self.dir);
+            if path.exists() {
+                continue;
+            }
+            let Some(size) = size else {
+                debug!("Size of {} unknown", episode.title);
+                continue;
+            };
+            // A partial download is resumed, so only the rest is needed
+            let partial = std::fs::metadata(part_path(&path)).map_or(0, |m| m.len());
+            needed += size.saturating_sub(partial);
+        }
+        space::check(&self.dir, needed)
+    }
+
+    /// Tell `webhook` about each episode downloaded or failed. A webhook
+    /// that can't be reached is only logged.
+    async fn post_webhook(&self, webhook: &Webhook, report: &[EpisodeReport]) {
+        for episode in report {
+            let event = match episode.status {
+                "downloaded" => WebhookEventKind::Downloaded,
+                "failed" => WebhookEventKind::Failed,
+                _ => continue,
+            };
+            let event = WebhookEvent {
+                event,
+                show: self.feed.title.clone(),
+                feed_url: self.feed_url.to_string(),
+                title: episode.title.clone(),
+                guid: episode.guid.clone(),
+                path: episode.path.clone(),
+                error: episode.error.clone(),
+            };
+            if let Err(e) = self.client.post_webhook(webhook, &event).await {
+                warn!("{:#}", e);
+            }
+        }
+    }
+
+    /// Add the files saved to the show's playlist, and the new ones to the
+    /// playlist of new episodes under `root`. Returns the show's playlist.
+    fn update_playlists(
+        &self,
+        root: &Path,
+        episodes: &[&Episode],
+        report: &[EpisodeReport],
+    ) -> Result<PathBuf> {
+        let show_path = playlist::show_playlist_path(&self.dir, &self.feed.title);
+        let mut show = Playlist::load(&show_path)?;
+        let mut new = Playlist::load(&root.join(NEW_EPISODES_PLAYLIST))?;
+        for entry in report {
+            let Some(path) = &entry.path else { continue };
+            let Some(episode) = episodes
+                .iter()
+                .find(|e| e.guid.is_some() && e.guid == entry.guid)
+                .or_else(|| episodes.iter().find(|e| e.title == entry.title))
+            else {
+                continue;
+            };
+            show.add(PlaylistEntry::new(episode, path));
+            if entry.status == "downloaded" {
+                new.add(PlaylistEntry::new(episode, path));
+            }
+        }
+        show.save()?;
+        new.save()?;
+        Ok(show_path)
+    }
+
+    async fn download_all(&self, episodes: &[&Episode]) -> Result<()> {
+        // A single episode keeps the simple flow and propagates its error directly
+        if let [episode] = episodes {
+            info!("\n{}", tr!("downloading", title = &episode.title));
+            let pb = new_progress_bar()?;
+            let mut result = self.download(episode, &pb).await;
+            self.post_process(episode, &mut result).await;
+            self.remember(episode, &result);
+            match result? {
+                DownloadOutcome::Downloaded(path) => {
+                    info!(
+                        "{}",
+                        tr!(
+                            "saved-to",
+                            path = path.display().to_string(),
+                            throughput = Throughput::of(&pb).to_string()
+                        )
+                    )
+                }
+                DownloadOutcome::AlreadyExists(path) => {
+                    info!(
+                        "{}",
+                        tr!("already-downloaded", title = path.display().to_string())
+                    );
+                    return Ok(());
+                }
+            }
+            info!("\n{}", tr!("download-complete"));
+            return Ok(());
+        }
+
+        if self.jobs > 1 {
+            return self.run_concurrently(episodes).await;
+        }
+
+        let mut summary = BatchSummary::new();
+
+        for (i, episode) in episodes.iter().enumerate() {
+            info!(
+                "\n{}",
+                tr!(
+                    "downloading-nth",
+                    n = i + 1,
+                    total = episodes.len(),
+                    title = &episode.title
+                )
+            );
+            let pb = new_progress_bar()?;
+            let mut result = self.download(episode, &pb).await;
+            if result.is_err() {
+                pb.abandon();
+            }
+            self.post_process(episode, &mut result).await;
+            self.remember(episode, &result);
+            info!("{}", summary.record(episode, result, &pb));
+            if interrupt::is_interrupted() {
+                break;
+            }
+        }
+
+        summary.finish(episodes.len())
+    }
+
+    /// Download up to `jobs` episodes at a time, with one progress bar per
+    /// active download above an aggregate bar
+    async fn run_concurrently(&self, episodes: &[&Episode]) -> Result<()> {
+        let mp = MultiProgress::with_draw_target(output::progress_target());
+        let overall = mp.add(ProgressBar::new(episodes.len() as u64));
+        overall.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "Overall  [{bar:40.green/white}] {pos}/{len} episodes ({elapsed_precise})",
+                )
+                .context("Failed to create progress bar template")?
+                .progress_chars("=>-"),
+        );
+        let file_style = ProgressStyle::default_bar()
+            .template(
+                "{msg:24!} [{bar:40.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec} (ETA {eta})",
+            )
+            .context("Failed to create progress bar template")?
+            .progress_chars("=>-");
+
+        // After Ctrl+C, let running downloads save their progress but start no more
+        let mut results = stream::iter(episodes.iter().copied())
+            .take_while(|_| future::ready(!interrupt::is_interrupted()))
+            .map(|episode| {
+                let pb = mp.insert_before(&overall, ProgressBar::new(0));
+                pb.set_style(file_style.clone());
+                pb.set_message(episode.title.clone());
+                async move {
+                    let mut result = self.download(episode, &pb).await;
+                    pb.finish_and_clear();
+                    self.post_process(episode, &mut result).await;
+                    (episode, pb, result)
+                }
+            })
+            .buffer_unordered(self.jobs);
+
+        let mut summary = BatchSummary::new();
+        while let Some((episode, pb, result)) = results.next().await {
+            mp.remove(&pb);
+            self.remember(episode, &result);
+            let _ = mp.println(summary.record(episode, result, &pb));
+            overall.inc(1);
+        }
+
+        overall.finish();
+        summary.finish(episodes.len())
+    }
+
+    /// Tag and transcode the file and save its show notes, chapters and
+    /// transcripts, as requested. A transcoded file takes the original's
+    /// place in `result`. Failures here are reported but don't fail the
+    /// download.
+    async fn post_process(&self, episode: &Episode, result: &mut Result<DownloadOutcome>) {
+        let downloaded = matches!(result, Ok(DownloadOutcome::Downloaded(_)));
+        let path = match result {
+            Ok(DownloadOutcome::Downloaded(path)) => {
+                if self.tag {
+                    self.tag(episode, path).await;
+                }
+                // Read before transcoding, which drops chapters embedded in the file
+                let chapters = if self.split_chapters {
+                    self.chapters(episode, path).await
+                } else {
+                    Vec::new()
+                };
+                // The tags written above are carried over to the new file
+                if let Some(transcode) = &self.transcode {
+                    match transcode.run(path, self.keep_original).await {
+                        Ok(output) => {
+                            info!(
+                                "{}",
+                                tr!("transcoded-to", path = output.display().to_string())
+                            );
+                            *path = output;
+                        }
+                        Err(e) => warn!("{:#}", e),
+                    }
+                }
+                // After tagging, which would bump the time again
+                if self.preserve_dates
+                    && let Some(published) = episode.published
+                    && let Err(e) = set_file_date(path, published).await
+                {
+                    warn!("{:#}", e);
+                }
+                if !chapters.is_empty() {
+                    match chapters::split(path, &chapters).await {
+                        Ok(files) => info!(
+                            "{}",
+                            tr!(
+                                "split-into-chapters",
+                                count = files.len(),
+                                dir = chapters::split_dir(path).display().to_string()
+                            )
+                        ),
+                        Err(e) => warn!("{:#}", e),
+                    }
+                }
+                path
+            }
+            Ok(DownloadOutcome::AlreadyExists(path)) => path,
+            Err(_) => return,
+        };
+        if let Some(format) = self.save_notes
+            && let Err(e) = notes::save_notes(path, episode, format).await
+        {
+            warn!("{:#}", e);
+        }
+        if self.extras {
+            for (url, extra) in extras::extra_files(episode, path) {
+                if extra.exists() {
+                    continue;
+                }
+                if let Err(e) = self.client.download_file(&url, &extra).await {
+                    warn!("{:#}", e);
+                }
+            }
+        }
+        // Last, so the command sees the finished file and its sidecars
+        if let Some(command) = &self.post_cmd
+            && downloaded
+            && let Err(e) = command.run(path, &episode.title, &self.feed.title).await
+        {
+            warn!("{:#}", e);
+        }
+        // After the command, which may add files of its own next to it
+        if let Some(target) = &self.upload
+            && downloaded
+        {
+            self.upload(target, path).await;
+        }
+    }
+
+    /// Copy a download, the sidecar files saved with it and the show's
+    /// artwork to `target`. Failures are reported but leave the download be.
+    async fn upload(&self, target: &UploadTarget, path: &Path) {
+        match target.upload(path).await {
+            Ok(remote) => info!("{}", tr!("uploaded-to", remote = remote)),
+            Err(e) => {
+                warn!("{:#}", e);
+                return;
+            }
+        }
+        let mut files = prune::sidecars(path);
+        if !self.cover_uploaded.replace(true) {
+            files.extend(site::cover(&self.dir));
+        }
+        for file in files {
+            match target.upload(&file).await {
+                Ok(remote) => debug!("Uploaded to: {}", remote),
+                Err(e) => warn!("{:#}", e),
+            }
+        }
+    }
+
+    /// The chapters to split `path` into: the feed's, else those embedded in
+    /// an mp3. Missing chapters are only reported.
+    async fn chapters(&self, episode: &Episode, path: &Path) -> Vec<Chapter> {
+        let chapters = match &episode.chapters_url {
+            Some(url) => self.client.fetch_chapters(url).await,
+            None => {
+                let file = path.to_path_buf();
+                tokio::task::spawn_blocking(move || chapters::read_id3(&file))
+                    .await
+                    .context("Failed to read chapters")
+                    .and_then(|chapters| chapters)
+            }
+        };
+        match chapters {
+            Ok(chapters) if chapters.is_empty() => {
+                warn!(
+                    "{}",
+                    tr!("no-chapters-to-split", path = path.display().to_string())
+                );
+                Vec::new()
+            }
+            Ok(chapters) => chapters,
+            Err(e) => {
+                warn!("{:#}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Write metadata and cover art into a freshly downloaded file
+    async fn tag(&self, episode: &Episode, path: &Path) {
+        let artwork = match &episode.image {
+            Some(url) => self.artwork(url).await,
+            None => None,
+        };
+        let (file, episode, show) = (path.to_path_buf(), episode.clone(), self.feed.title.clone());
+        let tagged = tokio::task::spawn_blocking(move || {
+            tag::tag_file(&file, &episode, &show, artwork.as_deref())
+        });
+        match tagged.await {
+            Ok(Ok(true)) => {}
+            Ok(Ok(false)) => warn!("{}", tr!("cannot-tag", path = path.display().to_string())),
+            Ok(Err(e)) => warn!(
+                "{}",
+                tr!(
+                    "tag-failed",
+                    path = path.display().to_string(),
+                    error = format!("{:#}", e)
+                )
+            ),
+            Err(e) => warn!(
+                "{}",
+                tr!(
+                    "tag-failed",
+                    path = path.display().to_string(),
+                    error = e.to_string()
+                )
+            ),
+        }
+    }
+
+    /// Fetch artwork once per URL, warning if it can't be downloaded
+    async fn artwork(&self, url: &str) -> Option<Arc<Artwork>> {
+        if let Some(artwork) = self.artwork.borrow().get(url) {
+            return artwork.clone();
+        }
+        let artwork = match self.client.fetch_artwork(url).await {
+            Ok(artwork) => Some(Arc::new(artwork)),
+            Err(e) => {
+                warn!(
+                    "{}",
+                    tr!("artwork-failed", url = url, error = format!("{:#}", e))
+                );
+                None
+            }
+        };
+        self.artwork
+            .borrow_mut()
+            .insert(url.to_string(), artwork.clone());
+        artwork
+    }
+
+    /// Save the show artwork as `cover.<ext>` unless the folder already has one
+    async fn save_cover(&self) {
+        let Some(url) = &self.feed.image else {
+            return;
+        };
+        if ["jpg", "png"]
+            .iter()
+            .any(|ext| self.dir.join(format!("cover.{}", ext)).exists())
+        {
+            return;
+        }
+        let Some(artwork) = self.artwork(url).await else {
+            return;
+        };
+        let path = self.dir.join(format!("cover.{}", artwork.extension()));
+        let written = tokio::fs::create_dir_all(&self.dir)
+            .await
+            .and(tokio::fs::write(&path, &artwork.data).await);
+        match written {
+            Ok(()) => info!("{}", tr!("saved-cover", path = path.display().to_string())),
+            Err(e) => warn!("Failed to save {}: {}", path.display(), e),
+        }
+    }
+
+    /// Record a finished download in the history so later runs skip it, and
+    /// note the file for the checksums file and the report
+    fn remember(&self, episode: &Episode, result: &Result<DownloadOutcome>) {
+        let (status, path) = match result {
+            Ok(DownloadOutcome::Downloaded(path)) => {
+                metrics::episode_downloaded();
+                ("downloaded", path)
+            }
+            Ok(DownloadOutcome::AlreadyExists(path)) => ("exists", path),
+            Err(e) => {
+                let status = if e.is::<Interrupted>() {
+                    "interrupted"
+                } else {
+                    metrics::download_failed();
+                    if let Err(e) = self.history.record_failure(
+                        self.feed_url,
+                        &self.feed.title,
+                        episode,
+                        &format!("{:#}", e),
+                    ) {
+                        warn!("{:#}", e);
+                    }
+                    "failed"
+                };
+                self.report.borrow_mut().push(EpisodeReport {
+                    error: Some(format!("{:#}", e)),
+                    ..EpisodeReport::new(episode, status)
+                });
+                return;
+            }
+        };
+        self.report.borrow_mut().push(EpisodeReport {
+            path: Some(path.clone()),
+            ..EpisodeReport::new(episode, status)
+        });
+        self.saved.borrow_mut().push(path.clone());
+        if let Err(e) = self
+            .history
+            .record(self.feed_url, &self.feed.title, episode, path)
+        {
+            warn!("{:#}", e);
+        }
+    }
+}
+
+/// What happened to one episode, as printed with `--json`
+#[derive(Serialize)]
+struct EpisodeReport {
+    title: String,
+    guid: Option<String>,
+    /// `downloaded`, `exists` (file already there), `in_history`, `failed` or `interrupted`
+    status: &'static str,
+    path: Option<PathBuf>,
+    error: Option<String>,
+}
+
+impl EpisodeReport {
+    fn new(episode: &Episode, status: &'static str) -> Self {
+        Self {
+            title: episode.title.clone(),
+            guid: episode.guid.clone(),
+            status,
+            path: None,
+            error: None,
+        }
+    }
+}
+
+/// What a finished batch did, for a notification; `None` if it neither
+/// downloaded nor failed anything
+fn batch_message(report: &[EpisodeReport]) -> Option<String> {
+    let titles = |status| -> Vec<&str> {
+        report
+            .iter()
+            .filter(|e| e.status == status)
+            .map(|e| e.title.as_str())
+            .collect()
+    };
+    let (downloaded, failed) = (titles("downloaded"), titles("failed"));
+    match (downloaded.as_slice(), failed.as_slice()) {
+        ([], []) => None,
+        ([title], []) => Some(tr!("notify-downloaded", title = *title)),
+        (downloaded, []) => Some(tr!("notify-downloaded-count", count = downloaded.len())),
+        (downloaded, failed) => Some(tr!(
+            "notify-some-failed",
+            downloaded = downloaded.len(),
+            failed = failed.len(),
+            titles = failed.join(", ")
+        )),
+    }
+}
+
+/// Print the outcome of a download run with `--json` or `--porcelain`
+fn emit_report(
+    feed_url: &str,
+    feed: &Feed,
+    episodes: Vec<EpisodeReport>,
+    checksums: Option<PathBuf>,
+) -> Result<()> {
+    if output::porcelain() {
+        for episode in &episodes {
+            output::row(&[
+                episode.status,
+                &feed.title,
+                &episode.title,
+                episode.guid.as_deref().unwrap_or_default(),
+                &episode
+                    .path
+                    .as_deref()
+                    .map(|path| path.to_string_lossy())
+                    .unwrap_or_default(),
+            ]);
+        }
+        return Ok(());
+    }
+    if !output::json() {
+        return Ok(());
+    }
+    output::emit(&serde_json::json!({
+        "feed": feed_url,
+        "show": feed.title,
+        "episodes": episodes,
+        "checksums": checksums,
+    }))
+}
+
+/// Bytes a download moved and how long it took, for the speed summaries
+#[derive(Debug, Clone, Copy, Default)]
+struct Throughput {
+    bytes: u64,
+    elapsed: Duration,
+}
+
+impl Throughput {
+    /// Read off a finished download's progress bar
+    fn of(pb: &ProgressBar) -> Self {
+        Self {
+            bytes: pb.position(),
+            elapsed: pb.elapsed(),
+        }
+    }
+}
+
+impl std::fmt::Display for Throughput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.elapsed.as_secs_f64();
+        let speed = if secs > 0.0 {
+            (self.bytes as f64 / secs) as u64
+        } else {
+            self.bytes
+        };
+        write!(
+            f,
+            "{} in {}, {}/s",
+            HumanBytes(self.bytes),
+            format_elapsed(self.elapsed),
+            HumanBytes(speed)
+        )
+    }
+}
+
+/// `4.2s` under a minute, `12m05s` or `1h02m07s` beyond
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..60 => format!("{:.1}s", elapsed.as_secs_f64()),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m{:02}s", secs / 3600, secs / 60 % 60, secs % 60),
+    }
+}
+
+/// Tally of a batch download, reported once every episode has been attempted
+struct BatchSummary {
+    downloaded: usize,
+    skipped: usize,
+    failed: Vec<String>,
+    /// Bytes downloaded across the batch
+    bytes: u64,
+    started: Instant,
+    /// Ctrl+C stopped the batch before every episode was attempted
+    interrupted: bool,
+}
+
+impl BatchSummary {
+    fn new() -> Self {
+        Self {
+            downloaded: 0,
+            skipped: 0,
+            failed: Vec::new(),
+            bytes: 0,
+            started: Instant::now(),
+            interrupted: false,
+        }
+    }
+
+    /// Count the outcome of one download and return the line describing it
+    fn record(
+        &mut self,
+        episode: &Episode,
+        result: Result<DownloadOutcome>,
+        pb: &ProgressBar,
+    ) -> String {
+        match result {
+            Ok(DownloadOutcome::Downloaded(path)) => {
+                let throughput = Throughput::of(pb);
+                self.downloaded += 1;
+                self.bytes += throughput.bytes;
+                tr!(
+                    "saved-to",
+                    path = path.display().to_string(),
+                    throughput = throughput.to_string()
+                )
+            }
+            Ok(DownloadOutcome::AlreadyExists(path)) => {
+                self.skipped += 1;
+                tr!("already-downloaded", title = path.display().to_string())
+            }
+            Err(e) if e.is::<Interrupted>() => {
+                self.interrupted = true;
+                tr!("episode-interrupted", title = &episode.title)
+            }
+            Err(e) => {
+                self.failed.push(episode.title.clone());
+                tr!(
+                    "episode-failed",
+                    title = &episode.title,
+                    error = format!("{:#}", e)
+                )
+            }
+        }
+    }
+
+    /// Print the totals, failing if any download in the batch failed
+    fn finish(self, total: usize) -> Result<()> {
+        info!(
+            "\n{}",
+            tr!(
+                "batch-summary",
+                downloaded = self.downloaded,
+                skipped = self.skipped,
+                failed = self.failed.len()
+            )
+        );
+        if self.downloaded > 0 {
+            let throughput = Throughput {
+                bytes: self.bytes,
+                elapsed: self.started.elapsed(),
+            };
+            info!("  {}", throughput);
+        }
+        for title in &self.failed {
+            info!("  ✗ {}", title);
+        }
+
+        if self.interrupted {
+            return Err(Interrupted.into());
+        }
+        if !self.failed.is_empty() {
+            return Err(BatchFailed {
+                failed: self.failed.len(),
+                total,
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+fn new_progress_bar() -> Result<ProgressBar> {
+    let pb = ProgressBar::with_draw_target(Some(0), output::progress_target());
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "[{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec} (ETA {eta})",
+            )
+            .context("Failed to create progress bar template")?
+            .progress_chars("=>-"),
+    );
+    Ok(pb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_message_names_the_downloaded_episode() {
+        pdl::i18n::set_lang(pdl::i18n::Lang::En);
+        let report = |title: &str, status| EpisodeReport {
+            title: title.to_string(),
+            guid: None,
+            status,
+            path: None,
+            error: None,
+        };
+        assert_eq!(
+            batch_message(&[report("Old", "in_history"), report("New", "downloaded")]),
+            Some("Downloaded New".to_string())
+        );
+        assert_eq!(
+            batch_message(&[report("Old", "exists"), report("Bad", "failed")]),
+            Some("0 downloaded, 1 failed: Bad".to_string())
+        );
+        assert_eq!(batch_message(&[report("Old", "exists")]), None);
+    }
+}
@@ -0,0 +1,125 @@
+use super::output;
+use anyhow::{Context, Result, bail};
+use pdl::Episode;
+use pdl::snapshot::{FeedDiff, SnapshotStore, Snapshots};
+use pdl::tr;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+/// List the snapshots kept of a feed, oldest first
+pub async fn list_snapshots(feed: &str) -> Result<()> {
+    let snapshots = snapshots_of(feed)?;
+    let paths = snapshots.list().await?;
+    if output::json() {
+        return output::emit(&paths);
+    }
+    if paths.is_empty() {
+        println!("{}", tr!("no-snapshots", feed = feed));
+        return Ok(());
+    }
+
+    for path in &paths {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// Show the episodes added, removed and edited between two snapshots of a
+/// feed: by default the latest one and the one before it
+pub async fn diff(feed: &str, from: Option<String>, to: Option<String>) -> Result<()> {
+    let snapshots = snapshots_of(feed)?;
+    let paths = snapshots.list().await?;
+    let to = match to {
+        Some(name) => find(&snapshots, &paths, &name)?,
+        None => paths.last().cloned().with_context(|| no_snapshots(feed))?,
+    };
+    let from = match from {
+        Some(name) => find(&snapshots, &paths, &name)?,
+        None => paths
+            .iter()
+            .rev()
+            .find(|path| **path < to)
+            .cloned()
+            .with_context(|| format!("No snapshot of {} older than {}", feed, name_of(&to)))?,
+    };
+
+    let diff = FeedDiff::between(&Snapshots::read(&from).await?, &Snapshots::read(&to).await?);
+    if output::json() {
+        let episode = |e: &Episode| json!({ "title": e.title, "guid": e.guid, "url": e.url });
+        return output::emit(&json!({
+            "from": from,
+            "to": to,
+            "added": diff.added.iter().map(episode).collect::<Vec<_>>(),
+            "removed": diff.removed.iter().map(episode).collect::<Vec<_>>(),
+            "edited": diff.edited.iter().map(|edit| json!({
+                "title": edit.after.title,
+                "guid": edit.after.guid,
+                "url": edit.after.url,
+                "fields": edit.fields,
+            })).collect::<Vec<_>>(),
+        }));
+    }
+
+    println!("{} → {}", name_of(&from), name_of(&to));
+    if diff.is_empty() {
+        println!("{}", tr!("no-episodes-changed"));
+        return Ok(());
+    }
+    for episode in &diff.added {
+        println!("+ {}", episode.title);
+    }
+    for episode in &diff.removed {
+        println!("- {}", episode.title);
+    }
+    for edit in &diff.edited {
+        println!("~ {} ({})", edit.after.title, edit.fields.join(", "));
+        if edit.before.title != edit.after.title {
+            println!(
+                "    {}",
+                tr!("snapshot-was", title = edit.before.title.as_str())
+            );
+        }
+    }
+    Ok(())
+}
+
+/// A folder of snapshots (such as an archive's `feed/`), or the snapshots
+/// kept for a feed URL
+fn snapshots_of(feed: &str) -> Result<Snapshots> {
+    let path = Path::new(feed);
+    if path.is_dir() {
+        return Ok(Snapshots::new(path));
+    }
+    Ok(SnapshotStore::open_default()?.for_feed(feed))
+}
+
+/// A snapshot given by path or by file name
+fn find(snapshots: &Snapshots, paths: &[PathBuf], name: &str) -> Result<PathBuf> {
+    let path = Path::new(name);
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    let path = snapshots.dir().join(name);
+    if !paths.contains(&path) {
+        bail!(
+            "No snapshot {:?} in {} (see `pdl feed snapshots`)",
+            name,
+            snapshots.dir().display()
+        );
+    }
+    Ok(path)
+}
+
+fn no_snapshots(feed: &str) -> String {
+    format!(
+        "No snapshots of {}; set `feed_snapshots = true` in config.toml to keep them",
+        feed
+    )
+}
+
+fn name_of(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
@@ -0,0 +1,345 @@
+use super::output;
+use anyhow::{Context, Result, bail};
+use futures_util::StreamExt;
+use futures_util::stream;
+use inquire::{Confirm, Select};
+use pdl::auth::Credentials;
+use pdl::cache::CachedFeed;
+use pdl::discover::FeedPage;
+use pdl::history::History;
+use pdl::opml::{self, OpmlFeed};
+use pdl::subscriptions::Subscriptions;
+use pdl::tr;
+use pdl::{Feed, PodcastClient};
+use serde_json::json;
+use std::fs;
+use std::io;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Number of feeds checked at once during an OPML import
+const IMPORT_CHECK_CONCURRENCY: usize = 8;
+
+/// Ask which subscribed feed to use, suggesting a few feeds to someone who
+/// hasn't subscribed to any
+pub fn select_feed() -> Result<String> {
+    let mut feeds = Subscriptions::load()?.feeds;
+    if feeds.is_empty() {
+        if !output::interactive() {
+            bail!("No subscribed feeds. Add one with `pdl subscribe <url>` or pass --feed <url>");
+        }
+        feeds = Subscriptions::suggestions();
+    }
+
+    let feed_names: Vec<&str> = feeds.iter().map(|f| f.name.as_str()).collect();
+    let selected_feed_name = Select::new(&tr!("select-feed"), feed_names)
+        .prompt()
+        .context("Failed to get feed selection")?;
+
+    let selected_feed = feeds
+        .iter()
+        .find(|f| f.name == selected_feed_name)
+        .context("Could not find selected feed")?;
+
+    Ok(selected_feed.url.clone())
+}
+
+/// The feed URLs listed in the file at `path`, or on stdin for `-`: one per
+/// line, skipping blank lines and `#` comments
+pub fn read_feed_list(path: &Path) -> Result<Vec<String>> {
+    let text = if path == Path::new("-") {
+        io::read_to_string(io::stdin()).context("Failed to read feed URLs from stdin")?
+    } else {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+    };
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Fetch the feed at `url`. Links shared from podcast apps are resolved to
+/// their RSS feed first, and when `url` turns out to be a show's web page,
+/// the feed the page links to is used, asking which one if there are several.
+/// Returns the URL the feed was read from along with it.
+pub async fn fetch_feed(
+    client: &PodcastClient,
+    url: &str,
+    limit: usize,
+    interactive: bool,
+) -> Result<(String, Feed)> {
+    let (url, _, feed) = fetch_feed_document(client, url, limit, interactive).await?;
+    Ok((url, feed))
+}
+
+/// [`fetch_feed`], also returning the feed document as served (its first
+/// page, for paged feeds)
+pub async fn fetch_feed_document(
+    client: &PodcastClient,
+    url: &str,
+    limit: usize,
+    interactive: bool,
+) -> Result<(String, CachedFeed, Feed)> {
+    let url = match client.resolve_share_link(url).await? {
+        Some(feed_url) => {
+            info!("Resolved {} to {}", url, feed_url);
+            feed_url
+        }
+        None => url.to_string(),
+    };
+    let (url, (document, feed)) = match client.fetch_feed_document(&url, limit).await {
+        Ok(fetched) => (url, fetched),
+        Err(error) => {
+            let Some(page) = error.downcast_ref::<FeedPage>() else {
+                return Err(error);
+            };
+            let found = match page.feeds.as_slice() {
+                [] => return Err(error),
+                [feed] => feed.clone(),
+                feeds if interactive => Select::new(&tr!("select-linked-feed"), feeds.to_vec())
+                    .prompt()
+                    .context("Failed to get feed selection")?,
+                feeds => {
+                    let urls: Vec<String> =
+                        feeds.iter().map(|feed| format!("  {}", feed)).collect();
+                    bail!("{}; pass one of them instead:\n{}", page, urls.join("\n"));
+                }
+            };
+            info!("Found feed: {}", found);
+            let fetched = client.fetch_feed_document(&found.url, limit).await?;
+            (found.url, fetched)
+        }
+    };
+    let feed = client.fetch_older_pages(feed, &url, limit).await?;
+    let url = follow_moved(client, &url, interactive)?.unwrap_or(url);
+    Ok((url, document, feed))
+}
+
+/// If the subscribed feed at `url` permanently moved when last fetched,
+/// point the subscription and its history at the new address, asking first
+/// when `interactive`. Returns the new URL if the subscription was updated.
+pub fn follow_moved(
+    client: &PodcastClient,
+    url: &str,
+    interactive: bool,
+) -> Result<Option<String>> {
+    let Some(moved) = client.feed_moved(url) else {
+        return Ok(None);
+    };
+    let mut subscriptions = Subscriptions::load()?;
+    let Some(subscription) = subscriptions.find_url(url) else {
+        info!("{} has moved to {}", url, moved);
+        return Ok(None);
+    };
+    let name = subscription.name.clone();
+    if subscriptions.contains_url(&moved) {
+        // Two subscriptions to the same feed would download everything twice
+        warn!(
+            "\"{}\" has moved to {}, which you're also subscribed to; `pdl unsubscribe \"{}\"` drops the old one",
+            name, moved, name
+        );
+        return Ok(None);
+    }
+    if Credentials::from_url(url).is_some() {
+        warn!(
+            "\"{}\" has moved to {}; subscribe to the new address with its login to keep it working",
+            name, moved
+        );
+        return Ok(None);
+    }
+    if interactive {
+        let update = Confirm::new(&tr!(
+            "confirm-moved",
+            name = name.as_str(),
+            url = moved.as_str()
+        ))
+        .with_default(true)
+        .prompt()
+        .context("Failed to get confirmation")?;
+        if !update {
+            return Ok(None);
+        }
+    }
+    for subscription in subscriptions.feeds.iter_mut().filter(|f| f.url == url) {
+        subscription.url = moved.clone();
+    }
+    subscriptions.save()?;
+    History::open_default()?.move_feed(url, &moved)?;
+    info!(
+        "{}",
+        tr!("feed-moved", name = name.as_str(), url = moved.as_str())
+    );
+    Ok(Some(moved))
+}
+
+pub async fn subscribe(client: &PodcastClient, url: &str, name: Option<String>) -> Result<()> {
+    let mut subscriptions = Subscriptions::load()?;
+
+    // Fetch the feed to validate it and to default the name to its title
+    let interactive = output::interactive();
+    let (url, feed) = fetch_feed(client, url, 0, interactive).await?;
+    let url = url.as_str();
+    let name = name.unwrap_or_else(|| match feed.title.as_str() {
+        "" => url.to_string(),
+        title => title.to_string(),
+    });
+
+    subscriptions.add(&name, url)?;
+    subscriptions.save()?;
+
+    if output::json() {
+        return output::emit(&json!({ "subscribed": { "name": name, "url": url } }));
+    }
+    println!("{}", tr!("subscribed", name = name.as_str()));
+    Ok(())
+}
+
+pub fn unsubscribe(name: &str) -> Result<()> {
+    let mut subscriptions = Subscriptions::load()?;
+    let removed = subscriptions.remove(name)?;
+    subscriptions.save()?;
+
+    if output::json() {
+        return output::emit(&json!({ "unsubscribed": removed }));
+    }
+    println!("{}", tr!("unsubscribed", name = &removed.name));
+    Ok(())
+}
+
+pub fn list_feeds() -> Result<()> {
+    let subscriptions = Subscriptions::load()?;
+    if output::json() {
+        return output::emit(&subscriptions.feeds);
+    }
+    if output::porcelain() {
+        for feed in &subscriptions.feeds {
+            output::row(&[&feed.name, &feed.url]);
+        }
+        return Ok(());
+    }
+    if subscriptions.feeds.is_empty() {
+        println!("{}", tr!("no-feeds"));
+        return Ok(());
+    }
+
+    for feed in &subscriptions.feeds {
+        println!("{}\n  {}", feed.name, feed.url);
+    }
+    Ok(())
+}
+
+pub async fn import_opml(client: &PodcastClient, path: &Path, check: bool) -> Result<()> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let feeds = opml::parse(&content)?;
+    if feeds.is_empty() {
+        info!("No feeds found in {}", path.display());
+        if output::json() {
+            return output::emit(&json!({ "imported": [], "duplicates": [], "unreachable": [] }));
+        }
+        return Ok(());
+    }
+
+    let mut subscriptions = Subscriptions::load()?;
+    let (mut duplicates, new): (Vec<OpmlFeed>, Vec<OpmlFeed>) = feeds
+        .into_iter()
+        .partition(|feed| subscriptions.contains_url(&feed.url));
+
+    // Make sure each new feed actually serves a parseable feed
+    let (reachable, unreachable) = if check {
+        info!("Checking {} feeds...", new.len());
+        let results: Vec<(OpmlFeed, Result<()>)> = stream::iter(new)
+            .map(|feed| async move {
+                let result = client.fetch_feed(&feed.url, 0).await.map(|_| ());
+                (feed, result)
+            })
+            .buffered(IMPORT_CHECK_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut reachable = Vec::new();
+        let mut unreachable = Vec::new();
+        for (feed, result) in results {
+            match result {
+                Ok(()) => reachable.push(feed),
+                Err(e) => unreachable.push((feed, e)),
+            }
+        }
+        (reachable, unreachable)
+    } else {
+        (new, Vec::new())
+    };
+
+    let mut imported = Vec::new();
+    for feed in reachable {
+        // The same feed may be listed more than once in the file
+        if subscriptions.contains_url(&feed.url) {
+            duplicates.push(feed);
+            continue;
+        }
+        let name = subscriptions.unique_name(&feed.title);
+        subscriptions.add(&name, &feed.url)?;
+        info!("✓ {}", name);
+        imported.push(json!({ "name": name, "url": feed.url }));
+    }
+    subscriptions.save()?;
+
+    if output::json() {
+        let unreachable: Vec<_> = unreachable
+            .iter()
+            .map(|(feed, e)| json!({ "title": feed.title, "url": feed.url, "error": format!("{:#}", e) }))
+            .collect();
+        let duplicates: Vec<_> = duplicates
+            .iter()
+            .map(|feed| json!({ "title": feed.title, "url": feed.url }))
+            .collect();
+        return output::emit(&json!({
+            "imported": imported,
+            "duplicates": duplicates,
+            "unreachable": unreachable,
+        }));
+    }
+
+    for feed in &duplicates {
+        println!("⏭ Already subscribed: {}", feed.title);
+    }
+    for (feed, e) in &unreachable {
+        println!("✗ Unreachable: {} ({:#})", feed.title, e);
+    }
+
+    println!(
+        "\nImported {} feeds, {} duplicates, {} unreachable",
+        imported.len(),
+        duplicates.len(),
+        unreachable.len()
+    );
+    Ok(())
+}
+
+pub fn export_opml(output: Option<&Path>) -> Result<()> {
+    let subscriptions = Subscriptions::load()?;
+    let feeds: Vec<OpmlFeed> = subscriptions
+        .feeds
+        .iter()
+        .map(|f| OpmlFeed {
+            title: f.name.clone(),
+            url: f.url.clone(),
+        })
+        .collect();
+    let xml = opml::write(&feeds);
+
+    match output {
+        Some(path) => {
+            fs::write(path, xml).with_context(|| format!("Failed to write {}", path.display()))?;
+            if output::json() {
+                return output::emit(&json!({ "path": path, "feeds": feeds.len() }));
+            }
+            println!("✓ Exported {} feeds to {}", feeds.len(), path.display());
+        }
+        None if output::json() => return output::emit(&json!({ "opml": xml })),
+        None => print!("{}", xml),
+    }
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use super::output;
+use anyhow::Result;
+use pdl::history::History;
+use pdl::tr;
+
+pub fn show_history(limit: usize) -> Result<()> {
+    let history = History::open_default()?;
+    let entries = history.entries(limit)?;
+    if output::json() {
+        return output::emit(&entries);
+    }
+    if output::porcelain() {
+        for entry in &entries {
+            output::row(&[
+                entry.downloaded_at.as_str(),
+                &entry.title,
+                &entry.path.to_string_lossy(),
+            ]);
+        }
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!("{}", tr!("no-history"));
+        return Ok(());
+    }
+
+    for entry in &entries {
+        println!("{}  {}", entry.downloaded_at, entry.title);
+        println!("  {}", entry.path.display());
+    }
+    Ok(())
+}
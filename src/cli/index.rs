@@ -0,0 +1,29 @@
+use super::output;
+use anyhow::Result;
+use pdl::config::Config;
+use pdl::history::History;
+use pdl::library::{EpisodeState, LibraryQuery};
+use pdl::site;
+use std::path::Path;
+use tracing::info;
+
+/// Write the HTML pages for browsing everything downloaded into `output_dir`,
+/// or the download directory
+pub fn index(config: &Config, output_dir: Option<&Path>) -> Result<()> {
+    let episodes = History::open_default()?.query(&LibraryQuery {
+        state: Some(EpisodeState::Downloaded),
+        ..Default::default()
+    })?;
+    let dir = output_dir.map_or_else(|| config.download_dir(), Path::to_path_buf);
+    let site = site::write(&dir, &episodes)?;
+    if output::json() {
+        return output::emit(&site);
+    }
+    info!(
+        "✓ Wrote pages for {} episode(s) of {} show(s); open {}",
+        site.episodes,
+        site.shows,
+        dir.join(site::INDEX_PAGE).display()
+    );
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use super::{FilterArgs, feeds, load_played};
+use anyhow::{Context, Result};
+use pdl::PodcastClient;
+use pdl::config::Config;
+use pdl::listing::{self, ListFormat};
+
+/// Print a feed's episodes without prompting
+pub async fn list(
+    client: &PodcastClient,
+    config: &Config,
+    feed: Option<String>,
+    limit: Option<usize>,
+    filter: FilterArgs,
+    format: ListFormat,
+) -> Result<()> {
+    let feed_url = feed
+        .or_else(|| config.default_feed.clone())
+        .context("No feed given: pass --feed <url> or set default_feed in config.toml")?;
+
+    let mut filter = filter.filter();
+    let fetch_limit = if filter.is_empty() {
+        limit.unwrap_or(usize::MAX)
+    } else {
+        usize::MAX
+    };
+    let (feed_url, mut feed) = feeds::fetch_feed(client, &feed_url, fetch_limit, false).await?;
+    load_played(&mut filter, &feed_url)?;
+    filter.apply(&mut feed.episodes);
+    if let Some(limit) = limit {
+        feed.episodes.truncate(limit);
+    }
+    print!("{}", listing::render(&feed.episodes, format)?);
+    Ok(())
+}
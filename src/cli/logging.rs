@@ -0,0 +1,174 @@
+use super::output;
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, MakeWriter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, Registry};
+
+/// Options for what pdl prints while it works, and where it keeps a log
+#[derive(clap::Args, Debug)]
+pub struct LogArgs {
+    /// Only print results and errors: no banner, status messages, warnings, progress bars or prompts
+    #[arg(short, long, global = true, conflicts_with = "debug")]
+    quiet: bool,
+
+    /// Also print debug messages: requests, retries, cache hits
+    #[arg(short, long, global = true)]
+    debug: bool,
+
+    /// Append log records, with timestamps and levels, to this file
+    #[arg(long, global = true, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Format of log records; JSON records go to stderr unless --log-file is given
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        value_name = "FORMAT"
+    )]
+    log_format: LogFormat,
+
+    /// How download progress is shown: bars, or JSON events on stderr for other programs to draw their own
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        value_name = "FORMAT"
+    )]
+    progress: ProgressFormat,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum ProgressFormat {
+    #[default]
+    Bar,
+    /// One JSON object per line: start, progress and done events
+    Json,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per record
+    Json,
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+impl LogArgs {
+    /// Install the logger for this run. `timestamps` dates each message on
+    /// the terminal too, for long-running commands whose output is a log.
+    pub fn init(&self, timestamps: bool) -> Result<()> {
+        output::set_quiet(self.quiet);
+        output::set_progress_json(self.progress == ProgressFormat::Json);
+        let level = if self.debug {
+            Level::DEBUG
+        } else if self.quiet {
+            Level::ERROR
+        } else {
+            Level::INFO
+        };
+        let console_filter = Targets::new().with_target("pdl", level);
+        let mut layers: Vec<BoxedLayer> = Vec::new();
+        layers.push(match (self.log_format, &self.log_file) {
+            (LogFormat::Json, None) => tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(io::stderr)
+                .with_filter(console_filter)
+                .boxed(),
+            _ => tracing_subscriber::fmt::layer()
+                .event_format(Console { timestamps })
+                .with_writer(ConsoleWriter)
+                .with_filter(console_filter)
+                .boxed(),
+        });
+
+        // The file gets status messages even with --quiet, for auditing
+        if let Some(path) = &self.log_file {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            let filter = Targets::new().with_target("pdl", level.max(Level::INFO));
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(Mutex::new(file));
+            layers.push(match self.log_format {
+                LogFormat::Text => layer.with_filter(filter).boxed(),
+                LogFormat::Json => layer.json().with_filter(filter).boxed(),
+            });
+        }
+
+        tracing_subscriber::registry()
+            .with(layers)
+            .try_init()
+            .context("Failed to set up logging")
+    }
+}
+
+/// Terminal output: just the message, marked for warnings and errors
+struct Console {
+    timestamps: bool,
+}
+
+impl<S, N> FormatEvent<S, N> for Console
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        if self.timestamps {
+            write!(
+                writer,
+                "[{}] ",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+            )?;
+        }
+        match *event.metadata().level() {
+            Level::ERROR => write!(writer, "Error: ")?,
+            Level::WARN => write!(writer, "⚠ ")?,
+            _ => {}
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+/// Status messages go to stdout like the rest of the output, except when
+/// stdout carries JSON results or episode data; everything else goes to stderr
+struct ConsoleWriter;
+
+impl<'a> MakeWriter<'a> for ConsoleWriter {
+    type Writer = Box<dyn io::Write + 'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        Box::new(io::stderr())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        if *meta.level() == Level::INFO && !output::stdout_reserved() {
+            Box::new(io::stdout())
+        } else {
+            Box::new(io::stderr())
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use anyhow::{Context, Result};
+use pdl::tr;
+use std::io;
+use std::path::Path;
+
+/// Print the man page for `command`, or with `output_dir`, write it there as
+/// `pdl.1` along with one page per subcommand (`pdl-download.1`, ...)
+pub fn man(command: clap::Command, output_dir: Option<&Path>) -> Result<()> {
+    let Some(dir) = output_dir else {
+        return clap_mangen::Man::new(command)
+            .render(&mut io::stdout().lock())
+            .context("Failed to write man page");
+    };
+
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    clap_mangen::generate_to(command, dir)
+        .with_context(|| format!("Failed to write man pages to {}", dir.display()))?;
+    println!("{}", tr!("man-written", dir = dir.display().to_string()));
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use super::output;
+use anyhow::{Result, bail};
+use pdl::history::History;
+use std::path::Path;
+use tracing::info;
+
+/// What `pdl mark` sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Mark {
+    Played,
+    Unplayed,
+}
+
+/// Mark the episodes matching each of `episodes` (a GUID, a downloaded
+/// file, or part of a title), or every episode of `show`, played or
+/// unplayed
+pub fn mark(mark: Mark, episodes: &[String], show: Option<&str>) -> Result<()> {
+    if episodes.is_empty() && show.is_none() {
+        bail!("Say which episodes to mark: a title, GUID or file, or --show for a whole show");
+    }
+    let played = mark == Mark::Played;
+    let history = History::open_default()?;
+    let mut marked = Vec::new();
+    if episodes.is_empty() {
+        marked = history.mark_played(None, show, played)?;
+    }
+    for episode in episodes {
+        let key = match Path::new(episode).is_file() {
+            true => std::path::absolute(episode)?.to_string_lossy().into_owned(),
+            false => episode.clone(),
+        };
+        let titles = history.mark_played(Some(&key), show, played)?;
+        if titles.is_empty() {
+            bail!(
+                "No episode in the library matches {:?} (`pdl query` searches it)",
+                episode
+            );
+        }
+        marked.extend(titles);
+    }
+
+    if output::json() {
+        return output::emit(&marked);
+    }
+    let state = if played { "played" } else { "unplayed" };
+    for title in &marked {
+        info!("✓ Marked {}: {}", state, title);
+    }
+    if marked.is_empty() {
+        info!("No episodes to mark.");
+    }
+    Ok(())
+}
@@ -0,0 +1,332 @@
+pub mod archive;
+pub mod auth;
+pub mod config;
+pub mod dedupe;
+pub mod download;
+pub mod feed;
+pub mod feeds;
+pub mod history;
+pub mod index;
+pub mod list;
+pub mod logging;
+pub mod man;
+pub mod mark;
+pub mod notify;
+pub mod output;
+pub mod play;
+pub mod progress;
+pub mod prune;
+pub mod query;
+pub mod queue;
+pub mod search;
+pub mod serve;
+pub mod serve_feed;
+pub mod stats;
+pub mod stream;
+pub mod sync;
+pub mod transcribe;
+pub mod transfers;
+pub mod tui;
+pub mod update;
+pub mod watch;
+
+use anyhow::{Context, Result, bail};
+use pdl::PodcastClient;
+use pdl::auth::Credentials;
+use pdl::backend::{Downloader, External};
+use pdl::cache::FeedCache;
+use pdl::client::{self, DEFAULT_USER_AGENT, Redirects};
+use pdl::config::Config;
+use pdl::credentials::CredentialStore;
+use pdl::history::History;
+use pdl::retry::RetryPolicy;
+use pdl::select::{self, EpisodeFilter, NumberRanges};
+use pdl::snapshot::SnapshotStore;
+use pdl::subscriptions::Subscriptions;
+use pdl::throttle::{self, RateLimit};
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Proxy};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Options for how pdl talks to feed and media servers
+#[derive(clap::Args, Debug)]
+pub struct NetworkArgs {
+    /// Times to retry a failed feed fetch or download (timeouts, resets, 5xx)
+    #[arg(long, global = true, default_value_t = 3, value_name = "N")]
+    retries: u32,
+
+    /// Delay before the first retry, doubled on each further retry (e.g. 500ms, 2s)
+    #[arg(long, global = true, default_value = "1s", value_parser = humantime::parse_duration)]
+    retry_delay: Duration,
+
+    /// Give up connecting to a server after this long (e.g. 10s)
+    #[arg(long, global = true, default_value = "30s", value_parser = humantime::parse_duration)]
+    connect_timeout: Duration,
+
+    /// Give up on a feed or download when no data arrives for this long (e.g. 2m)
+    #[arg(long, global = true, default_value = "60s", value_parser = humantime::parse_duration)]
+    read_timeout: Duration,
+
+    /// Send all requests through this proxy: http://, https://, socks5:// or socks5h://
+    /// (overrides the proxy setting and HTTP_PROXY/HTTPS_PROXY/ALL_PROXY)
+    #[arg(long, global = true, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// User-Agent to send with feed and download requests [default: pdl/VERSION]
+    #[arg(long, global = true, value_name = "STRING")]
+    user_agent: Option<String>,
+
+    /// Extra request header, e.g. "Referer: https://example.com" (repeatable)
+    #[arg(long = "header", short = 'H', global = true, value_name = "NAME: VALUE", value_parser = client::parse_header)]
+    headers: Vec<(HeaderName, HeaderValue)>,
+
+    /// Log in to private feeds with HTTP Basic auth (also sent to downloads from the feed's host)
+    #[arg(long, global = true, value_name = "USER:PASSWORD", value_parser = Credentials::basic)]
+    auth: Option<Credentials>,
+
+    /// Log in to private feeds with a bearer token (also sent to downloads from the feed's host)
+    #[arg(long, global = true, value_name = "TOKEN", conflicts_with = "auth")]
+    bearer: Option<String>,
+
+    /// Also trust the CA certificates in this PEM file (e.g. a company or intranet CA)
+    #[arg(long, global = true, value_name = "FILE")]
+    cacert: Option<PathBuf>,
+
+    /// Don't verify TLS certificates (for misconfigured hosts; anyone on the network can intercept)
+    #[arg(long, global = true)]
+    insecure: bool,
+
+    /// Cap the combined download speed, e.g. 500k or 2M (bytes per second)
+    #[arg(long, global = true, value_name = "RATE", value_parser = throttle::parse_rate)]
+    limit_rate: Option<u64>,
+
+    /// Fetch each download over up to N connections at once, from servers that allow it
+    #[arg(long, global = true, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=16))]
+    segments: u16,
+
+    /// Program that fetches episode media (defaults to `downloader` in config, then builtin)
+    #[arg(long, global = true, value_enum)]
+    downloader: Option<Downloader>,
+
+    /// Download feeds in full instead of revalidating cached copies
+    #[arg(long, global = true)]
+    no_cache: bool,
+}
+
+impl NetworkArgs {
+    /// Build the client used for every network request in this run
+    pub fn client(&self, config: &Config) -> Result<PodcastClient> {
+        // A stalled server times out and is retried instead of hanging the run
+        let mut http = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.read_timeout)
+            .user_agent(self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT))
+            .default_headers(self.headers.iter().cloned().collect::<HeaderMap>());
+        // Without one, reqwest picks up HTTP_PROXY, HTTPS_PROXY, ALL_PROXY and NO_PROXY
+        if let Some(proxy) = self.proxy.as_ref().or(config.proxy.as_ref()) {
+            http = http.proxy(proxy_from_url(proxy)?);
+        }
+        if let Some(path) = &self.cacert {
+            for certificate in read_certificates(path)? {
+                http = http.add_root_certificate(certificate);
+            }
+        }
+        if self.insecure {
+            tracing::warn!("TLS certificate verification is disabled (--insecure)");
+            http = http.danger_accept_invalid_certs(true);
+        }
+        // Logs each hop with -d and notes feeds that have moved for good
+        let redirects = Redirects::default();
+        let http = http
+            .redirect(redirects.policy())
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        let mut client = PodcastClient::with_http_client(http)
+            .with_redirects(redirects)
+            .with_retry_policy(RetryPolicy {
+                retries: self.retries,
+                base_delay: self.retry_delay,
+            })
+            .with_filename_template(config.filename_template()?)
+            .with_credential_store(CredentialStore::load()?);
+        let credentials = self
+            .auth
+            .clone()
+            .or(self.bearer.clone().map(Credentials::Bearer));
+        if let Some(credentials) = credentials {
+            client = client.with_credentials(credentials);
+        }
+        for subscription in Subscriptions::load()?.feeds {
+            // A broken login only affects its own feed, not the whole run
+            match subscription.credentials() {
+                Ok(Some(credentials)) => {
+                    client = client.with_feed_login(&subscription.url, credentials)
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("{:#}", e),
+            }
+        }
+        if let Some(rate) = self.limit_rate {
+            client = client.with_rate_limit(RateLimit::new(rate));
+        }
+        if self.segments > 1 {
+            client = client.with_segments(self.segments);
+        }
+        let downloader = self.downloader.or(config.downloader).unwrap_or_default();
+        if let Some(program) = downloader.program() {
+            match External::find(downloader) {
+                Some(external) => client = client.with_backend(self.external(external, config)),
+                None => tracing::warn!(
+                    "{} isn't installed; downloading with pdl's own client",
+                    program
+                ),
+            }
+        }
+        if config.feed_snapshots == Some(true) {
+            client = client.with_snapshot_store(SnapshotStore::open_default()?);
+        }
+        if self.no_cache {
+            return Ok(client);
+        }
+        Ok(client.with_feed_cache(FeedCache::open_default()?))
+    }
+
+    /// `external` set up to send what pdl's own client would: the
+    /// User-Agent, `--header`s and proxy
+    fn external(&self, external: External, config: &Config) -> External {
+        let headers = self
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let external = external
+            .with_user_agent(self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT))
+            .with_headers(headers);
+        match self.proxy.as_ref().or(config.proxy.as_ref()) {
+            Some(proxy) => external.with_proxy(proxy),
+            None => external,
+        }
+    }
+}
+
+/// The certificates in a PEM bundle
+fn read_certificates(path: &Path) -> Result<Vec<Certificate>> {
+    let pem = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let certificates = Certificate::from_pem_bundle(&pem)
+        .with_context(|| format!("Invalid certificate file {}", path.display()))?;
+    if certificates.is_empty() {
+        bail!("No certificates found in {}", path.display());
+    }
+    Ok(certificates)
+}
+
+/// A proxy for every request, checking the scheme is one reqwest can use
+fn proxy_from_url(url: &str) -> Result<Proxy> {
+    let parsed =
+        reqwest::Url::parse(url).with_context(|| format!("Invalid proxy URL {:?}", url))?;
+    if !PROXY_SCHEMES.contains(&parsed.scheme()) {
+        bail!(
+            "Unsupported proxy scheme {:?} (expected one of: {})",
+            parsed.scheme(),
+            PROXY_SCHEMES.join(", ")
+        );
+    }
+    Proxy::all(url).context("Invalid proxy URL")
+}
+
+/// Proxy URL schemes; `socks4a` and `socks5h` resolve host names on the proxy (needed for Tor)
+const PROXY_SCHEMES: [&str; 6] = ["http", "https", "socks4", "socks4a", "socks5", "socks5h"];
+
+/// Conditions that narrow a feed's episodes before listing or selecting them
+#[derive(clap::Args, Debug)]
+pub struct FilterArgs {
+    /// Only consider episodes whose title matches this pattern
+    #[arg(long, value_name = "REGEX", value_parser = Regex::new)]
+    filter: Option<Regex>,
+
+    /// Only consider episodes published on or after this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE", value_parser = select::parse_date)]
+    since: Option<chrono::NaiveDate>,
+
+    /// Only consider episodes published on or before this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE", value_parser = select::parse_date)]
+    until: Option<chrono::NaiveDate>,
+
+    /// Only consider episodes of this season
+    #[arg(long, value_name = "N")]
+    season: Option<u32>,
+
+    /// Only consider episodes with these numbers, e.g. 10-20 or 1,4,7-9
+    #[arg(long, value_name = "RANGES")]
+    episodes: Option<NumberRanges>,
+
+    /// Only consider episodes marked played (`pdl mark`)
+    #[arg(long, conflicts_with = "unplayed")]
+    played: bool,
+
+    /// Only consider episodes not marked played
+    #[arg(long)]
+    unplayed: bool,
+}
+
+impl FilterArgs {
+    pub fn filter(&self) -> EpisodeFilter {
+        EpisodeFilter {
+            title: self.filter.clone(),
+            since: self.since,
+            until: self.until,
+            season: self.season,
+            numbers: self.episodes.clone(),
+            played: played_filter(self.played, self.unplayed),
+            played_keys: HashSet::new(),
+        }
+    }
+}
+
+/// What `--played` and `--unplayed` ask for
+pub fn played_filter(played: bool, unplayed: bool) -> Option<bool> {
+    match (played, unplayed) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+/// Fill in which of the feed at `feed_url`'s episodes were played, if
+/// `filter` goes by that
+pub fn load_played(filter: &mut EpisodeFilter, feed_url: &str) -> Result<()> {
+    if filter.played.is_some() {
+        filter.played_keys = History::open_default()?.played_keys(feed_url)?;
+    }
+    Ok(())
+}
+
+/// Greet a person at a terminal; never printed into pipes, files or
+/// `--quiet` runs
+pub fn display_banner() {
+    if !io::stdout().is_terminal() || output::quiet() || output::stdout_reserved() {
+        return;
+    }
+    println!(
+        r#"
+╔═══════════════════════════════════════════════════════╗
+║                                                       ║
+║   ██████╗  ██████╗ ██████╗  ██████╗ █████╗ ███████╗ ║
+║   ██╔══██╗██╔═══██╗██╔══██╗██╔════╝██╔══██╗██╔════╝ ║
+║   ██████╔╝██║   ██║██║  ██║██║     ███████║███████╗ ║
+║   ██╔═══╝ ██║   ██║██║  ██║██║     ██╔══██║╚════██║ ║
+║   ██║     ╚██████╔╝██████╔╝╚██████╗██║  ██║███████║ ║
+║   ╚═╝      ╚═════╝ ╚═════╝  ╚═════╝╚═╝  ╚═╝╚══════╝ ║
+║                                                       ║
+║              Podcast Downloader v0.1.0                ║
+║                                                       ║
+╚═══════════════════════════════════════════════════════╝
+"#
+    );
+}
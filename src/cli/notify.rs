@@ -0,0 +1,41 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::warn;
+
+/// Set by `--notify` for the whole run
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Set once a notification has reported a failure, so the error the run
+/// ends with isn't reported again
+static FAILURE_SENT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Show a desktop notification, if `--notify` asked for them. Failing to
+/// show one is only logged: it shouldn't fail the work it reports on.
+pub fn send(summary: &str, body: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let shown = notify_rust::Notification::new()
+        .appname("pdl")
+        .summary(summary)
+        .body(body)
+        .show();
+    if let Err(e) = shown {
+        warn!("Failed to show a desktop notification: {}", e);
+    }
+}
+
+/// Show a desktop notification of a failure, which the run's own error
+/// notification then leaves out
+pub fn send_failure(summary: &str, body: &str) {
+    FAILURE_SENT.store(true, Ordering::Relaxed);
+    send(summary, body);
+}
+
+/// Whether a notification has already reported a failure
+pub fn failure_sent() -> bool {
+    FAILURE_SENT.load(Ordering::Relaxed)
+}
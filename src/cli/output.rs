@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use indicatif::ProgressDrawTarget;
+use pdl::listing;
+use ratatui::crossterm::{cursor, execute, terminal};
+use serde::Serialize;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by `--json` for the whole run
+static JSON: AtomicBool = AtomicBool::new(false);
+/// Set by `--porcelain` for the whole run
+static PORCELAIN: AtomicBool = AtomicBool::new(false);
+/// Set by `--quiet` for the whole run
+static QUIET: AtomicBool = AtomicBool::new(false);
+/// Set by `--progress json` for the whole run
+static PROGRESS_JSON: AtomicBool = AtomicBool::new(false);
+/// Set while episode data is written to stdout
+static STREAMING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(enabled: bool) {
+    JSON.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether results should be printed as JSON rather than text
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+pub fn set_porcelain(enabled: bool) {
+    PORCELAIN.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether results should be printed as tab-separated rows for scripts
+pub fn porcelain() -> bool {
+    PORCELAIN.load(Ordering::Relaxed)
+}
+
+pub fn set_quiet(enabled: bool) {
+    QUIET.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether progress bars and other chatter should be left out
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn set_progress_json(enabled: bool) {
+    PROGRESS_JSON.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether download progress is reported as JSON events instead of bars
+pub fn progress_json() -> bool {
+    PROGRESS_JSON.load(Ordering::Relaxed)
+}
+
+/// Keep stdout for the episode data `--output -` writes there
+pub fn set_streaming() {
+    STREAMING.store(true, Ordering::Relaxed);
+}
+
+/// Whether stdout is kept for JSON or porcelain results or episode data,
+/// so messages have to go to stderr
+pub fn stdout_reserved() -> bool {
+    json() || porcelain() || STREAMING.load(Ordering::Relaxed)
+}
+
+/// Whether pdl may prompt: stdin is a terminal and the run wasn't asked to
+/// be predictable with `--json`, `--porcelain` or `--quiet`
+pub fn interactive() -> bool {
+    io::stdin().is_terminal() && !json() && !porcelain() && !quiet()
+}
+
+/// Where progress bars draw: stderr, or nowhere with `--quiet` or
+/// `--progress json`
+pub fn progress_target() -> ProgressDrawTarget {
+    if quiet() || progress_json() {
+        ProgressDrawTarget::hidden()
+    } else {
+        ProgressDrawTarget::stderr()
+    }
+}
+
+/// Print `value` to stdout as one line of JSON
+pub fn emit(value: &impl Serialize) -> Result<()> {
+    let line = serde_json::to_string(value).context("Failed to serialize output")?;
+    println!("{}", line);
+    Ok(())
+}
+
+/// Print one `--porcelain` row to stdout: `fields` separated by tabs
+pub fn row<S: AsRef<str>>(fields: &[S]) {
+    println!("{}", listing::tsv_row(fields));
+}
+
+/// Undo what an interrupted prompt or progress bar may have left behind:
+/// raw mode and a hidden cursor
+pub fn restore_terminal() {
+    let _ = terminal::disable_raw_mode();
+    if io::stderr().is_terminal() {
+        let _ = execute!(io::stderr(), cursor::Show);
+    }
+}
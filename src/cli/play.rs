@@ -0,0 +1,59 @@
+use super::output;
+use anyhow::{Context, Result, bail};
+use inquire::Select;
+use pdl::config::Config;
+use pdl::history::{History, HistoryEntry};
+use pdl::player::Player;
+use pdl::tr;
+use std::fmt;
+use tracing::{info, warn};
+
+/// A history entry as listed in the picker
+struct Choice(HistoryEntry);
+
+impl fmt::Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}  {}", self.0.downloaded_at, self.0.title)
+    }
+}
+
+/// Pick one of the last `n` downloaded episodes whose file is still there
+/// and play it, or the newest with `latest`
+pub fn play(config: &Config, n: usize, latest: bool) -> Result<()> {
+    let player = Player::find(config.player.as_deref())?;
+    let history = History::open_default()?;
+    let entries: Vec<HistoryEntry> = history
+        .entries(n)?
+        .into_iter()
+        .filter(|entry| entry.path.exists())
+        .collect();
+    if entries.is_empty() {
+        bail!("No downloaded episodes to play (`pdl history` lists what was downloaded)");
+    }
+
+    let entry = if latest {
+        entries
+            .into_iter()
+            .next()
+            .context("No downloaded episodes")?
+    } else {
+        if !output::interactive() {
+            bail!("Picking an episode needs a terminal; use --latest to play the newest");
+        }
+        let choices = entries.into_iter().map(Choice).collect();
+        Select::new(&tr!("select-played-episode"), choices)
+            .prompt()
+            .context("Failed to get episode selection")?
+            .0
+    };
+
+    info!(
+        "{}",
+        tr!("playing", title = &entry.title, player = player.program())
+    );
+    player.play(&[&entry.path])?;
+    if let Err(e) = history.mark_file_played(&entry.path) {
+        warn!("{:#}", e);
+    }
+    Ok(())
+}
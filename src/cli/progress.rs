@@ -0,0 +1,100 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use pdl::{DownloadOutcome, Episode};
+use serde::Serialize;
+use std::future::Future;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Time between progress events for a running download
+const INTERVAL: Duration = Duration::from_millis(500);
+
+/// What `--progress json` writes to stderr, one per line
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum Event<'a> {
+    /// A download began; `file` is where it's headed
+    Start {
+        show: &'a str,
+        title: &'a str,
+        file: &'a Path,
+    },
+    Progress {
+        file: &'a Path,
+        bytes: u64,
+        /// The file's size, once the server gives it
+        total: Option<u64>,
+        percent: Option<f64>,
+        bytes_per_sec: u64,
+    },
+    /// `status` is `downloaded`, `exists` or `failed`
+    Done {
+        file: &'a Path,
+        bytes: u64,
+        status: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+fn emit(event: &Event) {
+    if let Ok(line) = serde_json::to_string(event) {
+        // One write per line, so events from parallel downloads don't mix
+        let _ = writeln!(io::stderr().lock(), "{}", line);
+    }
+}
+
+/// Run `download` of `episode` of `show` into `file`, reporting its
+/// progress, which `pb` keeps, as JSON events on stderr
+pub async fn report(
+    show: &str,
+    episode: &Episode,
+    file: &Path,
+    pb: &ProgressBar,
+    download: impl Future<Output = Result<DownloadOutcome>>,
+) -> Result<DownloadOutcome> {
+    emit(&Event::Start {
+        show,
+        title: &episode.title,
+        file,
+    });
+    let mut ticks = tokio::time::interval(INTERVAL);
+    let mut reported = None;
+    tokio::pin!(download);
+    let result = loop {
+        tokio::select! {
+            result = &mut download => break result,
+            _ = ticks.tick() => {
+                let bytes = pb.position();
+                if reported == Some(bytes) {
+                    continue;
+                }
+                reported = Some(bytes);
+                // Bars start out at length 0 until the server answers
+                let total = pb.length().filter(|&len| len > 0);
+                emit(&Event::Progress {
+                    file,
+                    bytes,
+                    total,
+                    percent: total.map(|total| {
+                        (bytes as f64 * 1000.0 / total as f64).round() / 10.0
+                    }),
+                    bytes_per_sec: pb.per_sec() as u64,
+                });
+            }
+        }
+    };
+    let (file, status, error) = match &result {
+        Ok(DownloadOutcome::Downloaded(path)) => (path.as_path(), "downloaded", None),
+        Ok(DownloadOutcome::AlreadyExists(path)) => (path.as_path(), "exists", None),
+        Err(e) => (file, "failed", Some(format!("{:#}", e))),
+    };
+    emit(&Event::Done {
+        file,
+        bytes: pb.position(),
+        status,
+        error,
+    });
+    result
+}
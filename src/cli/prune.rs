@@ -0,0 +1,107 @@
+use super::output;
+use anyhow::{Result, bail};
+use chrono::Utc;
+use indicatif::HumanBytes;
+use pdl::config::Config;
+use pdl::history::History;
+use pdl::prune::{self, Download};
+use pdl::subscriptions::{Subscription, Subscriptions};
+use serde::Serialize;
+use tracing::{info, warn};
+
+/// A download deleted (or to be deleted) by `pdl prune`, as printed with `--json`
+#[derive(Serialize)]
+struct Pruned {
+    feed: String,
+    #[serde(flatten)]
+    download: Download,
+}
+
+/// Delete downloads that fall outside each subscription's retention
+/// settings (or only `feed`'s), listing them instead with `dry_run`
+pub fn prune(config: &Config, feed: Option<&str>, dry_run: bool) -> Result<()> {
+    let subscriptions = Subscriptions::load()?;
+    let selected: Vec<&Subscription> = match feed {
+        Some(name) => {
+            let Some(subscription) = subscriptions
+                .feeds
+                .iter()
+                .find(|s| s.name.eq_ignore_ascii_case(name))
+            else {
+                bail!("No subscription named \"{}\"", name);
+            };
+            if config.retention(subscription).is_empty() {
+                bail!(
+                    "\"{}\" has no keep_latest, max_age_days or max_size_gb in subscriptions.toml or the config",
+                    subscription.name
+                );
+            }
+            vec![subscription]
+        }
+        None => subscriptions
+            .feeds
+            .iter()
+            .filter(|s| !config.retention(s).is_empty())
+            .collect(),
+    };
+    if selected.is_empty() {
+        bail!(
+            "No subscription has a retention setting; add keep_latest, max_age_days or max_size_gb to a feed in subscriptions.toml, or to the config"
+        );
+    }
+
+    let history = History::open_default()?;
+    let mut pruned = Vec::new();
+    for subscription in selected {
+        for download in prune_feed(config, &history, subscription, dry_run)? {
+            pruned.push(Pruned {
+                feed: subscription.name.clone(),
+                download,
+            });
+        }
+    }
+
+    if output::json() {
+        return output::emit(&pruned);
+    }
+    let freed: u64 = pruned.iter().map(|p| p.download.size).sum();
+    match (pruned.len(), dry_run) {
+        (0, _) => info!("Nothing to prune."),
+        (n, true) => info!(
+            "\n{} file(s) would be deleted, freeing {}",
+            n,
+            HumanBytes(freed)
+        ),
+        (n, false) => info!("\n✓ Deleted {} file(s), freeing {}", n, HumanBytes(freed)),
+    }
+    Ok(())
+}
+
+/// Delete `subscription`'s downloads that fall outside its retention
+/// settings (or the configured ones), returning those deleted. Files that can't be deleted are
+/// reported and left out.
+pub fn prune_feed(
+    config: &Config,
+    history: &History,
+    subscription: &Subscription,
+    dry_run: bool,
+) -> Result<Vec<Download>> {
+    let retention = config.retention(subscription);
+    if retention.is_empty() {
+        return Ok(Vec::new());
+    }
+    let downloads = prune::downloads(history.feed_entries(&subscription.url)?);
+    let mut pruned = Vec::new();
+    for download in prune::select(&downloads, &retention, Utc::now().naive_utc()) {
+        if dry_run {
+            info!("Would delete: {}", download.path.display());
+        } else if let Err(e) = prune::remove(&download.path) {
+            warn!("{:#}", e);
+            continue;
+        } else {
+            info!("Deleted: {}", download.path.display());
+        }
+        pruned.push(download);
+    }
+    Ok(pruned)
+}
@@ -0,0 +1,146 @@
+use super::{output, played_filter};
+use anyhow::Result;
+use chrono::NaiveDate;
+use pdl::history::History;
+use pdl::library::{EpisodeState, LibraryQuery, QueryResult};
+use pdl::select;
+use pdl::tr;
+
+/// Search every episode pdl has seen, by its metadata or with SQL
+#[derive(clap::Args, Debug)]
+pub struct QueryArgs {
+    /// Words to look for in titles and show notes
+    #[arg(num_args = 0..)]
+    text: Vec<String>,
+
+    /// Only episodes of shows whose title contains this
+    #[arg(long, value_name = "NAME")]
+    show: Option<String>,
+
+    /// Only episodes in this state
+    #[arg(long, value_enum)]
+    state: Option<EpisodeState>,
+
+    /// Only episodes published on or after this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE", value_parser = select::parse_date)]
+    since: Option<NaiveDate>,
+
+    /// Only episodes published on or before this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE", value_parser = select::parse_date)]
+    until: Option<NaiveDate>,
+
+    /// Only episodes marked played
+    #[arg(long, conflicts_with = "unplayed")]
+    played: bool,
+
+    /// Only episodes not marked played
+    #[arg(long)]
+    unplayed: bool,
+
+    /// Maximum number of results
+    #[arg(short, long, default_value_t = 50)]
+    n: usize,
+
+    /// Run this read-only SQL instead, e.g. "SELECT show, count(*) FROM episodes GROUP BY show"
+    #[arg(long, value_name = "SQL", conflicts_with_all = ["text", "show", "state", "since", "until", "played", "unplayed"])]
+    sql: Option<String>,
+}
+
+pub fn query(args: QueryArgs) -> Result<()> {
+    let history = History::open_default()?;
+    if let Some(sql) = &args.sql {
+        let result = history.query_sql(sql)?;
+        if output::json() {
+            return output::emit(&result);
+        }
+        print_rows(&result);
+        return Ok(());
+    }
+
+    let text = args.text.join(" ");
+    let episodes = history.query(&LibraryQuery {
+        text: (!text.trim().is_empty()).then_some(text),
+        show: args.show,
+        state: args.state,
+        since: args.since,
+        until: args.until,
+        played: played_filter(args.played, args.unplayed),
+        limit: Some(args.n),
+    })?;
+    if output::json() {
+        return output::emit(&episodes);
+    }
+    if output::porcelain() {
+        for episode in &episodes {
+            let state = match episode.played_at {
+                Some(_) => "played",
+                None => episode.state.as_str(),
+            };
+            output::row(&[
+                episode.published.as_deref().unwrap_or_default(),
+                state,
+                episode.show.as_deref().unwrap_or_default(),
+                &episode.title,
+                episode.guid.as_deref().unwrap_or_default(),
+                &episode
+                    .path
+                    .as_deref()
+                    .map(|path| path.to_string_lossy())
+                    .unwrap_or_default(),
+            ]);
+        }
+        return Ok(());
+    }
+    if episodes.is_empty() {
+        println!("{}", tr!("no-matching-episodes"));
+        return Ok(());
+    }
+
+    for episode in &episodes {
+        let date = episode
+            .published
+            .as_deref()
+            .and_then(|d| d.get(..10))
+            .unwrap_or("----------");
+        let state = match episode.played_at {
+            Some(_) => "played",
+            None => episode.state.as_str(),
+        };
+        println!(
+            "{}  {:<10}  {} — {}",
+            date,
+            state,
+            episode.show.as_deref().unwrap_or("?"),
+            episode.title
+        );
+        if let Some(snippet) = &episode.snippet {
+            println!(
+                "  {}",
+                snippet.split_whitespace().collect::<Vec<_>>().join(" ")
+            );
+        }
+        if let Some(path) = &episode.path {
+            println!("  {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// A header line (left out with `--porcelain`), then one tab-separated
+/// line per row
+fn print_rows(result: &QueryResult) {
+    if !output::porcelain() {
+        output::row(&result.columns);
+    }
+    for row in &result.rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|value| match value {
+                serde_json::Value::Null => String::new(),
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .collect();
+        output::row(&fields);
+    }
+}
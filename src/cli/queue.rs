@@ -0,0 +1,199 @@
+use super::download::download_configured;
+use super::output;
+use anyhow::{Result, bail};
+use pdl::config::Config;
+use pdl::history::History;
+use pdl::interrupt::{self, Interrupted};
+use pdl::schedule::DownloadWindow;
+use pdl::tr;
+use pdl::{Episode, Feed, PodcastClient};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// A queued episode, as `pdl --json queue list` prints it
+#[derive(Serialize)]
+pub struct QueuedEpisode {
+    id: i64,
+    feed_url: String,
+    show: String,
+    title: String,
+    guid: Option<String>,
+    url: String,
+    added_at: String,
+    paused: bool,
+    error: Option<String>,
+}
+
+pub fn list() -> Result<()> {
+    if output::json() {
+        return output::emit(&queued()?);
+    }
+    if output::porcelain() {
+        for item in queued()? {
+            let state = if item.paused { "paused" } else { "queued" };
+            output::row(&[
+                item.id.to_string(),
+                state.to_string(),
+                item.show,
+                item.title,
+                item.url,
+                item.error.unwrap_or_default(),
+            ]);
+        }
+        return Ok(());
+    }
+    let queue = History::open_default()?.queue()?;
+    if queue.is_empty() {
+        println!("{}", tr!("queue-empty"));
+        return Ok(());
+    }
+
+    for item in &queue {
+        let state = if item.paused { "paused" } else { "queued" };
+        println!(
+            "{:>4}  {}  {} — {}",
+            item.id, state, item.show, item.episode.title
+        );
+        if let Some(error) = &item.error {
+            println!("        ✗ {}", error);
+        }
+    }
+    Ok(())
+}
+
+/// Everything in the queue, in the order it was added
+pub fn queued() -> Result<Vec<QueuedEpisode>> {
+    let queue = History::open_default()?.queue()?;
+    Ok(queue
+        .into_iter()
+        .map(|item| QueuedEpisode {
+            id: item.id,
+            feed_url: item.feed_url,
+            show: item.show,
+            title: item.episode.title,
+            guid: item.episode.guid,
+            url: item.episode.url,
+            added_at: item.added_at,
+            paused: item.paused,
+            error: item.error,
+        })
+        .collect())
+}
+
+/// Take episodes out of the queue by id, or all of them
+pub fn remove(ids: &[i64], all: bool) -> Result<()> {
+    let history = History::open_default()?;
+    let ids = if all {
+        history.queue()?.iter().map(|item| item.id).collect()
+    } else {
+        ids.to_vec()
+    };
+    let removed = history.dequeue(&ids)?;
+    if removed == 0 && !all {
+        bail!("Nothing queued with those ids (`pdl queue list` shows them)");
+    }
+    info!("✓ Removed {} episode(s) from the queue", removed);
+    Ok(())
+}
+
+/// Pause or resume queued episodes by id, or all of them if `ids` is empty
+pub fn pause(ids: &[i64], paused: bool) -> Result<()> {
+    let changed = History::open_default()?.pause_queued(ids, paused)?;
+    if changed == 0 && !ids.is_empty() {
+        bail!("Nothing queued with those ids (`pdl queue list` shows them)");
+    }
+    let verb = if paused { "Paused" } else { "Resumed" };
+    info!("✓ {} {} episode(s)", verb, changed);
+    Ok(())
+}
+
+/// Download everything queued that isn't paused, with the configured
+/// settings. Unless `now`, waits for the download window to open first, if
+/// one is configured.
+pub async fn start(client: &PodcastClient, config: &Config, now: bool) -> Result<()> {
+    let window = if now { None } else { config.download_window()? };
+    if let Some(window) = window {
+        let wait = window.until_open();
+        if !wait.is_zero() && !queued_feeds(&History::open_default()?)?.is_empty() {
+            // Whole minutes read better than "3h 12m 47s 120ms"
+            let minutes = Duration::from_secs(wait.as_secs().div_ceil(60) * 60);
+            info!(
+                "Waiting for the download window ({}), which opens in {}",
+                window,
+                humantime::format_duration(minutes)
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = interrupt::interrupted() => return Err(Interrupted.into()),
+            }
+        }
+    }
+    download_queued(client, config, window).await
+}
+
+/// Download everything queued that isn't paused, a feed at a time, with the
+/// configured settings. Downloads leave the queue as they finish; one that
+/// fails stays queued with its error, and Ctrl+C leaves the rest queued, so
+/// the next start picks up where this one stopped. Feeds not started by the
+/// time `window` closes stay queued too.
+pub async fn download_queued(
+    client: &PodcastClient,
+    config: &Config,
+    window: Option<DownloadWindow>,
+) -> Result<()> {
+    let history = History::open_default()?;
+    let feeds = queued_feeds(&history)?;
+    if feeds.is_empty() {
+        info!("Nothing queued.");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for (feed_url, feed) in &feeds {
+        if let Some(window) = window.filter(|w| !w.is_open()) {
+            info!("\nThe download window ({}) has closed", window);
+            break;
+        }
+        info!("\n{}", feed.title);
+        let episodes: Vec<&Episode> = feed.episodes.iter().collect();
+        match download_configured(client, config, feed_url, feed, &episodes).await {
+            Ok(()) => {}
+            Err(e) if e.is::<Interrupted>() => return Err(e),
+            Err(e) => {
+                warn!("{}: {:#}", feed.title, e);
+                failed += 1;
+            }
+        }
+    }
+    let left = history.queue()?.len();
+    info!("\n✓ Queue worked through, {} episode(s) left in it", left);
+    if failed > 0 {
+        bail!(
+            "Downloads from {} of {} feeds failed; they stay queued",
+            failed,
+            feeds.len()
+        );
+    }
+    Ok(())
+}
+
+/// Queued episodes that aren't paused, grouped by feed in queue order
+fn queued_feeds(history: &History) -> Result<Vec<(String, Feed)>> {
+    let mut feeds: Vec<(String, Feed)> = Vec::new();
+    for item in history.queue()?.into_iter().filter(|item| !item.paused) {
+        match feeds.iter_mut().find(|(url, _)| *url == item.feed_url) {
+            Some((_, feed)) => feed.episodes.push(item.episode),
+            None => feeds.push((
+                item.feed_url,
+                Feed {
+                    title: item.show,
+                    image: None,
+                    episodes: vec![item.episode],
+                    next_page: None,
+                },
+            )),
+        }
+    }
+    Ok(feeds)
+}
@@ -0,0 +1,107 @@
+use super::output;
+use anyhow::{Context, Result};
+use inquire::{Confirm, Select};
+use pdl::PodcastClient;
+use pdl::config::Config;
+use pdl::search::{SearchBackend, SearchResult};
+use pdl::subscriptions::Subscriptions;
+use pdl::tr;
+use std::io::{self, IsTerminal};
+
+pub async fn search(
+    client: &PodcastClient,
+    config: &Config,
+    terms: &[String],
+    limit: usize,
+    backend: Option<SearchBackend>,
+) -> Result<()> {
+    let terms = terms.join(" ");
+    let results = match backend.or(config.search_backend).unwrap_or_default() {
+        SearchBackend::Itunes => client.search(&terms, limit).await?,
+        SearchBackend::PodcastIndex => {
+            let credentials = config.podcast_index_credentials().context(
+                "Podcast Index needs an API key: set PODCASTINDEX_API_KEY and \
+                 PODCASTINDEX_API_SECRET, or podcastindex_key/podcastindex_secret in config.toml",
+            )?;
+            client
+                .search_podcast_index(&credentials, &terms, limit)
+                .await?
+        }
+    };
+    if output::json() {
+        return output::emit(&results);
+    }
+    if output::porcelain() {
+        for result in &results {
+            output::row(&[
+                result.title.as_str(),
+                result.author.as_deref().unwrap_or_default(),
+                &result.feed_url,
+                &result
+                    .episode_count
+                    .map(|count| count.to_string())
+                    .unwrap_or_default(),
+            ]);
+        }
+        return Ok(());
+    }
+    if results.is_empty() {
+        println!("{}", tr!("no-podcasts-found", terms = terms.as_str()));
+        return Ok(());
+    }
+
+    // Without a terminal, just list what was found
+    if !output::interactive() || !io::stdout().is_terminal() {
+        for result in &results {
+            println!("{}", describe(result));
+            println!("  {}", result.feed_url);
+        }
+        return Ok(());
+    }
+
+    let options: Vec<String> = results.iter().map(describe).collect();
+    let selection = Select::new(&tr!("select-podcast"), options)
+        .with_page_size(15)
+        .raw_prompt()
+        .context("Failed to get podcast selection")?;
+    let chosen = &results[selection.index];
+
+    println!("\n{}", chosen.title);
+    if let Some(author) = &chosen.author {
+        println!("  {}", tr!("podcast-author", author = author));
+    }
+    if let Some(artwork) = &chosen.artwork_url {
+        println!("  {}", tr!("podcast-artwork", url = artwork));
+    }
+    println!("  {}\n", tr!("podcast-feed-url", url = &chosen.feed_url));
+
+    let mut subscriptions = Subscriptions::load()?;
+    if subscriptions.contains_url(&chosen.feed_url) {
+        println!("{}", tr!("already-subscribed"));
+        return Ok(());
+    }
+
+    let subscribe = Confirm::new(&tr!("confirm-subscribe", title = &chosen.title))
+        .with_default(true)
+        .prompt()
+        .context("Failed to get confirmation")?;
+    if subscribe {
+        let name = subscriptions.unique_name(&chosen.title);
+        subscriptions.add(&name, &chosen.feed_url)?;
+        subscriptions.save()?;
+        println!("{}", tr!("subscribed", name = name.as_str()));
+    }
+    Ok(())
+}
+
+/// One-line summary: title, author and episode count
+fn describe(result: &SearchResult) -> String {
+    let mut line = result.title.clone();
+    if let Some(author) = &result.author {
+        line.push_str(&format!(" — {}", author));
+    }
+    if let Some(count) = result.episode_count {
+        line.push_str(&format!(" ({} episodes)", count));
+    }
+    line
+}
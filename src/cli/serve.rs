@@ -0,0 +1,315 @@
+use super::queue::{self, download_queued};
+use super::{feeds, transfers};
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use futures_util::stream::FuturesUnordered;
+use pdl::config::Config;
+use pdl::history::History;
+use pdl::interrupt::{self, Interrupted};
+use pdl::listing::{self, ListFormat};
+use pdl::metrics;
+use pdl::server::{self, Request, Response};
+use pdl::subscriptions::Subscriptions;
+use pdl::{Episode, PodcastClient};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+/// Downloads `GET /history` returns when not told how many
+const DEFAULT_HISTORY: usize = 50;
+
+/// What `POST /downloads` takes
+#[derive(Deserialize)]
+struct DownloadRequest {
+    /// Subscription name or feed URL
+    feed: String,
+    /// GUIDs, enclosure URLs or titles of the episodes to download
+    #[serde(default)]
+    episodes: Vec<String>,
+    /// Or the newest this many (1 if neither is given)
+    latest: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct FeedInfo {
+    name: String,
+    url: String,
+}
+
+/// Answer HTTP requests on `listen` until Ctrl+C: list subscriptions and
+/// episodes, queue downloads (worked through one at a time, like `pdl queue
+/// start`) and report their progress and the download history, all as JSON
+pub async fn serve(client: &PodcastClient, config: &Config, listen: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to listen on {}", listen))?;
+    if !listen.ip().is_loopback() {
+        warn!(
+            "Anyone who can reach {} can download through pdl; there's no login",
+            listen
+        );
+    }
+    let local = listener.local_addr()?;
+    info!("Serving on http://{}", local);
+
+    let wake = Notify::new();
+    // Connections and downloads share this task, as downloads can't move
+    // between threads
+    let accept = async {
+        let mut connections = FuturesUnordered::new();
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, _) = accepted.context("Failed to accept connection")?;
+                    connections.push(answer(stream, local, client, &wake));
+                }
+                Some(()) = connections.next() => {}
+            }
+        }
+    };
+    let work = async {
+        loop {
+            wake.notified().await;
+            match download_queued(client, config, None).await {
+                Err(e) if e.is::<Interrupted>() => return Err(e),
+                Err(e) => warn!("{:#}", e),
+                Ok(()) => {}
+            }
+        }
+    };
+    tokio::select! {
+        result = accept => result,
+        result = work => result,
+        _ = interrupt::interrupted() => Err(Interrupted.into()),
+    }
+}
+
+/// Read one request from `stream`, which reached the server on `local`,
+/// and answer it
+async fn answer(mut stream: TcpStream, local: SocketAddr, client: &PodcastClient, wake: &Notify) {
+    let (response, head_only) = match server::read_request(&mut stream).await {
+        Ok(request) => {
+            debug!("{} {}", request.method, request.path);
+            let response = match request.refuse_cross_site(local) {
+                Some(refused) => refused,
+                None => route(&request, client, wake).await.unwrap_or_else(|e| {
+                    Response::error(StatusCode::INTERNAL_SERVER_ERROR, &format!("{:#}", e))
+                }),
+            };
+            (response, request.method == "HEAD")
+        }
+        Err(e) => (Response::unreadable(&e), false),
+    };
+    if let Err(e) = server::write_response(&mut stream, &response, head_only).await {
+        debug!("Failed to answer request: {:#}", e);
+    }
+}
+
+async fn route(request: &Request, client: &PodcastClient, wake: &Notify) -> Result<Response> {
+    let segments = request.segments();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let read = matches!(request.method.as_str(), "GET" | "HEAD");
+    match (segments.as_slice(), read) {
+        ([], true) => Response::json(&json!({
+            "endpoints": [
+                "GET /subscriptions",
+                "GET /episodes?feed=<name or URL>&n=<count>",
+                "GET /downloads",
+                "POST /downloads {\"feed\": ..., \"episodes\": [...], \"latest\": n}",
+                "GET /history?n=<count>",
+                "GET /metrics",
+            ]
+        })),
+        (["subscriptions"], true) => {
+            let feeds: Vec<FeedInfo> = Subscriptions::load()?
+                .feeds
+                .into_iter()
+                .map(|f| FeedInfo {
+                    name: f.name,
+                    url: f.url,
+                })
+                .collect();
+            Response::json(&feeds)
+        }
+        (["episodes"], true) => episodes(request, client).await,
+        (["downloads"], true) => Response::json(&json!({
+            "active": transfers::active(),
+            "queue": queue::queued()?,
+        })),
+        (["downloads"], false) if request.method == "POST" => {
+            queue_downloads(request, client, wake).await
+        }
+        (["history"], true) => {
+            let n = match request.param("n") {
+                Some(n) => match n.parse() {
+                    Ok(n) => n,
+                    Err(_) => return Ok(bad_request("n must be a number")),
+                },
+                None => DEFAULT_HISTORY,
+            };
+            Response::json(&History::open_default()?.entries(n)?)
+        }
+        (["metrics"], true) => metrics(),
+        (["subscriptions" | "episodes" | "downloads" | "history" | "metrics"] | [], _) => {
+            Ok(Response::error(
+                StatusCode::METHOD_NOT_ALLOWED,
+                &format!("{} isn't supported here", request.method),
+            ))
+        }
+        _ => Ok(Response::error(StatusCode::NOT_FOUND, "No such endpoint")),
+    }
+}
+
+/// `GET /metrics`: counters and gauges for Prometheus
+fn metrics() -> Result<Response> {
+    let gauges = metrics::Gauges {
+        queue_depth: queue::queued()?.len(),
+        active_downloads: transfers::active().len(),
+    };
+    Ok(Response {
+        status: StatusCode::OK,
+        content_type: "text/plain; version=0.0.4",
+        body: metrics::render(gauges).into_bytes(),
+    })
+}
+
+/// Answer `GET /metrics` on `listen` until Ctrl+C, for `pdl watch --metrics`
+pub async fn serve_metrics(listen: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to listen on {}", listen))?;
+    info!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+    let mut connections = FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted.context("Failed to accept connection")?;
+                connections.push(answer_metrics(stream));
+            }
+            Some(()) = connections.next() => {}
+        }
+    }
+}
+
+async fn answer_metrics(mut stream: TcpStream) {
+    let (response, head_only) = match server::read_request(&mut stream).await {
+        Ok(request) => {
+            let response = match (request.segments().as_slice(), request.method.as_str()) {
+                ([path], "GET" | "HEAD") if path == "metrics" => metrics().unwrap_or_else(|e| {
+                    Response::error(StatusCode::INTERNAL_SERVER_ERROR, &format!("{:#}", e))
+                }),
+                _ => Response::error(StatusCode::NOT_FOUND, "Only /metrics is served here"),
+            };
+            (response, request.method == "HEAD")
+        }
+        Err(e) => (Response::unreadable(&e), false),
+    };
+    if let Err(e) = server::write_response(&mut stream, &response, head_only).await {
+        debug!("Failed to answer request: {:#}", e);
+    }
+}
+
+/// `GET /episodes`: the newest `n` episodes of a feed, as `pdl list` prints them
+async fn episodes(request: &Request, client: &PodcastClient) -> Result<Response> {
+    let Some(feed) = request.param("feed") else {
+        return Ok(bad_request(
+            "Pass the feed: ?feed=<subscription name or URL>",
+        ));
+    };
+    let n = match request.param("n").map(str::parse).transpose() {
+        Ok(n) => n.unwrap_or(usize::MAX),
+        Err(_) => return Ok(bad_request("n must be a number")),
+    };
+    let feed = match feeds::fetch_feed(client, &feed_url(feed)?, n, false).await {
+        Ok((_, feed)) => feed,
+        Err(e) => {
+            return Ok(Response::error(
+                StatusCode::BAD_GATEWAY,
+                &format!("{:#}", e),
+            ));
+        }
+    };
+    Ok(Response {
+        status: StatusCode::OK,
+        content_type: "application/json",
+        body: listing::render(&feed.episodes, ListFormat::Json)?.into_bytes(),
+    })
+}
+
+/// `POST /downloads`: queue episodes of a feed and start on the queue
+async fn queue_downloads(
+    request: &Request,
+    client: &PodcastClient,
+    wake: &Notify,
+) -> Result<Response> {
+    let wanted: DownloadRequest = match serde_json::from_slice(&request.body) {
+        Ok(wanted) => wanted,
+        Err(e) => return Ok(bad_request(&format!("Invalid request body: {}", e))),
+    };
+    let limit = if wanted.episodes.is_empty() {
+        wanted.latest.unwrap_or(1)
+    } else {
+        usize::MAX
+    };
+    let (url, feed) = match feeds::fetch_feed(client, &feed_url(&wanted.feed)?, limit, false).await
+    {
+        Ok(fetched) => fetched,
+        Err(e) => {
+            return Ok(Response::error(
+                StatusCode::BAD_GATEWAY,
+                &format!("{:#}", e),
+            ));
+        }
+    };
+    let mut episodes: Vec<&Episode> = Vec::new();
+    for wanted in &wanted.episodes {
+        let found = feed
+            .episodes
+            .iter()
+            .find(|e| e.guid.as_ref() == Some(wanted) || e.url == *wanted || e.title == *wanted);
+        match found {
+            Some(episode) => episodes.push(episode),
+            None => {
+                return Ok(Response::error(
+                    StatusCode::NOT_FOUND,
+                    &format!("No episode {:?} in {}", wanted, feed.title),
+                ));
+            }
+        }
+    }
+    if wanted.episodes.is_empty() {
+        episodes.extend(feed.episodes.iter().take(limit));
+    }
+
+    let history = History::open_default()?;
+    let mut queued = 0;
+    for episode in &episodes {
+        if history.enqueue(&url, &feed.title, episode)? {
+            queued += 1;
+        }
+    }
+    info!("{}: queued {} episode(s)", feed.title, queued);
+    wake.notify_one();
+    Ok(Response::json(&json!({ "queued": queued }))?.with_status(StatusCode::ACCEPTED))
+}
+
+/// The URL of the subscription named `feed`, else `feed` itself
+fn feed_url(feed: &str) -> Result<String> {
+    let subscriptions = Subscriptions::load()?;
+    Ok(subscriptions
+        .feeds
+        .into_iter()
+        .find(|f| f.name == feed)
+        .map_or_else(|| feed.to_string(), |f| f.url))
+}
+
+fn bad_request(message: &str) -> Response {
+    Response::error(StatusCode::BAD_REQUEST, message)
+}
@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use futures_util::stream::FuturesUnordered;
+use pdl::download::mime_from_extension;
+use pdl::history::History;
+use pdl::interrupt::{self, Interrupted};
+use pdl::library::{EpisodeState, LibraryQuery, on_disk_by_show};
+use pdl::localfeed::{self, FEED_FILE};
+use pdl::opml::{self, OpmlFeed};
+use pdl::server::{self, Request, Response, encode_segment};
+use pdl::site;
+use reqwest::StatusCode;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::path::PathBuf;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+/// What a request gets: a generated document, or a downloaded file
+enum Answer {
+    Response(Response),
+    File(PathBuf),
+}
+
+/// Serve an RSS feed of each show's downloaded episodes on `listen` until
+/// Ctrl+C, with the files they point at, for podcast apps on other devices
+/// to subscribe to. `/` lists the feeds as OPML. Links in the feeds start
+/// with `base_url`, or the address listened on.
+pub async fn serve_feed(listen: SocketAddr, base_url: Option<String>) -> Result<()> {
+    if let Some(url) = &base_url {
+        reqwest::Url::parse(url).with_context(|| format!("Invalid base URL {}", url))?;
+    }
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to listen on {}", listen))?;
+    let addr = listener.local_addr()?;
+    if listen.ip().is_loopback() {
+        warn!(
+            "Only this computer can reach {}; use --listen 0.0.0.0:{} for other devices",
+            addr,
+            addr.port()
+        );
+    } else {
+        info!(
+            "Anyone who can reach port {} can download your archive; there's no login",
+            addr.port()
+        );
+    }
+    let root = match base_url {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => format!("http://{}", reachable(addr)?),
+    };
+    info!("Serving feeds on {}/ (an OPML list of them is there)", root);
+
+    let mut connections = FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted.context("Failed to accept connection")?;
+                connections.push(answer(stream, &root));
+            }
+            Some(()) = connections.next() => {}
+            _ = interrupt::interrupted() => return Err(Interrupted.into()),
+        }
+    }
+}
+
+/// Read one request from `stream` and answer it
+async fn answer(mut stream: TcpStream, root: &str) {
+    let request = match server::read_request(&mut stream).await {
+        Ok(request) => request,
+        Err(e) => {
+            let response = Response::unreadable(&e);
+            let _ = server::write_response(&mut stream, &response, false).await;
+            return;
+        }
+    };
+    debug!("{} {}", request.method, request.path);
+    let head_only = request.method == "HEAD";
+    let answer = route(&request, root).unwrap_or_else(|e| {
+        Answer::Response(Response::error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("{:#}", e),
+        ))
+    });
+    let result = match answer {
+        Answer::Response(response) => {
+            server::write_response(&mut stream, &response, head_only).await
+        }
+        Answer::File(path) => {
+            let extension = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            server::write_file(
+                &mut stream,
+                &path,
+                mime_from_extension(&extension),
+                request.header("range"),
+                head_only,
+            )
+            .await
+        }
+    };
+    if let Err(e) = result {
+        debug!("Failed to answer request: {:#}", e);
+    }
+}
+
+/// The address other devices reach `addr` by: itself, or when it's every
+/// address (0.0.0.0), this computer's address on the network
+fn reachable(addr: SocketAddr) -> Result<SocketAddr> {
+    if !addr.ip().is_unspecified() {
+        return Ok(addr);
+    }
+    // Connecting a UDP socket only picks the route; nothing is sent
+    let (local, remote): (IpAddr, IpAddr) = match addr {
+        SocketAddr::V4(_) => (
+            Ipv4Addr::UNSPECIFIED.into(),
+            Ipv4Addr::new(192, 0, 2, 1).into(),
+        ),
+        SocketAddr::V6(_) => (
+            Ipv6Addr::UNSPECIFIED.into(),
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).into(),
+        ),
+    };
+    let socket = UdpSocket::bind((local, 0))?;
+    let ip = socket
+        .connect((remote, 9))
+        .and_then(|()| socket.local_addr())
+        .map(|found| found.ip())
+        .context("Failed to find this computer's network address; pass --base-url")?;
+    Ok(SocketAddr::new(ip, addr.port()))
+}
+
+fn route(request: &Request, root: &str) -> Result<Answer> {
+    if !matches!(request.method.as_str(), "GET" | "HEAD") {
+        return Ok(Answer::Response(Response::error(
+            StatusCode::METHOD_NOT_ALLOWED,
+            &format!("{} isn't supported here", request.method),
+        )));
+    }
+    let show_url = |show: &str| format!("{}/shows/{}", root, encode_segment(show));
+
+    let episodes = History::open_default()?.query(&LibraryQuery {
+        state: Some(EpisodeState::Downloaded),
+        ..Default::default()
+    })?;
+    let shows = on_disk_by_show(&episodes);
+    let segments = request.segments();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let not_found = || Answer::Response(Response::error(StatusCode::NOT_FOUND, "Not found"));
+    match segments.as_slice() {
+        [] => {
+            let feeds: Vec<OpmlFeed> = shows
+                .iter()
+                .map(|show| OpmlFeed {
+                    title: show.show.to_string(),
+                    url: format!("{}/{}", show_url(show.show), FEED_FILE),
+                })
+                .collect();
+            Ok(Answer::Response(Response {
+                status: StatusCode::OK,
+                content_type: "text/x-opml; charset=utf-8",
+                body: opml::write(&feeds).into_bytes(),
+            }))
+        }
+        ["shows", name, file] => {
+            let Some(show) = shows.iter().find(|show| show.show == *name) else {
+                return Ok(not_found());
+            };
+            // The artwork is saved next to the episodes
+            let cover = show
+                .files
+                .iter()
+                .find_map(|(_, path)| site::cover(path.parent()?));
+            if *file == FEED_FILE {
+                return Ok(Answer::Response(Response {
+                    status: StatusCode::OK,
+                    content_type: "application/rss+xml; charset=utf-8",
+                    body: localfeed::render(show, &show_url(show.show), cover.as_deref())
+                        .into_bytes(),
+                }));
+            }
+            // Only files in the feed are served, never anything else by path
+            let named = |path: &std::path::Path| path.file_name().is_some_and(|n| n == *file);
+            let found = show
+                .files
+                .iter()
+                .map(|(_, path)| path.to_path_buf())
+                .chain(cover)
+                .find(|path| named(path));
+            Ok(found.map_or_else(not_found, Answer::File))
+        }
+        _ => Ok(not_found()),
+    }
+}
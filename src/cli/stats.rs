@@ -0,0 +1,123 @@
+use super::output;
+use anyhow::Result;
+use indicatif::HumanBytes;
+use pdl::history::History;
+use pdl::stats::{self, EpisodeRef};
+use pdl::subscriptions::Subscriptions;
+use pdl::tr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Months of download history shown in the trend
+const TREND_MONTHS: usize = 12;
+
+/// Widest bar in the trend
+const BAR_WIDTH: usize = 30;
+
+/// Print per-show and total statistics over everything downloaded
+pub fn show_stats() -> Result<()> {
+    let subscriptions = Subscriptions::load()?;
+    let entries = History::open_default()?.all_entries()?;
+    let stats = stats::compute(&entries, |url| {
+        subscriptions.find_url(url).map(|s| s.name.clone())
+    });
+    if output::json() {
+        return output::emit(&stats);
+    }
+    if stats.shows.is_empty() {
+        println!("{}", tr!("no-history"));
+        return Ok(());
+    }
+
+    let width = stats
+        .shows
+        .iter()
+        .map(|s| s.show.width())
+        .max()
+        .unwrap_or(0)
+        .clamp(4, 40);
+    // Padded by terminal columns, which Korean headings use two of per letter
+    println!(
+        "{}  {}  {}  {}  {}  {}  {}",
+        pad(&tr!("stats-show"), width, false),
+        pad(&tr!("stats-episodes"), 8, true),
+        pad(&tr!("stats-on-disk"), 7, true),
+        pad(&tr!("stats-size"), 10, true),
+        pad(&tr!("stats-listening"), 9, true),
+        pad(&tr!("stats-oldest"), 10, false),
+        tr!("stats-newest")
+    );
+    for show in &stats.shows {
+        println!(
+            "{}  {:>8}  {:>7}  {:>10}  {:>9}  {:<10}  {}",
+            pad(&truncate(&show.show, width), width, false),
+            show.downloaded,
+            show.on_disk,
+            HumanBytes(show.bytes).to_string(),
+            hours(show.listening_seconds),
+            date(show.oldest.as_ref()),
+            date(show.newest.as_ref()),
+        );
+    }
+    let total = &stats.total;
+    println!(
+        "\n{}",
+        tr!(
+            "stats-total",
+            shows = total.shows,
+            downloaded = total.downloaded,
+            on_disk = total.on_disk,
+            size = HumanBytes(total.bytes).to_string(),
+            listening = hours(total.listening_seconds)
+        )
+    );
+
+    let recent = &stats.monthly[stats.monthly.len().saturating_sub(TREND_MONTHS)..];
+    let most = recent.iter().map(|m| m.downloads).max().unwrap_or(0).max(1);
+    println!("\n{}", tr!("stats-per-month"));
+    for month in recent {
+        let bar = (month.downloads * BAR_WIDTH).div_ceil(most);
+        println!(
+            "  {}  {:>5}  {}",
+            month.month,
+            month.downloads,
+            "█".repeat(bar)
+        );
+    }
+    Ok(())
+}
+
+/// `12h 05m`
+fn hours(seconds: u64) -> String {
+    format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+}
+
+fn date(episode: Option<&EpisodeRef>) -> &str {
+    episode.and_then(|e| e.published.get(..10)).unwrap_or("-")
+}
+
+/// `s` cut to `width` terminal columns, ending in `…` if it was cut
+fn truncate(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_string();
+    }
+    let mut short = String::new();
+    for c in s.chars() {
+        if short.width() + c.width().unwrap_or(0) >= width {
+            break;
+        }
+        short.push(c);
+    }
+    short.push('…');
+    short
+}
+
+/// `s` filled out with spaces to `width` terminal columns, on the left when
+/// `right`-aligned
+fn pad(s: &str, width: usize, right: bool) -> String {
+    let fill = " ".repeat(width.saturating_sub(s.width()));
+    if right {
+        format!("{}{}", fill, s)
+    } else {
+        format!("{}{}", s, fill)
+    }
+}
@@ -0,0 +1,51 @@
+use super::download::select_episode;
+use super::feeds;
+use super::output;
+use anyhow::{Context, Result, bail};
+use pdl::PodcastClient;
+use pdl::config::Config;
+use pdl::error::NoEpisodes;
+use pdl::history::History;
+use pdl::player::Player;
+use pdl::select::Selector;
+use std::io::{self, IsTerminal};
+use tracing::{info, warn};
+
+/// Play an episode straight from the feed without saving it. The player
+/// reads from a local relay, so private feeds' credentials never leave pdl.
+pub async fn stream(
+    client: &PodcastClient,
+    config: &Config,
+    feed: Option<String>,
+    n: usize,
+    selector: Option<Selector>,
+) -> Result<()> {
+    let player = Player::find(config.player.as_deref())?;
+    let feed_url = feed
+        .or_else(|| config.default_feed.clone())
+        .context("No feed given: pass --feed <url> or set default_feed in config.toml")?;
+    let interactive = output::interactive() && io::stdout().is_terminal();
+    if selector.is_none() && !interactive {
+        bail!("Choose an episode with --latest or --episode when running non-interactively");
+    }
+
+    let (feed_url, feed) = feeds::fetch_feed(client, &feed_url, n, interactive).await?;
+    let episode = match &selector {
+        Some(selector) => *selector.select(&feed.episodes).first().ok_or(NoEpisodes)?,
+        None if feed.episodes.is_empty() => bail!("No episodes found in the feed."),
+        None => select_episode(&feed.episodes)?,
+    };
+
+    let relay = client.relay_episode(episode).await?;
+    info!("Streaming {} with {}...", episode.title, player.program());
+    let url = relay.url().to_string();
+    tokio::task::spawn_blocking(move || player.play(&[url]))
+        .await
+        .context("Player task failed")??;
+    let marked = History::open_default()
+        .and_then(|history| history.mark_episode_played(&feed_url, &feed.title, episode));
+    if let Err(e) = marked {
+        warn!("{:#}", e);
+    }
+    Ok(())
+}
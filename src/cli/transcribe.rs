@@ -0,0 +1,78 @@
+use anyhow::{Context, Result, bail};
+use pdl::config::Config;
+use pdl::history::{History, HistoryEntry};
+use pdl::transcribe::{TranscriptFormat, Whisper, transcript_path};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Downloads searched for an episode named on the command line
+const HISTORY_SEARCH_LIMIT: usize = 1000;
+
+/// Transcribe `target` (a file, or the title or GUID of a downloaded
+/// episode) with whisper.cpp, or the latest download with `latest`
+pub async fn transcribe(
+    config: &Config,
+    target: Option<String>,
+    latest: bool,
+    language: &str,
+    formats: &[TranscriptFormat],
+    force: bool,
+) -> Result<()> {
+    let whisper = Whisper::find(
+        config.whisper_cmd.as_deref(),
+        config.whisper_model().as_deref(),
+    )?;
+    let audio = match target {
+        Some(target) if Path::new(&target).is_file() => PathBuf::from(target),
+        Some(target) => find_download(&target)?.path,
+        None if latest => find_latest()?.path,
+        None => bail!("Say what to transcribe: a file, an episode title or GUID, or --latest"),
+    };
+
+    let existing: Vec<PathBuf> = formats
+        .iter()
+        .map(|&format| transcript_path(&audio, format))
+        .filter(|path| path.exists())
+        .collect();
+    if !force && existing.len() == formats.len() {
+        info!("⏭ Already transcribed: {}", existing[0].display());
+        return Ok(());
+    }
+
+    info!("Transcribing: {}", audio.display());
+    for path in whisper.transcribe(&audio, language, formats).await? {
+        info!("✓ Saved: {}", path.display());
+    }
+    Ok(())
+}
+
+fn downloads() -> Result<Vec<HistoryEntry>> {
+    Ok(History::open_default()?
+        .entries(HISTORY_SEARCH_LIMIT)?
+        .into_iter()
+        .filter(|entry| entry.path.exists())
+        .collect())
+}
+
+/// The newest download whose GUID is `key` or whose title contains it
+fn find_download(key: &str) -> Result<HistoryEntry> {
+    let needle = key.to_lowercase();
+    downloads()?
+        .into_iter()
+        .find(|entry| {
+            entry.guid.as_deref() == Some(key) || entry.title.to_lowercase().contains(&needle)
+        })
+        .with_context(|| {
+            format!(
+                "No file or downloaded episode matches {:?} (`pdl history` lists downloads)",
+                key
+            )
+        })
+}
+
+fn find_latest() -> Result<HistoryEntry> {
+    downloads()?
+        .into_iter()
+        .next()
+        .context("No downloaded episodes to transcribe")
+}
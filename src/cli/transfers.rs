@@ -0,0 +1,72 @@
+use indicatif::ProgressBar;
+use pdl::Episode;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Downloads running in this process, for `pdl serve` to report
+static ACTIVE: Mutex<Vec<Active>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+struct Active {
+    id: u64,
+    show: String,
+    title: String,
+    url: String,
+    pb: ProgressBar,
+}
+
+/// A download in progress, as `GET /downloads` reports it
+#[derive(Serialize)]
+pub struct Transfer {
+    pub show: String,
+    pub title: String,
+    pub url: String,
+    /// Bytes received so far
+    pub bytes: u64,
+    /// The file's size, if the server gave it
+    pub total: Option<u64>,
+    pub bytes_per_sec: u64,
+}
+
+/// Keeps a download listed in [`active`] until dropped
+pub struct Tracked(u64);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+        active.retain(|transfer| transfer.id != self.0);
+    }
+}
+
+/// List the download of `episode` of `show`, whose progress `pb` shows,
+/// while the returned guard lives
+pub fn track(show: &str, episode: &Episode, pb: &ProgressBar) -> Tracked {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+    active.push(Active {
+        id,
+        show: show.to_string(),
+        title: episode.title.clone(),
+        url: episode.url.clone(),
+        pb: pb.clone(),
+    });
+    Tracked(id)
+}
+
+/// The downloads running now, in the order they started
+pub fn active() -> Vec<Transfer> {
+    let active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+    active
+        .iter()
+        .map(|transfer| Transfer {
+            show: transfer.show.clone(),
+            title: transfer.title.clone(),
+            url: transfer.url.clone(),
+            bytes: transfer.pb.position(),
+            // Bars start out at length 0 until the server answers
+            total: transfer.pb.length().filter(|&len| len > 0),
+            bytes_per_sec: transfer.pb.per_sec() as u64,
+        })
+        .collect()
+}
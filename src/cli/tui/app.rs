@@ -0,0 +1,398 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use pdl::history::History;
+use pdl::subscriptions::Subscription;
+use pdl::tr;
+use pdl::{DownloadOutcome, Episode, Feed};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::widgets::ListState;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Pane that receives movement keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    Feeds,
+    Episodes,
+    Downloads,
+}
+
+/// Work for the background tasks, returned from key handling
+pub enum Action {
+    LoadFeed(String),
+    Download(usize),
+}
+
+/// Results sent back by background tasks
+pub enum Message {
+    FeedLoaded {
+        url: String,
+        result: Result<Feed>,
+    },
+    DownloadFinished {
+        index: usize,
+        result: Result<DownloadOutcome>,
+    },
+}
+
+pub enum DownloadState {
+    Queued,
+    Active,
+    Done(PathBuf),
+    Failed(String),
+}
+
+/// An entry in the downloads pane
+pub struct Download {
+    pub feed_url: String,
+    pub show: String,
+    pub episode: Episode,
+    /// Hidden bar the download reports into; read for the gauge
+    pub progress: ProgressBar,
+    pub state: DownloadState,
+}
+
+/// The feed whose episodes are listed
+pub struct LoadedFeed {
+    pub url: String,
+    pub feed: Feed,
+    /// Indices of episodes in the download history
+    pub downloaded: HashSet<usize>,
+}
+
+pub struct App {
+    pub feeds: Vec<Subscription>,
+    pub feed_state: ListState,
+    pub feed_filter: String,
+    pub loaded: Option<LoadedFeed>,
+    /// URL of the feed being fetched
+    pub loading: Option<String>,
+    pub episode_state: ListState,
+    pub episode_filter: String,
+    pub downloads: Vec<Download>,
+    pub download_state: ListState,
+    pub focus: Focus,
+    /// Typing into the focused pane's filter
+    pub searching: bool,
+    /// Last thing that happened, shown in the footer
+    pub status: String,
+    pub jobs: usize,
+    pub quit: bool,
+}
+
+impl App {
+    pub fn new(feeds: Vec<Subscription>, jobs: usize) -> Self {
+        Self {
+            feed_state: ListState::default().with_selected((!feeds.is_empty()).then_some(0)),
+            feeds,
+            feed_filter: String::new(),
+            loaded: None,
+            loading: None,
+            episode_state: ListState::default(),
+            episode_filter: String::new(),
+            downloads: Vec::new(),
+            download_state: ListState::default(),
+            focus: Focus::Feeds,
+            searching: false,
+            status: tr!("tui-help"),
+            jobs: jobs.max(1),
+            quit: false,
+        }
+    }
+
+    /// Indices of the feeds passing the feed filter
+    pub fn visible_feeds(&self) -> Vec<usize> {
+        let filter = self.feed_filter.to_lowercase();
+        (0..self.feeds.len())
+            .filter(|&i| self.feeds[i].name.to_lowercase().contains(&filter))
+            .collect()
+    }
+
+    /// Indices of the loaded feed's episodes passing the episode filter
+    pub fn visible_episodes(&self) -> Vec<usize> {
+        let Some(loaded) = &self.loaded else {
+            return Vec::new();
+        };
+        let filter = self.episode_filter.to_lowercase();
+        (0..loaded.feed.episodes.len())
+            .filter(|&i| {
+                loaded.feed.episodes[i]
+                    .title
+                    .to_lowercase()
+                    .contains(&filter)
+            })
+            .collect()
+    }
+
+    /// The episode under the cursor
+    pub fn selected_episode(&self) -> Option<(usize, &Episode)> {
+        let index = *self
+            .visible_episodes()
+            .get(self.episode_state.selected()?)?;
+        let loaded = self.loaded.as_ref()?;
+        Some((index, &loaded.feed.episodes[index]))
+    }
+
+    /// Whether an episode of the loaded feed is queued or downloading
+    pub fn is_queued(&self, episode: &Episode) -> bool {
+        self.downloads.iter().any(|d| {
+            d.episode.url == episode.url
+                && matches!(d.state, DownloadState::Queued | DownloadState::Active)
+        })
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return None;
+        }
+        if self.searching {
+            self.handle_search_key(key);
+            return None;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Tab => self.cycle_focus(true),
+            KeyCode::BackTab => self.cycle_focus(false),
+            KeyCode::Left | KeyCode::Char('h') => self.focus = Focus::Feeds,
+            KeyCode::Right | KeyCode::Char('l') if self.loaded.is_some() => {
+                self.focus = Focus::Episodes
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-10),
+            KeyCode::PageDown => self.move_cursor(10),
+            KeyCode::Char('/') if self.focus != Focus::Downloads => {
+                self.searching = true;
+            }
+            KeyCode::Esc => {
+                if let Some(filter) = self.filter_mut() {
+                    filter.clear();
+                }
+            }
+            KeyCode::Char('r') => {
+                if let Some(loaded) = &self.loaded {
+                    return self.load(loaded.url.clone());
+                }
+            }
+            KeyCode::Enter | KeyCode::Char('d') => return self.activate(),
+            _ => {}
+        }
+        None
+    }
+
+    fn handle_search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.searching = false,
+            KeyCode::Esc => {
+                self.searching = false;
+                if let Some(filter) = self.filter_mut() {
+                    filter.clear();
+                }
+            }
+            KeyCode::Backspace => {
+                if let Some(filter) = self.filter_mut() {
+                    filter.pop();
+                }
+            }
+            KeyCode::Char(c) => {
+                if let Some(filter) = self.filter_mut() {
+                    filter.push(c);
+                }
+            }
+            _ => return,
+        }
+        // The filtered list may be shorter than the old cursor position
+        let state = self.focused_state();
+        state.select(Some(0));
+    }
+
+    fn filter_mut(&mut self) -> Option<&mut String> {
+        match self.focus {
+            Focus::Feeds => Some(&mut self.feed_filter),
+            Focus::Episodes => Some(&mut self.episode_filter),
+            Focus::Downloads => None,
+        }
+    }
+
+    fn focused_state(&mut self) -> &mut ListState {
+        match self.focus {
+            Focus::Feeds => &mut self.feed_state,
+            Focus::Episodes => &mut self.episode_state,
+            Focus::Downloads => &mut self.download_state,
+        }
+    }
+
+    fn cycle_focus(&mut self, forward: bool) {
+        let mut panes = vec![Focus::Feeds];
+        if self.loaded.is_some() {
+            panes.push(Focus::Episodes);
+        }
+        if !self.downloads.is_empty() {
+            panes.push(Focus::Downloads);
+        }
+        let current = panes.iter().position(|&p| p == self.focus).unwrap_or(0);
+        let next = if forward {
+            (current + 1) % panes.len()
+        } else {
+            (current + panes.len() - 1) % panes.len()
+        };
+        self.focus = panes[next];
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let len = match self.focus {
+            Focus::Feeds => self.visible_feeds().len(),
+            Focus::Episodes => self.visible_episodes().len(),
+            Focus::Downloads => self.downloads.len(),
+        };
+        let state = self.focused_state();
+        if len == 0 {
+            state.select(None);
+            return;
+        }
+        let current = state.selected().unwrap_or(0) as isize;
+        state.select(Some((current + delta).clamp(0, len as isize - 1) as usize));
+    }
+
+    /// Enter on a feed opens it; on an episode, queues it for download
+    fn activate(&mut self) -> Option<Action> {
+        match self.focus {
+            Focus::Feeds => {
+                let index = *self.visible_feeds().get(self.feed_state.selected()?)?;
+                self.load(self.feeds[index].url.clone())
+            }
+            Focus::Episodes => self.enqueue(),
+            Focus::Downloads => None,
+        }
+    }
+
+    fn load(&mut self, url: String) -> Option<Action> {
+        if self.loading.is_some() {
+            return None;
+        }
+        self.status = tr!("fetching-url", url = url.as_str());
+        self.loading = Some(url.clone());
+        Some(Action::LoadFeed(url))
+    }
+
+    fn enqueue(&mut self) -> Option<Action> {
+        let (index, episode) = self.selected_episode()?;
+        let episode = episode.clone();
+        if self.is_queued(&episode) {
+            self.status = tr!("already-queued", title = episode.title.as_str());
+            return None;
+        }
+        let loaded = self.loaded.as_ref()?;
+        self.status = if loaded.downloaded.contains(&index) {
+            tr!("tui-downloading-again", title = episode.title.as_str())
+        } else {
+            tr!("queued", title = episode.title.as_str())
+        };
+        self.downloads.push(Download {
+            feed_url: loaded.url.clone(),
+            show: loaded.feed.title.clone(),
+            episode,
+            progress: ProgressBar::hidden(),
+            state: DownloadState::Queued,
+        });
+        self.start_queued()
+    }
+
+    /// Start the next queued download if fewer than `jobs` are running
+    pub fn start_queued(&mut self) -> Option<Action> {
+        let active = self
+            .downloads
+            .iter()
+            .filter(|d| matches!(d.state, DownloadState::Active))
+            .count();
+        if active >= self.jobs {
+            return None;
+        }
+        let index = self
+            .downloads
+            .iter()
+            .position(|d| matches!(d.state, DownloadState::Queued))?;
+        self.downloads[index].state = DownloadState::Active;
+        Some(Action::Download(index))
+    }
+
+    pub fn handle_message(&mut self, message: Message, history: &History) {
+        match message {
+            Message::FeedLoaded { url, result } => {
+                self.loading = None;
+                match result {
+                    Ok(feed) => {
+                        let downloaded = feed
+                            .episodes
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, e)| history.contains(e).unwrap_or(false))
+                            .map(|(i, _)| i)
+                            .collect();
+                        self.status = tr!(
+                            "tui-feed-loaded",
+                            show = feed.title.as_str(),
+                            count = feed.episodes.len()
+                        );
+                        self.episode_state
+                            .select((!feed.episodes.is_empty()).then_some(0));
+                        self.episode_filter.clear();
+                        self.loaded = Some(LoadedFeed {
+                            url,
+                            feed,
+                            downloaded,
+                        });
+                        self.focus = Focus::Episodes;
+                    }
+                    Err(e) => self.status = format!("✗ {:#}", e),
+                }
+            }
+            Message::DownloadFinished { index, result } => {
+                let state = match result {
+                    Ok(
+                        DownloadOutcome::Downloaded(path) | DownloadOutcome::AlreadyExists(path),
+                    ) => {
+                        let download = &self.downloads[index];
+                        self.status = match history.record(
+                            &download.feed_url,
+                            &download.show,
+                            &download.episode,
+                            &path,
+                        ) {
+                            Ok(()) => tr!("tui-saved", path = path.display().to_string()),
+                            Err(e) => format!("⚠ {:#}", e),
+                        };
+                        self.mark_downloaded(index);
+                        DownloadState::Done(path)
+                    }
+                    Err(e) => {
+                        let title = &self.downloads[index].episode.title;
+                        self.status = format!("✗ {}: {:#}", title, e);
+                        DownloadState::Failed(format!("{:#}", e))
+                    }
+                };
+                self.downloads[index].state = state;
+                if self.download_state.selected().is_none() {
+                    self.download_state.select(Some(0));
+                }
+            }
+        }
+    }
+
+    /// Tick the finished download off in the episode list if its feed is open
+    fn mark_downloaded(&mut self, index: usize) {
+        let download = &self.downloads[index];
+        if let Some(loaded) = &mut self.loaded
+            && loaded.url == download.feed_url
+            && let Some(i) = loaded
+                .feed
+                .episodes
+                .iter()
+                .position(|e| e.url == download.episode.url)
+        {
+            loaded.downloaded.insert(i);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+mod app;
+mod ui;
+
+use anyhow::{Context, Result};
+use app::{Action, App, Message};
+use pdl::PodcastClient;
+use pdl::config::Config;
+use pdl::download::show_dir;
+use pdl::history::History;
+use pdl::subscriptions::Subscriptions;
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+/// How often the screen is redrawn while waiting for keys
+const TICK: Duration = Duration::from_millis(100);
+
+/// Run the interactive terminal UI until the user quits
+pub async fn run(client: &PodcastClient, config: &Config) -> Result<()> {
+    let feeds = Subscriptions::load()?.feeds;
+    let jobs = config.jobs.unwrap_or(1).into();
+    let mut overrides = HashMap::new();
+    for feed in &feeds {
+        if feed.download_dir.is_some() || feed.filename_template.is_some() {
+            let client = client
+                .clone()
+                .with_filename_template(config.filename_template_for(Some(feed))?);
+            overrides.insert(
+                feed.url.clone(),
+                (client, config.download_dir_for(Some(feed))),
+            );
+        }
+    }
+    let tasks = Tasks {
+        client: client.clone(),
+        root: config.download_dir(),
+        overrides,
+        runtime: Handle::current(),
+    };
+
+    // The terminal loop blocks, so it gets its own thread; network work is
+    // spawned back onto the runtime
+    tokio::task::spawn_blocking(move || {
+        let history = History::open_default()?;
+        let mut terminal = ratatui::init();
+        let result = event_loop(&mut terminal, App::new(feeds, jobs), &tasks, &history);
+        ratatui::restore();
+        result
+    })
+    .await
+    .context("Terminal UI crashed")?
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    mut app: App,
+    tasks: &Tasks,
+    history: &History,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    while !app.quit {
+        terminal
+            .draw(|frame| ui::draw(frame, &mut app))
+            .context("Failed to draw")?;
+
+        while let Ok(message) = rx.try_recv() {
+            app.handle_message(message, history);
+            while let Some(action) = app.start_queued() {
+                tasks.spawn(&app, action, &tx);
+            }
+        }
+
+        if event::poll(TICK).context("Failed to read terminal events")?
+            && let Event::Key(key) = event::read().context("Failed to read terminal events")?
+            && key.kind == KeyEventKind::Press
+            && let Some(action) = app.handle_key(key)
+        {
+            tasks.spawn(&app, action, &tx);
+        }
+    }
+    Ok(())
+}
+
+/// Starts feed fetches and downloads in the background
+struct Tasks {
+    client: PodcastClient,
+    root: PathBuf,
+    /// Client and download directory for feeds with their own settings
+    overrides: HashMap<String, (PodcastClient, PathBuf)>,
+    runtime: Handle,
+}
+
+impl Tasks {
+    fn spawn(&self, app: &App, action: Action, tx: &Sender<Message>) {
+        let tx = tx.clone();
+        match action {
+            Action::LoadFeed(url) => {
+                let client = self.client.clone();
+                self.runtime.spawn(async move {
+                    let result = client.fetch_feed(&url, usize::MAX).await;
+                    let _ = tx.send(Message::FeedLoaded { url, result });
+                });
+            }
+            Action::Download(index) => {
+                let download = &app.downloads[index];
+                let (client, root) = match self.overrides.get(&download.feed_url) {
+                    Some((client, root)) => (client.clone(), root),
+                    None => (self.client.clone(), &self.root),
+                };
+                let dir = show_dir(root, &download.show);
+                let episode = download.episode.clone();
+                let progress = download.progress.clone();
+                self.runtime.spawn(async move {
+                    let result = client.download_episode(&episode, &dir, &progress).await;
+                    let _ = tx.send(Message::DownloadFinished { index, result });
+                });
+            }
+        }
+    }
+}
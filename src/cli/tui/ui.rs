@@ -0,0 +1,204 @@
+use super::app::{App, DownloadState, Focus};
+use indicatif::HumanBytes;
+use pdl::listing;
+use pdl::tr;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, List, ListItem, Paragraph, Wrap};
+
+/// Most rows the downloads pane grows to
+const MAX_DOWNLOAD_ROWS: u16 = 8;
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let download_rows = (app.downloads.len() as u16).clamp(1, MAX_DOWNLOAD_ROWS);
+    let [main, downloads, footer] = Layout::vertical([
+        Constraint::Min(8),
+        Constraint::Length(download_rows + 2),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [feeds, right] =
+        Layout::horizontal([Constraint::Percentage(25), Constraint::Percentage(75)]).areas(main);
+    let [episodes, details] =
+        Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(right);
+
+    draw_feeds(frame, app, feeds);
+    draw_episodes(frame, app, episodes);
+    draw_details(frame, app, details);
+    draw_downloads(frame, app, downloads);
+    draw_footer(frame, app, footer);
+}
+
+fn pane(title: String, focused: bool) -> Block<'static> {
+    let block = Block::bordered().title(title);
+    if focused {
+        block.border_style(Style::new().fg(Color::Cyan))
+    } else {
+        block
+    }
+}
+
+fn title_with_filter(name: &str, filter: &str) -> String {
+    if filter.is_empty() {
+        format!(" {} ", name)
+    } else {
+        format!(" {} /{} ", name, filter)
+    }
+}
+
+fn highlight() -> Style {
+    Style::new().add_modifier(Modifier::REVERSED)
+}
+
+fn draw_feeds(frame: &mut Frame, app: &mut App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .visible_feeds()
+        .into_iter()
+        .map(|i| {
+            let feed = &app.feeds[i];
+            let open = app.loaded.as_ref().is_some_and(|l| l.url == feed.url);
+            let marker = if open { "▸ " } else { "  " };
+            ListItem::new(format!("{}{}", marker, feed.name))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(pane(
+            title_with_filter(&tr!("tui-subscriptions"), &app.feed_filter),
+            app.focus == Focus::Feeds,
+        ))
+        .highlight_style(highlight());
+    frame.render_stateful_widget(list, area, &mut app.feed_state);
+}
+
+fn draw_episodes(frame: &mut Frame, app: &mut App, area: Rect) {
+    let name = match (&app.loading, &app.loaded) {
+        (Some(_), _) => tr!("tui-episodes-loading"),
+        (None, Some(loaded)) => loaded.feed.title.clone(),
+        (None, None) => tr!("tui-episodes"),
+    };
+    let items: Vec<ListItem> = match &app.loaded {
+        Some(loaded) => app
+            .visible_episodes()
+            .into_iter()
+            .map(|i| {
+                let episode = &loaded.feed.episodes[i];
+                let marker = if app.is_queued(episode) {
+                    "↓ "
+                } else if loaded.downloaded.contains(&i) {
+                    "✓ "
+                } else {
+                    "  "
+                };
+                let date = episode
+                    .published
+                    .map(|d| d.format("%Y-%m-%d  ").to_string())
+                    .unwrap_or_default();
+                ListItem::new(format!("{}{}{}", marker, date, episode.title))
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    let list = List::new(items)
+        .block(pane(
+            title_with_filter(&name, &app.episode_filter),
+            app.focus == Focus::Episodes,
+        ))
+        .highlight_style(highlight());
+    frame.render_stateful_widget(list, area, &mut app.episode_state);
+}
+
+fn draw_details(frame: &mut Frame, app: &App, area: Rect) {
+    let text = match app.selected_episode() {
+        Some((_, episode)) => listing::details(episode),
+        None => String::new(),
+    };
+    let details = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .block(Block::bordered().title(format!(" {} ", tr!("tui-details"))));
+    frame.render_widget(details, area);
+}
+
+fn draw_downloads(frame: &mut Frame, app: &App, area: Rect) {
+    let block = pane(
+        format!(" {} ", tr!("tui-downloads")),
+        app.focus == Focus::Downloads,
+    );
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    if app.downloads.is_empty() {
+        frame.render_widget(Paragraph::new(tr!("tui-no-downloads")).dark_gray(), inner);
+        return;
+    }
+
+    // Keep the selected download in view
+    let rows = inner.height as usize;
+    let selected = app.download_state.selected().unwrap_or(0);
+    let first = (selected + 1).saturating_sub(rows);
+    for (row, (i, download)) in app
+        .downloads
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(rows)
+        .enumerate()
+    {
+        let line = Rect {
+            y: inner.y + row as u16,
+            height: 1,
+            ..inner
+        };
+        let [label, bar] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+                .areas(line);
+
+        let mut title = Line::from(format!("{} — {}", download.show, download.episode.title));
+        if app.focus == Focus::Downloads && i == selected {
+            title = title.style(highlight());
+        }
+        frame.render_widget(Paragraph::new(title), label);
+
+        match &download.state {
+            DownloadState::Queued => frame.render_widget(
+                Paragraph::new(format!(" {}", tr!("tui-queued"))).dark_gray(),
+                bar,
+            ),
+            DownloadState::Active => {
+                let done = download.progress.position();
+                let (ratio, text) = match download.progress.length().filter(|&l| l > 0) {
+                    Some(total) => (
+                        (done as f64 / total as f64).min(1.0),
+                        format!("{} / {}", HumanBytes(done), HumanBytes(total)),
+                    ),
+                    None => (0.0, HumanBytes(done).to_string()),
+                };
+                let gauge = Gauge::default()
+                    .gauge_style(Style::new().fg(Color::Cyan))
+                    .ratio(ratio)
+                    .label(text);
+                frame.render_widget(gauge, bar);
+            }
+            DownloadState::Done(path) => frame.render_widget(
+                Paragraph::new(format!(" ✓ {}", path.display())).green(),
+                bar,
+            ),
+            DownloadState::Failed(error) => {
+                frame.render_widget(Paragraph::new(format!(" ✗ {}", error)).red(), bar)
+            }
+        }
+    }
+}
+
+fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
+    let line = if app.searching {
+        let filter = match app.focus {
+            Focus::Feeds => &app.feed_filter,
+            _ => &app.episode_filter,
+        };
+        Line::from(format!("/{}▏  ({})", filter, tr!("tui-filter-help")))
+    } else {
+        Line::from(format!("{}   ·  {}", app.status, tr!("tui-keys"))).dark_gray()
+    };
+    frame.render_widget(Paragraph::new(line), area);
+}
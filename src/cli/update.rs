@@ -0,0 +1,290 @@
+use super::download::{download_configured, download_pending};
+use super::feeds;
+use super::output;
+use anyhow::{Result, bail};
+use indicatif::HumanBytes;
+use pdl::changes::{DEFAULT_TOLERANCE, RecordedMedia, RefreshChanged, size_changed};
+use pdl::config::Config;
+use pdl::download::IfExists;
+use pdl::history::{History, LastSeen};
+use pdl::interrupt::Interrupted;
+use pdl::listing;
+use pdl::subscriptions::{Subscription, Subscriptions};
+use pdl::tr;
+use pdl::{Episode, PodcastClient};
+use serde::Serialize;
+use tracing::{info, warn};
+
+/// Downloads per feed and update that the server is asked about, newest
+/// first, when the feed doesn't give their size
+const MAX_PROBES: usize = 5;
+
+/// What `pdl update` found in one feed, as printed with `--json`
+#[derive(Serialize)]
+struct FeedUpdate {
+    name: String,
+    url: String,
+    /// The feed hadn't been checked before, so nothing counts as new yet
+    first_check: bool,
+    new: Vec<NewEpisode>,
+    /// Downloaded episodes whose audio the publisher has since replaced
+    replaced: Vec<NewEpisode>,
+    error: Option<String>,
+}
+
+/// How `pdl update` treats downloads whose audio has been replaced since
+#[derive(Clone, Copy)]
+struct Replacements {
+    policy: RefreshChanged,
+    /// Percent a download's size may change by before it counts
+    tolerance: u8,
+}
+
+#[derive(Serialize)]
+struct NewEpisode {
+    title: String,
+    guid: Option<String>,
+    url: String,
+    published: Option<String>,
+}
+
+impl NewEpisode {
+    fn new(episode: &Episode) -> Self {
+        Self {
+            title: episode.title.clone(),
+            guid: episode.guid.clone(),
+            url: episode.url.clone(),
+            published: episode.published.map(|d| d.to_rfc3339()),
+        }
+    }
+}
+
+/// Check every subscription for episodes published since the last update,
+/// downloading them too with `download`, and for downloads the publisher has
+/// replaced, dealt with according to `refresh_changed`
+pub async fn update(
+    client: &PodcastClient,
+    config: &Config,
+    download: bool,
+    refresh_changed: Option<RefreshChanged>,
+) -> Result<()> {
+    let replacements = Replacements {
+        policy: refresh_changed
+            .or(config.refresh_changed)
+            .unwrap_or_default(),
+        tolerance: config.change_tolerance.unwrap_or(DEFAULT_TOLERANCE),
+    };
+    let subscriptions = Subscriptions::load()?;
+    let history = History::open_default()?;
+
+    let mut updates = Vec::new();
+    for subscription in &subscriptions.feeds {
+        let mut update = FeedUpdate {
+            name: subscription.name.clone(),
+            url: subscription.url.clone(),
+            first_check: false,
+            new: Vec::new(),
+            replaced: Vec::new(),
+            error: None,
+        };
+        if let Err(e) = check(
+            client,
+            config,
+            &history,
+            subscription,
+            download,
+            replacements,
+            &mut update,
+        )
+        .await
+        {
+            if e.is::<Interrupted>() {
+                return Err(e);
+            }
+            warn!("{}: {:#}", subscription.name, e);
+            update.error = Some(format!("{:#}", e));
+        }
+        updates.push(update);
+    }
+
+    let failed = updates.iter().filter(|u| u.error.is_some()).count();
+    if output::json() {
+        output::emit(&updates)?;
+    } else if output::porcelain() {
+        for update in &updates {
+            let new = update.new.iter().map(|episode| ("new", episode));
+            let replaced = update.replaced.iter().map(|episode| ("replaced", episode));
+            for (kind, episode) in new.chain(replaced) {
+                output::row(&[
+                    kind,
+                    &update.name,
+                    &episode.title,
+                    episode.guid.as_deref().unwrap_or_default(),
+                    episode.published.as_deref().unwrap_or_default(),
+                    &episode.url,
+                ]);
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} feeds could not be updated", failed, updates.len());
+    }
+    Ok(())
+}
+
+/// Report (and download) the new episodes of one feed, then remember its
+/// newest episode for next time. A failed download leaves the feed's last
+/// seen episode alone, so the next update offers the episodes again.
+async fn check(
+    client: &PodcastClient,
+    config: &Config,
+    history: &History,
+    subscription: &Subscription,
+    download: bool,
+    replacements: Replacements,
+    update: &mut FeedUpdate,
+) -> Result<()> {
+    let feed = client.fetch_feed(&subscription.url, usize::MAX).await?;
+    let interactive = output::interactive();
+    let moved;
+    let subscription = match feeds::follow_moved(client, &subscription.url, interactive)? {
+        Some(url) => {
+            update.url = url.clone();
+            moved = Subscription {
+                url,
+                ..subscription.clone()
+            };
+            &moved
+        }
+        None => subscription,
+    };
+    let Some(newest) = feed.episodes.first() else {
+        info!(
+            "{}",
+            tr!("update-no-episodes", name = subscription.name.as_str())
+        );
+        return Ok(());
+    };
+
+    let new = match history.last_seen(&subscription.url)? {
+        Some(seen) => seen.new_episodes(&feed.episodes),
+        None => {
+            update.first_check = true;
+            Vec::new()
+        }
+    };
+    update.new = new.iter().map(|e| NewEpisode::new(e)).collect();
+
+    if update.first_check {
+        info!(
+            "{}",
+            tr!("update-now-tracking", name = subscription.name.as_str())
+        );
+    } else if new.is_empty() {
+        info!(
+            "{}",
+            tr!("update-nothing-new", name = subscription.name.as_str())
+        );
+    } else {
+        info!(
+            "{}",
+            tr!(
+                "update-new",
+                name = subscription.name.as_str(),
+                count = new.len()
+            )
+        );
+        for episode in &new {
+            info!("  • {}", listing::summary(episode));
+        }
+    }
+
+    if download && !new.is_empty() {
+        download_pending(client, config, subscription, &feed, &new).await?;
+    }
+    history.set_last_seen(&subscription.url, &LastSeen::of(newest))?;
+
+    if replacements.policy == RefreshChanged::Ignore {
+        return Ok(());
+    }
+    let replaced = find_replaced(client, history, &feed.episodes, replacements.tolerance).await?;
+    update.replaced = replaced
+        .iter()
+        .map(|r| NewEpisode::new(r.episode))
+        .collect();
+    for replaced in &replaced {
+        warn!(
+            "{}",
+            tr!(
+                "episode-replaced",
+                name = subscription.name.as_str(),
+                title = replaced.episode.title.as_str(),
+                sizes = replaced.sizes.to_string()
+            )
+        );
+    }
+    if replacements.policy == RefreshChanged::Redownload && !replaced.is_empty() {
+        let episodes: Vec<&Episode> = replaced.iter().map(|r| r.episode).collect();
+        let client = client.clone().with_if_exists(IfExists::Overwrite);
+        download_configured(&client, config, &subscription.url, &feed, &episodes).await?;
+    }
+    Ok(())
+}
+
+/// A downloaded episode whose media has been swapped for a different file
+struct Replaced<'a> {
+    episode: &'a Episode,
+    /// The size before and after, e.g. `57.0 MB → 31.2 MB`
+    sizes: String,
+}
+
+/// Downloaded `episodes` whose media changed size by more than `tolerance`
+/// percent since, going by the sizes the feed gives. For the few newest
+/// that it gives none for, the server is asked for the media's ETag instead,
+/// which is remembered the first time; only a new ETag with a changed size
+/// counts, as stitched-in ads change the ETag too.
+async fn find_replaced<'a>(
+    client: &PodcastClient,
+    history: &History,
+    episodes: &'a [Episode],
+    tolerance: u8,
+) -> Result<Vec<Replaced<'a>>> {
+    let mut replaced = Vec::new();
+    let mut probes = 0;
+    for episode in episodes {
+        let Some(recorded) = history.recorded_media(episode)? else {
+            continue;
+        };
+        let (was, now) = match (recorded.length, episode.size) {
+            (Some(was), Some(now)) => (was, now),
+            _ if probes == MAX_PROBES => continue,
+            _ => {
+                probes += 1;
+                let Some(probe) = client.probe_media(episode).await else {
+                    continue;
+                };
+                match (recorded.etag, recorded.length, probe.length) {
+                    (None, length, _) => {
+                        let baseline = RecordedMedia {
+                            length: length.or(probe.length),
+                            etag: probe.etag,
+                        };
+                        history.set_recorded_media(episode, &baseline)?;
+                        continue;
+                    }
+                    (Some(etag), Some(was), Some(now)) if probe.etag.as_ref() != Some(&etag) => {
+                        (was, now)
+                    }
+                    _ => continue,
+                }
+            }
+        };
+        if size_changed(was, now, tolerance) {
+            replaced.push(Replaced {
+                episode,
+                sizes: format!("{} → {}", HumanBytes(was), HumanBytes(now)),
+            });
+        }
+    }
+    Ok(replaced)
+}
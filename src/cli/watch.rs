@@ -0,0 +1,123 @@
+use super::download::queue_new;
+use super::prune::prune_feed;
+use super::{notify, queue, serve};
+use anyhow::Result;
+use pdl::PodcastClient;
+use pdl::config::Config;
+use pdl::history::History;
+use pdl::interrupt::{self, Interrupted};
+use pdl::subscriptions::Subscriptions;
+use pdl::tr;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Check every subscription for new episodes, then again every `interval`
+/// (or once, with `once`), queueing what each feed's rule picks and then
+/// downloading everything queued. Outside the download window, if one is
+/// configured and not overridden with `now`, episodes are only queued.
+/// With `metrics`, Prometheus metrics are served there meanwhile.
+pub async fn watch(
+    client: &PodcastClient,
+    config: &Config,
+    interval: Option<Duration>,
+    once: bool,
+    now: bool,
+    metrics: Option<SocketAddr>,
+) -> Result<()> {
+    match metrics {
+        // The checks end the watch, the server only with an error
+        Some(listen) => tokio::select! {
+            result = check(client, config, interval, once, now) => result,
+            result = serve::serve_metrics(listen) => result,
+        },
+        None => check(client, config, interval, once, now).await,
+    }
+}
+
+async fn check(
+    client: &PodcastClient,
+    config: &Config,
+    interval: Option<Duration>,
+    once: bool,
+    now: bool,
+) -> Result<()> {
+    let interval = match interval {
+        Some(interval) => interval,
+        None => config.watch_interval()?,
+    };
+    let window = if now { None } else { config.download_window()? };
+    if !once {
+        info!(
+            "{}",
+            tr!(
+                "watching",
+                interval = humantime::format_duration(interval).to_string()
+            )
+        );
+    }
+
+    loop {
+        // Re-read each round so feeds added or removed meanwhile are picked up
+        let subscriptions = Subscriptions::load()?;
+        let history = History::open_default()?;
+        let mut new = 0;
+        for subscription in &subscriptions.feeds {
+            match queue_new(client, subscription).await {
+                Err(e) if e.is::<Interrupted>() => return Err(e),
+                Ok(0) => {}
+                Ok(n) => {
+                    info!(
+                        "{}",
+                        tr!("watch-new", name = subscription.name.as_str(), count = n)
+                    );
+                    new += n;
+                }
+                // One broken feed shouldn't stop the others being checked
+                Err(e) => {
+                    warn!("{}: {:#}", subscription.name, e);
+                    notify::send(&subscription.name, &format!("{:#}", e));
+                }
+            }
+        }
+        info!(
+            "{}",
+            tr!(
+                "watch-checked",
+                feeds = subscriptions.feeds.len(),
+                count = new
+            )
+        );
+
+        // Episodes queued by hand go along with the new ones
+        match window {
+            Some(window) if !window.is_open() => {
+                info!("{}", tr!("outside-window", window = window.to_string()))
+            }
+            _ => match queue::download_queued(client, config, window).await {
+                Err(e) if e.is::<Interrupted>() => return Err(e),
+                Err(e) => warn!("{:#}", e),
+                Ok(()) => {}
+            },
+        }
+        // Make room after the downloads, per each feed's retention settings
+        for subscription in &subscriptions.feeds {
+            if let Err(e) = prune_feed(config, &history, subscription, false) {
+                warn!("{}: {:#}", subscription.name, e);
+            }
+        }
+
+        if once {
+            return Ok(());
+        }
+        // Wake up when the window opens rather than up to an interval later
+        let wait = window
+            .map(|w| w.until_open())
+            .filter(|wait| !wait.is_zero())
+            .map_or(interval, |wait| wait.min(interval));
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = interrupt::interrupted() => return Err(Interrupted.into()),
+        }
+    }
+}
@@ -0,0 +1,851 @@
+use crate::backend::Downloader;
+use crate::changes::RefreshChanged;
+use crate::download::FilenameMode;
+use crate::gpodder::SyncApi;
+use crate::hook::PostCommand;
+use crate::naming::{FilenameTemplate, NameScheme};
+use crate::player::Player;
+use crate::prune::Retention;
+use crate::schedule::DownloadWindow;
+use crate::search::{PodcastIndexCredentials, SearchBackend};
+use crate::subscriptions::Subscription;
+use crate::torrent::TorrentClient;
+use crate::transcode::Transcode;
+use crate::upload::UploadTarget;
+use crate::webhook::{Webhook, WebhookFormat};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Name of the folder used for downloads when none is configured
+const DEFAULT_DOWNLOAD_FOLDER: &str = "podcast-downloads";
+
+/// How often `pdl watch` checks feeds when no interval is configured
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// User settings loaded from `<config dir>/pdl/config.toml`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Root directory for downloaded episodes (`PDL_DOWNLOAD_DIR`)
+    pub download_dir: Option<PathBuf>,
+
+    /// Feed to download from when `--feed` isn't given (`PDL_FEED`)
+    pub default_feed: Option<String>,
+
+    /// Number of episodes to list (`PDL_EPISODES`)
+    pub episodes: Option<usize>,
+
+    /// Number of parallel downloads (`PDL_JOBS`)
+    pub jobs: Option<u16>,
+
+    /// Proxy URL for all requests (`PDL_PROXY`)
+    pub proxy: Option<String>,
+
+    /// File name for episodes, e.g. `{date} {title}` (`PDL_FILENAME_TEMPLATE`)
+    pub filename_template: Option<String>,
+
+    /// How often `pdl watch` checks the subscriptions, e.g. `30m` (`PDL_WATCH_INTERVAL`)
+    pub watch_interval: Option<String>,
+
+    /// Hours when `pdl watch` and `pdl queue start` may download, e.g.
+    /// `01:00-06:00` (`PDL_DOWNLOAD_WINDOW`)
+    pub download_window: Option<String>,
+
+    /// Directory used by `pdl search` when `--search-backend` isn't given
+    pub search_backend: Option<SearchBackend>,
+
+    /// podcastindex.org API key (or `PODCASTINDEX_API_KEY`)
+    pub podcastindex_key: Option<String>,
+
+    /// podcastindex.org API secret (or `PODCASTINDEX_API_SECRET`)
+    pub podcastindex_secret: Option<String>,
+
+    /// Keep a copy of each feed every time it changes, for `pdl feed diff`
+    /// (`PDL_FEED_SNAPSHOTS`)
+    pub feed_snapshots: Option<bool>,
+
+    /// Keep an `.m3u8` playlist per show, and one of new episodes, after
+    /// each batch of downloads (`PDL_PLAYLISTS`)
+    pub playlists: Option<bool>,
+
+    /// Command that plays episodes for `--play` and `pdl play`, e.g. `mpv
+    /// --no-video` (`PDL_PLAYER`)
+    pub player: Option<String>,
+
+    /// Command run on each downloaded file, e.g. `loudgain {file}`
+    /// (`PDL_POST_CMD`)
+    pub post_cmd: Option<String>,
+
+    /// ggml model file for `pdl transcribe` (`PDL_WHISPER_MODEL`)
+    pub whisper_model: Option<PathBuf>,
+
+    /// whisper.cpp program for `pdl transcribe`, if not `whisper-cli` on
+    /// `PATH` (`PDL_WHISPER_CMD`)
+    pub whisper_cmd: Option<String>,
+
+    /// URL to post to when `pdl watch` or `pdl update --download` downloads
+    /// an episode or fails to (`PDL_WEBHOOK_URL`)
+    pub webhook_url: Option<String>,
+
+    /// Body posted to `webhook_url`: json, slack or discord (`PDL_WEBHOOK_FORMAT`)
+    pub webhook_format: Option<WebhookFormat>,
+
+    /// Server for `pdl sync gpodder`, if not gpodder.net (`PDL_GPODDER_SERVER`)
+    pub gpodder_server: Option<String>,
+
+    /// API the sync server speaks: gpodder or nextcloud (`PDL_GPODDER_API`)
+    pub gpodder_api: Option<SyncApi>,
+
+    /// Device pdl syncs as on gpodder.net, if not `pdl` (`PDL_GPODDER_DEVICE`)
+    pub gpodder_device: Option<String>,
+
+    /// Convert downloads with ffmpeg, like `--transcode`, e.g. `mp3:96k`
+    /// (`PDL_TRANSCODE`)
+    pub transcode: Option<String>,
+
+    /// Retention for feeds that don't set their own in `subscriptions.toml`
+    /// (`PDL_KEEP_LATEST`, `PDL_MAX_AGE_DAYS`, `PDL_MAX_SIZE_GB`)
+    pub keep_latest: Option<usize>,
+    pub max_age_days: Option<u64>,
+    pub max_size_gb: Option<f64>,
+
+    /// What fetches episode media: builtin, aria2 or curl (`PDL_DOWNLOADER`)
+    pub downloader: Option<Downloader>,
+
+    /// BitTorrent client for `--prefer-torrent`, e.g. `transmission-cli -w
+    /// {dir}` (`PDL_TORRENT_CMD`)
+    pub torrent_cmd: Option<String>,
+
+    /// What `pdl update` does about downloads whose audio the publisher has
+    /// since replaced: ignore, flag or redownload (`PDL_REFRESH_CHANGED`)
+    pub refresh_changed: Option<RefreshChanged>,
+
+    /// How much, in percent, a download's size may change before it counts
+    /// as replaced; 10 if not set (`PDL_CHANGE_TOLERANCE`)
+    pub change_tolerance: Option<u8>,
+
+    /// Copy finished downloads here, like `--upload`: `s3://bucket/prefix`
+    /// or an rclone `remote:path` (`PDL_UPLOAD`)
+    pub upload: Option<String>,
+
+    /// How strictly episode file names are cleaned up; portable if not set
+    /// (`PDL_FILENAME_MODE`)
+    pub filename_mode: Option<FilenameMode>,
+
+    /// What episode files are named after, like `--name-scheme`; the
+    /// filename template if not set (`PDL_NAME_SCHEME`)
+    pub name_scheme: Option<NameScheme>,
+}
+
+/// Config file table holding the named profiles, e.g. `[profiles.car]`
+const PROFILES: &str = "profiles";
+
+/// Settings that `pdl config get/set` can read and write
+pub const KEYS: [&str; 33] = [
+    "download_dir",
+    "default_feed",
+    "episodes",
+    "jobs",
+    "proxy",
+    "filename_template",
+    "watch_interval",
+    "download_window",
+    "search_backend",
+    "podcastindex_key",
+    "podcastindex_secret",
+    "feed_snapshots",
+    "playlists",
+    "player",
+    "post_cmd",
+    "whisper_model",
+    "whisper_cmd",
+    "webhook_url",
+    "webhook_format",
+    "gpodder_server",
+    "gpodder_api",
+    "gpodder_device",
+    "transcode",
+    "keep_latest",
+    "max_age_days",
+    "max_size_gb",
+    "downloader",
+    "torrent_cmd",
+    "refresh_changed",
+    "change_tolerance",
+    "upload",
+    "filename_mode",
+    "name_scheme",
+];
+
+/// Directory holding pdl's config and subscription files
+pub fn config_dir() -> Result<PathBuf> {
+    let config_dir = dirs::config_dir().context("Could not determine config directory")?;
+    Ok(config_dir.join("pdl"))
+}
+
+/// Directory holding pdl's databases and caches
+pub fn data_dir() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir().context("Could not determine data directory")?;
+    Ok(data_dir.join("pdl"))
+}
+
+impl Config {
+    /// Path of the config file
+    pub fn path() -> Result<PathBuf> {
+        Ok(config_dir()?.join("config.toml"))
+    }
+
+    /// Load the config file (defaults if it doesn't exist) with the settings
+    /// of `profile` in place of the ones they cover, then apply `PDL_*`
+    /// environment variables on top
+    pub fn load(profile: Option<&str>) -> Result<Self> {
+        let mut config = Self::load_file(profile)?;
+        config.apply_env(|name| env::var(name).ok())?;
+        Ok(config)
+    }
+
+    fn load_file(profile: Option<&str>) -> Result<Self> {
+        let path = Self::path()?;
+        let content = if path.exists() {
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?
+        } else {
+            String::new()
+        };
+        Self::from_toml_profile(&content, profile)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// The settings in `content`, overridden by those in its
+    /// `[profiles.<profile>]` table
+    fn from_toml_profile(content: &str, profile: Option<&str>) -> Result<Self> {
+        let Some(name) = profile else {
+            return Self::from_toml(content);
+        };
+        let mut table: toml::Table = toml::from_str(content)?;
+        let settings = table
+            .remove(PROFILES)
+            .and_then(|profiles| profiles.get(name).cloned())
+            .with_context(|| {
+                format!(
+                    "No profile named {:?}; add a [{}.{}] section to the config file",
+                    name, PROFILES, name
+                )
+            })?;
+        let toml::Value::Table(settings) = settings else {
+            bail!("Profile {:?} is not a table", name);
+        };
+        table.extend(settings);
+        Ok(table.try_into()?)
+    }
+
+    /// Override settings with environment variables, as looked up by `var`
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        fn parse<T: std::str::FromStr>(name: &str, value: String) -> Result<T>
+        where
+            T::Err: std::error::Error + Send + Sync + 'static,
+        {
+            value
+                .parse()
+                .with_context(|| format!("Invalid {} value {:?}", name, value))
+        }
+
+        if let Some(dir) = var("PDL_DOWNLOAD_DIR") {
+            self.download_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(feed) = var("PDL_FEED") {
+            self.default_feed = Some(feed);
+        }
+        if let Some(n) = var("PDL_EPISODES") {
+            self.episodes = Some(parse("PDL_EPISODES", n)?);
+        }
+        if let Some(jobs) = var("PDL_JOBS") {
+            self.jobs = Some(parse("PDL_JOBS", jobs)?);
+        }
+        if let Some(proxy) = var("PDL_PROXY") {
+            self.proxy = Some(proxy);
+        }
+        if let Some(template) = var("PDL_FILENAME_TEMPLATE") {
+            self.filename_template = Some(template);
+        }
+        if let Some(interval) = var("PDL_WATCH_INTERVAL") {
+            self.watch_interval = Some(interval);
+        }
+        if let Some(window) = var("PDL_DOWNLOAD_WINDOW") {
+            self.download_window = Some(window);
+        }
+        if let Some(snapshots) = var("PDL_FEED_SNAPSHOTS") {
+            self.feed_snapshots = Some(parse("PDL_FEED_SNAPSHOTS", snapshots)?);
+        }
+        if let Some(playlists) = var("PDL_PLAYLISTS") {
+            self.playlists = Some(parse("PDL_PLAYLISTS", playlists)?);
+        }
+        if let Some(player) = var("PDL_PLAYER") {
+            self.player = Some(player);
+        }
+        if let Some(command) = var("PDL_POST_CMD") {
+            self.post_cmd = Some(command);
+        }
+        if let Some(model) = var("PDL_WHISPER_MODEL") {
+            self.whisper_model = Some(PathBuf::from(model));
+        }
+        if let Some(program) = var("PDL_WHISPER_CMD") {
+            self.whisper_cmd = Some(program);
+        }
+        if let Some(url) = var("PDL_WEBHOOK_URL") {
+            self.webhook_url = Some(url);
+        }
+        if let Some(format) = var("PDL_WEBHOOK_FORMAT") {
+            self.webhook_format = Some(
+                toml::Value::String(format.clone())
+                    .try_into()
+                    .with_context(|| format!("Invalid PDL_WEBHOOK_FORMAT value {:?}", format))?,
+            );
+        }
+        if let Some(downloader) = var("PDL_DOWNLOADER") {
+            self.downloader = Some(
+                toml::Value::String(downloader.clone())
+                    .try_into()
+                    .with_context(|| format!("Invalid PDL_DOWNLOADER value {:?}", downloader))?,
+            );
+        }
+        if let Some(command) = var("PDL_TORRENT_CMD") {
+            self.torrent_cmd = Some(command);
+        }
+        if let Some(policy) = var("PDL_REFRESH_CHANGED") {
+            self.refresh_changed = Some(
+                toml::Value::String(policy.clone())
+                    .try_into()
+                    .with_context(|| format!("Invalid PDL_REFRESH_CHANGED value {:?}", policy))?,
+            );
+        }
+        if let Some(percent) = var("PDL_CHANGE_TOLERANCE") {
+            self.change_tolerance = Some(parse("PDL_CHANGE_TOLERANCE", percent)?);
+        }
+        if let Some(target) = var("PDL_UPLOAD") {
+            self.upload = Some(target);
+        }
+        if let Some(mode) = var("PDL_FILENAME_MODE") {
+            self.filename_mode = Some(
+                toml::Value::String(mode.clone())
+                    .try_into()
+                    .with_context(|| format!("Invalid PDL_FILENAME_MODE value {:?}", mode))?,
+            );
+        }
+        if let Some(scheme) = var("PDL_NAME_SCHEME") {
+            self.name_scheme = Some(
+                toml::Value::String(scheme.clone())
+                    .try_into()
+                    .with_context(|| format!("Invalid PDL_NAME_SCHEME value {:?}", scheme))?,
+            );
+        }
+        if let Some(server) = var("PDL_GPODDER_SERVER") {
+            self.gpodder_server = Some(server);
+        }
+        if let Some(api) = var("PDL_GPODDER_API") {
+            self.gpodder_api = Some(
+                toml::Value::String(api.clone())
+                    .try_into()
+                    .with_context(|| format!("Invalid PDL_GPODDER_API value {:?}", api))?,
+            );
+        }
+        if let Some(device) = var("PDL_GPODDER_DEVICE") {
+            self.gpodder_device = Some(device);
+        }
+        if let Some(transcode) = var("PDL_TRANSCODE") {
+            self.transcode = Some(transcode);
+        }
+        if let Some(n) = var("PDL_KEEP_LATEST") {
+            self.keep_latest = Some(parse("PDL_KEEP_LATEST", n)?);
+        }
+        if let Some(days) = var("PDL_MAX_AGE_DAYS") {
+            self.max_age_days = Some(parse("PDL_MAX_AGE_DAYS", days)?);
+        }
+        if let Some(gb) = var("PDL_MAX_SIZE_GB") {
+            self.max_size_gb = Some(parse("PDL_MAX_SIZE_GB", gb)?);
+        }
+        Ok(())
+    }
+
+    /// The configured filename template, or the default (`{title}`)
+    pub fn filename_template(&self) -> Result<FilenameTemplate> {
+        let template = match &self.filename_template {
+            Some(template) => template.parse().context("Invalid filename_template")?,
+            None => FilenameTemplate::default(),
+        };
+        Ok(self.name_files(template))
+    }
+
+    /// `template` with the configured `filename_mode` and `name_scheme`
+    fn name_files(&self, template: FilenameTemplate) -> FilenameTemplate {
+        template
+            .with_mode(self.filename_mode.unwrap_or_default())
+            .with_scheme(self.name_scheme.unwrap_or_default())
+    }
+
+    /// The file name template for episodes of `subscription`'s feed: its
+    /// own `filename_template`, else the configured one
+    pub fn filename_template_for(
+        &self,
+        subscription: Option<&Subscription>,
+    ) -> Result<FilenameTemplate> {
+        match subscription {
+            Some(Subscription {
+                name,
+                filename_template: Some(template),
+                ..
+            }) => template
+                .parse()
+                .map(|template| self.name_files(template))
+                .with_context(|| format!("Invalid filename_template for \"{}\"", name)),
+            _ => self.filename_template(),
+        }
+    }
+
+    /// The configured `pdl watch` interval, or the default (one hour)
+    pub fn watch_interval(&self) -> Result<Duration> {
+        match &self.watch_interval {
+            Some(interval) => humantime::parse_duration(interval).context("Invalid watch_interval"),
+            None => Ok(DEFAULT_WATCH_INTERVAL),
+        }
+    }
+
+    /// The hours downloads are limited to, if `download_window` is set
+    pub fn download_window(&self) -> Result<Option<DownloadWindow>> {
+        self.download_window
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .context("Invalid download_window")
+    }
+
+    fn from_toml(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Value of setting `key`, or `None` if it isn't set
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        check_key(key)?;
+        let table = toml::Table::try_from(self).context("Failed to serialize config")?;
+        Ok(table.get(key).map(|value| match value {
+            toml::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }))
+    }
+
+    /// Validate `value` for `key` and write it to the config file (in
+    /// `profile`'s table, if given), keeping the rest of the file (comments
+    /// included) as it is
+    pub fn set(key: &str, value: &str, profile: Option<&str>) -> Result<()> {
+        let path = Self::path()?;
+        let content = if path.exists() {
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?
+        } else {
+            String::new()
+        };
+
+        let content = set_in_toml(&content, key, value, profile)
+            .with_context(|| format!("Failed to update {}", path.display()))?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Failed to create config directory")?;
+        }
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Root download directory: the configured one, else a folder in the
+    /// platform downloads directory, else the data directory
+    pub fn download_dir(&self) -> PathBuf {
+        if let Some(dir) = &self.download_dir {
+            return expand_tilde(dir);
+        }
+
+        dirs::download_dir()
+            .map(|dir| dir.join(DEFAULT_DOWNLOAD_FOLDER))
+            .or_else(|| dirs::data_dir().map(|dir| dir.join("pdl").join("downloads")))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_DOWNLOAD_FOLDER))
+    }
+
+    /// The download directory for `subscription`'s feed: its own
+    /// `download_dir`, else the configured one
+    pub fn download_dir_for(&self, subscription: Option<&Subscription>) -> PathBuf {
+        match subscription.and_then(|s| s.download_dir.as_deref()) {
+            Some(dir) => expand_tilde(dir),
+            None => self.download_dir(),
+        }
+    }
+
+    /// The command to run on each file downloaded from `subscription`'s
+    /// feed: its own `post_cmd`, else the configured one
+    pub fn post_cmd(&self, subscription: Option<&Subscription>) -> Result<Option<PostCommand>> {
+        subscription
+            .and_then(|s| s.post_cmd.as_deref())
+            .or(self.post_cmd.as_deref())
+            .map(str::parse)
+            .transpose()
+            .context("Invalid post_cmd")
+    }
+
+    /// The conversion applied to downloads, if `transcode` is set
+    pub fn transcode(&self) -> Result<Option<Transcode>> {
+        self.transcode
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .context("Invalid transcode")
+    }
+
+    /// Where finished downloads are copied, if `upload` is set
+    pub fn upload(&self) -> Result<Option<UploadTarget>> {
+        self.upload
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .context("Invalid upload")
+    }
+
+    /// How many of `subscription`'s downloads to keep: each of its own
+    /// limits, else the configured one
+    pub fn retention(&self, subscription: &Subscription) -> Retention {
+        let own = subscription.retention();
+        Retention {
+            keep_latest: own.keep_latest.or(self.keep_latest),
+            max_age_days: own.max_age_days.or(self.max_age_days),
+            max_size_gb: own.max_size_gb.or(self.max_size_gb),
+        }
+    }
+
+    /// The whisper.cpp model for `pdl transcribe`, with `~` expanded
+    pub fn whisper_model(&self) -> Option<PathBuf> {
+        self.whisper_model.as_deref().map(expand_tilde)
+    }
+
+    /// Where to post download events, if a webhook is configured
+    pub fn webhook(&self) -> Option<Webhook> {
+        Some(Webhook {
+            url: self.webhook_url.clone()?,
+            format: self.webhook_format.unwrap_or_default(),
+        })
+    }
+
+    /// Podcast Index credentials from the environment, falling back to the config file
+    pub fn podcast_index_credentials(&self) -> Option<PodcastIndexCredentials> {
+        let key = env::var("PODCASTINDEX_API_KEY")
+            .ok()
+            .or_else(|| self.podcastindex_key.clone())?;
+        let secret = env::var("PODCASTINDEX_API_SECRET")
+            .ok()
+            .or_else(|| self.podcastindex_secret.clone())?;
+        Some(PodcastIndexCredentials { key, secret })
+    }
+}
+
+fn check_key(key: &str) -> Result<()> {
+    if !KEYS.contains(&key) {
+        bail!(
+            "Unknown config key {:?} (expected one of: {})",
+            key,
+            KEYS.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// `content` with `key` set to `value` (under `[profiles.<profile>]` if
+/// given), which is checked against the key's type
+fn set_in_toml(content: &str, key: &str, value: &str, profile: Option<&str>) -> Result<String> {
+    check_key(key)?;
+    let mut doc: toml_edit::DocumentMut = content.parse().context("Invalid TOML")?;
+
+    let item = match key {
+        "episodes" | "keep_latest" => {
+            let n: usize = value
+                .parse()
+                .with_context(|| format!("Invalid {} value {:?}", key, value))?;
+            toml_edit::value(n as i64)
+        }
+        "max_age_days" => {
+            let days: u64 = value
+                .parse()
+                .with_context(|| format!("Invalid max_age_days value {:?}", value))?;
+            toml_edit::value(days as i64)
+        }
+        "change_tolerance" => {
+            let percent: u8 = value.parse().with_context(|| {
+                format!("Invalid change_tolerance value {:?} (a percentage)", value)
+            })?;
+            toml_edit::value(i64::from(percent))
+        }
+        "max_size_gb" => {
+            let gb: f64 = value
+                .parse()
+                .with_context(|| format!("Invalid max_size_gb value {:?}", value))?;
+            toml_edit::value(gb)
+        }
+        "transcode" => {
+            value.parse::<Transcode>().context("Invalid transcode")?;
+            toml_edit::value(value)
+        }
+        "torrent_cmd" => {
+            value.parse::<TorrentClient>()?;
+            toml_edit::value(value)
+        }
+        "upload" => {
+            value.parse::<UploadTarget>()?;
+            toml_edit::value(value)
+        }
+        "download_window" => {
+            value
+                .parse::<DownloadWindow>()
+                .context("Invalid download_window")?;
+            toml_edit::value(value)
+        }
+        "jobs" => {
+            let jobs: u16 = value
+                .parse()
+                .with_context(|| format!("Invalid jobs value {:?}", value))?;
+            if jobs == 0 {
+                bail!("jobs must be at least 1");
+            }
+            toml_edit::value(i64::from(jobs))
+        }
+        "proxy" => {
+            reqwest::Url::parse(value).with_context(|| format!("Invalid proxy URL {:?}", value))?;
+            toml_edit::value(value)
+        }
+        "filename_template" => {
+            value
+                .parse::<FilenameTemplate>()
+                .context("Invalid filename_template")?;
+            toml_edit::value(value)
+        }
+        "watch_interval" => {
+            humantime::parse_duration(value)
+                .with_context(|| format!("Invalid watch_interval value {:?}", value))?;
+            toml_edit::value(value)
+        }
+        "feed_snapshots" | "playlists" => {
+            let enabled: bool = value.parse().with_context(|| {
+                format!("Invalid {} value {:?} (expected true or false)", key, value)
+            })?;
+            toml_edit::value(enabled)
+        }
+        "webhook_url" => {
+            reqwest::Url::parse(value)
+                .with_context(|| format!("Invalid webhook URL {:?}", value))?;
+            toml_edit::value(value)
+        }
+        "gpodder_server" => {
+            reqwest::Url::parse(value)
+                .with_context(|| format!("Invalid gpodder server URL {:?}", value))?;
+            toml_edit::value(value)
+        }
+        "player" => {
+            Player::from_command(value)?;
+            toml_edit::value(value)
+        }
+        "post_cmd" => {
+            value.parse::<PostCommand>()?;
+            toml_edit::value(value)
+        }
+        _ => toml_edit::value(value),
+    };
+    match profile {
+        Some(name) => {
+            let profiles = doc
+                .entry(PROFILES)
+                .or_insert(toml_edit::table())
+                .as_table_mut()
+                .with_context(|| format!("{} is not a table", PROFILES))?;
+            profiles.set_implicit(true);
+            profiles.entry(name).or_insert(toml_edit::table())[key] = item;
+        }
+        None => doc[key] = item,
+    }
+
+    // Catches the rest, e.g. an unknown search_backend
+    let content = doc.to_string();
+    Config::from_toml_profile(&content, profile)
+        .with_context(|| format!("Invalid {} value {:?}", key, value))?;
+    Ok(content)
+}
+
+/// Expand a leading `~` to the user's home directory
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => match dirs::home_dir() {
+            Some(home) => home.join(rest),
+            None => path.to_path_buf(),
+        },
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml_reads_download_dir() {
+        let config = Config::from_toml("download_dir = \"/srv/podcasts\"").unwrap();
+        assert_eq!(config.download_dir, Some(PathBuf::from("/srv/podcasts")));
+        assert_eq!(config.download_dir(), PathBuf::from("/srv/podcasts"));
+    }
+
+    #[test]
+    fn test_feed_settings_override_config() {
+        let config = Config::from_toml("download_dir = \"/srv/podcasts\"").unwrap();
+        let subscription: Subscription = toml::from_str(
+            "name = \"Show\"
+url = \"https://example.com/feed.rss\"
+download_dir = \"/srv/audiobooks\"
+filename_template = \"{number} {title}\"",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.download_dir_for(Some(&subscription)),
+            PathBuf::from("/srv/audiobooks")
+        );
+        assert_eq!(config.download_dir_for(None), config.download_dir());
+        assert_eq!(
+            config.filename_template_for(Some(&subscription)).unwrap(),
+            "{number} {title}".parse().unwrap()
+        );
+        assert_eq!(
+            config.filename_template_for(None).unwrap(),
+            FilenameTemplate::default()
+        );
+    }
+
+    #[test]
+    fn test_profiles_override_settings() {
+        let content = "download_dir = \"~/Podcasts\"
+jobs = 4
+
+[profiles.car]
+download_dir = \"/media/usb\"
+transcode = \"mp3:96k\"
+keep_latest = 3
+";
+        let base = Config::from_toml_profile(content, None).unwrap();
+        assert_eq!(base.download_dir, Some(PathBuf::from("~/Podcasts")));
+        assert_eq!(base.transcode, None);
+
+        let car = Config::from_toml_profile(content, Some("car")).unwrap();
+        assert_eq!(car.download_dir, Some(PathBuf::from("/media/usb")));
+        assert_eq!(car.jobs, Some(4));
+        assert_eq!(car.keep_latest, Some(3));
+        assert!(car.transcode().unwrap().is_some());
+        assert!(Config::from_toml_profile(content, Some("archive")).is_err());
+
+        let updated = set_in_toml(content, "jobs", "1", Some("car")).unwrap();
+        let updated = set_in_toml(&updated, "jobs", "2", Some("archive")).unwrap();
+        assert!(set_in_toml(&updated, "transcode", "wav", Some("car")).is_err());
+        let archive = Config::from_toml_profile(&updated, Some("archive")).unwrap();
+        assert_eq!(archive.jobs, Some(2));
+        assert_eq!(archive.download_dir, Some(PathBuf::from("~/Podcasts")));
+        let car = Config::from_toml_profile(&updated, Some("car")).unwrap();
+        assert_eq!(car.jobs, Some(1));
+        assert_eq!(Config::from_toml(&updated).unwrap().jobs, Some(4));
+    }
+
+    #[test]
+    fn test_from_toml_empty_uses_defaults() {
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_from_toml_reads_search_settings() {
+        let config = Config::from_toml(
+            "search_backend = \"podcastindex\"\npodcastindex_key = \"k\"\npodcastindex_secret = \"s\"",
+        )
+        .unwrap();
+        assert_eq!(config.search_backend, Some(SearchBackend::PodcastIndex));
+        assert_eq!(config.podcastindex_key.as_deref(), Some("k"));
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config = Config::from_toml(
+            "episodes = 5
+jobs = 2
+proxy = \"http://file:8080\"",
+        )
+        .unwrap();
+        let env = |name: &str| match name {
+            "PDL_JOBS" => Some("4".to_string()),
+            "PDL_FEED" => Some("https://example.com/feed.rss".to_string()),
+            _ => None,
+        };
+        config.apply_env(env).unwrap();
+
+        assert_eq!(config.episodes, Some(5));
+        assert_eq!(config.jobs, Some(4));
+        assert_eq!(config.proxy.as_deref(), Some("http://file:8080"));
+        assert_eq!(
+            config.default_feed.as_deref(),
+            Some("https://example.com/feed.rss")
+        );
+
+        let bad = |name: &str| (name == "PDL_EPISODES").then(|| "many".to_string());
+        assert!(config.apply_env(bad).is_err());
+    }
+
+    #[test]
+    fn test_set_in_toml_keeps_comments() {
+        let content = "# my settings\njobs = 2 # two at a time\n";
+
+        let updated = set_in_toml(content, "episodes", "25", None).unwrap();
+        let updated = set_in_toml(&updated, "download_dir", "~/Podcasts", None).unwrap();
+
+        assert!(updated.starts_with("# my settings\njobs = 2 # two at a time\n"));
+        let config = Config::from_toml(&updated).unwrap();
+        assert_eq!(config.episodes, Some(25));
+        assert_eq!(
+            config.get("download_dir").unwrap().as_deref(),
+            Some("~/Podcasts")
+        );
+        assert_eq!(config.get("episodes").unwrap().as_deref(), Some("25"));
+        assert_eq!(config.get("proxy").unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_in_toml_validates_values() {
+        assert!(set_in_toml("", "jobs", "0", None).is_err());
+        assert!(set_in_toml("", "episodes", "many", None).is_err());
+        assert!(set_in_toml("", "proxy", "not a url", None).is_err());
+        assert!(set_in_toml("", "filename_template", "{author}", None).is_err());
+        assert!(set_in_toml("", "search_backend", "bing", None).is_err());
+        assert!(set_in_toml("", "colour", "blue", None).is_err());
+        assert!(set_in_toml("", "watch_interval", "often", None).is_err());
+        assert!(set_in_toml("", "watch_interval", "30m", None).is_ok());
+        assert!(set_in_toml("", "download_window", "night", None).is_err());
+        assert!(set_in_toml("", "download_window", "01:00-06:00", None).is_ok());
+        assert!(set_in_toml("", "change_tolerance", "5%", None).is_err());
+        assert!(set_in_toml("", "refresh_changed", "sometimes", None).is_err());
+        assert!(set_in_toml("", "refresh_changed", "redownload", None).is_ok());
+        assert!(set_in_toml("", "upload", "/mnt/backup", None).is_err());
+        assert!(set_in_toml("", "upload", "s3://bucket/podcasts", None).is_ok());
+        assert!(set_in_toml("", "filename_mode", "strict", None).is_err());
+        assert!(set_in_toml("", "filename_mode", "posix", None).is_ok());
+        assert!(set_in_toml("", "name_scheme", "uuid", None).is_err());
+        assert!(set_in_toml("", "search_backend", "podcastindex", None).is_ok());
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_tilde(Path::new("~/Podcasts")), home.join("Podcasts"));
+        assert_eq!(
+            expand_tilde(Path::new("/abs/path")),
+            PathBuf::from("/abs/path")
+        );
+        assert_eq!(expand_tilde(Path::new("a/~/b")), PathBuf::from("a/~/b"));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::auth::Credentials;
+use crate::config;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Keyring service the secrets are filed under, one entry per feed URL
+const KEYRING_SERVICE: &str = "pdl";
+
+/// How a stored login authenticates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoginKind {
+    Basic,
+    Bearer,
+}
+
+/// A feed with credentials in the keyring. Only the secret-free part is
+/// written to disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredLogin {
+    pub url: String,
+    pub kind: LoginKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+}
+
+/// Index of the feeds whose passwords and tokens are kept in the system
+/// keyring (macOS Keychain, Windows Credential Manager, Secret Service),
+/// stored as TOML in the user config dir. Keyring calls block, and some
+/// backends run their own async runtime, so call them from blocking threads
+/// (e.g. `spawn_blocking`) in async code.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CredentialStore {
+    #[serde(default)]
+    pub feeds: Vec<StoredLogin>,
+}
+
+impl CredentialStore {
+    /// Path of the index (`<config dir>/pdl/credentials.toml`)
+    pub fn path() -> Result<PathBuf> {
+        Ok(config::config_dir()?.join("credentials.toml"))
+    }
+
+    /// Load the index, empty if no file exists yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Write the index back to disk, creating the config dir if needed
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create config directory")?;
+        }
+
+        let content =
+            toml::to_string_pretty(self).context("Failed to serialize credentials index")?;
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn find(&self, url: &str) -> Option<&StoredLogin> {
+        self.feeds.iter().find(|login| login.url == url)
+    }
+
+    /// Put the secret for `url` in the keyring and note the feed in the
+    /// index, replacing what was stored for it before
+    pub fn add(&mut self, url: &str, credentials: &Credentials) -> Result<()> {
+        let (login, secret) = match credentials {
+            Credentials::Basic { username, password } => (
+                StoredLogin {
+                    url: url.to_string(),
+                    kind: LoginKind::Basic,
+                    username: Some(username.clone()),
+                },
+                password.clone().unwrap_or_default(),
+            ),
+            Credentials::Bearer(token) => (
+                StoredLogin {
+                    url: url.to_string(),
+                    kind: LoginKind::Bearer,
+                    username: None,
+                },
+                token.clone(),
+            ),
+        };
+        entry(url)?
+            .set_password(&secret)
+            .context("Failed to save credentials in the system keyring")?;
+
+        self.feeds.retain(|existing| existing.url != url);
+        self.feeds.push(login);
+        Ok(())
+    }
+
+    /// Delete the keyring entry for `url` and drop it from the index
+    pub fn remove(&mut self, url: &str) -> Result<StoredLogin> {
+        let Some(index) = self.feeds.iter().position(|login| login.url == url) else {
+            bail!("No stored credentials for {}", url);
+        };
+        match entry(url)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => {
+                return Err(e).context("Failed to delete credentials from the system keyring");
+            }
+        }
+        Ok(self.feeds.remove(index))
+    }
+
+    /// The credentials stored for `url`, read from the keyring
+    pub fn get(&self, url: &str) -> Result<Option<Credentials>> {
+        let Some(login) = self.find(url) else {
+            return Ok(None);
+        };
+        let secret = entry(url)?.get_password().with_context(|| {
+            format!(
+                "Failed to read credentials for {} from the system keyring",
+                url
+            )
+        })?;
+        Ok(Some(match login.kind {
+            LoginKind::Basic => Credentials::Basic {
+                username: login.username.clone().unwrap_or_default(),
+                password: Some(secret).filter(|p| !p.is_empty()),
+            },
+            LoginKind::Bearer => Credentials::Bearer(secret),
+        }))
+    }
+}
+
+fn entry(url: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, url).context("Failed to open the system keyring")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_has_no_secrets() {
+        let store = CredentialStore {
+            feeds: vec![
+                StoredLogin {
+                    url: "https://example.com/private.rss".to_string(),
+                    kind: LoginKind::Basic,
+                    username: Some("ann".to_string()),
+                },
+                StoredLogin {
+                    url: "https://example.com/token.rss".to_string(),
+                    kind: LoginKind::Bearer,
+                    username: None,
+                },
+            ],
+        };
+        let content = toml::to_string_pretty(&store).unwrap();
+        assert!(content.contains("kind = \"basic\""));
+        assert!(content.contains("kind = \"bearer\""));
+
+        let loaded: CredentialStore = toml::from_str(&content).unwrap();
+        assert_eq!(loaded.feeds, store.feeds);
+        assert_eq!(
+            loaded.find("https://example.com/token.rss").map(|l| l.kind),
+            Some(LoginKind::Bearer)
+        );
+        assert_eq!(loaded.find("https://example.com/other.rss"), None);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::checksum::{self, HashAlgorithm};
+use crate::library::{LibraryEpisode, UNKNOWN_SHOW};
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+/// Share of their words two titles must have in common to count as similar
+const TITLE_SIMILARITY: f64 = 0.8;
+
+/// Titles with fewer words only count as similar when they are the same
+/// and so are the episodes' lengths
+const MIN_TITLE_WORDS: usize = 3;
+
+/// Percent two episodes' lengths may differ by and still be one episode
+/// posted twice; inserted ads make cross-posts differ a little
+const DURATION_TOLERANCE: u64 = 5;
+
+/// A downloaded episode's file, as `pdl dedupe` reports it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LibraryFile {
+    pub show: String,
+    pub title: String,
+    pub path: PathBuf,
+}
+
+impl LibraryFile {
+    fn new(episode: &LibraryEpisode, path: &Path) -> Self {
+        Self {
+            show: episode.show.as_deref().unwrap_or(UNKNOWN_SHOW).to_string(),
+            title: episode.title.clone(),
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+/// Downloads whose files have the same content
+#[derive(Debug, Clone, Serialize)]
+pub struct IdenticalFiles {
+    /// SHA-256 of the content
+    pub hash: String,
+    /// Bytes of each file
+    pub size: u64,
+    /// By path; [`hardlink`] keeps the first
+    pub files: Vec<LibraryFile>,
+}
+
+impl IdenticalFiles {
+    /// Bytes that linking the files together frees: all but one copy
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64).saturating_sub(1)
+    }
+}
+
+/// The downloaded files among `episodes` that have the same content as
+/// another. Only files of the same size are hashed, and names for a file
+/// that is already hard-linked count once.
+pub async fn identical_files(episodes: &[LibraryEpisode]) -> Result<Vec<IdenticalFiles>> {
+    let mut seen = HashSet::new();
+    let mut by_size: BTreeMap<u64, Vec<LibraryFile>> = BTreeMap::new();
+    for episode in episodes {
+        let Some(path) = episode.path.as_deref() else {
+            continue;
+        };
+        let Ok(metadata) = fs::metadata(path) else {
+            continue;
+        };
+        if !metadata.is_file() || metadata.len() == 0 {
+            continue;
+        }
+        if file_id(&metadata).is_some_and(|id| !seen.insert(id)) {
+            continue;
+        }
+        by_size
+            .entry(metadata.len())
+            .or_default()
+            .push(LibraryFile::new(episode, path));
+    }
+
+    let mut identical = Vec::new();
+    for (size, files) in by_size.into_iter().filter(|(_, files)| files.len() > 1) {
+        let mut by_hash: BTreeMap<String, Vec<LibraryFile>> = BTreeMap::new();
+        for file in files {
+            let hash = checksum::file_digest(&file.path, HashAlgorithm::Sha256).await?;
+            by_hash.entry(hash).or_default().push(file);
+        }
+        for (hash, mut files) in by_hash.into_iter().filter(|(_, files)| files.len() > 1) {
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            identical.push(IdenticalFiles { hash, size, files });
+        }
+    }
+    identical.sort_by_key(|group| std::cmp::Reverse(group.wasted()));
+    Ok(identical)
+}
+
+/// What tells a file apart from other names for it: device and inode
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Replace `duplicate` with a hard link to `original`, so both names share
+/// one copy of the data. They have to be on the same filesystem.
+pub fn hardlink(original: &Path, duplicate: &Path) -> Result<()> {
+    let mut temporary = duplicate.as_os_str().to_os_string();
+    temporary.push(".pdl-link");
+    let temporary = PathBuf::from(temporary);
+    let _ = fs::remove_file(&temporary);
+    fs::hard_link(original, &temporary).with_context(|| {
+        format!(
+            "Failed to link {} to {} (they must be on the same filesystem)",
+            duplicate.display(),
+            original.display()
+        )
+    })?;
+    // Renaming over the duplicate never leaves it missing
+    if let Err(e) = fs::rename(&temporary, duplicate) {
+        let _ = fs::remove_file(&temporary);
+        return Err(e).with_context(|| format!("Failed to replace {}", duplicate.display()));
+    }
+    Ok(())
+}
+
+/// Link every file in `group` but the first to the first, returning the
+/// bytes freed. Stops at the first that can't be linked.
+pub fn link_group(group: &IdenticalFiles) -> Result<u64> {
+    let [original, duplicates @ ..] = group.files.as_slice() else {
+        bail!("Nothing to link");
+    };
+    let mut freed = 0;
+    for duplicate in duplicates {
+        hardlink(&original.path, &duplicate.path)?;
+        freed += group.size;
+    }
+    Ok(freed)
+}
+
+/// The words of `title`, compatibility-normalized and lowercased, so
+/// punctuation, case and full-width forms don't matter
+fn title_words(title: &str) -> Vec<String> {
+    let title: String = title.nfkc().collect::<String>().to_lowercase();
+    let mut words: Vec<String> = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// Whether two lengths in seconds are close enough for one episode
+fn durations_agree(a: u64, b: u64) -> bool {
+    a.abs_diff(b) * 100 <= a.max(b) * DURATION_TOLERANCE
+}
+
+/// Groups of downloads from different feeds whose titles are nearly the
+/// same and whose lengths, where known, agree: likely one episode posted
+/// to several feeds. Similar titles within one feed, like the parts of a
+/// series, never make a group.
+pub fn similar_titles(episodes: &[LibraryEpisode]) -> Vec<Vec<LibraryFile>> {
+    let files: Vec<(&LibraryEpisode, &Path, Vec<String>)> = episodes
+        .iter()
+        .filter_map(|episode| {
+            let path = episode.path.as_deref().filter(|path| path.is_file())?;
+            Some((episode, path, title_words(&episode.title)))
+        })
+        .filter(|(_, _, words)| !words.is_empty())
+        .collect();
+
+    // Only titles sharing one of their rarest words can be similar enough,
+    // so only those are compared
+    let mut frequency: HashMap<&str, usize> = HashMap::new();
+    for (_, _, words) in &files {
+        for word in words {
+            *frequency.entry(word).or_default() += 1;
+        }
+    }
+    let mut candidates: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut groups = UnionFind::new(files.len());
+    for (i, (_, _, words)) in files.iter().enumerate() {
+        let mut rare: Vec<&str> = words.iter().map(String::as_str).collect();
+        rare.sort_by_key(|word| (frequency[word], *word));
+        let needed = (TITLE_SIMILARITY * words.len() as f64).ceil() as usize;
+        let mut compared = HashSet::new();
+        for word in &rare[..words.len() - needed + 1] {
+            let others = candidates.entry(word).or_default();
+            for &j in others.iter() {
+                if compared.insert(j) && similar(&files[i], &files[j]) {
+                    groups.union(i, j);
+                }
+            }
+            others.push(i);
+        }
+    }
+
+    let mut clusters: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..files.len() {
+        clusters.entry(groups.find(i)).or_default().push(i);
+    }
+    clusters
+        .into_values()
+        // Only episodes posted to more than one feed are cross-posts
+        .filter(|cluster| {
+            let feeds: HashSet<&str> = cluster
+                .iter()
+                .map(|&i| files[i].0.feed_url.as_str())
+                .collect();
+            feeds.len() > 1
+        })
+        .map(|cluster| {
+            let mut cluster: Vec<LibraryFile> = cluster
+                .into_iter()
+                .map(|i| LibraryFile::new(files[i].0, files[i].1))
+                .collect();
+            cluster.sort_by(|a, b| (&a.show, &a.title).cmp(&(&b.show, &b.title)));
+            cluster
+        })
+        .collect()
+}
+
+fn similar(
+    (a, _, a_words): &(&LibraryEpisode, &Path, Vec<String>),
+    (b, _, b_words): &(&LibraryEpisode, &Path, Vec<String>),
+) -> bool {
+    if a.feed_url == b.feed_url {
+        return false;
+    }
+    let durations = a.duration.zip(b.duration);
+    if durations.is_some_and(|(a, b)| !durations_agree(a, b)) {
+        return false;
+    }
+    if a_words.len().min(b_words.len()) < MIN_TITLE_WORDS {
+        return durations.is_some() && a_words == b_words;
+    }
+    let shared = a_words.iter().filter(|word| b_words.contains(word)).count();
+    let all = a_words.len() + b_words.len() - shared;
+    shared as f64 >= TITLE_SIMILARITY * all as f64
+}
+
+/// Sets of indexes, merged as pairs turn out to belong together
+struct UnionFind(Vec<usize>);
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self((0..len).collect())
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.0[i] != i {
+            self.0[i] = self.0[self.0[i]];
+            i = self.0[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.0[a.max(b)] = a.min(b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::EpisodeState;
+
+    fn episode(show: &str, title: &str, duration: Option<u64>, path: &Path) -> LibraryEpisode {
+        LibraryEpisode {
+            feed_url: format!("https://example.com/{}.rss", show),
+            show: Some(show.to_string()),
+            title: title.to_string(),
+            guid: None,
+            url: String::new(),
+            published: None,
+            duration,
+            description: None,
+            path: Some(path.to_path_buf()),
+            state: EpisodeState::Downloaded,
+            played_at: None,
+            snippet: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_finds_and_links_duplicates() {
+        let dir = std::env::temp_dir().join(format!("pdl-dedupe-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, content: &str| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let (a, b, c, d) = (
+            file("a.mp3", "same audio"),
+            file("b.mp3", "same audio"),
+            file("c.mp3", "other audi"),
+            file("d.mp3", "different length"),
+        );
+        let episodes = vec![
+            episode("Show A", "Interview with Jane Doe, Part 1", Some(3600), &a),
+            episode("Show B", "Interview With Jane Doe (Part 1)", Some(3650), &b),
+            episode("Show B", "Interview with Jane Doe, part 1", Some(3600), &c),
+            episode("Show C", "Interview with Jane Doe, Part 1", Some(1800), &d),
+            episode("Show C", "Trailer", None, &d),
+            episode("Show D", "Trailer", None, &c),
+        ];
+
+        let identical = identical_files(&episodes).await.unwrap();
+        assert_eq!(identical.len(), 1);
+        assert_eq!(identical[0].files[0].path, a);
+        assert_eq!(identical[0].files[1].path, b);
+        assert_eq!(identical[0].wasted(), 10);
+
+        // Show B's two posts are one show's; Show C's is too short
+        let similar = similar_titles(&episodes);
+        assert_eq!(similar.len(), 1);
+        let shows: Vec<&str> = similar[0].iter().map(|f| f.show.as_str()).collect();
+        assert_eq!(shows, ["Show A", "Show B", "Show B"]);
+
+        assert_eq!(link_group(&identical[0]).unwrap(), 10);
+        assert_eq!(fs::read_to_string(&b).unwrap(), "same audio");
+        assert!(identical_files(&episodes).await.unwrap().is_empty() || !cfg!(unix));
+
+        // Parts of one show's series are alike but not duplicates
+        let title = "The Big Long Interview with Jane Doe Part";
+        let series = vec![
+            episode("Show A", &format!("{} 1", title), Some(3600), &a),
+            episode("Show A", &format!("{} 2", title), Some(3600), &b),
+            episode("Show A", &format!("{} 1 (Repost)", title), Some(3600), &c),
+        ];
+        assert!(similar_titles(&series).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
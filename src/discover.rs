@@ -0,0 +1,161 @@
+use regex::Regex;
+use reqwest::Url;
+use serde::Serialize;
+use std::fmt;
+use std::sync::LazyLock;
+
+static LINK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<link\b[^>]*>").unwrap());
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)([\w:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+
+/// `type`s of `<link rel="alternate">` that point at a feed
+const FEED_TYPES: [&str; 5] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+    "application/json",
+    "application/xml",
+];
+
+/// A feed a web page advertises with `<link rel="alternate">`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiscoveredFeed {
+    pub url: String,
+    pub title: Option<String>,
+    /// The link's MIME type, e.g. `application/rss+xml`
+    pub kind: String,
+}
+
+impl fmt::Display for DiscoveredFeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.title {
+            Some(title) => write!(f, "{} ({})", title, self.url),
+            None => f.write_str(&self.url),
+        }
+    }
+}
+
+/// The URL given was a web page rather than a feed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedPage {
+    pub url: String,
+    /// Feeds the page links to, in page order
+    pub feeds: Vec<DiscoveredFeed>,
+}
+
+impl fmt::Display for FeedPage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is a web page, not a feed", self.url)?;
+        match self.feeds.len() {
+            0 => write!(f, ", and it doesn't link to any feeds"),
+            1 => write!(f, "; it links to the feed {}", self.feeds[0].url),
+            n => write!(f, "; it links to {} feeds", n),
+        }
+    }
+}
+
+impl std::error::Error for FeedPage {}
+
+/// Feeds linked from the `<head>` of an HTML page, with relative URLs
+/// resolved against `base` (the page's URL)
+pub fn discover_feeds(html: &str, base: &str) -> Vec<DiscoveredFeed> {
+    let base = Url::parse(base).ok();
+    let mut feeds: Vec<DiscoveredFeed> = Vec::new();
+    for tag in LINK_TAG.find_iter(html) {
+        let attribute = |name: &str| {
+            ATTRIBUTE.captures_iter(tag.as_str()).find_map(|captures| {
+                captures[1].eq_ignore_ascii_case(name).then(|| {
+                    let value = captures
+                        .get(2)
+                        .or(captures.get(3))
+                        .or(captures.get(4))
+                        .map_or("", |m| m.as_str());
+                    decode_entities(value.trim())
+                })
+            })
+        };
+        let is_alternate = attribute("rel").is_some_and(|rel| {
+            rel.split_ascii_whitespace()
+                .any(|token| token.eq_ignore_ascii_case("alternate"))
+        });
+        let Some(kind) = attribute("type").map(|t| t.to_ascii_lowercase()) else {
+            continue;
+        };
+        let Some(href) = attribute("href").filter(|href| !href.is_empty()) else {
+            continue;
+        };
+        if !is_alternate || !FEED_TYPES.contains(&kind.as_str()) {
+            continue;
+        }
+
+        let url = match &base {
+            Some(base) => match base.join(&href) {
+                Ok(url) => url.to_string(),
+                Err(_) => continue,
+            },
+            None => href,
+        };
+        if feeds.iter().any(|feed| feed.url == url) {
+            continue;
+        }
+        feeds.push(DiscoveredFeed {
+            url,
+            title: attribute("title").filter(|title| !title.is_empty()),
+            kind,
+        });
+    }
+    feeds
+}
+
+/// Undo the escaping attribute values commonly carry (`&amp;` in query strings)
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_feeds() {
+        let html = r#"<!DOCTYPE html><html><head>
+            <link rel="stylesheet" href="/style.css" type="text/css">
+            <link rel="alternate" type="application/rss+xml" title="Show &amp; Tell" href="/feed.rss?a=1&amp;b=2">
+            <LINK REL='alternate' TYPE='application/atom+xml' HREF='https://cdn.example.com/atom.xml'>
+            <link href="feed.json" rel="alternate" type="application/feed+json" />
+            <link rel="alternate" type="application/rss+xml" href="/feed.rss?a=1&b=2">
+            <link rel="alternate" hreflang="de" href="/de/">
+        </head><body></body></html>"#;
+        let feeds = discover_feeds(html, "https://example.com/shows/tell/");
+        let urls: Vec<&str> = feeds.iter().map(|f| f.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/feed.rss?a=1&b=2",
+                "https://cdn.example.com/atom.xml",
+                "https://example.com/shows/tell/feed.json",
+            ]
+        );
+        assert_eq!(feeds[0].title.as_deref(), Some("Show & Tell"));
+        assert_eq!(feeds[1].kind, "application/atom+xml");
+        assert_eq!(feeds[2].title, None);
+    }
+
+    #[test]
+    fn test_no_feeds() {
+        assert!(
+            discover_feeds(
+                "<html><head><title>Hi</title></head></html>",
+                "https://x.test/"
+            )
+            .is_empty()
+        );
+    }
+}
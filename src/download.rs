@@ -0,0 +1,1249 @@
+use crate::backend::{Backend, Fetch};
+use crate::checksum::{self, HashAlgorithm};
+use crate::client::Http;
+use crate::feed::Episode;
+use crate::interrupt::{self, Interrupted};
+use crate::metrics;
+use crate::naming::{self, FilenameTemplate, NameScheme};
+use crate::retry::check_response;
+use crate::sniff::{self, Sniffed};
+use crate::throttle::RateLimit;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, FixedOffset};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use reqwest::header::{ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_TYPE, RANGE};
+use reqwest::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+use unicode_normalization::UnicodeNormalization;
+
+/// Result of a download request
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadOutcome {
+    /// The episode was downloaded to this path
+    Downloaded(PathBuf),
+    /// A file for the episode already existed at this path
+    AlreadyExists(PathBuf),
+}
+
+/// The bytes received didn't add up to the size the server announced
+#[derive(Debug)]
+pub struct SizeMismatch {
+    /// The partial file, unless the data went to a stream
+    pub part_path: Option<PathBuf>,
+    pub expected: u64,
+    pub received: u64,
+}
+
+impl fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Download incomplete: received {} of {} bytes",
+            self.received, self.expected
+        )?;
+        if self.received < self.expected
+            && let Some(path) = &self.part_path
+        {
+            write!(f, " (partial file kept at {})", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SizeMismatch {}
+
+/// What to do when the file an episode would be saved as already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IfExists {
+    /// Keep the existing file and don't download
+    #[default]
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+    /// Save next to it as `name (1).ext`, `name (2).ext`, ...
+    Rename,
+}
+
+/// The server sent a web page (an error or login page) instead of the media
+#[derive(Debug)]
+pub struct NotMedia;
+
+impl fmt::Display for NotMedia {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The server sent a web page instead of the episode's media (the enclosure URL may be broken)"
+        )
+    }
+}
+
+impl std::error::Error for NotMedia {}
+
+/// Smallest piece of a file worth its own connection
+const MIN_SEGMENT: u64 = 1024 * 1024;
+
+/// How [`download_with`] names and fetches a file
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SaveOptions<'a> {
+    pub template: &'a FilenameTemplate,
+    pub if_exists: IfExists,
+    pub rate_limit: Option<&'a RateLimit>,
+    /// Connections to fetch one file over, if the server takes ranges
+    pub segments: u16,
+    /// Fetches the media in place of pdl's own client, if set
+    pub backend: Option<&'a dyn Backend>,
+}
+
+/// Download `episode` into `dir`, reporting progress on `pb`
+pub async fn download_episode(
+    episode: &Episode,
+    dir: &Path,
+    pb: &ProgressBar,
+) -> Result<DownloadOutcome> {
+    let options = SaveOptions {
+        template: &FilenameTemplate::default(),
+        if_exists: IfExists::default(),
+        rate_limit: None,
+        segments: 1,
+        backend: None,
+    };
+    download_with(&Http::default(), episode, dir, options, pb).await
+}
+
+pub(crate) async fn download_with(
+    client: &Http,
+    episode: &Episode,
+    dir: &Path,
+    options: SaveOptions<'_>,
+    pb: &ProgressBar,
+) -> Result<DownloadOutcome> {
+    let SaveOptions {
+        template,
+        if_exists,
+        rate_limit,
+        segments,
+        backend,
+    } = options;
+    if interrupt::is_interrupted() {
+        return Err(Interrupted.into());
+    }
+
+    // Create the download directory if it doesn't exist
+    fs::create_dir_all(dir)
+        .await
+        .context("Failed to create download directory")?;
+
+    // Before asking the server, guess the extension from the URL or the
+    // feed's declared type; the partial file is named after the guess so an
+    // interrupted download can be resumed
+    let filename = template.render(episode);
+    let guess = guess_extension(episode);
+    let file_with = |extension: &str| dir.join(file_name(&filename, extension));
+
+    // Check if file already exists, dropping any leftover partial copy of it
+    let part_path = match &guess {
+        Some(extension) => part_path(&file_with(extension)),
+        None => dir.join(format!("{}.part", filename)),
+    };
+    let skip = if_exists == IfExists::Skip;
+    let scheme = template.scheme();
+    // A hashed file's name isn't known until it's downloaded, but the
+    // names file remembers it
+    if skip
+        && scheme != NameScheme::Title
+        && let Some(path) = naming::recorded_file(dir, episode)
+    {
+        let _ = fs::remove_file(&part_path).await;
+        return Ok(DownloadOutcome::AlreadyExists(path));
+    }
+    if skip
+        && let Some(extension) = &guess
+        && fs::try_exists(file_with(extension)).await.unwrap_or(false)
+    {
+        let _ = fs::remove_file(&part_path).await;
+        return Ok(DownloadOutcome::AlreadyExists(file_with(extension)));
+    }
+
+    // Resume from a partial file left behind by an interrupted run
+    let existing = fs::metadata(&part_path).await.map(|m| m.len()).unwrap_or(0);
+
+    let mut response = send_download_request(client, &episode.url, existing).await?;
+    if existing > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file doesn't match what the server has; start over
+        response = send_download_request(client, &episode.url, 0).await?;
+    }
+    let mut response = check_response(response).context("Download request failed")?;
+    debug!(
+        "{} {} ({} bytes)",
+        response.status(),
+        response.url(),
+        response
+            .content_length()
+            .map_or_else(|| "unknown".to_string(), |len| len.to_string())
+    );
+    if is_html(&response) {
+        return Err(NotMedia.into());
+    }
+
+    // What the server says the file is wins over the guess
+    let extension = response_extension(&response)
+        .or(guess.clone())
+        .unwrap_or_else(|| DEFAULT_EXTENSION.to_string());
+    let filepath = file_with(&extension);
+    if skip
+        && guess.as_ref() != Some(&extension)
+        && fs::try_exists(&filepath).await.unwrap_or(false)
+    {
+        let _ = fs::remove_file(&part_path).await;
+        return Ok(DownloadOutcome::AlreadyExists(filepath));
+    }
+
+    // Servers that ignore Range reply 200 with the whole file
+    let resumed = existing > 0 && response.status() == StatusCode::PARTIAL_CONTENT;
+    let offset = if resumed { existing } else { 0 };
+
+    // Chunked responses don't say how big they are; show a spinner instead
+    let total_size = response.content_length().map(|len| len + offset);
+    match total_size {
+        Some(total) => pb.set_length(total),
+        None => {
+            pb.set_style(spinner_style());
+            pb.enable_steady_tick(Duration::from_millis(120));
+        }
+    }
+    pb.set_position(offset);
+
+    // Large files from servers that take ranges can come in pieces at once
+    let ranges = match total_size {
+        Some(total) if segments > 1 && (resumed || accepts_ranges(&response)) => {
+            segment_ranges(total, segments, offset)
+        }
+        _ => Vec::new(),
+    };
+
+    // Download with progress
+    let mut file = if resumed {
+        OpenOptions::new()
+            .append(true)
+            .open(&part_path)
+            .await
+            .context("Failed to open partial file")?
+    } else {
+        // Whatever was there didn't match what the server has
+        let _ = fs::remove_file(aria2_control_path(&part_path)).await;
+        File::create(&part_path)
+            .await
+            .context("Failed to create output file")?
+    };
+    let downloaded = match backend {
+        Some(backend) => {
+            drop(file);
+            let url = response.url().to_string();
+            drop(response);
+            let fetch = Fetch {
+                url: &url,
+                path: &part_path,
+                headers: &request_headers(client, &url),
+                rate_limit: rate_limit.map(RateLimit::bytes_per_sec),
+                segments,
+            };
+            fetch_with(backend, fetch, pb).await?
+        }
+        None if ranges.len() > 1 => {
+            download_segments(
+                client, response, &mut file, &part_path, &ranges, rate_limit, pb,
+            )
+            .await?
+        }
+        None => offset + write_body(&mut response, &mut file, None, rate_limit, pb).await?,
+    };
+
+    if let Some(total) = total_size
+        && downloaded != total
+    {
+        // A short file can be resumed next attempt; an oversized one can't
+        if downloaded > total {
+            let _ = fs::remove_file(&part_path).await;
+        }
+        return Err(SizeMismatch {
+            part_path: Some(part_path),
+            expected: total,
+            received: downloaded,
+        }
+        .into());
+    }
+
+    if let Some(expected) = &episode.hash
+        && let Err(e) = checksum::verify(&part_path, expected).await
+    {
+        let _ = fs::remove_file(&part_path).await;
+        return Err(e);
+    }
+
+    // Trust the file's own header over the name it was given
+    let filepath = match sniff::sniff_file(&part_path).await? {
+        Some(Sniffed::Html) => {
+            let _ = fs::remove_file(&part_path).await;
+            return Err(NotMedia.into());
+        }
+        Some(sniffed) => match sniffed.better_extension(&extension) {
+            Some(extension) => file_with(extension),
+            None => filepath,
+        },
+        None => filepath,
+    };
+    let filepath = match scheme {
+        NameScheme::Hash => {
+            let digest = checksum::file_digest(&part_path, HashAlgorithm::Sha256).await?;
+            let extension = filepath.extension().unwrap_or_default().to_string_lossy();
+            dir.join(file_name(&digest, &extension))
+        }
+        _ => filepath,
+    };
+    let filepath = match if_exists {
+        IfExists::Skip if fs::try_exists(&filepath).await.unwrap_or(false) => {
+            let _ = fs::remove_file(&part_path).await;
+            return Ok(DownloadOutcome::AlreadyExists(filepath));
+        }
+        IfExists::Rename => free_path(filepath).await,
+        _ => filepath,
+    };
+
+    fs::rename(&part_path, &filepath)
+        .await
+        .context("Failed to move completed download into place")?;
+    if scheme != NameScheme::Title {
+        naming::record_name(&filepath, episode)?;
+    }
+
+    match total_size {
+        Some(_) => pb.finish_with_message("Download complete"),
+        None => pb.finish_with_message(format!("Downloaded {}", HumanBytes(downloaded))),
+    }
+
+    Ok(DownloadOutcome::Downloaded(filepath))
+}
+
+/// Write the body of `response` to `file`, stopping after `limit` bytes if
+/// given, and return how many bytes were written. Ctrl+C keeps what has
+/// arrived so the next run resumes from it.
+async fn write_body(
+    response: &mut Response,
+    file: &mut File,
+    limit: Option<u64>,
+    rate_limit: Option<&RateLimit>,
+    pb: &ProgressBar,
+) -> Result<u64> {
+    let mut written: u64 = 0;
+    while limit.is_none_or(|limit| written < limit) {
+        let chunk = tokio::select! {
+            biased;
+            _ = interrupt::interrupted() => {
+                file.flush().await.context("Failed to write to file")?;
+                pb.abandon();
+                return Err(Interrupted.into());
+            }
+            chunk = response.chunk() => chunk.context("Failed to read download chunk")?,
+        };
+        let Some(mut chunk) = chunk else {
+            break;
+        };
+        if let Some(limit) = limit {
+            chunk.truncate((limit - written).min(chunk.len() as u64) as usize);
+        }
+        file.write_all(&chunk)
+            .await
+            .context("Failed to write to file")?;
+
+        written += chunk.len() as u64;
+        pb.inc(chunk.len() as u64);
+        metrics::add_bytes(chunk.len() as u64);
+        if let Some(limit) = rate_limit {
+            tokio::select! {
+                _ = limit.consume(chunk.len()) => {}
+                _ = interrupt::interrupted() => {}
+            }
+        }
+    }
+    file.flush().await.context("Failed to write to file")?;
+    Ok(written)
+}
+
+/// Have `backend` download `fetch`, showing its progress by watching the
+/// file grow, and return the file's size
+async fn fetch_with(backend: &dyn Backend, fetch: Fetch<'_>, pb: &ProgressBar) -> Result<u64> {
+    debug!("Handing {} to the external downloader", fetch.url);
+    let path = fetch.path;
+    let mut download = backend.fetch(fetch);
+    let mut tick = tokio::time::interval(Duration::from_millis(250));
+    loop {
+        tokio::select! {
+            biased;
+            _ = interrupt::interrupted() => {
+                // Dropping the download stops the program, keeping what it saved
+                pb.abandon();
+                return Err(Interrupted.into());
+            }
+            result = &mut download => {
+                result?;
+                break;
+            }
+            _ = tick.tick() => {
+                if let Ok(metadata) = fs::metadata(path).await {
+                    pb.set_position(metadata.len());
+                }
+            }
+        }
+    }
+    let size = fs::metadata(path)
+        .await
+        .context("The downloader saved no file")?
+        .len();
+    pb.set_position(size);
+    Ok(size)
+}
+
+/// Headers pdl's own client would add to a request for `url`, such as the
+/// login for its host, for handing to a [`Backend`]
+fn request_headers(client: &Http, url: &str) -> Vec<(String, String)> {
+    let Ok(request) = client.get(url).build() else {
+        return Vec::new();
+    };
+    request
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// Byte ranges to fetch at once for a `total`-byte file split `segments`
+/// ways, when the partial file already holds `have` bytes. The first range
+/// carries on the partial file; each other one is fetched into a file of its
+/// own. The split depends only on `total` and `segments`, so an interrupted
+/// run picks up the same pieces.
+fn segment_ranges(total: u64, segments: u16, have: u64) -> Vec<Range<u64>> {
+    let n = u64::from(segments).min(total / MIN_SEGMENT).max(1);
+    let bounds: Vec<u64> = (0..=n).map(|k| total * k / n).collect();
+    let first_end = bounds.iter().copied().find(|&b| b > have).unwrap_or(total);
+    let rest = bounds
+        .windows(2)
+        .filter(|w| w[0] >= first_end)
+        .map(|w| w[0]..w[1]);
+    std::iter::once(have..first_end).chain(rest).collect()
+}
+
+/// Fetch `ranges` of a file at once: the first from `response` into `file`,
+/// the others over connections of their own into segment files, which are
+/// then appended to `file`. Returns the size of `file`; short of the end of
+/// the first range if the server stopped early, in which case the segment
+/// files are kept for the next attempt.
+async fn download_segments(
+    client: &Http,
+    mut response: Response,
+    file: &mut File,
+    part_path: &Path,
+    ranges: &[Range<u64>],
+    rate_limit: Option<&RateLimit>,
+    pb: &ProgressBar,
+) -> Result<u64> {
+    let url = response.url().to_string();
+    debug!("Downloading {} in {} segments", url, ranges.len());
+    let first = &ranges[0];
+    let rest = ranges[1..].iter().map(|range| {
+        let path = segment_path(part_path, range.start);
+        download_segment(client, &url, range.clone(), path, rate_limit, pb)
+    });
+    let (written, _) = tokio::try_join!(
+        write_body(
+            &mut response,
+            file,
+            Some(first.end - first.start),
+            rate_limit,
+            pb
+        ),
+        futures_util::future::try_join_all(rest),
+    )?;
+    if first.start + written < first.end {
+        return Ok(first.start + written);
+    }
+
+    for range in &ranges[1..] {
+        let path = segment_path(part_path, range.start);
+        let mut segment = File::open(&path)
+            .await
+            .context("Failed to open download segment")?;
+        tokio::io::copy(&mut segment, file)
+            .await
+            .context("Failed to join download segments")?;
+        let _ = fs::remove_file(&path).await;
+    }
+    file.flush().await.context("Failed to write to file")?;
+    Ok(ranges.last().map_or(first.end, |range| range.end))
+}
+
+/// Fetch `range` of the file at `url` into the segment file at `path`,
+/// carrying on from what an earlier attempt left there
+async fn download_segment(
+    client: &Http,
+    url: &str,
+    range: Range<u64>,
+    path: PathBuf,
+    rate_limit: Option<&RateLimit>,
+    pb: &ProgressBar,
+) -> Result<()> {
+    let len = range.end - range.start;
+    let have = fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+    if have > len {
+        let _ = fs::remove_file(&path).await;
+    }
+    let have = if have > len { 0 } else { have };
+    pb.inc(have);
+    if have == len {
+        return Ok(());
+    }
+
+    let from = range.start + have;
+    debug!("Downloading bytes {}-{} of {}", from, range.end - 1, url);
+    let response = client
+        .get(url)
+        .header(RANGE, format!("bytes={}-{}", from, range.end - 1))
+        .send()
+        .await
+        .context("Failed to start download")?;
+    let mut response = check_response(response).context("Download request failed")?;
+    if response.status() != StatusCode::PARTIAL_CONTENT
+        || response.content_length() != Some(len - have)
+    {
+        bail!("The server doesn't send parts of files; download without --segments");
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+        .context("Failed to open download segment")?;
+    let written = write_body(&mut response, &mut file, Some(len - have), rate_limit, pb).await?;
+    if have + written < len {
+        return Err(SizeMismatch {
+            part_path: Some(path),
+            expected: len,
+            received: have + written,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Where the segment of a download starting at byte `start` is kept
+/// (`<name>.<ext>.part.<start>`)
+fn segment_path(part_path: &Path, start: u64) -> PathBuf {
+    let mut name = part_path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", start));
+    part_path.with_file_name(name)
+}
+
+/// Whether the server says it can send parts of the file
+fn accepts_ranges(response: &Response) -> bool {
+    response
+        .headers()
+        .get(ACCEPT_RANGES)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("bytes"))
+}
+
+/// Request `url` for streaming: a checked response that isn't a web page
+pub(crate) async fn open_stream(client: &Http, url: &str) -> Result<Response> {
+    let response = send_download_request(client, url, 0).await?;
+    let response = check_response(response).context("Download request failed")?;
+    if is_html(&response) {
+        return Err(NotMedia.into());
+    }
+    Ok(response)
+}
+
+/// Copy the body of `response` to `writer`, reporting progress on `pb`, and
+/// return how many bytes were written. Nothing is kept to resume from, so
+/// unlike [`download_with`] a failure part way can't be retried.
+pub(crate) async fn copy_stream<W: AsyncWrite + Unpin>(
+    mut response: Response,
+    writer: &mut W,
+    rate_limit: Option<&RateLimit>,
+    pb: &ProgressBar,
+) -> Result<u64> {
+    let total_size = response.content_length();
+    match total_size {
+        Some(total) => pb.set_length(total),
+        None => {
+            pb.set_style(spinner_style());
+            pb.enable_steady_tick(Duration::from_millis(120));
+        }
+    }
+
+    let mut written: u64 = 0;
+    loop {
+        let chunk = tokio::select! {
+            biased;
+            _ = interrupt::interrupted() => {
+                pb.abandon();
+                return Err(Interrupted.into());
+            }
+            chunk = response.chunk() => chunk.context("Failed to read download chunk")?,
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        writer
+            .write_all(&chunk)
+            .await
+            .context("Failed to write episode data")?;
+
+        written += chunk.len() as u64;
+        pb.set_position(written);
+        if let Some(limit) = rate_limit {
+            tokio::select! {
+                _ = limit.consume(chunk.len()) => {}
+                _ = interrupt::interrupted() => {}
+            }
+        }
+    }
+    writer
+        .flush()
+        .await
+        .context("Failed to write episode data")?;
+
+    if let Some(total) = total_size
+        && written != total
+    {
+        return Err(SizeMismatch {
+            part_path: None,
+            expected: total,
+            received: written,
+        }
+        .into());
+    }
+    pb.finish_with_message(format!("Streamed {}", HumanBytes(written)));
+    Ok(written)
+}
+
+/// Whether the server answered with a web page (an error or login page)
+fn is_html(response: &Response) -> bool {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|t| t.trim_start().to_ascii_lowercase().starts_with("text/html"))
+}
+
+/// Extension to expect before asking the server: the URL's, else the one
+/// for the MIME type the feed declares
+fn guess_extension(episode: &Episode) -> Option<String> {
+    extension_from_url(&episode.url).or_else(|| {
+        episode
+            .mime_type
+            .as_deref()
+            .and_then(extension_from_mime)
+            .map(str::to_string)
+    })
+}
+
+/// Where `episode` will most likely be saved in `dir`. The server can still
+/// name a different file type once the download starts, and a file named
+/// by its hash is only known once the names file records it.
+pub fn planned_path(episode: &Episode, dir: &Path, template: &FilenameTemplate) -> PathBuf {
+    if template.scheme() != NameScheme::Title
+        && let Some(path) = naming::recorded_file(dir, episode)
+    {
+        return path;
+    }
+    let extension = guess_extension(episode).unwrap_or_else(|| DEFAULT_EXTENSION.to_string());
+    dir.join(file_name(&template.render(episode), &extension))
+}
+
+/// `path` if nothing is there, else the first free `name (1).ext`, `name (2).ext`, ...
+pub(crate) async fn free_path(path: PathBuf) -> PathBuf {
+    if !fs::try_exists(&path).await.unwrap_or(false) {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let extension = path.extension().map(|e| e.to_string_lossy().into_owned());
+    for n in 1.. {
+        let name = match &extension {
+            Some(extension) => format!("{} ({}).{}", stem, n, extension),
+            None => format!("{} ({})", stem, n),
+        };
+        let candidate = path.with_file_name(name);
+        if !fs::try_exists(&candidate).await.unwrap_or(false) {
+            return candidate;
+        }
+    }
+    unreachable!("unbounded range always yields a free name")
+}
+
+/// Indeterminate progress display for downloads of unknown size
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::default_spinner()
+        .template("[{elapsed_precise}] {spinner:.cyan} {bytes} ({bytes_per_sec}) {msg}")
+        .unwrap_or_else(|_| ProgressStyle::default_spinner())
+}
+
+async fn send_download_request(client: &Http, url: &str, offset: u64) -> Result<Response> {
+    let mut request = client.get(url);
+    if offset > 0 {
+        debug!("Resuming {} from byte {}", url, offset);
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    } else {
+        debug!("Downloading {}", url);
+    }
+    request.send().await.context("Failed to start download")
+}
+
+/// Set the modification time of the file at `path` to `date`, e.g. an
+/// episode's publish date so file browsers sort by it
+pub async fn set_file_date(path: &Path, date: DateTime<FixedOffset>) -> Result<()> {
+    let file = path.to_path_buf();
+    let time = SystemTime::from(date);
+    tokio::task::spawn_blocking(move || {
+        std::fs::File::options()
+            .write(true)
+            .open(file)?
+            .set_modified(time)
+    })
+    .await?
+    .with_context(|| format!("Failed to set the date of {}", path.display()))
+}
+
+/// Delete `.part` files in `dir` that haven't been written to for `max_age`,
+/// returning the paths removed. Fresher ones are kept so they can be resumed.
+pub async fn remove_stale_parts(dir: &Path, max_age: Duration) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(removed),
+        Err(e) => return Err(e).context("Failed to read download directory"),
+    };
+
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .context("Failed to read download directory")?
+    {
+        let path = entry.path();
+        if !is_part_file(&path) {
+            continue;
+        }
+        let age = entry
+            .metadata()
+            .await
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+        if age.is_some_and(|age| age >= max_age) {
+            fs::remove_file(&path)
+                .await
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+/// aria2c's record of how far it got with the download into `part_path`
+fn aria2_control_path(part_path: &Path) -> PathBuf {
+    let mut name = part_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".aria2");
+    part_path.with_file_name(name)
+}
+
+/// Whether `path` is an in-progress file, a segment of one or aria2c's
+/// record of one
+fn is_part_file(path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) if ext == "part" => true,
+        Some(ext)
+            if ext == "aria2" || ext.to_string_lossy().bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            path.file_stem()
+                .is_some_and(|stem| Path::new(stem).extension().is_some_and(|e| e == "part"))
+        }
+        _ => false,
+    }
+}
+
+/// Path of the in-progress file for `path` (`<name>.<ext>.part`)
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// How strictly episode file names are cleaned up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FilenameMode {
+    /// Names every common filesystem takes, so the library can be copied to
+    /// a Windows disk or a FAT-formatted player
+    #[default]
+    Portable,
+    /// Only what the OS pdl runs on forbids: portable on Windows, posix
+    /// elsewhere
+    Native,
+    /// Only what Unix filesystems forbid: `/` and control characters
+    Posix,
+}
+
+/// Names Windows keeps for devices, with or without an extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Longest file name pdl writes, in bytes. Filesystems allow 255; the rest
+/// leaves room for the `.part` and sidecar suffixes added to it.
+pub const MAX_FILENAME_BYTES: usize = 240;
+
+impl FilenameMode {
+    /// `name` made safe to use as a file name: composed to NFC, so the same
+    /// title always gives the same bytes; separators, control characters and,
+    /// unless posix, the characters Windows forbids replaced; Windows device
+    /// names and trailing dots avoided; and cut to [`MAX_FILENAME_BYTES`]
+    pub fn sanitize(self, name: &str) -> String {
+        let portable = match self {
+            Self::Portable => true,
+            Self::Native => cfg!(windows),
+            Self::Posix => false,
+        };
+        let name: String = name
+            .nfc()
+            .map(|c| match c {
+                '/' => '-',
+                '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' if portable => '-',
+                c if c.is_control() => ' ',
+                c => c,
+            })
+            .collect();
+        let name = name.trim();
+        if !portable {
+            return truncate_filename(name, MAX_FILENAME_BYTES);
+        }
+        // Suffixed before cutting the name down, so the `_` counts too
+        let stem = name.split('.').next().unwrap_or_default().trim_end();
+        let name = if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+            format!("{}_{}", stem, &name[stem.len()..])
+        } else {
+            name.to_string()
+        };
+        // Windows drops trailing dots and spaces, so `Why?.` and `Why-`
+        // would name the same file
+        truncate_filename(&name, MAX_FILENAME_BYTES)
+            .trim_end_matches(['.', ' '])
+            .to_string()
+    }
+}
+
+/// `title` made safe to use as a file name on any OS; see
+/// [`FilenameMode::sanitize`]
+pub fn sanitize_filename(title: &str) -> String {
+    FilenameMode::Portable.sanitize(title)
+}
+
+/// `name` cut to at most `max_bytes` bytes, on a character boundary, keeping
+/// its extension
+pub fn truncate_filename(name: &str, max_bytes: usize) -> String {
+    if name.len() <= max_bytes {
+        return name.to_string();
+    }
+    let extension = file_extension(name).map_or(0, |extension| extension.len() + 1);
+    let (stem, extension) = name.split_at(name.len() - extension);
+    let mut end = max_bytes.saturating_sub(extension.len());
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", stem[..end].trim_end(), extension)
+}
+
+/// `stem.extension`, with the stem shortened to fit [`MAX_FILENAME_BYTES`]
+pub fn file_name(stem: &str, extension: &str) -> String {
+    truncate_filename(&format!("{}.{}", stem, extension), MAX_FILENAME_BYTES)
+}
+
+/// Folder name for shows whose title leaves nothing usable after sanitizing
+const UNTITLED_SHOW: &str = "Untitled show";
+
+/// Folder for a show's episodes under the download directory `root`, named
+/// after the sanitized show title
+pub fn show_dir(root: &Path, show: &str) -> PathBuf {
+    let name = sanitize_filename(show);
+    // An empty or all-dots name would point at `root` or its parent
+    if name.chars().all(|c| c == '.') {
+        return root.join(UNTITLED_SHOW);
+    }
+    root.join(name)
+}
+
+/// Extension used when neither the server nor the URL gives one
+pub(crate) const DEFAULT_EXTENSION: &str = "mp3";
+
+/// Extension of the file named by `url`, or `mp3` if it has none
+pub fn get_extension_from_url(url: &str) -> String {
+    extension_from_url(url).unwrap_or_else(|| DEFAULT_EXTENSION.to_string())
+}
+
+/// Extension of the last path segment of `url`, ignoring the query string
+pub fn extension_from_url(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = path.rsplit('/').next().unwrap_or(path);
+    file_extension(name)
+}
+
+/// Lowercased extension of a file name, if it looks like one (a few letters or digits)
+fn file_extension(name: &str) -> Option<String> {
+    let (stem, extension) = name.rsplit_once('.')?;
+    let valid = !stem.is_empty()
+        && (1..=5).contains(&extension.len())
+        && extension.chars().all(|c| c.is_ascii_alphanumeric());
+    valid.then(|| extension.to_ascii_lowercase())
+}
+
+/// Extension for a media MIME type such as `audio/mpeg`
+pub fn extension_from_mime(mime: &str) -> Option<&'static str> {
+    let mime = mime.split(';').next()?.trim().to_ascii_lowercase();
+    let extension = match mime.as_str() {
+        "audio/mpeg" | "audio/mp3" | "audio/mpeg3" | "audio/x-mpeg" => "mp3",
+        "audio/mp4" | "audio/x-m4a" | "audio/m4a" | "audio/aac-mp4" => "m4a",
+        "audio/x-m4b" => "m4b",
+        "audio/aac" | "audio/x-aac" => "aac",
+        "audio/ogg" | "audio/vorbis" => "ogg",
+        "audio/opus" => "opus",
+        "audio/flac" | "audio/x-flac" => "flac",
+        "audio/wav" | "audio/x-wav" | "audio/wave" => "wav",
+        "audio/webm" | "video/webm" => "webm",
+        "video/mp4" => "mp4",
+        "video/x-m4v" => "m4v",
+        "video/quicktime" => "mov",
+        _ => return None,
+    };
+    Some(extension)
+}
+
+/// MIME type to serve a file with this (lowercase) extension as
+pub fn mime_from_extension(extension: &str) -> &'static str {
+    match extension {
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "m4b" => "audio/x-m4b",
+        "aac" => "audio/aac",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "flac" => "audio/flac",
+        "wav" => "audio/wav",
+        "webm" => "audio/webm",
+        "mp4" => "video/mp4",
+        "m4v" => "video/x-m4v",
+        "mov" => "video/quicktime",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Extension of the file name in a `Content-Disposition` header value
+fn extension_from_disposition(value: &str) -> Option<String> {
+    // `filename*=UTF-8''name.m4a` takes precedence over plain `filename=`
+    let mut fallback = None;
+    for param in value.split(';').map(str::trim) {
+        let Some((key, name)) = param.split_once('=') else {
+            continue;
+        };
+        let name = name.trim().trim_matches('"');
+        match key.trim().to_ascii_lowercase().as_str() {
+            "filename*" => {
+                let name = name.rsplit('\'').next().unwrap_or(name);
+                return file_extension(name);
+            }
+            "filename" => fallback = file_extension(name),
+            _ => {}
+        }
+    }
+    fallback
+}
+
+/// Extension the response says the file has: the `Content-Disposition` file
+/// name's, else one matching its `Content-Type`
+fn response_extension(response: &Response) -> Option<String> {
+    let header = |name| response.headers().get(name).and_then(|v| v.to_str().ok());
+    header(CONTENT_DISPOSITION)
+        .and_then(extension_from_disposition)
+        .or_else(|| {
+            header(CONTENT_TYPE)
+                .and_then(extension_from_mime)
+                .map(str::to_string)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_set_file_date() {
+        let path = std::env::temp_dir().join(format!("pdl-file-date-{}.mp3", std::process::id()));
+        std::fs::write(&path, b"audio").unwrap();
+        let date = DateTime::parse_from_rfc3339("2024-01-02T10:00:00+02:00").unwrap();
+
+        set_file_date(&path, date).await.unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(modified, SystemTime::from(date));
+    }
+
+    #[tokio::test]
+    async fn test_free_path_appends_counter() {
+        let dir = std::env::temp_dir().join(format!("pdl-free-path-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Pilot.mp3");
+        let first = free_path(path.clone()).await;
+        std::fs::write(&path, b"1").unwrap();
+        let second = free_path(path.clone()).await;
+        std::fs::write(dir.join("Pilot (1).mp3"), b"2").unwrap();
+        let third = free_path(path.clone()).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first, path);
+        assert_eq!(second, dir.join("Pilot (1).mp3"));
+        assert_eq!(third, dir.join("Pilot (2).mp3"));
+    }
+
+    #[test]
+    fn test_show_dir_stays_under_root() {
+        let root = Path::new("/podcasts");
+        assert_eq!(show_dir(root, "Show: Live"), root.join("Show- Live"));
+        assert_eq!(show_dir(root, ".."), root.join("Untitled show"));
+        assert_eq!(show_dir(root, " "), root.join("Untitled show"));
+    }
+
+    #[test]
+    fn test_sanitize_filename_removes_invalid_chars() {
+        assert_eq!(sanitize_filename("hello/world"), "hello-world");
+        assert_eq!(sanitize_filename("file:name"), "file-name");
+        assert_eq!(sanitize_filename("test*file?"), "test-file-");
+        assert_eq!(sanitize_filename("a<b>c"), "a-b-c");
+        assert_eq!(sanitize_filename("pipe|char"), "pipe-char");
+        assert_eq!(sanitize_filename("back\\slash"), "back-slash");
+        assert_eq!(sanitize_filename("quote\"test"), "quote-test");
+    }
+
+    #[test]
+    fn test_sanitize_filename_preserves_valid_chars() {
+        assert_eq!(sanitize_filename("hello world"), "hello world");
+        assert_eq!(sanitize_filename("episode-01"), "episode-01");
+        assert_eq!(sanitize_filename("podcast_name"), "podcast_name");
+        assert_eq!(sanitize_filename("한글 제목"), "한글 제목");
+    }
+
+    #[test]
+    fn test_sanitize_filename_trims_whitespace() {
+        assert_eq!(sanitize_filename("  hello  "), "hello");
+        assert_eq!(sanitize_filename("\ttest\n"), "test");
+    }
+
+    #[test]
+    fn test_sanitize_filename_for_windows_and_unicode() {
+        assert_eq!(sanitize_filename("CON"), "CON_");
+        assert_eq!(sanitize_filename("nul.txt"), "nul_.txt");
+        assert_eq!(sanitize_filename("Console"), "Console");
+        assert_eq!(sanitize_filename("Why not..."), "Why not");
+        // The suffix doesn't push a name at the limit over it
+        let long = format!("CON.{}.mp3", "a".repeat(MAX_FILENAME_BYTES - 8));
+        assert_eq!(long.len(), MAX_FILENAME_BYTES);
+        let name = sanitize_filename(&long);
+        assert!(name.len() <= MAX_FILENAME_BYTES);
+        assert!(name.starts_with("CON_.") && name.ends_with(".mp3"));
+        assert_eq!(sanitize_filename("a\u{7}b\r\nc"), "a b  c");
+        // Decomposed Hangul (as macOS stores it) is composed
+        assert_eq!(sanitize_filename("\u{1112}\u{1161}\u{11AB}"), "한");
+
+        assert_eq!(FilenameMode::Posix.sanitize("CON: a/b?."), "CON: a-b?.");
+        assert_eq!(
+            FilenameMode::Native.sanitize("a:b"),
+            if cfg!(windows) { "a-b" } else { "a:b" }
+        );
+    }
+
+    #[test]
+    fn test_truncate_filename_keeps_extension() {
+        assert_eq!(truncate_filename("short.mp3", 20), "short.mp3");
+        assert_eq!(truncate_filename("a long title.mp3", 10), "a long.mp3");
+        // Never splits a character
+        assert_eq!(truncate_filename("한글 제목.m4a", 11), "한글.m4a");
+        assert_eq!(truncate_filename("no extension here", 5), "no ex");
+
+        let long = "제".repeat(200);
+        let name = file_name(&long, "mp3");
+        assert!(name.len() <= MAX_FILENAME_BYTES);
+        assert!(name.ends_with("제.mp3"));
+        assert!(sanitize_filename(&long).len() <= MAX_FILENAME_BYTES);
+    }
+
+    #[test]
+    fn test_part_path_appends_suffix() {
+        assert_eq!(
+            part_path(Path::new("downloads/episode.mp3")),
+            PathBuf::from("downloads/episode.mp3.part")
+        );
+        assert_eq!(
+            part_path(Path::new("한글 제목.m4a")),
+            PathBuf::from("한글 제목.m4a.part")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_stale_parts_keeps_recent_files() {
+        let dir = std::env::temp_dir().join(format!("pdl-stale-parts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = dir.join("old.mp3.part");
+        let fresh = dir.join("fresh.mp3.part");
+        let done = dir.join("done.mp3");
+        for path in [&old, &fresh, &done] {
+            std::fs::write(path, b"data").unwrap();
+        }
+        let week_ago = std::time::SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(week_ago)
+            .unwrap();
+
+        let removed = remove_stale_parts(&dir, Duration::from_secs(24 * 60 * 60))
+            .await
+            .unwrap();
+
+        assert_eq!(removed, vec![old.clone()]);
+        assert!(!old.exists());
+        assert!(fresh.exists());
+        assert!(done.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_segment_ranges() {
+        let mib = MIN_SEGMENT;
+        assert_eq!(
+            segment_ranges(4 * mib, 4, 0),
+            vec![0..mib, mib..2 * mib, 2 * mib..3 * mib, 3 * mib..4 * mib]
+        );
+        // Resuming keeps the same split, the partial file growing into the
+        // first piece it hasn't finished
+        assert_eq!(
+            segment_ranges(4 * mib, 4, mib + 10),
+            vec![mib + 10..2 * mib, 2 * mib..3 * mib, 3 * mib..4 * mib]
+        );
+        // Small files aren't worth splitting
+        assert_eq!(segment_ranges(mib + 1, 8, 0).len(), 1);
+        assert_eq!(segment_ranges(1000, 4, 0).len(), 1);
+
+        assert!(is_part_file(Path::new("a/Ep 1.mp3.part")));
+        assert!(is_part_file(Path::new("a/Ep 1.mp3.part.1048576")));
+        assert!(is_part_file(Path::new("a/Ep 1.mp3.part.aria2")));
+        assert!(!is_part_file(Path::new("a/Ep 1.mp3")));
+        assert!(!is_part_file(Path::new("a/Part 2.mp3")));
+    }
+
+    #[test]
+    fn test_get_extension_from_url_basic() {
+        assert_eq!(
+            get_extension_from_url("https://example.com/file.mp3"),
+            "mp3"
+        );
+        assert_eq!(
+            get_extension_from_url("https://example.com/file.MP3"),
+            "mp3"
+        );
+        assert_eq!(
+            get_extension_from_url("https://example.com/audio.m4a"),
+            "m4a"
+        );
+        assert_eq!(
+            get_extension_from_url("https://example.com/video.mp4"),
+            "mp4"
+        );
+    }
+
+    #[test]
+    fn test_get_extension_from_url_with_query_params() {
+        assert_eq!(
+            get_extension_from_url("https://example.com/file.mp3?token=abc123"),
+            "mp3"
+        );
+        assert_eq!(
+            get_extension_from_url("https://cdn.example.com/podcast.m4a?expires=123&sig=xyz"),
+            "m4a"
+        );
+    }
+
+    #[test]
+    fn test_get_extension_from_url_no_extension() {
+        // Dots in the host or earlier path segments don't count
+        assert_eq!(get_extension_from_url("https://example.com/file"), "mp3");
+        assert_eq!(extension_from_url("https://example.com/file"), None);
+        assert_eq!(
+            extension_from_url("https://cdn.example.com/v1.2/episode/123"),
+            None
+        );
+        assert_eq!(extension_from_url("https://example.com/.hidden"), None);
+        assert_eq!(
+            extension_from_url("https://example.com/ep.mp3#t=10").as_deref(),
+            Some("mp3")
+        );
+    }
+
+    #[test]
+    fn test_extension_from_mime() {
+        assert_eq!(extension_from_mime("audio/mpeg"), Some("mp3"));
+        assert_eq!(extension_from_mime("Audio/MP4; codecs=mp4a"), Some("m4a"));
+        assert_eq!(extension_from_mime("video/mp4"), Some("mp4"));
+        assert_eq!(extension_from_mime("application/octet-stream"), None);
+        for extension in ["mp3", "m4a", "opus", "mp4"] {
+            assert_eq!(
+                extension_from_mime(mime_from_extension(extension)),
+                Some(extension)
+            );
+        }
+    }
+
+    #[test]
+    fn test_extension_from_disposition() {
+        let extension = |value| extension_from_disposition(value);
+        assert_eq!(
+            extension("attachment; filename=\"Episode 12.M4A\"").as_deref(),
+            Some("m4a")
+        );
+        assert_eq!(
+            extension("attachment; filename=\"ep.mp3\"; filename*=UTF-8''ep%20one.opus").as_deref(),
+            Some("opus")
+        );
+        assert_eq!(extension("inline"), None);
+        assert_eq!(extension("attachment; filename=episode"), None);
+    }
+
+    #[test]
+    fn test_get_extension_from_url_multiple_dots() {
+        assert_eq!(
+            get_extension_from_url("https://example.com/file.name.mp3"),
+            "mp3"
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use crate::feed::Episode;
+use anyhow::{Result, bail};
+use std::fmt;
+use std::str::FromStr;
+
+/// One version of an episode's media: the `<enclosure>` itself or a
+/// `<podcast:alternateEnclosure>`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Enclosure {
+    pub url: String,
+    pub mime_type: Option<String>,
+    /// Bits per second, if the feed says
+    pub bitrate: Option<u64>,
+    /// RFC 6381 codecs string, e.g. `opus` or `mp4a.40.2`
+    pub codecs: Option<String>,
+    pub title: Option<String>,
+    /// Marked `default="true"` in the feed
+    pub default: bool,
+}
+
+impl Enclosure {
+    /// Whether this version uses `codec`, judged by its codecs string or MIME type
+    pub fn matches_codec(&self, codec: &str) -> bool {
+        let codec = codec.to_ascii_lowercase();
+        let in_codecs = self
+            .codecs
+            .as_deref()
+            .is_some_and(|c| c.to_ascii_lowercase().contains(&codec));
+        let subtype = self
+            .mime_type
+            .as_deref()
+            .and_then(|m| m.split('/').nth(1))
+            .map(str::to_ascii_lowercase);
+        let aliases: &[&str] = match codec.as_str() {
+            "mp3" => &["mpeg", "mp3"],
+            "aac" => &["aac", "mp4", "m4a", "x-m4a"],
+            _ => &[],
+        };
+        in_codecs || subtype.is_some_and(|s| s == codec || aliases.contains(&s.as_str()))
+    }
+}
+
+impl fmt::Display for Enclosure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mime_type.as_deref().unwrap_or("unknown type"))?;
+        if let Some(codecs) = &self.codecs {
+            write!(f, " ({})", codecs)?;
+        }
+        if let Some(bitrate) = self.bitrate {
+            write!(f, ", {} kbps", bitrate / 1000)?;
+        }
+        if let Some(title) = &self.title {
+            write!(f, " — {}", title)?;
+        }
+        Ok(())
+    }
+}
+
+/// Which bitrate to prefer among an episode's versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitratePreference {
+    Highest,
+    Lowest,
+    /// Closest to this many kbps
+    Kbps(u64),
+}
+
+impl FromStr for BitratePreference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "highest" | "high" | "max" => Ok(Self::Highest),
+            "lowest" | "low" | "min" => Ok(Self::Lowest),
+            other => match other.trim_end_matches("kbps").trim().parse() {
+                Ok(kbps) => Ok(Self::Kbps(kbps)),
+                Err(_) => bail!("Expected highest, lowest or a bitrate in kbps, got {:?}", s),
+            },
+        }
+    }
+}
+
+/// How to pick among an episode's enclosures without asking
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnclosurePolicy {
+    pub codec: Option<String>,
+    pub bitrate: Option<BitratePreference>,
+}
+
+impl EnclosurePolicy {
+    pub fn is_empty(&self) -> bool {
+        self.codec.is_none() && self.bitrate.is_none()
+    }
+
+    /// The best of `options` under this policy (`None` if `options` is empty)
+    pub fn choose<'a>(&self, options: &'a [Enclosure]) -> Option<&'a Enclosure> {
+        let mut candidates: Vec<&Enclosure> = options.iter().collect();
+        if let Some(codec) = &self.codec {
+            let matching: Vec<_> = options.iter().filter(|e| e.matches_codec(codec)).collect();
+            // Fall back to every version rather than nothing
+            if !matching.is_empty() {
+                candidates = matching;
+            }
+        }
+
+        let known = candidates.iter().copied().filter(|e| e.bitrate.is_some());
+        let by_bitrate = match self.bitrate {
+            Some(BitratePreference::Highest) => known.max_by_key(|e| e.bitrate),
+            Some(BitratePreference::Lowest) => known.min_by_key(|e| e.bitrate),
+            Some(BitratePreference::Kbps(kbps)) => {
+                known.min_by_key(|e| e.bitrate.unwrap_or(0).abs_diff(kbps * 1000))
+            }
+            None => None,
+        };
+
+        by_bitrate
+            .or_else(|| candidates.iter().copied().find(|e| e.default))
+            .or_else(|| candidates.first().copied())
+    }
+}
+
+/// Every version of `episode`: its main enclosure first, then the alternates
+pub fn options(episode: &Episode) -> Vec<Enclosure> {
+    let main = Enclosure {
+        url: episode.url.clone(),
+        mime_type: episode.mime_type.clone(),
+        ..Default::default()
+    };
+    std::iter::once(main)
+        .chain(
+            episode
+                .alternates
+                .iter()
+                .filter(|e| e.url != episode.url)
+                .cloned(),
+        )
+        .collect()
+}
+
+/// `episode` switched over to download `enclosure` instead of its main one
+pub fn with_enclosure(episode: &Episode, enclosure: &Enclosure) -> Episode {
+    if enclosure.url == episode.url {
+        return episode.clone();
+    }
+    Episode {
+        url: enclosure.url.clone(),
+        mime_type: enclosure.mime_type.clone(),
+        // The published hash describes the main enclosure
+        hash: None,
+        ..episode.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enclosure(mime_type: &str, codecs: Option<&str>, kbps: Option<u64>) -> Enclosure {
+        Enclosure {
+            url: format!("https://example.com/{}-{:?}", mime_type, kbps),
+            mime_type: Some(mime_type.to_string()),
+            bitrate: kbps.map(|k| k * 1000),
+            codecs: codecs.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_bitrate_preference_from_str() {
+        assert_eq!(
+            "high".parse::<BitratePreference>().unwrap(),
+            BitratePreference::Highest
+        );
+        assert_eq!(
+            "64kbps".parse::<BitratePreference>().unwrap(),
+            BitratePreference::Kbps(64)
+        );
+        assert!("fast".parse::<BitratePreference>().is_err());
+    }
+
+    #[test]
+    fn test_choose_by_codec_and_bitrate() {
+        let options = vec![
+            enclosure("audio/mpeg", None, None),
+            enclosure("audio/opus", Some("opus"), Some(32)),
+            enclosure("audio/opus", Some("opus"), Some(96)),
+            enclosure("audio/aac", Some("mp4a.40.2"), Some(128)),
+        ];
+        let policy = |codec: Option<&str>, bitrate| EnclosurePolicy {
+            codec: codec.map(str::to_string),
+            bitrate,
+        };
+
+        let pick = |p: EnclosurePolicy| p.choose(&options).unwrap().url.clone();
+        assert_eq!(pick(policy(Some("opus"), None)), options[1].url);
+        assert_eq!(
+            pick(policy(Some("opus"), Some(BitratePreference::Highest))),
+            options[2].url
+        );
+        assert_eq!(
+            pick(policy(None, Some(BitratePreference::Kbps(120)))),
+            options[3].url
+        );
+        assert_eq!(pick(policy(Some("mp3"), None)), options[0].url);
+        // No match for the codec: fall back to the other preferences
+        assert_eq!(
+            pick(policy(Some("flac"), Some(BitratePreference::Lowest))),
+            options[1].url
+        );
+    }
+
+    #[test]
+    fn test_with_enclosure_drops_main_hash() {
+        let episode = Episode {
+            url: "https://example.com/main.mp3".to_string(),
+            hash: Some(crate::checksum::Checksum {
+                algorithm: crate::checksum::HashAlgorithm::Md5,
+                hex: "00".to_string(),
+            }),
+            ..Default::default()
+        };
+        let alternate = enclosure("audio/opus", Some("opus"), Some(32));
+
+        let switched = with_enclosure(&episode, &alternate);
+
+        assert_eq!(switched.url, alternate.url);
+        assert_eq!(switched.hash, None);
+        assert_eq!(with_enclosure(&episode, &options(&episode)[0]), episode);
+    }
+}
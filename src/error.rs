@@ -0,0 +1,159 @@
+use crate::checksum::ChecksumMismatch;
+use crate::discover::FeedPage;
+use crate::download::{NotMedia, SizeMismatch};
+use crate::feed::UnknownFeedFormat;
+use crate::interrupt::Interrupted;
+use crate::retry::RateLimited;
+use crate::space::InsufficientSpace;
+use serde::Serialize;
+use std::fmt;
+use std::io;
+
+/// What went wrong, broadly: the classes of failure a caller (or a shell
+/// script, through the exit status) may want to handle differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// A request failed: no connection, timeout, error status, rate limiting
+    /// or a truncated or corrupted download
+    Network,
+    /// A feed, or another document pdl reads, couldn't be parsed
+    Parse,
+    /// No episodes matched the selection
+    NoEpisodes,
+    /// The user stopped pdl (Ctrl+C, or Esc at a prompt)
+    Aborted,
+    /// Reading or writing a local file failed
+    Disk,
+    /// Some downloads in a batch failed; the rest were saved
+    PartialFailure,
+    Other,
+}
+
+impl ErrorKind {
+    /// Classify `error` by the first cause in its chain that says what kind
+    /// of failure it is. Being interrupted wins over anything it caused.
+    pub fn of(error: &anyhow::Error) -> Self {
+        if error.chain().any(|cause| cause.is::<Interrupted>()) {
+            return Self::Aborted;
+        }
+        error
+            .chain()
+            .find_map(Self::of_cause)
+            .unwrap_or(Self::Other)
+    }
+
+    fn of_cause(cause: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        if cause.is::<NoEpisodes>() {
+            Some(Self::NoEpisodes)
+        } else if cause.is::<BatchFailed>() {
+            Some(Self::PartialFailure)
+        } else if cause.is::<reqwest::Error>()
+            || cause.is::<RateLimited>()
+            || cause.is::<SizeMismatch>()
+            || cause.is::<ChecksumMismatch>()
+            || cause.is::<NotMedia>()
+        {
+            Some(Self::Network)
+        } else if cause.is::<InsufficientSpace>() {
+            Some(Self::Disk)
+        } else if cause.is::<UnknownFeedFormat>()
+            || cause.is::<FeedPage>()
+            || cause.is::<rss::Error>()
+            || cause.is::<atom_syndication::Error>()
+            || cause.is::<serde_json::Error>()
+            || cause.is::<quick_xml::Error>()
+            || cause.is::<toml::de::Error>()
+        {
+            Some(Self::Parse)
+        } else {
+            // Outside of reqwest (matched above), only dropped streams are
+            // network trouble
+            cause.downcast_ref::<io::Error>().map(|e| match e.kind() {
+                io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::TimedOut => Self::Network,
+                _ => Self::Disk,
+            })
+        }
+    }
+
+    /// Exit status for a command that failed this way. `2` is left to
+    /// command line usage errors.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Other => 1,
+            Self::NoEpisodes => 3,
+            Self::PartialFailure => 4,
+            Self::Network => 5,
+            Self::Parse => 6,
+            Self::Disk => 7,
+            // 128 + SIGINT, as shells report it
+            Self::Aborted => 130,
+        }
+    }
+}
+
+/// No episodes matched the requested selection
+#[derive(Debug)]
+pub struct NoEpisodes;
+
+impl fmt::Display for NoEpisodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No episodes matched the selection")
+    }
+}
+
+impl std::error::Error for NoEpisodes {}
+
+/// Some downloads in a batch failed
+#[derive(Debug)]
+pub struct BatchFailed {
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl fmt::Display for BatchFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} downloads failed", self.failed, self.total)
+    }
+}
+
+impl std::error::Error for BatchFailed {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::Feed;
+    use anyhow::{Context, anyhow};
+
+    #[test]
+    fn test_error_kinds() {
+        let disk = Err::<(), _>(io::Error::from(io::ErrorKind::PermissionDenied))
+            .context("Failed to create downloads/Show")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&disk), ErrorKind::Disk);
+
+        let reset = Err::<(), _>(io::Error::from(io::ErrorKind::ConnectionReset))
+            .context("Failed to read download chunk")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&reset), ErrorKind::Network);
+
+        let garbage = Feed::parse(b"<html></html>", 10).unwrap_err();
+        assert_eq!(ErrorKind::of(&garbage), ErrorKind::Parse);
+        let broken = Feed::parse(b"<rss><channel>", 10)
+            .context("Failed to load feed")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&broken), ErrorKind::Parse);
+
+        let interrupted = anyhow::Error::from(Interrupted).context("Failed to download");
+        assert_eq!(ErrorKind::of(&interrupted), ErrorKind::Aborted);
+        let batch = BatchFailed {
+            failed: 1,
+            total: 2,
+        };
+        assert_eq!(ErrorKind::of(&batch.into()), ErrorKind::PartialFailure);
+        assert_eq!(ErrorKind::of(&NoEpisodes.into()), ErrorKind::NoEpisodes);
+        assert_eq!(ErrorKind::of(&anyhow!("Something else")), ErrorKind::Other);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::client::Http;
+use crate::feed::Episode;
+use crate::retry::check_response;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// A `<podcast:transcript>` linked from an episode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    pub url: String,
+    /// MIME type, e.g. `text/vtt` or `application/x-subrip`
+    pub mime_type: String,
+    pub language: Option<String>,
+}
+
+impl Transcript {
+    /// File extension for the transcript's format
+    pub fn extension(&self) -> &str {
+        match self.mime_type.to_ascii_lowercase().as_str() {
+            "text/vtt" => "vtt",
+            "application/x-subrip" | "application/srt" | "text/srt" => "srt",
+            "text/html" => "html",
+            "application/json" => "json",
+            _ => "txt",
+        }
+    }
+}
+
+/// Chapters and transcripts to fetch for `episode`, with the paths to save
+/// them at next to `audio` (`<name>.chapters.json`, `<name>.vtt`, ...)
+pub fn extra_files(episode: &Episode, audio: &Path) -> Vec<(String, PathBuf)> {
+    let with_suffix = |suffix: &str| {
+        let mut name = audio.file_stem().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        audio.with_file_name(name)
+    };
+
+    let mut files = Vec::new();
+    if let Some(url) = &episode.chapters_url {
+        files.push((url.clone(), with_suffix(".chapters.json")));
+    }
+    for transcript in &episode.transcripts {
+        let ext = transcript.extension();
+        // Several transcripts of one format are told apart by language
+        let shared = episode
+            .transcripts
+            .iter()
+            .filter(|t| t.extension() == ext)
+            .count()
+            > 1;
+        let suffix = match (&transcript.language, shared) {
+            (Some(lang), true) => format!(".{}.{}", lang, ext),
+            _ => format!(".{}", ext),
+        };
+        let path = with_suffix(&suffix);
+        if !files.iter().any(|(_, p)| *p == path) {
+            files.push((transcript.url.clone(), path));
+        }
+    }
+    files
+}
+
+/// Save the document at `url` to `path`
+pub(crate) async fn download_file(client: &Http, url: &str, path: &Path) -> Result<()> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch {}", url))?;
+    let bytes = check_response(response)
+        .with_context(|| format!("Failed to fetch {}", url))?
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to read {}", url))?;
+    tokio::fs::write(path, bytes)
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript(mime_type: &str, language: Option<&str>) -> Transcript {
+        Transcript {
+            url: format!("https://example.com/{}", mime_type.replace('/', "-")),
+            mime_type: mime_type.to_string(),
+            language: language.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_extra_files_match_audio_name() {
+        let episode = Episode {
+            chapters_url: Some("https://example.com/chapters.json".to_string()),
+            transcripts: vec![
+                transcript("text/vtt", Some("en")),
+                transcript("application/x-subrip", None),
+            ],
+            ..Default::default()
+        };
+
+        let files = extra_files(&episode, Path::new("Show/Ep 1.mp3"));
+        let paths: Vec<_> = files.iter().map(|(_, p)| p.clone()).collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("Show/Ep 1.chapters.json"),
+                PathBuf::from("Show/Ep 1.vtt"),
+                PathBuf::from("Show/Ep 1.srt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extra_files_separate_languages() {
+        let mut french = transcript("text/vtt", Some("fr"));
+        french.url = "https://example.com/fr.vtt".to_string();
+        let episode = Episode {
+            transcripts: vec![transcript("text/vtt", Some("en")), french],
+            ..Default::default()
+        };
+
+        let files = extra_files(&episode, Path::new("Ep.m4a"));
+
+        assert_eq!(files[0].1, PathBuf::from("Ep.en.vtt"));
+        assert_eq!(files[1].1, PathBuf::from("Ep.fr.vtt"));
+    }
+}
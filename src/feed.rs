@@ -0,0 +1,977 @@
+use crate::cache::{CachedFeed, FeedCache};
+use crate::checksum::{Checksum, HashAlgorithm};
+use crate::client::Http;
+use crate::discover::{FeedPage, discover_feeds};
+use crate::enclosure::Enclosure;
+use crate::extras::Transcript;
+use crate::retry::check_response;
+use crate::sniff::{self, Sniffed};
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use rss::extension::Extension;
+use rss::{Channel, Item};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::time::Duration;
+use tracing::debug;
+
+/// A downloadable podcast episode
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Episode {
+    pub title: String,
+    pub url: String,
+    /// Item `<guid>`, if the feed provides one
+    pub guid: Option<String>,
+    /// Published digest of the media file (`<media:hash>`), if any
+    pub hash: Option<Checksum>,
+    /// Publication date (`<pubDate>`, Atom `published`, JSON `date_published`)
+    pub published: Option<DateTime<FixedOffset>>,
+    /// Show notes, possibly HTML
+    pub description: Option<String>,
+    /// `<itunes:episode>` number
+    pub number: Option<u32>,
+    /// `<itunes:season>` number
+    pub season: Option<u32>,
+    /// Cover art URL: the episode's own image, else the show's
+    pub image: Option<String>,
+    /// `<podcast:chapters>` JSON URL
+    pub chapters_url: Option<String>,
+    /// `<podcast:transcript>` links
+    pub transcripts: Vec<Transcript>,
+    /// MIME type of the enclosure at `url`
+    pub mime_type: Option<String>,
+    /// Other versions offered via `<podcast:alternateEnclosure>`
+    pub alternates: Vec<Enclosure>,
+    /// Magnet link or `.torrent` URL, from a BitTorrent alternate enclosure
+    /// or an enclosure that is itself a torrent
+    pub torrent: Option<String>,
+    /// Running time (`<itunes:duration>`, JSON `duration_in_seconds`)
+    pub duration: Option<Duration>,
+    /// Enclosure size in bytes, as the feed declares it
+    pub size: Option<u64>,
+    /// 1-based place in the feed counting from its last (oldest) item
+    pub position: Option<u32>,
+}
+
+impl Episode {
+    /// What identifies the episode across refreshes and re-publishes: its
+    /// GUID, or the enclosure URL when the feed gives none
+    pub fn key(&self) -> &str {
+        self.guid.as_deref().unwrap_or(&self.url)
+    }
+}
+
+/// A parsed podcast feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feed {
+    /// Show title from the channel
+    pub title: String,
+    /// Show cover art URL
+    pub image: Option<String>,
+    pub episodes: Vec<Episode>,
+    /// Next page of a paged feed with older episodes (RFC 5005
+    /// `<atom:link rel="next">`, JSON Feed `next_url`), as written in the feed
+    pub next_page: Option<String>,
+}
+
+/// Syndication formats pdl can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+    /// JSON Feed (jsonfeed.org)
+    Json,
+}
+
+impl FeedFormat {
+    /// Format implied by a response `Content-Type`, if it names one
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "application/rss+xml" => Some(FeedFormat::Rss),
+            "application/atom+xml" => Some(FeedFormat::Atom),
+            "application/feed+json" | "application/json" => Some(FeedFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A document that is none of the feed formats pdl reads
+#[derive(Debug)]
+pub struct UnknownFeedFormat;
+
+impl fmt::Display for UnknownFeedFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unrecognized feed format (expected RSS, Atom or JSON Feed)"
+        )
+    }
+}
+
+impl std::error::Error for UnknownFeedFormat {}
+
+impl Feed {
+    /// Parse an RSS, Atom or JSON Feed document, keeping at most `limit` episodes
+    pub fn parse(content: &[u8], limit: usize) -> Result<Self> {
+        match detect_format(content) {
+            Some(format) => Self::parse_as(content, format, limit),
+            None => Err(UnknownFeedFormat.into()),
+        }
+    }
+
+    /// Parse `content` as the given format, keeping at most `limit` episodes
+    pub fn parse_as(content: &[u8], format: FeedFormat, limit: usize) -> Result<Self> {
+        match format {
+            FeedFormat::Rss => {
+                let channel = Channel::read_from(content).context("Failed to parse RSS feed")?;
+                Ok(Self::from_channel(&channel, limit))
+            }
+            FeedFormat::Atom => {
+                let feed = atom_syndication::Feed::read_from(content)
+                    .context("Failed to parse Atom feed")?;
+                Ok(Self::from_atom(&feed, limit))
+            }
+            FeedFormat::Json => {
+                let feed: JsonFeed =
+                    serde_json::from_slice(content).context("Failed to parse JSON Feed")?;
+                Ok(Self::from_json(&feed, limit))
+            }
+        }
+    }
+
+    /// Build a feed from an RSS channel, keeping at most `limit` episodes
+    pub fn from_channel(channel: &Channel, limit: usize) -> Self {
+        Self {
+            title: channel.title().trim().to_string(),
+            image: channel_image(channel),
+            episodes: dedupe(episodes_from_channel(channel, limit)),
+            next_page: channel.atom_ext().and_then(|atom| {
+                atom.links()
+                    .iter()
+                    .find(|link| link.rel() == "next")
+                    .and_then(|link| non_empty(Some(link.href())))
+            }),
+        }
+    }
+
+    /// Build a feed from an Atom feed, keeping at most `limit` episodes
+    pub fn from_atom(feed: &atom_syndication::Feed, limit: usize) -> Self {
+        Self {
+            title: feed.title().trim().to_string(),
+            image: non_empty(feed.logo().or(feed.icon())),
+            episodes: dedupe(episodes_from_atom(feed, limit)),
+            next_page: feed
+                .links()
+                .iter()
+                .find(|link| link.rel() == "next")
+                .and_then(|link| non_empty(Some(link.href()))),
+        }
+    }
+}
+
+impl Feed {
+    /// Add the episodes of `page`, the next (older) page of this feed.
+    /// Positions so far move up by the size of the older page, so they keep
+    /// counting from the oldest episode fetched.
+    pub fn append_page(&mut self, page: Feed) {
+        let page_size = page
+            .episodes
+            .iter()
+            .filter_map(|episode| episode.position)
+            .max()
+            .unwrap_or(0);
+        for episode in &mut self.episodes {
+            episode.position = episode.position.map(|position| position + page_size);
+        }
+        let episodes = std::mem::take(&mut self.episodes);
+        self.episodes = dedupe(episodes.into_iter().chain(page.episodes));
+        self.next_page = page.next_page;
+    }
+}
+
+/// Drop repeats of an episode (same [`Episode::key`]), keeping the first,
+/// newest copy: re-published items sometimes stay in the feed twice, and
+/// the pages of a paged feed can overlap
+fn dedupe(episodes: impl IntoIterator<Item = Episode>) -> Vec<Episode> {
+    let mut seen = HashSet::new();
+    episodes
+        .into_iter()
+        .filter(|episode| seen.insert(episode.key().to_string()))
+        .collect()
+}
+
+/// Identify a feed document by its root element (or a leading `{` for JSON)
+pub fn detect_format(content: &[u8]) -> Option<FeedFormat> {
+    if content.trim_ascii_start().starts_with(b"{") {
+        return Some(FeedFormat::Json);
+    }
+
+    let mut reader = Reader::from_reader(content);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf).ok()? {
+            Event::Start(e) | Event::Empty(e) => {
+                return match e.local_name().as_ref() {
+                    b"rss" => Some(FeedFormat::Rss),
+                    b"feed" => Some(FeedFormat::Atom),
+                    _ => None,
+                };
+            }
+            Event::Eof => return None,
+            _ => buf.clear(),
+        }
+    }
+}
+
+/// Fetch up to `limit` episodes from the feed at `url`
+pub async fn fetch_episodes(url: &str, limit: usize) -> Result<Vec<Episode>> {
+    let feed = fetch_feed(&Http::default(), url, limit, None).await?;
+    Ok(feed.episodes)
+}
+
+/// Fetch and parse the feed at `url`. With a `cache`, a copy saved earlier is
+/// revalidated with `If-None-Match`/`If-Modified-Since` and reused on `304`.
+pub(crate) async fn fetch_feed(
+    client: &Http,
+    url: &str,
+    limit: usize,
+    cache: Option<&FeedCache>,
+) -> Result<Feed> {
+    Ok(fetch_document(client, url, limit, cache).await?.1)
+}
+
+/// Fetch the feed at `url` like [`fetch_feed`], also returning the document
+/// as served (or as cached, when the server said it is unchanged)
+pub(crate) async fn fetch_document(
+    client: &Http,
+    url: &str,
+    limit: usize,
+    cache: Option<&FeedCache>,
+) -> Result<(CachedFeed, Feed)> {
+    let cached = match cache {
+        Some(cache) => cache.load(url).await,
+        None => None,
+    };
+
+    debug!("Fetching feed {}", url);
+    let mut request = client.get(url);
+    if let Some(cached) = &cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let response = request.send().await.context("Failed to fetch feed")?;
+    if response.status() == StatusCode::NOT_MODIFIED
+        && let Some(cached) = cached
+    {
+        debug!("Feed not modified, using the cached copy of {}", url);
+        let feed = parse_fetched(&cached.content, cached.content_type.as_deref(), limit)?;
+        return Ok((cached, feed));
+    }
+    let response = check_response(response).context("Failed to fetch feed")?;
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let fetched = CachedFeed {
+        content_type: header(CONTENT_TYPE),
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+        content: Vec::new(),
+    };
+    let page_url = response.url().to_string();
+    let content = response
+        .bytes()
+        .await
+        .context("Failed to read feed response")?;
+    // A show's homepage instead of its feed: report the feeds it links to
+    if sniff::sniff(&content[..content.len().min(sniff::HEADER_LEN)]) == Some(Sniffed::Html) {
+        return Err(FeedPage {
+            url: url.to_string(),
+            feeds: discover_feeds(&String::from_utf8_lossy(&content), &page_url),
+        }
+        .into());
+    }
+    let feed = parse_fetched(&content, fetched.content_type.as_deref(), limit)?;
+    let fetched = CachedFeed {
+        content: content.to_vec(),
+        ..fetched
+    };
+
+    if let Some(cache) = cache
+        && fetched.has_validators()
+    {
+        // A copy that can't be saved only costs a full fetch next time
+        let _ = cache.store(url, &fetched).await;
+    }
+    Ok((fetched, feed))
+}
+
+/// Parse a fetched feed, trusting a specific Content-Type and sniffing the body otherwise
+fn parse_fetched(content: &[u8], content_type: Option<&str>, limit: usize) -> Result<Feed> {
+    match content_type.and_then(FeedFormat::from_content_type) {
+        Some(format) => Feed::parse_as(content, format, limit),
+        None => Feed::parse(content, limit),
+    }
+}
+
+/// Show artwork: `<itunes:image>`, else the RSS `<image>`
+fn channel_image(channel: &Channel) -> Option<String> {
+    non_empty(
+        channel
+            .itunes_ext()
+            .and_then(|i| i.image())
+            .or(channel.image().map(|i| i.url())),
+    )
+}
+
+/// Collect the first `limit` items that have a title and an enclosure
+pub fn episodes_from_channel(channel: &Channel, limit: usize) -> Vec<Episode> {
+    let show_image = channel_image(channel);
+    let total = channel.items().len();
+    channel
+        .items()
+        .iter()
+        .enumerate()
+        .take(limit)
+        .filter_map(|(i, item)| {
+            let title = item.title()?.to_string();
+            let enclosure = item.enclosure()?;
+            let url = enclosure.url().to_string();
+            let guid = item.guid().map(|g| g.value().to_string());
+            let itunes = item.itunes_ext();
+            let itunes_number =
+                |value: Option<&str>| value.and_then(|v| v.trim().parse::<u32>().ok());
+            Some(Episode {
+                title,
+                url,
+                guid,
+                hash: media_hash(item),
+                published: item
+                    .pub_date()
+                    .and_then(|d| DateTime::parse_from_rfc2822(d.trim()).ok()),
+                description: non_empty(
+                    item.content()
+                        .or(item.description())
+                        .or(itunes.and_then(|i| i.summary())),
+                ),
+                number: itunes_number(itunes.and_then(|i| i.episode())),
+                season: itunes_number(itunes.and_then(|i| i.season())),
+                image: non_empty(itunes.and_then(|i| i.image())).or(show_image.clone()),
+                chapters_url: podcast_tags(item, "chapters")
+                    .find_map(|tag| non_empty(tag.attrs().get("url").map(String::as_str))),
+                transcripts: podcast_tags(item, "transcript")
+                    .filter_map(transcript_from_extension)
+                    .collect(),
+                mime_type: non_empty(Some(enclosure.mime_type())),
+                alternates: podcast_tags(item, "alternateEnclosure")
+                    .filter_map(alternate_from_extension)
+                    .collect(),
+                torrent: podcast_tags(item, "alternateEnclosure")
+                    .find_map(torrent_from_extension)
+                    .or_else(|| {
+                        (enclosure.mime_type() == TORRENT_TYPE).then(|| enclosure.url().to_string())
+                    }),
+                duration: itunes.and_then(|i| i.duration()).and_then(parse_duration),
+                size: parse_size(enclosure.length()),
+                position: position(total, i),
+            })
+        })
+        .collect()
+}
+
+/// Position of item `index` (0-based, newest first) out of `total`, counted from the oldest
+fn position(total: usize, index: usize) -> Option<u32> {
+    u32::try_from(total - index).ok()
+}
+
+fn non_empty(text: Option<&str>) -> Option<String> {
+    text.map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+}
+
+/// Parse an `<itunes:duration>`: seconds, `MM:SS` or `HH:MM:SS`
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    let (seconds, rest) = parts.split_last()?;
+    let seconds: f64 = seconds.parse().ok()?;
+    let mut total = 0u64;
+    for part in rest {
+        total = total * 60 + part.parse::<u64>().ok()?;
+    }
+    let seconds = total as f64 * 60.0 + seconds;
+    (seconds.is_finite() && seconds >= 0.0).then(|| Duration::from_secs_f64(seconds))
+}
+
+/// Enclosure `length`, ignoring the 0 many feeds put there when unknown
+fn parse_size(length: &str) -> Option<u64> {
+    length.trim().parse().ok().filter(|&n| n > 0)
+}
+
+/// The item's `<podcast:name>` (Podcasting 2.0) elements
+fn podcast_tags<'a>(item: &'a Item, name: &str) -> impl Iterator<Item = &'a Extension> {
+    item.extensions()
+        .get("podcast")
+        .and_then(|tags| tags.get(name))
+        .into_iter()
+        .flatten()
+}
+
+fn transcript_from_extension(tag: &Extension) -> Option<Transcript> {
+    let attr = |name: &str| non_empty(tag.attrs().get(name).map(String::as_str));
+    Some(Transcript {
+        url: attr("url")?,
+        mime_type: attr("type")?,
+        language: attr("language"),
+    })
+}
+
+/// MIME type of `.torrent` files
+const TORRENT_TYPE: &str = "application/x-bittorrent";
+
+/// An alternate enclosure with an HTTP source (torrent and IPFS-only ones are skipped)
+fn alternate_from_extension(tag: &Extension) -> Option<Enclosure> {
+    let attr = |name: &str| non_empty(tag.attrs().get(name).map(String::as_str));
+    if attr("type").as_deref() == Some(TORRENT_TYPE) {
+        return None;
+    }
+    let url = tag
+        .children()
+        .get("source")
+        .into_iter()
+        .flatten()
+        .filter_map(|source| source.attrs().get("uri"))
+        .find(|uri| uri.starts_with("http://") || uri.starts_with("https://"))?;
+    Some(Enclosure {
+        url: url.clone(),
+        mime_type: attr("type"),
+        bitrate: attr("bitrate")
+            .and_then(|b| b.parse::<f64>().ok())
+            .map(|b| b as u64),
+        codecs: attr("codecs"),
+        title: attr("title"),
+        default: attr("default").is_some_and(|d| d == "true"),
+    })
+}
+
+/// The magnet link, or `.torrent` file URL, of a BitTorrent alternate enclosure
+fn torrent_from_extension(tag: &Extension) -> Option<String> {
+    let is_torrent = tag.attrs().get("type").is_some_and(|t| t == TORRENT_TYPE);
+    tag.children()
+        .get("source")
+        .into_iter()
+        .flatten()
+        .filter_map(|source| source.attrs().get("uri"))
+        .find(|uri| uri.starts_with("magnet:") || (is_torrent && uri.starts_with("http")))
+        .cloned()
+}
+
+/// First usable `<media:hash>` on the item, or on its `<media:content>`
+fn media_hash(item: &Item) -> Option<Checksum> {
+    let media = item.extensions().get("media")?;
+    let nested = media
+        .get("content")
+        .into_iter()
+        .flatten()
+        .filter_map(|content| content.children().get("hash"))
+        .flatten();
+    media
+        .get("hash")
+        .into_iter()
+        .flatten()
+        .chain(nested)
+        .find_map(checksum_from_extension)
+}
+
+fn checksum_from_extension(hash: &Extension) -> Option<Checksum> {
+    let algo = hash.attrs().get("algo").map(String::as_str).unwrap_or("");
+    let hex = hash.value()?.trim();
+    if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(Checksum {
+        algorithm: HashAlgorithm::from_media_algo(algo)?,
+        hex: hex.to_ascii_lowercase(),
+    })
+}
+
+/// The subset of a JSON Feed document pdl uses
+#[derive(Debug, Deserialize)]
+pub struct JsonFeed {
+    #[serde(default)]
+    pub title: String,
+    pub icon: Option<String>,
+    pub next_url: Option<String>,
+    #[serde(default)]
+    pub items: Vec<JsonFeedItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JsonFeedItem {
+    /// Spec says string, but some publishers emit numbers
+    pub id: serde_json::Value,
+    pub title: Option<String>,
+    pub content_html: Option<String>,
+    pub content_text: Option<String>,
+    pub summary: Option<String>,
+    pub date_published: Option<String>,
+    pub image: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<JsonFeedAttachment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JsonFeedAttachment {
+    pub url: String,
+    pub mime_type: Option<String>,
+    pub size_in_bytes: Option<u64>,
+    pub duration_in_seconds: Option<f64>,
+}
+
+impl Feed {
+    /// Build a feed from a JSON Feed document, keeping at most `limit` episodes
+    pub fn from_json(feed: &JsonFeed, limit: usize) -> Self {
+        Self {
+            title: feed.title.trim().to_string(),
+            image: non_empty(feed.icon.as_deref()),
+            episodes: dedupe(episodes_from_json(feed, limit)),
+            next_page: non_empty(feed.next_url.as_deref()),
+        }
+    }
+}
+
+/// Collect the first `limit` items with an attachment, preferring audio ones
+pub fn episodes_from_json(feed: &JsonFeed, limit: usize) -> Vec<Episode> {
+    let total = feed.items.len();
+    feed.items
+        .iter()
+        .enumerate()
+        .take(limit)
+        .filter_map(|(i, item)| {
+            let is_audio = |a: &&JsonFeedAttachment| {
+                a.mime_type
+                    .as_deref()
+                    .is_some_and(|m| m.starts_with("audio/"))
+            };
+            let attachment = item
+                .attachments
+                .iter()
+                .find(is_audio)
+                .or_else(|| item.attachments.first())?;
+
+            let guid = match &item.id {
+                serde_json::Value::String(id) => Some(id.clone()),
+                serde_json::Value::Null => None,
+                other => Some(other.to_string()),
+            };
+            let title = item
+                .title
+                .as_deref()
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .or_else(|| guid.clone())?;
+
+            Some(Episode {
+                title,
+                url: attachment.url.clone(),
+                mime_type: attachment.mime_type.clone(),
+                guid,
+                published: item
+                    .date_published
+                    .as_deref()
+                    .and_then(|d| DateTime::parse_from_rfc3339(d).ok()),
+                description: non_empty(
+                    item.content_html
+                        .as_deref()
+                        .or(item.content_text.as_deref())
+                        .or(item.summary.as_deref()),
+                ),
+                image: non_empty(item.image.as_deref().or(feed.icon.as_deref())),
+                duration: attachment
+                    .duration_in_seconds
+                    .filter(|s| s.is_finite() && *s >= 0.0)
+                    .map(Duration::from_secs_f64),
+                size: attachment.size_in_bytes.filter(|&n| n > 0),
+                position: position(total, i),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Collect the first `limit` entries that have an enclosure link
+pub fn episodes_from_atom(feed: &atom_syndication::Feed, limit: usize) -> Vec<Episode> {
+    let show_image = non_empty(feed.logo().or(feed.icon()));
+    let total = feed.entries().len();
+    feed.entries()
+        .iter()
+        .enumerate()
+        .take(limit)
+        .filter_map(|(i, entry)| {
+            let enclosure = entry.links().iter().find(|l| l.rel() == "enclosure")?;
+            Some(Episode {
+                title: entry.title().trim().to_string(),
+                url: enclosure.href().to_string(),
+                mime_type: enclosure.mime_type().map(str::to_string),
+                guid: Some(entry.id().to_string()).filter(|id| !id.is_empty()),
+                published: Some(*entry.published().unwrap_or(entry.updated())),
+                description: non_empty(
+                    entry
+                        .content()
+                        .and_then(|c| c.value())
+                        .or(entry.summary().map(|s| s.as_str())),
+                ),
+                image: show_image.clone(),
+                size: enclosure.length().and_then(parse_size),
+                position: position(total, i),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEED: &str = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"><channel><title>Show</title>
+<itunes:image href="https://example.com/show.jpg"/>
+<item><title>First</title><guid>ep-1</guid><pubDate>Tue, 02 Jan 2024 10:00:00 GMT</pubDate><description>Notes</description><itunes:season>2</itunes:season><itunes:episode>14</itunes:episode><itunes:duration>1:02:03</itunes:duration><itunes:image href="https://example.com/1.jpg"/><enclosure url="https://example.com/1.mp3" length="2048" type="audio/mpeg"/></item>
+<item><title>No audio</title></item>
+<item><title>Third</title><enclosure url="https://example.com/3.mp3" length="1" type="audio/mpeg"/></item>
+</channel></rss>"#;
+
+    #[test]
+    fn test_episodes_from_channel_skips_items_without_enclosure() {
+        let channel = Channel::read_from(FEED.as_bytes()).unwrap();
+        let episodes = episodes_from_channel(&channel, 10);
+
+        assert_eq!(episodes.len(), 2);
+        assert_eq!(episodes[0].title, "First");
+        assert_eq!(episodes[0].guid.as_deref(), Some("ep-1"));
+        assert_eq!(episodes[1].url, "https://example.com/3.mp3");
+        assert_eq!(episodes[1].guid, None);
+    }
+
+    #[test]
+    fn test_episodes_from_channel_reads_metadata() {
+        let channel = Channel::read_from(FEED.as_bytes()).unwrap();
+        let episodes = episodes_from_channel(&channel, 10);
+
+        assert_eq!(
+            episodes[0].published.map(|d| d.to_rfc3339()).as_deref(),
+            Some("2024-01-02T10:00:00+00:00")
+        );
+        assert_eq!(episodes[0].description.as_deref(), Some("Notes"));
+        assert_eq!(episodes[0].season, Some(2));
+        assert_eq!(episodes[0].number, Some(14));
+        assert_eq!(episodes[0].duration, Some(Duration::from_secs(3723)));
+        assert_eq!(episodes[0].size, Some(2048));
+        assert_eq!(episodes[1].published, None);
+        assert_eq!(episodes[1].number, None);
+        assert_eq!(episodes[1].duration, None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("3600"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("62:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_duration(" 1:02:03 "), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_duration("1:2:3:4"), None);
+        assert_eq!(parse_duration("an hour"), None);
+    }
+
+    #[test]
+    fn test_episode_image_falls_back_to_show_image() {
+        let channel = Channel::read_from(FEED.as_bytes()).unwrap();
+        let feed = Feed::from_channel(&channel, 10);
+
+        assert_eq!(feed.image.as_deref(), Some("https://example.com/show.jpg"));
+        assert_eq!(
+            feed.episodes[0].image.as_deref(),
+            Some("https://example.com/1.jpg")
+        );
+        assert_eq!(
+            feed.episodes[1].image.as_deref(),
+            Some("https://example.com/show.jpg")
+        );
+    }
+
+    #[test]
+    fn test_feed_from_channel_keeps_title() {
+        let channel = Channel::read_from(FEED.as_bytes()).unwrap();
+        let feed = Feed::from_channel(&channel, 10);
+
+        assert_eq!(feed.title, "Show");
+        assert_eq!(feed.episodes.len(), 2);
+    }
+
+    #[test]
+    fn test_repeated_episodes_are_dropped() {
+        let content = r#"<rss version="2.0"><channel><title>Show</title>
+            <item><title>Ep 1 (re-release)</title><guid>ep-1</guid><enclosure url="https://cdn.example.com/1.mp3" length="1" type="audio/mpeg"/></item>
+            <item><title>Ep 2</title><enclosure url="https://example.com/2.mp3" length="1" type="audio/mpeg"/></item>
+            <item><title>Ep 1</title><guid>ep-1</guid><enclosure url="https://example.com/1.mp3" length="1" type="audio/mpeg"/></item>
+            <item><title>Ep 2 again</title><enclosure url="https://example.com/2.mp3" length="1" type="audio/mpeg"/></item>
+            </channel></rss>"#;
+        let feed = Feed::parse(content.as_bytes(), 10).unwrap();
+        let titles: Vec<&str> = feed.episodes.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, ["Ep 1 (re-release)", "Ep 2"]);
+        assert_eq!(feed.episodes[0].key(), "ep-1");
+        assert_eq!(feed.episodes[1].key(), "https://example.com/2.mp3");
+    }
+
+    #[test]
+    fn test_paged_feed() {
+        let page = |title: &str, next: &str| {
+            format!(
+                r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel>
+                <title>Show</title>{next}
+                <item><title>{title} 2</title><enclosure url="https://example.com/{title}2.mp3" length="1" type="audio/mpeg"/></item>
+                <item><title>{title} 1</title><enclosure url="https://example.com/{title}1.mp3" length="1" type="audio/mpeg"/></item>
+                </channel></rss>"#
+            )
+        };
+        let mut feed = Feed::parse(
+            page("new", r#"<atom:link rel="next" href="?page=2"/>"#).as_bytes(),
+            10,
+        )
+        .unwrap();
+        assert_eq!(feed.next_page.as_deref(), Some("?page=2"));
+
+        feed.append_page(Feed::parse(page("old", "").as_bytes(), 10).unwrap());
+        let positions: Vec<_> = feed
+            .episodes
+            .iter()
+            .map(|e| (e.title.as_str(), e.position))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                ("new 2", Some(4)),
+                ("new 1", Some(3)),
+                ("old 2", Some(2)),
+                ("old 1", Some(1)),
+            ]
+        );
+        assert_eq!(feed.next_page, None);
+
+        let json = br#"{"version": "https://jsonfeed.org/version/1.1", "title": "J", "next_url": "https://example.com/feed.json?page=2", "items": []}"#;
+        assert_eq!(
+            Feed::parse(json, 10).unwrap().next_page.as_deref(),
+            Some("https://example.com/feed.json?page=2")
+        );
+    }
+
+    const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Atom Show</title>
+  <id>urn:show</id>
+  <updated>2024-01-01T00:00:00Z</updated>
+  <entry>
+    <title>Atom Episode</title>
+    <id>urn:ep:1</id>
+    <updated>2024-01-01T00:00:00Z</updated>
+    <link rel="alternate" href="https://example.com/ep1"/>
+    <link rel="enclosure" type="audio/mpeg" href="https://example.com/ep1.mp3"/>
+  </entry>
+  <entry>
+    <title>Text post</title>
+    <id>urn:ep:2</id>
+    <updated>2024-01-01T00:00:00Z</updated>
+    <link href="https://example.com/post"/>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format(FEED.as_bytes()), Some(FeedFormat::Rss));
+        assert_eq!(detect_format(ATOM.as_bytes()), Some(FeedFormat::Atom));
+        assert_eq!(detect_format(b"<html><body></body></html>"), None);
+        assert_eq!(detect_format(b"not xml"), None);
+    }
+
+    #[test]
+    fn test_parse_atom_feed_uses_enclosure_links() {
+        let feed = Feed::parse(ATOM.as_bytes(), 10).unwrap();
+
+        assert_eq!(feed.title, "Atom Show");
+        assert_eq!(
+            feed.episodes,
+            vec![Episode {
+                title: "Atom Episode".to_string(),
+                url: "https://example.com/ep1.mp3".to_string(),
+                mime_type: Some("audio/mpeg".to_string()),
+                guid: Some("urn:ep:1".to_string()),
+                published: DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").ok(),
+                // Second from the end, counting the text post
+                position: Some(2),
+                ..Default::default()
+            }]
+        );
+    }
+
+    const JSON_FEED: &str = r#"{
+  "version": "https://jsonfeed.org/version/1.1",
+  "title": "JSON Show",
+  "items": [
+    {
+      "id": "1",
+      "title": "With audio",
+      "attachments": [
+        {"url": "https://example.com/1.txt", "mime_type": "text/plain"},
+        {"url": "https://example.com/1.m4a", "mime_type": "audio/x-m4a"}
+      ]
+    },
+    {"id": "2", "title": "No attachments"},
+    {"id": 3, "attachments": [{"url": "https://example.com/3.mp3"}]}
+  ]
+}"#;
+
+    #[test]
+    fn test_parse_json_feed_attachments() {
+        assert_eq!(detect_format(JSON_FEED.as_bytes()), Some(FeedFormat::Json));
+        let feed = Feed::parse(JSON_FEED.as_bytes(), 10).unwrap();
+
+        assert_eq!(feed.title, "JSON Show");
+        assert_eq!(feed.episodes.len(), 2);
+        assert_eq!(feed.episodes[0].title, "With audio");
+        assert_eq!(feed.episodes[0].url, "https://example.com/1.m4a");
+        // Untitled item falls back to its (numeric) id
+        assert_eq!(feed.episodes[1].title, "3");
+        assert_eq!(feed.episodes[1].guid.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_format_from_content_type() {
+        assert_eq!(
+            FeedFormat::from_content_type("application/feed+json; charset=utf-8"),
+            Some(FeedFormat::Json)
+        );
+        assert_eq!(
+            FeedFormat::from_content_type("application/atom+xml"),
+            Some(FeedFormat::Atom)
+        );
+        assert_eq!(FeedFormat::from_content_type("text/xml"), None);
+    }
+
+    #[test]
+    fn test_parse_rss_feed() {
+        let feed = Feed::parse(FEED.as_bytes(), 10).unwrap();
+        assert_eq!(feed.title, "Show");
+        assert_eq!(feed.episodes.len(), 2);
+    }
+
+    #[test]
+    fn test_podcast_chapters_and_transcripts() {
+        let feed = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:podcast="https://podcastindex.org/namespace/1.0"><channel><title>Show</title>
+<item><title>Ep</title><enclosure url="https://example.com/1.mp3" length="1" type="audio/mpeg"/>
+  <podcast:chapters url="https://example.com/1.json" type="application/json+chapters"/>
+  <podcast:transcript url="https://example.com/1.vtt" type="text/vtt" language="en"/>
+  <podcast:transcript url="https://example.com/1.srt"/>
+</item>
+</channel></rss>"#;
+        let episodes = Feed::parse(feed.as_bytes(), 10).unwrap().episodes;
+
+        assert_eq!(
+            episodes[0].chapters_url.as_deref(),
+            Some("https://example.com/1.json")
+        );
+        // The transcript without a type is skipped
+        assert_eq!(
+            episodes[0].transcripts,
+            vec![Transcript {
+                url: "https://example.com/1.vtt".to_string(),
+                mime_type: "text/vtt".to_string(),
+                language: Some("en".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_alternate_enclosures_need_http_source() {
+        let feed = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:podcast="https://podcastindex.org/namespace/1.0"><channel><title>Show</title>
+<item><title>Ep</title><enclosure url="https://example.com/1.mp3" length="1" type="audio/mpeg"/>
+  <podcast:alternateEnclosure type="audio/opus" bitrate="32000.0" codecs="opus" title="Low" default="true">
+    <podcast:source uri="ipfs://Qm123"/>
+    <podcast:source uri="https://example.com/1.opus"/>
+  </podcast:alternateEnclosure>
+  <podcast:alternateEnclosure type="application/x-bittorrent">
+    <podcast:source uri="magnet:?xt=urn:btih:123"/>
+  </podcast:alternateEnclosure>
+</item>
+</channel></rss>"#;
+        let episode = &Feed::parse(feed.as_bytes(), 10).unwrap().episodes[0];
+
+        assert_eq!(episode.mime_type.as_deref(), Some("audio/mpeg"));
+        assert_eq!(
+            episode.alternates,
+            vec![Enclosure {
+                url: "https://example.com/1.opus".to_string(),
+                mime_type: Some("audio/opus".to_string()),
+                bitrate: Some(32000),
+                codecs: Some("opus".to_string()),
+                title: Some("Low".to_string()),
+                default: true,
+            }]
+        );
+        assert_eq!(episode.torrent.as_deref(), Some("magnet:?xt=urn:btih:123"));
+    }
+
+    #[test]
+    fn test_media_hash_on_item_or_content() {
+        let feed = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/"><channel><title>Show</title>
+<item><title>Top</title><enclosure url="https://example.com/1.mp3" length="1" type="audio/mpeg"/>
+  <media:hash algo="sha-256">BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD</media:hash></item>
+<item><title>Nested</title><enclosure url="https://example.com/2.mp3" length="1" type="audio/mpeg"/>
+  <media:content url="https://example.com/2.mp3"><media:hash>900150983cd24fb0d6963f7d28e17f72</media:hash></media:content></item>
+<item><title>Unknown algo</title><enclosure url="https://example.com/3.mp3" length="1" type="audio/mpeg"/>
+  <media:hash algo="crc32">352441c2</media:hash></item>
+</channel></rss>"#;
+        let episodes = Feed::parse(feed.as_bytes(), 10).unwrap().episodes;
+
+        assert_eq!(
+            episodes[0].hash,
+            Some(Checksum {
+                algorithm: HashAlgorithm::Sha256,
+                hex: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+            })
+        );
+        assert_eq!(
+            episodes[1].hash.as_ref().map(|h| h.algorithm),
+            Some(HashAlgorithm::Md5)
+        );
+        assert_eq!(episodes[2].hash, None);
+    }
+
+    #[test]
+    fn test_episodes_from_channel_respects_limit() {
+        let channel = Channel::read_from(FEED.as_bytes()).unwrap();
+        let episodes = episodes_from_channel(&channel, 1);
+
+        assert_eq!(episodes.len(), 1);
+        assert_eq!(episodes[0].title, "First");
+    }
+}
@@ -0,0 +1,418 @@
+use crate::auth::Credentials;
+use crate::retry::{RetryPolicy, check_response};
+use anyhow::{Context, Result, bail};
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Server used when `gpodder_server` isn't set
+pub const DEFAULT_SERVER: &str = "https://gpodder.net";
+
+/// Device name pdl registers as on gpodder.net when `gpodder_device` isn't set
+pub const DEFAULT_DEVICE: &str = "pdl";
+
+/// Which sync API the server speaks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncApi {
+    /// gpodder.net and servers compatible with its API v2 (e.g. opodsync)
+    #[default]
+    Gpodder,
+    /// The Nextcloud "GPodder Sync" app
+    Nextcloud,
+}
+
+/// A gpodder.net (or Nextcloud) account to sync with
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpodderAccount {
+    /// Base URL, e.g. `https://gpodder.net` or `https://cloud.example.com`
+    pub server: String,
+    pub api: SyncApi,
+    pub username: String,
+    pub password: Option<String>,
+    /// gpodder.net device id; subscriptions are kept per device there
+    pub device: String,
+}
+
+impl GpodderAccount {
+    /// The account `credentials` log in to on `server`
+    pub fn new(server: &str, api: SyncApi, credentials: Credentials, device: &str) -> Result<Self> {
+        let Credentials::Basic { username, password } = credentials else {
+            bail!("gpodder sync needs a user name and password, not a token");
+        };
+        Ok(Self {
+            server: server.trim_end_matches('/').to_string(),
+            api,
+            username,
+            password,
+            device: device.to_string(),
+        })
+    }
+
+    /// What sync state is kept under, so switching accounts starts afresh
+    pub fn id(&self) -> String {
+        format!("{}|{}|{}", self.server, self.username, self.device)
+    }
+
+    fn subscriptions_url(&self) -> String {
+        match self.api {
+            SyncApi::Gpodder => format!(
+                "{}/api/2/subscriptions/{}/{}.json",
+                self.server, self.username, self.device
+            ),
+            SyncApi::Nextcloud => {
+                format!("{}/index.php/apps/gpoddersync/subscriptions", self.server)
+            }
+        }
+    }
+
+    fn subscription_upload_url(&self) -> String {
+        match self.api {
+            SyncApi::Gpodder => self.subscriptions_url(),
+            SyncApi::Nextcloud => format!(
+                "{}/index.php/apps/gpoddersync/subscription_change/create",
+                self.server
+            ),
+        }
+    }
+
+    fn actions_url(&self) -> String {
+        match self.api {
+            SyncApi::Gpodder => format!("{}/api/2/episodes/{}.json", self.server, self.username),
+            SyncApi::Nextcloud => {
+                format!("{}/index.php/apps/gpoddersync/episode_action", self.server)
+            }
+        }
+    }
+
+    fn action_upload_url(&self) -> String {
+        match self.api {
+            SyncApi::Gpodder => self.actions_url(),
+            SyncApi::Nextcloud => format!(
+                "{}/index.php/apps/gpoddersync/episode_action/create",
+                self.server
+            ),
+        }
+    }
+}
+
+/// Subscriptions added and removed on the server since a timestamp
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct SubscriptionChanges {
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+    pub timestamp: i64,
+}
+
+#[derive(Serialize)]
+struct SubscriptionUpload<'a> {
+    add: &'a [String],
+    remove: &'a [String],
+}
+
+/// What an upload returns: the timestamp to ask for changes since next time
+#[derive(Deserialize)]
+struct Uploaded {
+    timestamp: i64,
+}
+
+/// Kind of [`EpisodeAction`]. Some clients send them in capitals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionKind {
+    #[serde(alias = "DOWNLOAD")]
+    Download,
+    #[serde(alias = "PLAY")]
+    Play,
+    #[serde(alias = "DELETE")]
+    Delete,
+    /// Marked new (unplayed) again
+    #[serde(alias = "NEW")]
+    New,
+    #[serde(other)]
+    Other,
+}
+
+/// Something done with an episode on some device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EpisodeAction {
+    /// Feed URL
+    pub podcast: String,
+    /// Enclosure URL
+    pub episode: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+    pub action: ActionKind,
+    /// UTC, `YYYY-MM-DDTHH:MM:SS`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// Play actions: seconds where playback started and stopped, and the
+    /// episode's length
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+/// Seconds from the end of an episode within which playback stopping
+/// counts as having finished it
+const FINISHED_MARGIN: i64 = 60;
+
+impl EpisodeAction {
+    /// A play action for an episode listened to the end
+    pub fn played(
+        podcast: &str,
+        episode: &str,
+        guid: Option<&str>,
+        at: &str,
+        length: Option<u64>,
+    ) -> Self {
+        let length = length.map(|l| l as i64);
+        Self {
+            podcast: podcast.to_string(),
+            episode: episode.to_string(),
+            guid: guid.map(str::to_string),
+            action: ActionKind::Play,
+            timestamp: Some(timestamp(at)),
+            device: None,
+            started: length.map(|_| 0),
+            position: length,
+            total: length,
+        }
+    }
+
+    /// A download action
+    pub fn downloaded(podcast: &str, episode: &str, guid: Option<&str>, at: &str) -> Self {
+        Self {
+            action: ActionKind::Download,
+            started: None,
+            position: None,
+            total: None,
+            ..Self::played(podcast, episode, guid, at, None)
+        }
+    }
+
+    /// Whether the action marks the episode played (`Some(true)`) or
+    /// unplayed (`Some(false)`). Playback that stopped before the end
+    /// doesn't count.
+    pub fn played_state(&self) -> Option<bool> {
+        match self.action {
+            ActionKind::Play => match (self.position, self.total) {
+                (Some(position), Some(total)) if total > 0 => {
+                    Some(position + FINISHED_MARGIN >= total)
+                }
+                _ => Some(true),
+            }
+            .filter(|&finished| finished),
+            ActionKind::New => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// `YYYY-MM-DD HH:MM:SS` (as SQLite writes it) in the API's form
+fn timestamp(at: &str) -> String {
+    at.replacen(' ', "T", 1)
+}
+
+/// Episode actions from all devices since a timestamp
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct EpisodeActions {
+    #[serde(default)]
+    pub actions: Vec<EpisodeAction>,
+    pub timestamp: i64,
+}
+
+/// What pdl remembers of an account between syncs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncState {
+    /// Server timestamp to ask for subscription changes since
+    pub subscriptions_since: i64,
+    /// Server timestamp to ask for episode actions since
+    pub actions_since: i64,
+    /// When episode actions were last uploaded (UTC, `YYYY-MM-DD HH:MM:SS`)
+    pub pushed_at: Option<String>,
+    /// Feed URLs subscribed to as of the last sync, to tell what was
+    /// subscribed to or dropped locally since
+    pub subscriptions: Vec<String>,
+}
+
+/// How to bring the subscriptions on both sides together
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubscriptionPlan {
+    /// Subscribed to elsewhere: subscribe here
+    pub subscribe: Vec<String>,
+    /// Dropped elsewhere: unsubscribe here
+    pub unsubscribe: Vec<String>,
+    /// Subscribed to here since the last sync: upload
+    pub add: Vec<String>,
+    /// Dropped here since the last sync: upload
+    pub remove: Vec<String>,
+}
+
+impl SubscriptionPlan {
+    /// Compare the `local` subscriptions with those at the last sync
+    /// (`synced`) and the `remote` changes since. A change made here wins
+    /// over one made elsewhere to the same feed.
+    pub fn new(local: &[String], synced: &[String], remote: &SubscriptionChanges) -> Self {
+        let local_set: HashSet<&String> = local.iter().collect();
+        let synced: HashSet<&String> = synced.iter().collect();
+        let add: Vec<String> = local
+            .iter()
+            .filter(|url| !synced.contains(url))
+            .cloned()
+            .collect();
+        let remove: Vec<String> = synced
+            .iter()
+            .filter(|url| !local_set.contains(*url))
+            .map(|url| url.to_string())
+            .collect();
+        let subscribe = remote
+            .add
+            .iter()
+            .filter(|url| !local_set.contains(url) && !remove.contains(url))
+            .cloned()
+            .collect();
+        let unsubscribe = remote
+            .remove
+            .iter()
+            .filter(|url| local_set.contains(url) && !add.contains(url))
+            .cloned()
+            .collect();
+        Self {
+            subscribe,
+            unsubscribe,
+            add,
+            remove,
+        }
+    }
+}
+
+/// Talks to a sync server for one account
+#[derive(Debug, Clone)]
+pub struct GpodderClient {
+    http: Client,
+    retry: RetryPolicy,
+    account: GpodderAccount,
+}
+
+impl GpodderClient {
+    pub(crate) fn new(http: Client, retry: RetryPolicy, account: GpodderAccount) -> Self {
+        Self {
+            http,
+            retry,
+            account,
+        }
+    }
+
+    pub fn account(&self) -> &GpodderAccount {
+        &self.account
+    }
+
+    /// Subscription changes since `since` (0 for all subscriptions)
+    pub async fn subscription_changes(&self, since: i64) -> Result<SubscriptionChanges> {
+        self.send(|| {
+            self.http
+                .get(self.account.subscriptions_url())
+                .query(&[("since", since)])
+        })
+        .await
+        .context("Failed to fetch subscriptions from the sync server")
+    }
+
+    /// Upload subscriptions added and removed, returning the new timestamp
+    pub async fn upload_subscriptions(&self, add: &[String], remove: &[String]) -> Result<i64> {
+        let body = SubscriptionUpload { add, remove };
+        let uploaded: Uploaded = self
+            .send(|| {
+                self.http
+                    .post(self.account.subscription_upload_url())
+                    .json(&body)
+            })
+            .await
+            .context("Failed to upload subscriptions to the sync server")?;
+        Ok(uploaded.timestamp)
+    }
+
+    /// Episode actions from every device since `since`
+    pub async fn episode_actions(&self, since: i64) -> Result<EpisodeActions> {
+        self.send(|| {
+            self.http
+                .get(self.account.actions_url())
+                .query(&[("since", since)])
+        })
+        .await
+        .context("Failed to fetch episode actions from the sync server")
+    }
+
+    /// Upload episode actions done here
+    pub async fn upload_actions(&self, actions: &[EpisodeAction]) -> Result<i64> {
+        let uploaded: Uploaded = self
+            .send(|| {
+                self.http
+                    .post(self.account.action_upload_url())
+                    .json(actions)
+            })
+            .await
+            .context("Failed to upload episode actions to the sync server")?;
+        Ok(uploaded.timestamp)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: impl Fn() -> RequestBuilder) -> Result<T> {
+        self.retry
+            .run(|| async {
+                let response = request()
+                    .basic_auth(&self.account.username, self.account.password.as_ref())
+                    .send()
+                    .await?;
+                Ok(check_response(response)?.json::<T>().await?)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|u| u.to_string()).collect()
+    }
+
+    #[test]
+    fn test_subscription_plan() {
+        let remote = SubscriptionChanges {
+            add: urls(&["phone-new", "dropped-here"]),
+            remove: urls(&["kept", "added-here"]),
+            timestamp: 10,
+        };
+        let plan = SubscriptionPlan::new(
+            &urls(&["kept", "added-here"]),
+            &urls(&["kept", "dropped-here"]),
+            &remote,
+        );
+        assert_eq!(plan.subscribe, ["phone-new"]);
+        assert_eq!(plan.unsubscribe, ["kept"]);
+        assert_eq!(plan.add, ["added-here"]);
+        assert_eq!(plan.remove, ["dropped-here"]);
+
+        let actions: EpisodeActions = serde_json::from_str(
+            r#"{"actions": [
+                {"podcast": "f", "episode": "e", "action": "play", "position": 100, "total": 3600},
+                {"podcast": "f", "episode": "e", "action": "PLAY", "position": 3590, "total": 3600},
+                {"podcast": "f", "episode": "e", "action": "new"},
+                {"podcast": "f", "episode": "e", "action": "flattr"}
+            ], "timestamp": 12}"#,
+        )
+        .unwrap();
+        let states: Vec<Option<bool>> = actions.actions.iter().map(|a| a.played_state()).collect();
+        assert_eq!(states, [None, Some(true), Some(false), None]);
+    }
+}
@@ -0,0 +1,746 @@
+use crate::changes::RecordedMedia;
+use crate::config;
+use crate::feed::Episode;
+use crate::gpodder::SyncState;
+use crate::library::{self, EpisodeState, LibraryEpisode, LibraryQuery, QueryResult};
+use crate::queue::{self, QueueItem};
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A previously downloaded episode
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub feed_url: String,
+    pub guid: Option<String>,
+    pub url: String,
+    pub title: String,
+    pub path: PathBuf,
+    /// UTC timestamp (`YYYY-MM-DD HH:MM:SS`)
+    pub downloaded_at: String,
+    /// Show title; unknown for downloads recorded by older versions
+    pub show: Option<String>,
+    /// When the episode was published (RFC 3339), if the feed said
+    pub published: Option<String>,
+    /// Running time in seconds, if the feed said
+    pub duration: Option<u64>,
+}
+
+/// The newest episode of a feed as of the last `pdl update`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastSeen {
+    pub guid: Option<String>,
+    pub url: String,
+    pub published: Option<DateTime<FixedOffset>>,
+}
+
+impl LastSeen {
+    pub fn of(episode: &Episode) -> Self {
+        Self {
+            guid: episode.guid.clone(),
+            url: episode.url.clone(),
+            published: episode.published,
+        }
+    }
+
+    fn is(&self, episode: &Episode) -> bool {
+        match (&self.guid, &episode.guid) {
+            (Some(seen), Some(guid)) => seen == guid,
+            _ => self.url == episode.url,
+        }
+    }
+
+    /// Episodes of a feed (newest first) that came out after this one: those
+    /// above it in the list, or, if it has left the feed, those published later
+    pub fn new_episodes<'a>(&self, episodes: &'a [Episode]) -> Vec<&'a Episode> {
+        if let Some(i) = episodes.iter().position(|e| self.is(e)) {
+            return episodes[..i].iter().collect();
+        }
+        let Some(seen) = self.published else {
+            return Vec::new();
+        };
+        episodes
+            .iter()
+            .filter(|e| e.published.is_some_and(|d| d > seen))
+            .collect()
+    }
+}
+
+/// Columns added to `downloads` since it was first created, with their types
+const DOWNLOAD_COLUMNS: [(&str, &str); 5] = [
+    ("show", "TEXT"),
+    ("published", "TEXT"),
+    ("duration", "INTEGER"),
+    ("length", "INTEGER"),
+    ("etag", "TEXT"),
+];
+
+/// Columns read into a [`HistoryEntry`]
+const ENTRY_COLUMNS: &str =
+    "feed_url, guid, url, title, path, downloaded_at, show, published, duration";
+
+/// SQLite database of downloaded episodes, used to skip re-downloads, and
+/// of every episode seen in a feed (the library `pdl query` searches)
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// Path of the history database (`<data dir>/pdl/history.db`)
+    pub fn path() -> Result<PathBuf> {
+        Ok(config::data_dir()?.join("history.db"))
+    }
+
+    /// Open the history database at its default location
+    pub fn open_default() -> Result<Self> {
+        Self::open(&Self::path()?)
+    }
+
+    /// Open (creating if needed) the history database at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create data directory")?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open history database {}", path.display()))?;
+        Self::init(conn)
+    }
+
+    /// Open a throwaway in-memory database
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS downloads (
+                id INTEGER PRIMARY KEY,
+                feed_url TEXT NOT NULL,
+                guid TEXT,
+                url TEXT NOT NULL,
+                title TEXT NOT NULL,
+                path TEXT NOT NULL,
+                downloaded_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS downloads_guid ON downloads (guid);
+            CREATE INDEX IF NOT EXISTS downloads_url ON downloads (url);
+            CREATE TABLE IF NOT EXISTS last_seen (
+                feed_url TEXT PRIMARY KEY,
+                guid TEXT,
+                url TEXT NOT NULL,
+                published TEXT
+            );
+            CREATE TABLE IF NOT EXISTS sync_state (
+                account TEXT PRIMARY KEY,
+                state TEXT NOT NULL
+            );",
+        )
+        .context("Failed to initialize history database")?;
+        add_columns(&conn, "downloads", &DOWNLOAD_COLUMNS)
+            .context("Failed to upgrade history database")?;
+        library::init(&conn).context("Failed to initialize episode library")?;
+        queue::init(&conn).context("Failed to initialize download queue")?;
+        Ok(Self { conn })
+    }
+
+    /// Whether `episode` was already downloaded, matching by GUID or enclosure URL
+    pub fn contains(&self, episode: &Episode) -> Result<bool> {
+        let found = self
+            .conn
+            .query_row(
+                "SELECT 1 FROM downloads WHERE url = ?1 OR (guid IS NOT NULL AND guid = ?2) LIMIT 1",
+                params![episode.url, episode.guid],
+                |_| Ok(()),
+            )
+            .optional()
+            .context("Failed to query download history")?;
+        Ok(found.is_some())
+    }
+
+    /// Record a completed download of `episode` of `show` to `path`
+    pub fn record(&self, feed_url: &str, show: &str, episode: &Episode, path: &Path) -> Result<()> {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        self.conn
+            .execute(
+                "INSERT INTO downloads (feed_url, guid, url, title, path, show, published, duration, length)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    feed_url,
+                    episode.guid,
+                    episode.url,
+                    episode.title,
+                    path.to_string_lossy(),
+                    show,
+                    episode.published.map(|d| d.to_rfc3339()),
+                    episode.duration.map(|d| d.as_secs() as i64),
+                    episode.size.map(|s| s as i64)
+                ],
+            )
+            .context("Failed to record download history")?;
+        library::upsert(
+            &self.conn,
+            feed_url,
+            show,
+            episode,
+            Some(EpisodeState::Downloaded),
+            Some(&path),
+        )
+        .context("Failed to record download history")?;
+        queue::remove_episode(&self.conn, feed_url, episode)
+            .context("Failed to update download queue")
+    }
+
+    /// The size and ETag of `episode`'s media as of its latest download, if
+    /// it was downloaded
+    pub fn recorded_media(&self, episode: &Episode) -> Result<Option<RecordedMedia>> {
+        self.conn
+            .query_row(
+                "SELECT length, etag FROM downloads
+                 WHERE url = ?1 OR (guid IS NOT NULL AND guid = ?2) ORDER BY id DESC LIMIT 1",
+                params![episode.url, episode.guid],
+                |row| {
+                    let length: Option<i64> = row.get(0)?;
+                    Ok(RecordedMedia {
+                        length: length.map(|l| l as u64),
+                        etag: row.get(1)?,
+                    })
+                },
+            )
+            .optional()
+            .context("Failed to query download history")
+    }
+
+    /// Remember `media` as the size and ETag of `episode`'s latest download
+    pub fn set_recorded_media(&self, episode: &Episode, media: &RecordedMedia) -> Result<()> {
+        self.conn
+            .execute(
+                "UPDATE downloads SET length = ?3, etag = ?4 WHERE id = (
+                    SELECT id FROM downloads
+                    WHERE url = ?1 OR (guid IS NOT NULL AND guid = ?2) ORDER BY id DESC LIMIT 1
+                 )",
+                params![
+                    episode.url,
+                    episode.guid,
+                    media.length.map(|l| l as i64),
+                    media.etag
+                ],
+            )
+            .context("Failed to update download history")?;
+        Ok(())
+    }
+
+    /// Note in the library that downloading `episode` of `show` failed, and
+    /// why on its queue item if it's queued
+    pub fn record_failure(
+        &self,
+        feed_url: &str,
+        show: &str,
+        episode: &Episode,
+        error: &str,
+    ) -> Result<()> {
+        library::upsert(
+            &self.conn,
+            feed_url,
+            show,
+            episode,
+            Some(EpisodeState::Failed),
+            None,
+        )
+        .context("Failed to update episode library")?;
+        queue::set_error(&self.conn, feed_url, episode, error)
+            .context("Failed to update download queue")
+    }
+
+    /// Add `episode` of `show` to the download queue, returning whether it
+    /// wasn't queued already
+    pub fn enqueue(&self, feed_url: &str, show: &str, episode: &Episode) -> Result<bool> {
+        queue::add(&self.conn, feed_url, show, episode).context("Failed to update download queue")
+    }
+
+    /// The download queue, oldest first
+    pub fn queue(&self) -> Result<Vec<QueueItem>> {
+        queue::items(&self.conn).context("Failed to read download queue")
+    }
+
+    /// Take the items with these ids out of the queue, returning how many
+    /// there were
+    pub fn dequeue(&self, ids: &[i64]) -> Result<usize> {
+        queue::remove(&self.conn, ids).context("Failed to update download queue")
+    }
+
+    /// Pause or resume the queue items with these ids (all of them if
+    /// `ids` is empty), returning how many there were
+    pub fn pause_queued(&self, ids: &[i64], paused: bool) -> Result<usize> {
+        queue::set_paused(&self.conn, ids, paused).context("Failed to update download queue")
+    }
+
+    /// Add a feed's `episodes` to the library, keeping what it already knows
+    /// of their downloads
+    pub fn remember_episodes(
+        &self,
+        feed_url: &str,
+        show: &str,
+        episodes: &[Episode],
+    ) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for episode in episodes {
+            library::upsert(&tx, feed_url, show, episode, None, None)
+                .context("Failed to update episode library")?;
+        }
+        tx.commit().context("Failed to update episode library")
+    }
+
+    /// Episodes in the library that match `query`
+    pub fn query(&self, query: &LibraryQuery) -> Result<Vec<LibraryEpisode>> {
+        library::search(&self.conn, query)
+    }
+
+    /// Mark episodes in the library played or unplayed (see
+    /// [`library::set_played`]), returning their titles
+    pub fn mark_played(
+        &self,
+        episode: Option<&str>,
+        show: Option<&str>,
+        played: bool,
+    ) -> Result<Vec<String>> {
+        library::set_played(&self.conn, episode, show, played)
+            .context("Failed to update episode library")
+    }
+
+    /// Mark the downloaded episode at `path` played, returning whether it is
+    /// in the library
+    pub fn mark_file_played(&self, path: &Path) -> Result<bool> {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        library::set_file_played(&self.conn, &path).context("Failed to update episode library")
+    }
+
+    /// Mark `episode` of `show` played, adding it to the library if needed
+    pub fn mark_episode_played(&self, feed_url: &str, show: &str, episode: &Episode) -> Result<()> {
+        library::upsert(&self.conn, feed_url, show, episode, None, None)
+            .and_then(|()| library::set_episode_played(&self.conn, feed_url, episode.key()))
+            .context("Failed to update episode library")
+    }
+
+    /// The keys ([`Episode::key`]) of the feed's episodes marked played
+    pub fn played_keys(&self, feed_url: &str) -> Result<HashSet<String>> {
+        library::played_keys(&self.conn, feed_url).context("Failed to query episode library")
+    }
+
+    /// Mark the episode of the feed at `feed_url` with enclosure `url` or
+    /// GUID `guid` played or unplayed, returning whether it is in the library
+    pub fn mark_url_played(
+        &self,
+        feed_url: &str,
+        url: &str,
+        guid: Option<&str>,
+        played: bool,
+    ) -> Result<bool> {
+        library::set_played_by_url(&self.conn, feed_url, url, guid, played)
+            .context("Failed to update episode library")
+    }
+
+    /// Episodes marked played after `since` (UTC, `YYYY-MM-DD HH:MM:SS`), or
+    /// all of them, in the order they were played
+    pub fn played_since(&self, since: Option<&str>) -> Result<Vec<LibraryEpisode>> {
+        library::played_since(&self.conn, since).context("Failed to query episode library")
+    }
+
+    /// Downloads made after `since` (UTC, `YYYY-MM-DD HH:MM:SS`), or all of
+    /// them, oldest first
+    pub fn entries_since(&self, since: Option<&str>) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM downloads WHERE ?1 IS NULL OR downloaded_at > ?1
+             ORDER BY downloaded_at, id",
+            ENTRY_COLUMNS
+        ))?;
+        let entries = stmt
+            .query_map(params![since], entry_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read download history")?;
+        Ok(entries)
+    }
+
+    /// What was remembered of the sync account `account` after its last sync
+    pub fn sync_state(&self, account: &str) -> Result<SyncState> {
+        let state: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT state FROM sync_state WHERE account = ?1",
+                params![account],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to read sync state")?;
+        match state {
+            Some(state) => serde_json::from_str(&state).context("Invalid sync state"),
+            None => Ok(SyncState::default()),
+        }
+    }
+
+    /// Remember `state` for the sync account `account`
+    pub fn set_sync_state(&self, account: &str, state: &SyncState) -> Result<()> {
+        let state = serde_json::to_string(state).context("Failed to serialize sync state")?;
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO sync_state (account, state) VALUES (?1, ?2)",
+                params![account, state],
+            )
+            .context("Failed to save sync state")?;
+        Ok(())
+    }
+
+    /// Run a read-only SQL query over the history and library tables
+    pub fn query_sql(&self, sql: &str) -> Result<QueryResult> {
+        library::run_sql(&self.conn, sql)
+    }
+
+    /// The newest episode `pdl update` saw in the feed at `feed_url`, if it
+    /// has checked the feed before
+    pub fn last_seen(&self, feed_url: &str) -> Result<Option<LastSeen>> {
+        self.conn
+            .query_row(
+                "SELECT guid, url, published FROM last_seen WHERE feed_url = ?1",
+                params![feed_url],
+                |row| {
+                    let published: Option<String> = row.get(2)?;
+                    Ok(LastSeen {
+                        guid: row.get(0)?,
+                        url: row.get(1)?,
+                        published: published.and_then(|d| DateTime::parse_from_rfc3339(&d).ok()),
+                    })
+                },
+            )
+            .optional()
+            .context("Failed to query download history")
+    }
+
+    /// Remember `seen` as the newest episode of the feed at `feed_url`
+    pub fn set_last_seen(&self, feed_url: &str, seen: &LastSeen) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO last_seen (feed_url, guid, url, published) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    feed_url,
+                    seen.guid,
+                    seen.url,
+                    seen.published.map(|d| d.to_rfc3339())
+                ],
+            )
+            .context("Failed to record last seen episode")?;
+        Ok(())
+    }
+
+    /// Carry everything recorded for the feed at `from` over to `to`, for a
+    /// feed that has moved. Rows `to` already has are left as they are.
+    pub fn move_feed(&self, from: &str, to: &str) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for table in ["downloads", "last_seen", "episodes", "queue"] {
+            tx.execute(
+                &format!(
+                    "UPDATE OR IGNORE {} SET feed_url = ?2 WHERE feed_url = ?1",
+                    table
+                ),
+                params![from, to],
+            )
+            .context("Failed to update download history")?;
+        }
+        tx.commit().context("Failed to update download history")
+    }
+
+    /// The most recent `limit` downloads, newest first
+    pub fn entries(&self, limit: usize) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM downloads ORDER BY downloaded_at DESC, id DESC LIMIT ?1",
+            ENTRY_COLUMNS
+        ))?;
+        let entries = stmt
+            .query_map(params![limit as i64], entry_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read download history")?;
+        Ok(entries)
+    }
+
+    /// Every download, newest first
+    pub fn all_entries(&self) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM downloads ORDER BY downloaded_at DESC, id DESC",
+            ENTRY_COLUMNS
+        ))?;
+        let entries = stmt
+            .query_map([], entry_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read download history")?;
+        Ok(entries)
+    }
+
+    /// Every download from the feed at `feed_url`, newest first
+    pub fn feed_entries(&self, feed_url: &str) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM downloads WHERE feed_url = ?1 ORDER BY downloaded_at DESC, id DESC",
+            ENTRY_COLUMNS
+        ))?;
+        let entries = stmt
+            .query_map(params![feed_url], entry_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read download history")?;
+        Ok(entries)
+    }
+}
+
+/// Add the `columns` later versions record to `table` in a database made
+/// by an earlier one
+pub(crate) fn add_columns(
+    conn: &Connection,
+    table: &str,
+    columns: &[(&str, &str)],
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+    let existing = stmt
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (name, kind) in columns {
+        if !existing.iter().any(|column| column == name) {
+            conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} {}", table, name, kind),
+                [],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        feed_url: row.get(0)?,
+        guid: row.get(1)?,
+        url: row.get(2)?,
+        title: row.get(3)?,
+        path: PathBuf::from(row.get::<_, String>(4)?),
+        downloaded_at: row.get(5)?,
+        show: row.get(6)?,
+        published: row.get(7)?,
+        duration: row.get::<_, Option<i64>>(8)?.map(|d| d as u64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episode(guid: Option<&str>, url: &str) -> Episode {
+        Episode {
+            title: "Episode".to_string(),
+            url: url.to_string(),
+            guid: guid.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_recorded_media() {
+        let history = History::open_in_memory().unwrap();
+        let feed = "https://example.com/feed";
+        let mut first = episode(Some("guid-1"), "https://example.com/1.mp3");
+        assert_eq!(history.recorded_media(&first).unwrap(), None);
+
+        first.size = Some(1000);
+        history
+            .record(feed, "Show", &first, Path::new("/tmp/1.mp3"))
+            .unwrap();
+        let recorded = history.recorded_media(&first).unwrap().unwrap();
+        assert_eq!(recorded.length, Some(1000));
+        assert_eq!(recorded.etag, None);
+
+        // Found by GUID after the enclosure moved
+        first.url = "https://cdn.example.com/1-v2.mp3".to_string();
+        let media = RecordedMedia {
+            length: Some(2000),
+            etag: Some("\"abc\"".to_string()),
+        };
+        history.set_recorded_media(&first, &media).unwrap();
+        assert_eq!(history.recorded_media(&first).unwrap(), Some(media));
+    }
+
+    #[test]
+    fn test_move_feed() {
+        let history = History::open_in_memory().unwrap();
+        let (old, new) = ("http://example.com/feed", "https://example.com/feed");
+        let first = episode(Some("guid-1"), "https://example.com/1.mp3");
+        history
+            .record(old, "Show", &first, Path::new("/tmp/1.mp3"))
+            .unwrap();
+        history.enqueue(old, "Show", &first).unwrap();
+        history.set_last_seen(old, &LastSeen::of(&first)).unwrap();
+
+        history.move_feed(old, new).unwrap();
+        assert_eq!(history.feed_entries(old).unwrap().len(), 0);
+        assert_eq!(history.feed_entries(new).unwrap().len(), 1);
+        assert_eq!(history.queue().unwrap()[0].feed_url, new);
+        assert!(history.last_seen(old).unwrap().is_none());
+        assert!(history.last_seen(new).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_queue() {
+        let history = History::open_in_memory().unwrap();
+        let feed = "https://example.com/feed";
+        let first = Episode {
+            number: Some(7),
+            duration: Some(std::time::Duration::from_secs(90)),
+            ..episode(Some("guid-1"), "https://example.com/1.mp3")
+        };
+        let second = episode(None, "https://example.com/2.mp3");
+        assert!(history.enqueue(feed, "Show", &first).unwrap());
+        assert!(history.enqueue(feed, "Show", &second).unwrap());
+        assert!(!history.enqueue(feed, "Show", &first).unwrap());
+
+        let queue = history.queue().unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue[0].episode, first);
+        assert_eq!(queue[0].show, "Show");
+
+        assert_eq!(history.pause_queued(&[queue[1].id], true).unwrap(), 1);
+        history
+            .record_failure(feed, "Show", &first, "Connection reset")
+            .unwrap();
+        let queue = history.queue().unwrap();
+        assert!(!queue[0].paused && queue[1].paused);
+        assert_eq!(queue[0].error.as_deref(), Some("Connection reset"));
+        assert_eq!(history.pause_queued(&[], false).unwrap(), 2);
+
+        // Downloading an episode takes it out of the queue
+        history
+            .record(feed, "Show", &first, Path::new("1.mp3"))
+            .unwrap();
+        let queue = history.queue().unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(history.dequeue(&[queue[0].id, 999]).unwrap(), 1);
+        assert!(history.queue().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_contains_matches_guid_or_url() {
+        let history = History::open_in_memory().unwrap();
+        let recorded = episode(Some("guid-1"), "https://example.com/1.mp3");
+        history
+            .record(
+                "https://example.com/feed",
+                "Show",
+                &recorded,
+                Path::new("1.mp3"),
+            )
+            .unwrap();
+
+        assert!(history.contains(&recorded).unwrap());
+        // Same GUID served from a new URL
+        assert!(
+            history
+                .contains(&episode(Some("guid-1"), "https://cdn.example.com/1.mp3"))
+                .unwrap()
+        );
+        // Same URL without a GUID
+        assert!(
+            history
+                .contains(&episode(None, "https://example.com/1.mp3"))
+                .unwrap()
+        );
+        assert!(
+            !history
+                .contains(&episode(Some("guid-2"), "https://example.com/2.mp3"))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_new_episodes_since_last_seen() {
+        let mut episodes: Vec<Episode> = (1..=3)
+            .rev()
+            .map(|i| Episode {
+                published: DateTime::parse_from_rfc3339(&format!("2024-01-0{}T00:00:00Z", i)).ok(),
+                ..episode(
+                    Some(&format!("guid-{}", i)),
+                    &format!("https://example.com/{}.mp3", i),
+                )
+            })
+            .collect();
+        let seen = LastSeen::of(&episodes[1]);
+        let urls = |new: Vec<&Episode>| new.iter().map(|e| e.url.clone()).collect::<Vec<_>>();
+
+        assert_eq!(
+            urls(seen.new_episodes(&episodes)),
+            ["https://example.com/3.mp3"]
+        );
+        assert!(
+            LastSeen::of(&episodes[0])
+                .new_episodes(&episodes)
+                .is_empty()
+        );
+        // The seen episode dropped out of the feed
+        episodes.remove(1);
+        assert_eq!(
+            urls(seen.new_episodes(&episodes)),
+            ["https://example.com/3.mp3"]
+        );
+
+        let history = History::open_in_memory().unwrap();
+        assert_eq!(history.last_seen("feed").unwrap(), None);
+        history.set_last_seen("feed", &seen).unwrap();
+        history.set_last_seen("feed", &seen).unwrap();
+        assert_eq!(history.last_seen("feed").unwrap(), Some(seen));
+    }
+
+    #[test]
+    fn test_entries_newest_first() {
+        let history = History::open_in_memory().unwrap();
+        for i in 1..=3 {
+            let ep = episode(None, &format!("https://example.com/{}.mp3", i));
+            history
+                .record("feed", "Show", &ep, Path::new(&format!("{}.mp3", i)))
+                .unwrap();
+        }
+
+        let entries = history.entries(2).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].path.ends_with("3.mp3"));
+        assert!(entries[1].path.ends_with("2.mp3"));
+    }
+
+    #[test]
+    fn test_upgrades_old_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE downloads (
+                id INTEGER PRIMARY KEY,
+                feed_url TEXT NOT NULL,
+                guid TEXT,
+                url TEXT NOT NULL,
+                title TEXT NOT NULL,
+                path TEXT NOT NULL,
+                downloaded_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            INSERT INTO downloads (feed_url, url, title, path) VALUES ('feed', 'u', 'Old', 'old.mp3');",
+        )
+        .unwrap();
+
+        let history = History::init(conn).unwrap();
+        let ep = Episode {
+            duration: Some(std::time::Duration::from_secs(90)),
+            ..episode(None, "https://example.com/new.mp3")
+        };
+        history
+            .record("feed", "Show", &ep, Path::new("new.mp3"))
+            .unwrap();
+
+        let entries = history.feed_entries("feed").unwrap();
+        assert_eq!(entries.len(), 2);
+        let new = entries.iter().find(|e| e.url == ep.url).unwrap();
+        assert_eq!(new.show.as_deref(), Some("Show"));
+        assert_eq!(new.duration, Some(90));
+        let old = entries.iter().find(|e| e.title == "Old").unwrap();
+        assert_eq!(old.show, None);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::download::sanitize_filename;
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::Stdio;
+use std::str::FromStr;
+use tokio::process::Command;
+
+/// Placeholders a post-download command may use, and what they stand for
+const PLACEHOLDERS: [&str; 3] = ["{file}", "{title}", "{show}"];
+
+/// A command run on each freshly downloaded file, e.g.
+/// `ffmpeg-normalize {file} -o {file}.norm.mp3`. Arguments are split on
+/// whitespace and run directly, without a shell, so file names with spaces
+/// or quotes are passed through intact. `{file}` is the downloaded file
+/// (appended if no argument mentions it), `{title}` the episode title and
+/// `{show}` the show's title, both cleaned up like file names so a feed
+/// can't point the command at another directory with `../` or `/`. The
+/// file and the titles as the feed gives them are in `PDL_FILE`,
+/// `PDL_TITLE` and `PDL_SHOW` for scripts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+impl FromStr for PostCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(command: &str) -> Result<Self> {
+        let mut words = command.split_whitespace().map(str::to_string);
+        let Some(program) = words.next() else {
+            bail!("The post-download command is empty");
+        };
+        Ok(Self {
+            program,
+            args: words.collect(),
+        })
+    }
+}
+
+impl PostCommand {
+    /// The arguments for `file`, with placeholders filled in
+    fn args_for(&self, file: &Path, title: &str, show: &str) -> Vec<String> {
+        let file = file.to_string_lossy();
+        let (title, show) = (sanitize_filename(title), sanitize_filename(show));
+        let values = [file.as_ref(), &title, &show];
+        let mut args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| {
+                PLACEHOLDERS
+                    .iter()
+                    .zip(values)
+                    .fold(arg.clone(), |arg, (placeholder, value)| {
+                        arg.replace(placeholder, value)
+                    })
+            })
+            .collect();
+        if !self.args.iter().any(|arg| arg.contains(PLACEHOLDERS[0])) {
+            args.push(file.into_owned());
+        }
+        args
+    }
+
+    /// Run the command on `file`, an episode of `show` titled `title`, and
+    /// wait for it. Fails with the command's error output if it exits
+    /// unsuccessfully.
+    pub async fn run(&self, file: &Path, title: &str, show: &str) -> Result<()> {
+        let output = Command::new(&self.program)
+            .args(self.args_for(file, title, show))
+            .env("PDL_FILE", file)
+            .env("PDL_TITLE", title)
+            .env("PDL_SHOW", show)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .with_context(|| format!("Failed to run post-download command {:?}", self.program))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!(
+                "Post-download command {:?} exited with {} for {}: {}",
+                self.program,
+                output.status,
+                file.display(),
+                stderr.trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_post_command_args() {
+        let command: PostCommand = "cp {file} /archive/{show}/".parse().unwrap();
+        assert_eq!(
+            command.args_for(Path::new("/podcasts/My Show/Ep 1.mp3"), "Ep 1", "My Show"),
+            ["/podcasts/My Show/Ep 1.mp3", "/archive/My Show/"]
+        );
+
+        let command: PostCommand = "notify-send Downloaded".parse().unwrap();
+        assert_eq!(
+            command.args_for(Path::new("a.mp3"), "A", "Show"),
+            ["Downloaded", "a.mp3"]
+        );
+
+        let command: PostCommand = "cp {file} /archive/{show}/{title}.mp3".parse().unwrap();
+        assert_eq!(
+            command.args_for(Path::new("a.mp3"), "../../.ssh/authorized_keys", "/etc"),
+            ["a.mp3", "/archive/-etc/..-..-.ssh-authorized_keys.mp3"]
+        );
+
+        assert!("  ".parse::<PostCommand>().is_err());
+    }
+}
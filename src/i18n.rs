@@ -0,0 +1,171 @@
+//! Translations of what pdl says to people, kept as Fluent messages in
+//! `locales/<lang>.ftl`. Messages missing from a translation fall back to
+//! English. Output meant for programs (`--json`, `--porcelain`) stays
+//! untranslated.
+
+use fluent_bundle::FluentResource;
+use fluent_bundle::concurrent::FluentBundle;
+use std::env;
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+pub use fluent_bundle::FluentArgs;
+
+/// Languages pdl speaks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Lang {
+    #[default]
+    En,
+    Ko,
+}
+
+impl Lang {
+    /// The language of a locale such as `ko_KR.UTF-8` or `en-US`, if pdl
+    /// has a translation for it
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let language = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default();
+        match language.to_ascii_lowercase().as_str() {
+            "en" | "c" | "posix" => Some(Self::En),
+            "ko" => Some(Self::Ko),
+            _ => None,
+        }
+    }
+
+    /// The language of the user's locale: the first of `LC_ALL`,
+    /// `LC_MESSAGES` and `LANG` that is set, as POSIX orders them, or
+    /// English when pdl has no translation for it
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .and_then(|locale| Self::from_locale(&locale))
+            .unwrap_or_default()
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Self::En => include_str!("../locales/en.ftl"),
+            Self::Ko => include_str!("../locales/ko.ftl"),
+        }
+    }
+
+    fn id(self) -> LanguageIdentifier {
+        let id = match self {
+            Self::En => "en-US",
+            Self::Ko => "ko-KR",
+        };
+        id.parse().expect("valid language identifier")
+    }
+
+    fn bundle(self) -> &'static FluentBundle<FluentResource> {
+        static EN: OnceLock<FluentBundle<FluentResource>> = OnceLock::new();
+        static KO: OnceLock<FluentBundle<FluentResource>> = OnceLock::new();
+        let cell = match self {
+            Self::En => &EN,
+            Self::Ko => &KO,
+        };
+        cell.get_or_init(|| {
+            // A broken entry only loses that message; the test catches it
+            let resource = FluentResource::try_new(self.source().to_string())
+                .unwrap_or_else(|(resource, _)| resource);
+            let mut bundle = FluentBundle::new_concurrent(vec![self.id()]);
+            // Terminals show the bidi isolation marks around arguments as junk
+            bundle.set_use_isolating(false);
+            let _ = bundle.add_resource(resource);
+            bundle
+        })
+    }
+
+    /// Message `id` in this language, or English if it isn't translated,
+    /// with `args` filled in. Unknown ids come back as they are.
+    pub fn message(self, id: &str, args: Option<&FluentArgs>) -> String {
+        for lang in [self, Self::En] {
+            let bundle = lang.bundle();
+            if let Some(pattern) = bundle.get_message(id).and_then(|m| m.value()) {
+                let mut errors = Vec::new();
+                return bundle
+                    .format_pattern(pattern, args, &mut errors)
+                    .into_owned();
+            }
+        }
+        id.to_string()
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Speak `lang` for the rest of the run; only the first call counts
+pub fn set_lang(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+/// The language messages are shown in: the one set, or the user's locale's
+pub fn lang() -> Lang {
+    *LANG.get_or_init(Lang::from_env)
+}
+
+/// Message `id` in the current language, with `args` filled in
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    lang().message(id, args)
+}
+
+/// A message in the current language: `tr!("saved-to", path = p)` fills
+/// the message's `$path` with `p`
+#[macro_export]
+macro_rules! tr {
+    ($id:expr) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = $crate::i18n::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ids of the messages in a locale, in order
+    fn ids(source: &str) -> Vec<&str> {
+        assert!(FluentResource::try_new(source.to_string()).is_ok());
+        source
+            .lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_alphabetic()))
+            .filter_map(|line| Some(line.split_once(" =")?.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_locales_translate_every_message() {
+        let english = ids(Lang::En.source());
+        assert!(!english.is_empty());
+        assert_eq!(ids(Lang::Ko.source()), english);
+
+        let mut args = FluentArgs::new();
+        args.set("count", 1);
+        assert_eq!(
+            Lang::En.message("confirm-download", Some(&args)),
+            "Download this episode?"
+        );
+        args.set("count", 3);
+        assert_eq!(
+            Lang::En.message("confirm-download", Some(&args)),
+            "Download these 3 episodes?"
+        );
+        assert_eq!(Lang::Ko.message("no-such-message", None), "no-such-message");
+    }
+
+    #[test]
+    fn test_from_locale() {
+        assert_eq!(Lang::from_locale("ko_KR.UTF-8"), Some(Lang::Ko));
+        assert_eq!(Lang::from_locale("en-US"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("C.UTF-8"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("de_DE"), None);
+    }
+}
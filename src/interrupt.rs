@@ -0,0 +1,41 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static NOTIFY: Notify = Notify::const_new();
+
+/// Ask work in progress to stop: downloads write out the chunk at hand and
+/// keep their `.part` file to resume from, and retries stop waiting
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    NOTIFY.notify_waiters();
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Resolve once [`interrupt`] has been called
+pub async fn interrupted() {
+    loop {
+        // Registered before the check so an interrupt in between isn't missed
+        let notified = NOTIFY.notified();
+        if is_interrupted() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Work stopped because of [`interrupt`] (e.g. Ctrl+C)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
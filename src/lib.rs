@@ -0,0 +1,79 @@
+//! Podcast Downloader library
+//!
+//! Fetches podcast episodes from RSS feeds and downloads their audio files.
+//! The `pdl` binary is a thin interactive CLI over this crate.
+//!
+//! ```no_run
+//! use indicatif::ProgressBar;
+//! use pdl::PodcastClient;
+//! use std::path::Path;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let client = PodcastClient::new();
+//! let episodes = client.fetch_episodes("https://example.com/feed.rss", 5).await?;
+//! if let Some(episode) = episodes.first() {
+//!     let pb = ProgressBar::hidden();
+//!     client.download_episode(episode, Path::new("downloads"), &pb).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod auth;
+pub mod backend;
+pub mod cache;
+pub mod changes;
+pub mod chapters;
+pub mod checksum;
+pub mod client;
+pub mod config;
+pub mod credentials;
+pub mod dedupe;
+pub mod discover;
+pub mod download;
+pub mod enclosure;
+pub mod error;
+pub mod extras;
+pub mod feed;
+pub mod gpodder;
+pub mod history;
+pub mod hook;
+pub mod i18n;
+pub mod interrupt;
+pub mod library;
+pub mod listing;
+pub mod localfeed;
+pub mod metrics;
+mod mp4;
+pub mod naming;
+pub mod notes;
+pub mod opml;
+pub mod player;
+pub mod playlist;
+pub mod prune;
+pub mod queue;
+pub mod relay;
+pub mod resolve;
+pub mod retry;
+pub mod schedule;
+pub mod search;
+pub mod select;
+pub mod server;
+pub mod site;
+pub mod snapshot;
+pub mod sniff;
+pub mod space;
+pub mod stats;
+pub mod subscriptions;
+pub mod tag;
+pub mod throttle;
+pub mod torrent;
+pub mod transcode;
+pub mod transcribe;
+pub mod upload;
+pub mod webhook;
+
+pub use client::PodcastClient;
+pub use download::{DownloadOutcome, download_episode};
+pub use feed::{Episode, Feed, fetch_episodes};
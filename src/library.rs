@@ -0,0 +1,560 @@
+use crate::feed::Episode;
+use crate::history::add_columns;
+use crate::tag::plain_text;
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{Connection, params, params_from_iter};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Columns added to `episodes` since it was first created, with their types
+const EPISODE_COLUMNS: [(&str, &str); 1] = [("played_at", "TEXT")];
+
+/// Start of the statements marking episodes played (`?1` true) or unplayed
+const SET_PLAYED: &str =
+    "UPDATE episodes SET played_at = CASE WHEN ?1 THEN COALESCE(played_at, datetime('now')) END";
+
+/// Where an episode in the library stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum EpisodeState {
+    /// Seen in a feed, not downloaded
+    Available,
+    Downloaded,
+    /// The last download attempt failed
+    Failed,
+}
+
+impl EpisodeState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Available => "available",
+            Self::Downloaded => "downloaded",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "downloaded" => Self::Downloaded,
+            "failed" => Self::Failed,
+            _ => Self::Available,
+        }
+    }
+}
+
+/// An episode in the library, as `pdl query` prints it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LibraryEpisode {
+    pub feed_url: String,
+    pub show: Option<String>,
+    pub title: String,
+    pub guid: Option<String>,
+    pub url: String,
+    /// RFC 3339
+    pub published: Option<String>,
+    /// Seconds
+    pub duration: Option<u64>,
+    /// Show notes as plain text
+    pub description: Option<String>,
+    /// Where the episode was last downloaded to
+    pub path: Option<PathBuf>,
+    pub state: EpisodeState,
+    /// When it was marked played (UTC, `YYYY-MM-DD HH:MM:SS`), if it was
+    pub played_at: Option<String>,
+    /// The part of the title or show notes that matched a text search
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+/// Episodes the library doesn't know the show of are listed under this
+pub const UNKNOWN_SHOW: &str = "Unknown show";
+
+/// A show's downloaded episodes whose files are still on disk
+#[derive(Debug, Clone)]
+pub struct ShowFiles<'a> {
+    pub show: &'a str,
+    pub files: Vec<(&'a LibraryEpisode, &'a Path)>,
+}
+
+/// Group the `episodes` whose file is still there by show, keeping their
+/// order within each show; shows are sorted by name
+pub fn on_disk_by_show(episodes: &[LibraryEpisode]) -> Vec<ShowFiles<'_>> {
+    let mut shows: Vec<ShowFiles> = Vec::new();
+    for episode in episodes {
+        let Some(path) = episode.path.as_deref().filter(|p| p.exists()) else {
+            continue;
+        };
+        let show = episode.show.as_deref().unwrap_or(UNKNOWN_SHOW);
+        match shows.iter_mut().find(|s| s.show == show) {
+            Some(s) => s.files.push((episode, path)),
+            None => shows.push(ShowFiles {
+                show,
+                files: vec![(episode, path)],
+            }),
+        }
+    }
+    shows.sort_by_key(|s| s.show.to_lowercase());
+    shows
+}
+
+/// Conditions for `pdl query`; every one that is set must hold
+#[derive(Debug, Clone, Default)]
+pub struct LibraryQuery {
+    /// Words that must all appear in the title or show notes
+    pub text: Option<String>,
+    /// Show title (case-insensitive substring)
+    pub show: Option<String>,
+    pub state: Option<EpisodeState>,
+    /// Published on or after
+    pub since: Option<NaiveDate>,
+    /// Published on or before
+    pub until: Option<NaiveDate>,
+    /// Only played (`true`) or unplayed (`false`) episodes
+    pub played: Option<bool>,
+    pub limit: Option<usize>,
+}
+
+/// The result of a raw SQL query
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+}
+
+/// Create the library tables, filling them from the download history the
+/// first time
+pub(crate) fn init(conn: &Connection) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'episodes')",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS episodes (
+            id INTEGER PRIMARY KEY,
+            feed_url TEXT NOT NULL,
+            key TEXT NOT NULL,
+            show TEXT,
+            title TEXT NOT NULL,
+            guid TEXT,
+            url TEXT NOT NULL,
+            published TEXT,
+            duration INTEGER,
+            description TEXT,
+            path TEXT,
+            state TEXT NOT NULL DEFAULT 'available',
+            updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+            played_at TEXT,
+            UNIQUE (feed_url, key)
+        );
+        CREATE VIRTUAL TABLE IF NOT EXISTS episodes_fts USING fts5(
+            title, description, content = 'episodes', content_rowid = 'id'
+        );
+        CREATE TRIGGER IF NOT EXISTS episodes_fts_insert AFTER INSERT ON episodes BEGIN
+            INSERT INTO episodes_fts (rowid, title, description)
+            VALUES (new.id, new.title, new.description);
+        END;
+        CREATE TRIGGER IF NOT EXISTS episodes_fts_delete AFTER DELETE ON episodes BEGIN
+            INSERT INTO episodes_fts (episodes_fts, rowid, title, description)
+            VALUES ('delete', old.id, old.title, old.description);
+        END;
+        CREATE TRIGGER IF NOT EXISTS episodes_fts_update AFTER UPDATE OF title, description ON episodes BEGIN
+            INSERT INTO episodes_fts (episodes_fts, rowid, title, description)
+            VALUES ('delete', old.id, old.title, old.description);
+            INSERT INTO episodes_fts (rowid, title, description)
+            VALUES (new.id, new.title, new.description);
+        END;",
+    )?;
+    add_columns(conn, "episodes", &EPISODE_COLUMNS)?;
+    if !exists {
+        // Newest first, so each episode keeps the file it was last saved to
+        conn.execute(
+            "INSERT OR IGNORE INTO episodes
+                (feed_url, key, show, title, guid, url, published, duration, path, state)
+             SELECT feed_url, COALESCE(guid, url), show, title, guid, url, published, duration,
+                path, 'downloaded'
+             FROM downloads ORDER BY id DESC",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Add `episode` to the library, or refresh what it knows of it. `state`
+/// and `path` are only changed when given, and a download that failed
+/// doesn't hide an earlier one that succeeded.
+pub(crate) fn upsert(
+    conn: &Connection,
+    feed_url: &str,
+    show: &str,
+    episode: &Episode,
+    state: Option<EpisodeState>,
+    path: Option<&Path>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO episodes
+            (feed_url, key, show, title, guid, url, published, duration, description, path, state)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, COALESCE(?11, 'available'))
+         ON CONFLICT (feed_url, key) DO UPDATE SET
+            show = excluded.show,
+            title = excluded.title,
+            guid = excluded.guid,
+            url = excluded.url,
+            published = COALESCE(excluded.published, published),
+            duration = COALESCE(excluded.duration, duration),
+            description = COALESCE(excluded.description, description),
+            path = COALESCE(excluded.path, path),
+            state = CASE
+                WHEN ?11 IS NULL OR (?11 = 'failed' AND state = 'downloaded') THEN state
+                ELSE ?11
+            END,
+            updated_at = datetime('now')",
+        params![
+            feed_url,
+            episode.key(),
+            show,
+            episode.title,
+            episode.guid,
+            episode.url,
+            episode.published.map(|d| d.to_rfc3339()),
+            episode.duration.map(|d| d.as_secs() as i64),
+            episode.description.as_deref().map(plain_text),
+            path.map(|p| p.to_string_lossy().into_owned()),
+            state.map(EpisodeState::as_str),
+        ],
+    )?;
+    Ok(())
+}
+
+/// The episodes matching `query`, newest first (best match first for a
+/// text search)
+pub(crate) fn search(conn: &Connection, query: &LibraryQuery) -> Result<Vec<LibraryEpisode>> {
+    let mut conditions = Vec::new();
+    let mut values: Vec<Value> = Vec::new();
+    let mut condition = |sql: &str, value: Value| {
+        values.push(value);
+        conditions.push(sql.replace('?', &format!("?{}", values.len())));
+    };
+    let text = query
+        .text
+        .as_deref()
+        .map(fts_terms)
+        .filter(|t| !t.is_empty());
+    if let Some(text) = &text {
+        condition("episodes_fts MATCH ?", Value::Text(text.clone()));
+    }
+    if let Some(show) = &query.show {
+        condition(
+            "instr(lower(e.show), lower(?)) > 0",
+            Value::Text(show.clone()),
+        );
+    }
+    if let Some(state) = query.state {
+        condition("e.state = ?", Value::Text(state.as_str().to_string()));
+    }
+    if let Some(since) = query.since {
+        condition(
+            "substr(e.published, 1, 10) >= ?",
+            Value::Text(since.to_string()),
+        );
+    }
+    if let Some(until) = query.until {
+        condition(
+            "substr(e.published, 1, 10) <= ?",
+            Value::Text(until.to_string()),
+        );
+    }
+    match query.played {
+        Some(true) => conditions.push("e.played_at IS NOT NULL".to_string()),
+        Some(false) => conditions.push("e.played_at IS NULL".to_string()),
+        None => {}
+    }
+
+    let (snippet, from, order) = if text.is_some() {
+        (
+            "snippet(episodes_fts, -1, '[', ']', '…', 12)",
+            "episodes_fts JOIN episodes e ON e.id = episodes_fts.rowid",
+            "rank",
+        )
+    } else {
+        ("NULL", "episodes e", "e.published DESC, e.id DESC")
+    };
+    let mut sql = format!("SELECT {}, {} FROM {}", SELECT_COLUMNS, snippet, from);
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY ");
+    sql.push_str(order);
+    if let Some(limit) = query.limit {
+        sql.push_str(&format!(" LIMIT {}", limit));
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let episodes = stmt
+        .query_map(params_from_iter(values), |row| {
+            Ok(LibraryEpisode {
+                snippet: row.get(11)?,
+                ..episode_from_row(row)?
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .context("Failed to query the library")?;
+    Ok(episodes)
+}
+
+/// Episodes marked played after `since` (UTC, `YYYY-MM-DD HH:MM:SS`), or
+/// ever
+pub(crate) fn played_since(
+    conn: &Connection,
+    since: Option<&str>,
+) -> rusqlite::Result<Vec<LibraryEpisode>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM episodes e
+         WHERE e.played_at IS NOT NULL AND (?1 IS NULL OR e.played_at > ?1)
+         ORDER BY e.played_at",
+        SELECT_COLUMNS
+    ))?;
+    stmt.query_map([since], episode_from_row)?.collect()
+}
+
+/// Mark the episode of the feed at `feed_url` with enclosure `url` or
+/// GUID `guid` played or unplayed, returning whether the library knows it
+pub(crate) fn set_played_by_url(
+    conn: &Connection,
+    feed_url: &str,
+    url: &str,
+    guid: Option<&str>,
+    played: bool,
+) -> rusqlite::Result<bool> {
+    let changed = conn.execute(
+        &format!(
+            "{} WHERE feed_url = ?2 AND (url = ?3 OR (guid IS NOT NULL AND guid = ?4))",
+            SET_PLAYED
+        ),
+        params![played, feed_url, url, guid],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Mark the episodes whose GUID or file is `episode`, or whose title
+/// contains it, of shows whose title contains `show`, as played or
+/// unplayed, returning their titles. Either may be left out, not both.
+pub(crate) fn set_played(
+    conn: &Connection,
+    episode: Option<&str>,
+    show: Option<&str>,
+    played: bool,
+) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} WHERE (?2 IS NULL OR guid = ?2 OR path = ?2 OR instr(lower(title), lower(?2)) > 0)
+            AND (?3 IS NULL OR instr(lower(show), lower(?3)) > 0)
+            AND (?2 IS NOT NULL OR ?3 IS NOT NULL)
+         RETURNING title",
+        SET_PLAYED
+    ))?;
+    stmt.query_map(params![played, episode, show], |row| row.get(0))?
+        .collect()
+}
+
+/// Mark the episode saved as `path` played, returning whether the library
+/// knows it
+pub(crate) fn set_file_played(conn: &Connection, path: &Path) -> rusqlite::Result<bool> {
+    let changed = conn.execute(
+        &format!("{} WHERE path = ?2", SET_PLAYED),
+        params![true, path.to_string_lossy()],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Mark the episode with `key` ([`Episode::key`]) of the feed at `feed_url`
+/// played
+pub(crate) fn set_episode_played(
+    conn: &Connection,
+    feed_url: &str,
+    key: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        &format!("{} WHERE feed_url = ?2 AND key = ?3", SET_PLAYED),
+        params![true, feed_url, key],
+    )?;
+    Ok(())
+}
+
+/// The keys ([`Episode::key`]) of the played episodes of the feed at
+/// `feed_url`
+pub(crate) fn played_keys(conn: &Connection, feed_url: &str) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt =
+        conn.prepare("SELECT key FROM episodes WHERE feed_url = ?1 AND played_at IS NOT NULL")?;
+    stmt.query_map([feed_url], |row| row.get(0))?.collect()
+}
+
+/// Run `sql`, which may only read
+pub(crate) fn run_sql(conn: &Connection, sql: &str) -> Result<QueryResult> {
+    let mut stmt = conn.prepare(sql).context("Invalid SQL")?;
+    if !stmt.readonly() {
+        bail!("Only queries that read (SELECT, WITH ...) can be run");
+    }
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = Vec::new();
+    let mut results = stmt.query([]).context("Failed to run query")?;
+    while let Some(row) = results.next().context("Failed to run query")? {
+        let values = (0..columns.len())
+            .map(|i| row.get_ref(i).map(json_value))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.push(values);
+    }
+    Ok(QueryResult { columns, rows })
+}
+
+/// Columns of `episodes e` read by [`episode_from_row`]
+const SELECT_COLUMNS: &str = "e.feed_url, e.show, e.title, e.guid, e.url, e.published, e.duration,
+    e.description, e.path, e.state, e.played_at";
+
+fn episode_from_row(row: &rusqlite::Row) -> rusqlite::Result<LibraryEpisode> {
+    Ok(LibraryEpisode {
+        feed_url: row.get(0)?,
+        show: row.get(1)?,
+        title: row.get(2)?,
+        guid: row.get(3)?,
+        url: row.get(4)?,
+        published: row.get(5)?,
+        duration: row.get::<_, Option<i64>>(6)?.map(|d| d as u64),
+        description: row.get(7)?,
+        path: row.get::<_, Option<String>>(8)?.map(PathBuf::from),
+        state: EpisodeState::parse(&row.get::<_, String>(9)?),
+        played_at: row.get(10)?,
+        snippet: None,
+    })
+}
+
+fn json_value(value: ValueRef) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => f.into(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into(),
+        ValueRef::Blob(blob) => format!("<{} bytes>", blob.len()).into(),
+    }
+}
+
+/// An FTS5 query matching every word of `text`, taken literally: `don't`
+/// or `C++` is a word to look for, not query syntax
+fn fts_terms(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episode(guid: &str, title: &str, notes: &str) -> Episode {
+        Episode {
+            title: title.to_string(),
+            url: format!("https://example.com/{}.mp3", guid),
+            guid: Some(guid.to_string()),
+            description: Some(notes.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_search_library() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE downloads (id INTEGER PRIMARY KEY, feed_url TEXT, guid TEXT,
+                url TEXT, title TEXT, path TEXT, show TEXT, published TEXT, duration INTEGER)",
+        )
+        .unwrap();
+        init(&conn).unwrap();
+
+        let rust = episode(
+            "1",
+            "Rust in production",
+            "<p>We talk about <b>async</b> I/O</p>",
+        );
+        let go = episode("2", "Go generics", "Type parameters, finally");
+        for ep in [&rust, &go] {
+            upsert(&conn, "feed", "Show", ep, None, None).unwrap();
+        }
+        upsert(
+            &conn,
+            "feed",
+            "Show",
+            &rust,
+            Some(EpisodeState::Downloaded),
+            Some(Path::new("rust.mp3")),
+        )
+        .unwrap();
+        // A later failure doesn't undo the download
+        upsert(
+            &conn,
+            "feed",
+            "Show",
+            &rust,
+            Some(EpisodeState::Failed),
+            None,
+        )
+        .unwrap();
+
+        let found = |query: LibraryQuery| -> Vec<String> {
+            search(&conn, &query)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.title)
+                .collect()
+        };
+        let text = |text: &str| LibraryQuery {
+            text: Some(text.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(found(LibraryQuery::default()).len(), 2);
+        assert_eq!(found(text("async")), ["Rust in production"]);
+        assert_eq!(found(text("I/O")), ["Rust in production"]);
+        assert_eq!(found(text("PARAMETERS type")), ["Go generics"]);
+        assert!(found(text("kotlin")).is_empty());
+        let downloaded = LibraryQuery {
+            state: Some(EpisodeState::Downloaded),
+            ..Default::default()
+        };
+        assert_eq!(found(downloaded), ["Rust in production"]);
+
+        let result = run_sql(
+            &conn,
+            "SELECT title, path FROM episodes WHERE path IS NOT NULL",
+        )
+        .unwrap();
+        assert_eq!(result.columns, ["title", "path"]);
+        assert_eq!(
+            result.rows,
+            [vec![
+                serde_json::Value::from("Rust in production"),
+                serde_json::Value::from("rust.mp3")
+            ]]
+        );
+        assert!(run_sql(&conn, "DELETE FROM episodes").is_err());
+
+        assert_eq!(
+            set_played(&conn, Some("generics"), None, true).unwrap(),
+            ["Go generics"]
+        );
+        assert!(set_file_played(&conn, Path::new("rust.mp3")).unwrap());
+        assert!(set_played(&conn, None, None, true).unwrap().is_empty());
+        assert_eq!(played_keys(&conn, "feed").unwrap().len(), 2);
+        assert_eq!(
+            set_played(&conn, None, Some("show"), false).unwrap().len(),
+            2
+        );
+        let unplayed = LibraryQuery {
+            played: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(found(unplayed).len(), 2);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::feed::Episode;
+use crate::tag::plain_text;
+use anyhow::{Context, Result};
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Output format of `pdl list`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ListFormat {
+    /// Aligned columns for reading in a terminal
+    #[default]
+    Table,
+    /// An array of objects, one per episode
+    Json,
+    /// A header row, then one row per episode
+    Csv,
+    /// One tab-separated row per episode with no header, as `--porcelain`
+    /// prints: title, guid, published, duration, size and url
+    Tsv,
+}
+
+/// One episode as printed by `pdl list`
+#[derive(Debug, Serialize)]
+struct Row<'a> {
+    title: &'a str,
+    guid: Option<&'a str>,
+    /// RFC 3339
+    published: Option<String>,
+    /// Seconds
+    duration: Option<u64>,
+    /// Bytes
+    size: Option<u64>,
+    url: &'a str,
+}
+
+impl Row<'_> {
+    /// The columns as text, empty where unknown
+    fn fields(&self) -> [String; 6] {
+        [
+            self.title.to_string(),
+            self.guid.unwrap_or_default().to_string(),
+            self.published.clone().unwrap_or_default(),
+            self.duration.map(|d| d.to_string()).unwrap_or_default(),
+            self.size.map(|s| s.to_string()).unwrap_or_default(),
+            self.url.to_string(),
+        ]
+    }
+}
+
+impl<'a> From<&'a Episode> for Row<'a> {
+    fn from(episode: &'a Episode) -> Self {
+        Self {
+            title: &episode.title,
+            guid: episode.guid.as_deref(),
+            published: episode.published.map(|d| d.to_rfc3339()),
+            duration: episode.duration.map(|d| d.as_secs()),
+            size: episode.size,
+            url: &episode.url,
+        }
+    }
+}
+
+/// `episodes` in `format`, ending with a newline
+pub fn render(episodes: &[Episode], format: ListFormat) -> Result<String> {
+    let rows = || episodes.iter().map(Row::from).collect::<Vec<_>>();
+    match format {
+        ListFormat::Table => Ok(table(episodes)),
+        ListFormat::Json => {
+            let mut json =
+                serde_json::to_string_pretty(&rows()).context("Failed to serialize episodes")?;
+            json.push('\n');
+            Ok(json)
+        }
+        ListFormat::Csv => Ok(csv(&rows())),
+        ListFormat::Tsv => Ok(rows()
+            .iter()
+            .map(|row| tsv_row(&row.fields()) + "\n")
+            .collect()),
+    }
+}
+
+/// `fields` joined by tabs, with any tabs and line breaks inside them
+/// turned into spaces so each row stays one line
+pub fn tsv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| field.as_ref().replace(['\t', '\n', '\r'], " "))
+        .collect();
+    fields.join("\t")
+}
+
+fn csv(rows: &[Row]) -> String {
+    let mut out = String::from("title,guid,published,duration,size,url\n");
+    for row in rows {
+        let fields: Vec<String> = row.fields().iter().map(|f| csv_field(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn table(episodes: &[Episode]) -> String {
+    let mut out = format!("{:<10}  {:>8}  {:>10}  TITLE\n", "DATE", "LENGTH", "SIZE");
+    for episode in episodes {
+        let date = episode
+            .published
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let duration = episode.duration.map(format_duration).unwrap_or_default();
+        let size = episode
+            .size
+            .map(|s| HumanBytes(s).to_string())
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "{:<10}  {:>8}  {:>10}  {}\n{:34}{}\n",
+            date, duration, size, episode.title, "", episode.url
+        ));
+    }
+    out
+}
+
+/// Characters of show notes shown by [`details`]
+const SNIPPET_CHARS: usize = 300;
+
+/// One line for an episode: title, then date, duration and size where known
+pub fn summary(episode: &Episode) -> String {
+    let facts: Vec<String> = [
+        episode.published.map(|d| d.format("%Y-%m-%d").to_string()),
+        episode.duration.map(format_duration),
+        episode.size.map(|s| HumanBytes(s).to_string()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if facts.is_empty() {
+        episode.title.clone()
+    } else {
+        format!("{} ({})", episode.title, facts.join(", "))
+    }
+}
+
+/// Several lines describing an episode before it's downloaded: title, date,
+/// duration, size and the start of the show notes
+pub fn details(episode: &Episode) -> String {
+    let mut out = format!("{}\n", episode.title);
+    if let Some(date) = episode.published {
+        out.push_str(&format!("  Published: {}\n", date.format("%Y-%m-%d")));
+    }
+    if let Some(duration) = episode.duration {
+        out.push_str(&format!("  Duration:  {}\n", format_duration(duration)));
+    }
+    if let Some(size) = episode.size {
+        out.push_str(&format!("  Size:      {}\n", HumanBytes(size)));
+    }
+    if let Some(description) = &episode.description {
+        let text = plain_text(description);
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !text.is_empty() {
+            out.push_str(&format!("\n  {}\n", snippet(&text, SNIPPET_CHARS)));
+        }
+    }
+    out
+}
+
+/// The first `max` characters of `text`, marked with `…` if cut short
+fn snippet(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+/// `H:MM:SS`, or `M:SS` under an hour
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn episodes() -> Vec<Episode> {
+        vec![
+            Episode {
+                title: "Hello, \"World\"".to_string(),
+                url: "https://example.com/1.mp3".to_string(),
+                guid: Some("ep-1".to_string()),
+                published: DateTime::parse_from_rfc3339("2024-01-02T10:00:00Z").ok(),
+                duration: Some(Duration::from_secs(3723)),
+                size: Some(2048),
+                ..Default::default()
+            },
+            Episode {
+                title: "Bare".to_string(),
+                url: "https://example.com/2.mp3".to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_csv_quotes_fields() {
+        let csv = render(&episodes(), ListFormat::Csv).unwrap();
+        assert_eq!(
+            csv,
+            "title,guid,published,duration,size,url\n\
+             \"Hello, \"\"World\"\"\",ep-1,2024-01-02T10:00:00+00:00,3723,2048,https://example.com/1.mp3\n\
+             Bare,,,,,https://example.com/2.mp3\n"
+        );
+    }
+
+    #[test]
+    fn test_render_tsv_keeps_rows_on_one_line() {
+        let mut episodes = episodes();
+        episodes[1].title = "Two\tlines\nhere".to_string();
+        assert_eq!(
+            render(&episodes, ListFormat::Tsv).unwrap(),
+            "Hello, \"World\"\tep-1\t2024-01-02T10:00:00+00:00\t3723\t2048\thttps://example.com/1.mp3\n\
+             Two lines here\t\t\t\t\thttps://example.com/2.mp3\n"
+        );
+    }
+
+    #[test]
+    fn test_render_json_uses_null_for_missing_values() {
+        let json = render(&episodes(), ListFormat::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value[0]["duration"], 3723);
+        assert_eq!(value[0]["published"], "2024-01-02T10:00:00+00:00");
+        assert_eq!(value[1]["guid"], serde_json::Value::Null);
+        assert_eq!(value[1]["url"], "https://example.com/2.mp3");
+    }
+
+    #[test]
+    fn test_details_and_summary() {
+        let mut episode = episodes().remove(0);
+        episode.description = Some("<p>Guest &amp; host\n talk.</p>".to_string());
+
+        assert_eq!(
+            details(&episode),
+            "Hello, \"World\"\n  Published: 2024-01-02\n  Duration:  1:02:03\n  Size:      2.00 KiB\n\n  Guest & host talk.\n"
+        );
+        assert_eq!(
+            summary(&episode),
+            "Hello, \"World\" (2024-01-02, 1:02:03, 2.00 KiB)"
+        );
+        assert_eq!(summary(&episodes()[1]), "Bare");
+        assert_eq!(snippet("abcdef", 3), "abc…");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1:02:03");
+        assert_eq!(format_duration(Duration::from_secs(59)), "0:59");
+    }
+}
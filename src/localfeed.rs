@@ -0,0 +1,128 @@
+use crate::download::mime_from_extension;
+use crate::library::ShowFiles;
+use crate::server::encode_segment;
+use chrono::DateTime;
+use quick_xml::escape::escape;
+use std::fs;
+use std::path::Path;
+
+/// Path of a show's feed under its URL on the server
+pub const FEED_FILE: &str = "feed.rss";
+
+/// An RSS feed of a show's downloaded episodes, newest first as given, for
+/// `pdl serve-feed`. `base` is the show's URL on the server: each
+/// enclosure is its file's name under it, as is `cover`, the artwork's.
+pub fn render(show: &ShowFiles, base: &str, cover: Option<&Path>) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\">\n\
+         <channel>\n\
+         <title>{}</title>\n\
+         <link>{}/{}</link>\n\
+         <description>{} episodes archived by pdl</description>\n",
+        escape(show.show),
+        escape(base),
+        FEED_FILE,
+        escape(show.show)
+    );
+    if let Some(cover) = cover {
+        xml.push_str(&format!(
+            "<itunes:image href=\"{}\"/>\n",
+            escape(file_url(base, cover))
+        ));
+    }
+    for (episode, path) in &show.files {
+        xml.push_str("<item>\n");
+        xml.push_str(&format!("<title>{}</title>\n", escape(&episode.title)));
+        xml.push_str(&format!(
+            "<guid isPermaLink=\"false\">{}</guid>\n",
+            escape(episode.guid.as_deref().unwrap_or(&episode.url))
+        ));
+        if let Some(published) = episode
+            .published
+            .as_deref()
+            .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+        {
+            xml.push_str(&format!("<pubDate>{}</pubDate>\n", published.to_rfc2822()));
+        }
+        if let Some(notes) = episode.description.as_deref().filter(|n| !n.is_empty()) {
+            xml.push_str(&format!("<description>{}</description>\n", escape(notes)));
+        }
+        if let Some(duration) = episode.duration {
+            xml.push_str(&format!(
+                "<itunes:duration>{}</itunes:duration>\n",
+                duration
+            ));
+        }
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        xml.push_str(&format!(
+            "<enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n",
+            escape(file_url(base, path)),
+            fs::metadata(path).map_or(0, |m| m.len()),
+            mime_from_extension(&extension)
+        ));
+        xml.push_str("</item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+/// URL of the file at `path` under `base`, by its name
+pub fn file_url(base: &str, path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    format!("{}/{}", base, encode_segment(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::episodes_from_channel;
+    use crate::library::{EpisodeState, LibraryEpisode};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn test_render_parses_as_feed() {
+        let episode = LibraryEpisode {
+            feed_url: "https://example.com/feed.rss".to_string(),
+            show: Some("Tom & Jerry".to_string()),
+            title: "Ep <1>".to_string(),
+            guid: Some("ep-1".to_string()),
+            url: "https://example.com/1.mp3".to_string(),
+            published: Some("2024-01-02T10:00:00+00:00".to_string()),
+            duration: Some(3723),
+            description: Some("Notes & links".to_string()),
+            path: Some(PathBuf::from("/nonexistent/Ep 1.mp3")),
+            state: EpisodeState::Downloaded,
+            played_at: None,
+            snippet: None,
+        };
+        let show = ShowFiles {
+            show: "Tom & Jerry",
+            files: vec![(&episode, Path::new("/nonexistent/Ep 1.mp3"))],
+        };
+        let xml = render(&show, "http://pi:8081/shows/Tom%20%26%20Jerry", None);
+        let channel = rss::Channel::read_from(xml.as_bytes()).unwrap();
+        assert_eq!(channel.title, "Tom & Jerry");
+        let episodes = episodes_from_channel(&channel, usize::MAX);
+        assert_eq!(episodes.len(), 1);
+        assert_eq!(episodes[0].title, "Ep <1>");
+        assert_eq!(episodes[0].guid.as_deref(), Some("ep-1"));
+        assert_eq!(
+            episodes[0].url,
+            "http://pi:8081/shows/Tom%20%26%20Jerry/Ep%201.mp3"
+        );
+        assert_eq!(episodes[0].mime_type.as_deref(), Some("audio/mpeg"));
+        assert_eq!(episodes[0].duration, Some(Duration::from_secs(3723)));
+        assert_eq!(
+            episodes[0].published.map(|d| d.to_rfc3339()).as_deref(),
+            Some("2024-01-02T10:00:00+00:00")
+        );
+    }
+}
@@ -1,12 +1,31 @@
-use anyhow::{Context, Result};
-use clap::Parser;
-use indicatif::{ProgressBar, ProgressStyle};
-use inquire::Select;
-use reqwest::blocking::Client;
-use rss::Channel;
-use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
+mod cli;
+
+use anyhow::Result;
+use clap::parser::ValueSource;
+use clap::{Args as _, CommandFactory, FromArgMatches, Parser, Subcommand};
+use cli::download::{DownloadArgs, run_download};
+use cli::logging::LogArgs;
+use cli::{
+    FilterArgs, NetworkArgs, archive, auth, config, dedupe, feed, feeds, history, index, list, man,
+    mark, notify, output, play, prune, query, queue, search, serve, serve_feed, stats, stream,
+    sync, transcribe, tui, update, watch,
+};
+use inquire::InquireError;
+use pdl::changes::RefreshChanged;
+use pdl::config::Config;
+use pdl::error::ErrorKind;
+use pdl::gpodder::SyncApi;
+use pdl::i18n::{self, Lang};
+use pdl::interrupt;
+use pdl::listing::ListFormat;
+use pdl::search::SearchBackend;
+use pdl::select::Selector;
+use pdl::transcribe::TranscriptFormat;
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
 
 /// Podcast Downloader - Download podcast episodes from RSS feeds
 #[derive(Parser, Debug)]
@@ -16,293 +35,695 @@ struct Args {
     #[arg(short = 'v', long = "version", action = clap::ArgAction::Version)]
     version: (),
 
-    /// Number of episodes to display
-    #[arg(short, long, default_value_t = 10)]
-    n: usize,
-}
+    #[command(flatten)]
+    download: DownloadArgs,
 
-struct Episode {
-    title: String,
-    url: String,
-}
+    #[command(flatten)]
+    network: NetworkArgs,
+
+    #[command(flatten)]
+    log: LogArgs,
+
+    /// Print results and errors as JSON on stdout (messages go to stderr)
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Print results as tab-separated rows with no header, a format kept stable for scripts (messages go to stderr)
+    #[arg(long, global = true, conflicts_with = "json")]
+    porcelain: bool,
+
+    /// Show a desktop notification when downloads finish or fail
+    #[arg(long, global = true)]
+    notify: bool,
+
+    /// Language of messages and prompts [default: from LC_ALL, LC_MESSAGES or LANG]
+    #[arg(long, global = true, value_enum, value_name = "LANG")]
+    lang: Option<Lang>,
 
-struct PodcastFeed {
-    name: &'static str,
-    url: &'static str,
+    /// Use the settings of this profile (`[profiles.NAME]` in the config file) [env: PDL_PROFILE]
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-const FEEDS: &[PodcastFeed] = &[
-    PodcastFeed {
-        name: "Cozy Up (Doctor)",
-        url: "https://omny.fm/shows/cozy-up/playlists/doctor.rss",
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Download episodes (the default when no command is given)
+    Download(Box<DownloadArgs>),
+
+    /// Subscribe to a podcast feed
+    Subscribe {
+        /// Feed URL (RSS, Atom or JSON Feed)
+        url: String,
+
+        /// Name to list the feed under (defaults to the feed's title)
+        #[arg(long)]
+        name: Option<String>,
     },
-    PodcastFeed {
-        name: "Cozy Up (Podcast)",
-        url: "https://omny.fm/shows/cozy-up/playlists/podcast.rss",
+
+    /// Remove a subscribed feed
+    Unsubscribe {
+        /// Name of the subscription to remove
+        name: String,
     },
-];
 
-fn main() -> Result<()> {
-    // Parse CLI arguments (before banner so -v works cleanly)
-    let args = Args::parse();
+    /// List subscribed feeds
+    Feeds,
 
-    // Display banner
-    display_banner();
+    /// Print a feed's episodes (title, guid, date, duration, size, URL) without prompting
+    List {
+        /// Feed URL (defaults to `default_feed` in config)
+        #[arg(short, long)]
+        feed: Option<String>,
 
-    // Select podcast feed
-    let feed_names: Vec<&str> = FEEDS.iter().map(|f| f.name).collect();
-    let selected_feed_name = Select::new("Select a podcast feed:", feed_names)
-        .prompt()
-        .context("Failed to get feed selection")?;
+        /// Number of episodes to list [default: all]
+        #[arg(short, long)]
+        n: Option<usize>,
 
-    let selected_feed = FEEDS
-        .iter()
-        .find(|f| f.name == selected_feed_name)
-        .context("Could not find selected feed")?;
+        #[command(flatten)]
+        filter: FilterArgs,
 
-    println!("\nFetching RSS feed...\n");
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: ListFormat,
+    },
 
-    // Fetch and parse RSS feed
-    let episodes = fetch_episodes(selected_feed.url, args.n)?;
+    /// Search a podcast directory and subscribe to a result
+    Search {
+        /// Search terms
+        #[arg(required = true, num_args = 1..)]
+        terms: Vec<String>,
 
-    if episodes.is_empty() {
-        println!("No episodes found in the feed.");
-        return Ok(());
-    }
+        /// Maximum number of results
+        #[arg(short, long, default_value_t = 25)]
+        n: usize,
 
-    // Create interactive selection menu
-    let episode_titles: Vec<String> = episodes
-        .iter()
-        .enumerate()
-        .map(|(i, ep)| format!("{}. {}", i + 1, ep.title))
-        .collect();
+        /// Directory to search (defaults to `search_backend` in config, then iTunes)
+        #[arg(long, value_enum)]
+        search_backend: Option<SearchBackend>,
+    },
+
+    /// Import subscriptions from another podcast app
+    #[command(subcommand)]
+    Import(ImportCommand),
+
+    /// Export subscriptions for another podcast app
+    #[command(subcommand)]
+    Export(ExportCommand),
+
+    /// Browse subscriptions and download episodes in a full-screen terminal UI
+    Tui,
+
+    /// Check subscriptions for episodes published since the last update
+    Update {
+        /// Also download the new episodes
+        #[arg(long)]
+        download: bool,
+
+        /// What to do about downloads whose audio the publisher has replaced
+        /// since (defaults to `refresh_changed` in config, then flag)
+        #[arg(long, value_enum)]
+        refresh_changed: Option<RefreshChanged>,
+    },
 
-    let selection = Select::new("Select an episode to download:", episode_titles)
-        .prompt()
-        .context("Failed to get user selection")?;
+    /// Keep checking subscriptions and download new episodes (for running as a service)
+    Watch {
+        /// Time between checks, e.g. 30m or 6h (defaults to `watch_interval` in config, then 1h)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        interval: Option<Duration>,
 
-    // Extract index from selection
-    let selected_index = episodes
-        .iter()
-        .position(|ep| selection.contains(&ep.title))
-        .context("Could not find selected episode")?;
+        /// Check once and exit, e.g. when run from cron
+        #[arg(long)]
+        once: bool,
 
-    let selected_episode = &episodes[selected_index];
+        /// Download straight away, even outside `download_window`
+        #[arg(long)]
+        now: bool,
 
-    println!("\nDownloading: {}", selected_episode.title);
+        /// Serve Prometheus metrics at http://ADDR/metrics while watching
+        #[arg(long, value_name = "ADDR")]
+        metrics: Option<SocketAddr>,
+    },
+
+    /// Mirror a whole show: every episode with its artwork, show notes,
+    /// chapters and transcripts, plus a copy of the feed each time it changes.
+    /// Re-runs fetch only what is new and never overwrite or delete files.
+    Archive {
+        /// Feed URL (or a show's web page or podcast app link)
+        feed: String,
 
-    // Download the episode
-    download_episode(selected_episode)?;
+        /// Directory to keep the archive in [default: the show's folder in the download directory]
+        #[arg(short, long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+
+        /// Number of episodes to download in parallel [default: 1]
+        #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: Option<u16>,
+    },
+
+    /// Look at the copies of feeds kept over time (`feed_snapshots` in config, or `pdl archive`)
+    #[command(subcommand)]
+    Feed(FeedCommand),
+
+    /// Show previously downloaded episodes
+    History {
+        /// Number of entries to show
+        #[arg(short, long, default_value_t = 20)]
+        n: usize,
+    },
+
+    /// Search every episode seen in a feed by title, show notes, show, state or date, or with SQL
+    Query(query::QueryArgs),
+
+    /// Delete old downloads according to each feed's keep_latest, max_age_days and max_size_gb
+    Prune {
+        /// Only prune this subscription
+        name: Option<String>,
+
+        /// List what would be deleted without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show counts, disk usage, listening time and download trends for everything downloaded
+    Stats,
+
+    /// Write HTML pages for browsing downloads, with players and show notes
+    Index {
+        /// Where to write them [default: the download directory]
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+    },
+
+    /// Find downloads with identical files and episodes cross-posted to several shows
+    Dedupe {
+        /// Replace identical copies with hard links to one of them, freeing the space
+        #[arg(long)]
+        link: bool,
+    },
+
+    /// Mark episodes played or unplayed (`--played`/`--unplayed` filter on it)
+    Mark {
+        #[arg(value_enum)]
+        mark: mark::Mark,
+
+        /// Episodes to mark: a GUID, a downloaded file, or part of a title
+        episodes: Vec<String>,
+
+        /// Only episodes of shows whose title contains this (every one, if no episodes are given)
+        #[arg(long, value_name = "NAME")]
+        show: Option<String>,
+    },
+
+    /// Play a downloaded episode with `player` from config (or mpv, vlc, ...)
+    Play {
+        /// Number of recent downloads to choose from
+        #[arg(short, long, default_value_t = 50)]
+        n: usize,
+
+        /// Play the most recent download without asking
+        #[arg(long)]
+        latest: bool,
+    },
+
+    /// Transcribe a downloaded episode with whisper.cpp, saving SRT and text transcripts next to it
+    Transcribe {
+        /// Audio file, or the title (or part of it) or GUID of a downloaded episode
+        #[arg(required_unless_present = "latest")]
+        target: Option<String>,
+
+        /// Transcribe the most recent download
+        #[arg(long, conflicts_with = "target")]
+        latest: bool,
+
+        /// Spoken language, e.g. `en`, or `auto` to detect it
+        #[arg(short, long, default_value = "auto")]
+        language: String,
+
+        /// Transcript formats to write
+        #[arg(long, value_enum, value_delimiter = ',', default_values = ["srt", "txt"])]
+        format: Vec<TranscriptFormat>,
+
+        /// Transcribe again even if the transcripts exist
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Play an episode from a feed without saving it
+    Stream {
+        /// Feed URL (defaults to `default_feed` in config)
+        #[arg(short, long)]
+        feed: Option<String>,
+
+        /// Number of episodes to choose from
+        #[arg(short, long, default_value_t = 10)]
+        n: usize,
+
+        /// Play the newest episode
+        #[arg(long, conflicts_with = "episode")]
+        latest: bool,
+
+        /// Play the episode at this position in the list, or with this GUID
+        #[arg(long, value_name = "INDEX|GUID")]
+        episode: Option<String>,
+    },
 
-    println!("\n✓ Download complete!");
+    /// Sync subscriptions and played episodes with another podcast app
+    #[command(subcommand)]
+    Sync(SyncCommand),
 
-    Ok(())
+    /// Queue episodes to download later, and work through the queue
+    #[command(subcommand)]
+    Queue(QueueCommand),
+
+    /// Answer HTTP requests for subscriptions, episodes, downloads and
+    /// history as JSON, so dashboards and scripts can drive pdl
+    Serve {
+        /// Address to listen on; anything but localhost lets the network in
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+    },
+
+    /// Serve an RSS feed of each show's downloaded episodes, with the files,
+    /// for podcast apps on other devices to subscribe to
+    ServeFeed {
+        /// Address to listen on; 0.0.0.0 lets other devices on the network in
+        #[arg(long, default_value = "0.0.0.0:8081")]
+        listen: SocketAddr,
+
+        /// URL the feeds link to their files by, e.g. `http://nas.local:8081`
+        /// [default: the address listened on]
+        #[arg(long, value_name = "URL")]
+        base_url: Option<String>,
+    },
+
+    /// Keep logins for private feeds in the system keyring
+    #[command(subcommand)]
+    Auth(AuthCommand),
+
+    /// Manage the config file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+
+    /// Print pdl's man page (roff), or write pages for every command to a folder
+    Man {
+        /// Write `pdl.1` and a page per subcommand here instead
+        #[arg(short, long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+    },
 }
 
-fn display_banner() {
-    println!(
-        r#"
-╔═══════════════════════════════════════════════════════╗
-║                                                       ║
-║   ██████╗  ██████╗ ██████╗  ██████╗ █████╗ ███████╗ ║
-║   ██╔══██╗██╔═══██╗██╔══██╗██╔════╝██╔══██╗██╔════╝ ║
-║   ██████╔╝██║   ██║██║  ██║██║     ███████║███████╗ ║
-║   ██╔═══╝ ██║   ██║██║  ██║██║     ██╔══██║╚════██║ ║
-║   ██║     ╚██████╔╝██████╔╝╚██████╗██║  ██║███████║ ║
-║   ╚═╝      ╚═════╝ ╚═════╝  ╚═════╝╚═╝  ╚═╝╚══════╝ ║
-║                                                       ║
-║              Podcast Downloader v0.1.0                ║
-║                                                       ║
-╚═══════════════════════════════════════════════════════╝
-"#
-    );
+#[derive(Subcommand, Debug)]
+enum SyncCommand {
+    /// Sync with a gpodder.net account (or Nextcloud's GPodder Sync app), e.g. to match a phone
+    Gpodder {
+        /// Server to sync with [default: `gpodder_server` from config, else https://gpodder.net]
+        #[arg(long, value_name = "URL")]
+        server: Option<String>,
+
+        /// API the server speaks [default: `gpodder_api` from config, else gpodder]
+        #[arg(long, value_enum)]
+        api: Option<SyncApi>,
+
+        /// Device to sync as on gpodder.net [default: `gpodder_device` from config, else pdl]
+        #[arg(long, value_name = "NAME")]
+        device: Option<String>,
+    },
 }
 
-fn fetch_episodes(url: &str, limit: usize) -> Result<Vec<Episode>> {
-    let client = Client::new();
-    let response = client
-        .get(url)
-        .send()
-        .context("Failed to fetch RSS feed")?
-        .bytes()
-        .context("Failed to read RSS feed response")?;
-
-    let channel = Channel::read_from(&response[..]).context("Failed to parse RSS feed")?;
-
-    let episodes: Vec<Episode> = channel
-        .items()
-        .iter()
-        .take(limit)
-        .filter_map(|item| {
-            let title = item.title()?.to_string();
-            let url = item.enclosure()?.url().to_string();
-            Some(Episode { title, url })
-        })
-        .collect();
-
-    Ok(episodes)
+#[derive(Subcommand, Debug)]
+enum AuthCommand {
+    /// Save a login for a feed (prompts for the secret, or reads it from stdin)
+    Add {
+        /// Feed URL the login is for
+        url: String,
+
+        /// User name for HTTP Basic auth
+        #[arg(long, required_unless_present = "token", conflicts_with = "token")]
+        user: Option<String>,
+
+        /// Save a bearer token instead of a user name and password
+        #[arg(long)]
+        token: bool,
+    },
+
+    /// Forget the login for a feed
+    Remove {
+        /// Feed URL the login is for
+        url: String,
+    },
+
+    /// List feeds with saved logins (secrets are not shown)
+    List,
 }
 
-fn download_episode(episode: &Episode) -> Result<()> {
-    // Create podcast-downloads directory if it doesn't exist
-    let download_dir = Path::new("podcast-downloads");
-    fs::create_dir_all(download_dir).context("Failed to create download directory")?;
+#[derive(Subcommand, Debug)]
+enum FeedCommand {
+    /// Show which episodes were added, removed or edited between two snapshots
+    Diff {
+        /// Feed URL, or a folder of snapshots such as an archive's `feed/`
+        feed: String,
 
-    // Sanitize filename
-    let filename = sanitize_filename(&episode.title);
-    let extension = get_extension_from_url(&episode.url);
-    let filepath = download_dir.join(format!("{}.{}", filename, extension));
+        /// Older snapshot (file name or path) [default: the one before --to]
+        #[arg(long, value_name = "SNAPSHOT")]
+        from: Option<String>,
 
-    // Check if file already exists
-    if filepath.exists() {
-        println!("⏭ Already downloaded: {}", filepath.display());
-        return Ok(());
-    }
+        /// Newer snapshot (file name or path) [default: the latest]
+        #[arg(long, value_name = "SNAPSHOT")]
+        to: Option<String>,
+    },
 
-    // Download file
-    let client = Client::new();
-    let mut response = client
-        .get(&episode.url)
-        .send()
-        .context("Failed to start download")?;
-
-    let total_size = response
-        .content_length()
-        .context("Failed to get content length")?;
-
-    // Create progress bar
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .context("Failed to create progress bar template")?
-            .progress_chars("=>-"),
-    );
+    /// List the snapshots of a feed, oldest first
+    Snapshots {
+        /// Feed URL, or a folder of snapshots such as an archive's `feed/`
+        feed: String,
+    },
+}
 
-    // Download with progress
-    let mut file = File::create(&filepath).context("Failed to create output file")?;
-    let mut downloaded: u64 = 0;
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the path of the config file
+    Path,
 
-    loop {
-        let mut buffer = vec![0; 8192];
-        let bytes_read = std::io::Read::read(&mut response, &mut buffer)
-            .context("Failed to read download chunk")?;
+    /// Open the config file in $VISUAL or $EDITOR
+    Edit,
 
-        if bytes_read == 0 {
-            break;
-        }
+    /// Print the value of a setting
+    Get {
+        /// Setting name, e.g. download_dir
+        key: String,
+    },
 
-        file.write_all(&buffer[..bytes_read])
-            .context("Failed to write to file")?;
+    /// Change a setting in the config file (in the profile's section with --profile)
+    Set {
+        /// Setting name, e.g. download_dir
+        key: String,
 
-        downloaded += bytes_read as u64;
-        pb.set_position(downloaded);
-    }
+        /// New value
+        value: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum QueueCommand {
+    /// Pick episodes as for downloading, and queue them instead
+    Add(Box<DownloadArgs>),
 
-    pb.finish_with_message("Download complete");
+    /// Show the queue, oldest first
+    List,
 
-    println!("Saved to: {}", filepath.display());
+    /// Take episodes out of the queue
+    Remove {
+        /// Queue ids, as `pdl queue list` shows them
+        #[arg(required_unless_present = "all")]
+        ids: Vec<i64>,
 
-    Ok(())
+        /// Empty the whole queue
+        #[arg(long, conflicts_with = "ids")]
+        all: bool,
+    },
+
+    /// Hold queued episodes back from `pdl queue start` and `pdl watch`
+    Pause {
+        /// Queue ids (all episodes if none are given)
+        ids: Vec<i64>,
+    },
+
+    /// Let paused episodes download again
+    Resume {
+        /// Queue ids (all episodes if none are given)
+        ids: Vec<i64>,
+    },
+
+    /// Download everything queued that isn't paused (Ctrl+C stops, the next start resumes)
+    Start {
+        /// Start straight away, even outside `download_window`
+        #[arg(long)]
+        now: bool,
+    },
 }
 
-fn sanitize_filename(title: &str) -> String {
-    title
-        .chars()
-        .map(|c| match c {
-            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
-            _ => c,
-        })
-        .collect::<String>()
-        .trim()
-        .to_string()
+#[derive(Subcommand, Debug)]
+enum ImportCommand {
+    /// Subscribe to every feed in an OPML file
+    Opml {
+        /// OPML file exported from AntennaPod, Overcast, Pocket Casts, etc.
+        file: PathBuf,
+
+        /// Add feeds without checking that they can be fetched
+        #[arg(long)]
+        no_check: bool,
+    },
 }
 
-fn get_extension_from_url(url: &str) -> String {
-    let path = url.split('?').next().unwrap_or(url);
-    path.split('.').next_back().unwrap_or("mp3").to_lowercase()
+#[derive(Subcommand, Debug)]
+enum ExportCommand {
+    /// Write subscriptions as an OPML file
+    Opml {
+        /// File to write (defaults to stdout)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_sanitize_filename_removes_invalid_chars() {
-        assert_eq!(sanitize_filename("hello/world"), "hello-world");
-        assert_eq!(sanitize_filename("file:name"), "file-name");
-        assert_eq!(sanitize_filename("test*file?"), "test-file-");
-        assert_eq!(sanitize_filename("a<b>c"), "a-b-c");
-        assert_eq!(sanitize_filename("pipe|char"), "pipe-char");
-        assert_eq!(sanitize_filename("back\\slash"), "back-slash");
-        assert_eq!(sanitize_filename("quote\"test"), "quote-test");
+/// How long the first Ctrl+C waits for downloads to save their progress
+/// before exiting anyway; a second Ctrl+C exits at once
+const INTERRUPT_GRACE: Duration = Duration::from_secs(3);
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tokio::spawn(handle_ctrl_c());
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let kind = match e.downcast_ref::<InquireError>() {
+                Some(InquireError::OperationInterrupted | InquireError::OperationCanceled) => {
+                    ErrorKind::Aborted
+                }
+                _ => ErrorKind::of(&e),
+            };
+            if kind == ErrorKind::Aborted {
+                output::restore_terminal();
+            }
+            let code = kind.exit_code();
+            if output::json() {
+                let error = serde_json::json!({
+                    "error": format!("{:#}", e),
+                    "kind": kind,
+                    "exit_code": code,
+                });
+                let _ = output::emit(&error);
+            } else if tracing::dispatcher::has_been_set() {
+                tracing::error!("{:#}", e);
+            } else {
+                // Logging itself failed to start
+                eprintln!("Error: {:#}", e);
+            }
+            // Batches report their own failures
+            if !matches!(kind, ErrorKind::PartialFailure | ErrorKind::Aborted)
+                && !notify::failure_sent()
+            {
+                notify::send(&pdl::tr!("notify-failed"), &format!("{:#}", e));
+            }
+            ExitCode::from(code)
+        }
     }
+}
 
-    #[test]
-    fn test_sanitize_filename_preserves_valid_chars() {
-        assert_eq!(sanitize_filename("hello world"), "hello world");
-        assert_eq!(sanitize_filename("episode-01"), "episode-01");
-        assert_eq!(sanitize_filename("podcast_name"), "podcast_name");
-        assert_eq!(sanitize_filename("한글 제목"), "한글 제목");
+/// Stop downloads on Ctrl+C so they keep their partial files, then exit
+/// once they have (or the grace period runs out)
+async fn handle_ctrl_c() {
+    if tokio::signal::ctrl_c().await.is_err() {
+        return;
+    }
+    interrupt::interrupt();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = tokio::time::sleep(INTERRUPT_GRACE) => {}
     }
+    output::restore_terminal();
+    eprintln!("\n{}", pdl::tr!("interrupted"));
+    std::process::exit(ErrorKind::Aborted.exit_code().into());
+}
 
-    #[test]
-    fn test_sanitize_filename_trims_whitespace() {
-        assert_eq!(sanitize_filename("  hello  "), "hello");
-        assert_eq!(sanitize_filename("\ttest\n"), "test");
+/// Parse the command line, rejecting download options given before a
+/// command, which would otherwise be silently ignored
+/// (`pdl --latest feeds`). Global options such as `--json` may still go
+/// either side of the command.
+fn parse_args() -> Args {
+    let matches = Args::command().get_matches();
+    if matches.subcommand_name().is_some() {
+        let download = DownloadArgs::augment_args(clap::Command::new("download"));
+        for arg in download.get_arguments() {
+            if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+                let flag = arg
+                    .get_long()
+                    .map(|long| format!("--{long}"))
+                    .unwrap_or_else(|| arg.get_id().to_string());
+                Args::command()
+                    .error(
+                        clap::error::ErrorKind::ArgumentConflict,
+                        format!(
+                            "'{flag}' cannot be used with a command; use `pdl download {flag} ...`"
+                        ),
+                    )
+                    .exit();
+            }
+        }
     }
+    Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+}
 
-    #[test]
-    fn test_get_extension_from_url_basic() {
-        assert_eq!(
-            get_extension_from_url("https://example.com/file.mp3"),
-            "mp3"
-        );
-        assert_eq!(
-            get_extension_from_url("https://example.com/file.MP3"),
-            "mp3"
-        );
-        assert_eq!(
-            get_extension_from_url("https://example.com/audio.m4a"),
-            "m4a"
-        );
-        assert_eq!(
-            get_extension_from_url("https://example.com/video.mp4"),
-            "mp4"
-        );
+async fn run() -> Result<()> {
+    // Parse CLI arguments (before banner so -v works cleanly)
+    let args = parse_args();
+    output::set_json(args.json);
+    output::set_porcelain(args.porcelain);
+    if let Some(lang) = args.lang {
+        i18n::set_lang(lang);
     }
+    notify::set_enabled(args.notify);
+    // `pdl watch` and the servers' output is a log, so it gets timestamps
+    let timestamps = matches!(
+        args.command,
+        Some(Command::Watch { .. } | Command::Serve { .. } | Command::ServeFeed { .. })
+    );
+    args.log.init(timestamps)?;
 
-    #[test]
-    fn test_get_extension_from_url_with_query_params() {
-        assert_eq!(
-            get_extension_from_url("https://example.com/file.mp3?token=abc123"),
-            "mp3"
-        );
-        assert_eq!(
-            get_extension_from_url("https://cdn.example.com/podcast.m4a?expires=123&sig=xyz"),
-            "m4a"
-        );
+    if let Some(Command::Man { output_dir }) = &args.command {
+        return man::man(Args::command(), output_dir.as_deref());
     }
 
-    #[test]
-    fn test_get_extension_from_url_no_extension() {
-        // Note: function splits by '.' so returns last segment after dot
-        assert_eq!(
-            get_extension_from_url("https://example.com/file"),
-            "com/file"
-        );
-        // URL with path ending in extension-less filename
-        assert_eq!(
-            get_extension_from_url("http://example/podcast"),
-            "http://example/podcast"
-        );
+    let profile = args
+        .profile
+        .clone()
+        .or_else(|| env::var("PDL_PROFILE").ok());
+    let profile = profile.as_deref();
+
+    // Config commands must work even when the config file is broken
+    if let Some(Command::Config(command)) = &args.command {
+        return match command {
+            ConfigCommand::Path => config::show_path(),
+            ConfigCommand::Edit => config::edit(profile),
+            ConfigCommand::Get { key } => config::get(key, profile),
+            ConfigCommand::Set { key, value } => config::set(key, value, profile),
+        };
     }
 
-    #[test]
-    fn test_get_extension_from_url_multiple_dots() {
-        assert_eq!(
-            get_extension_from_url("https://example.com/file.name.mp3"),
-            "mp3"
-        );
+    let config = Config::load(profile)?;
+    let client = args.network.client(&config)?;
+
+    match args.command {
+        Some(Command::Download(download)) => run_download(&client, &config, *download).await,
+        Some(Command::Subscribe { url, name }) => feeds::subscribe(&client, &url, name).await,
+        Some(Command::Unsubscribe { name }) => feeds::unsubscribe(&name),
+        Some(Command::Feeds) => feeds::list_feeds(),
+        Some(Command::List {
+            feed,
+            n,
+            filter,
+            format,
+        }) => {
+            let format = if args.json {
+                ListFormat::Json
+            } else if args.porcelain {
+                ListFormat::Tsv
+            } else {
+                format
+            };
+            list::list(&client, &config, feed, n, filter, format).await
+        }
+        Some(Command::Search {
+            terms,
+            n,
+            search_backend,
+        }) => search::search(&client, &config, &terms, n, search_backend).await,
+        Some(Command::Import(ImportCommand::Opml { file, no_check })) => {
+            feeds::import_opml(&client, &file, !no_check).await
+        }
+        Some(Command::Export(ExportCommand::Opml { output })) => {
+            feeds::export_opml(output.as_deref())
+        }
+        Some(Command::Archive {
+            feed,
+            output_dir,
+            jobs,
+        }) => archive::archive(&client, &config, &feed, output_dir, jobs).await,
+        Some(Command::Feed(FeedCommand::Diff { feed, from, to })) => {
+            feed::diff(&feed, from, to).await
+        }
+        Some(Command::Feed(FeedCommand::Snapshots { feed })) => feed::list_snapshots(&feed).await,
+        Some(Command::History { n }) => history::show_history(n),
+        Some(Command::Query(args)) => query::query(args),
+        Some(Command::Prune { name, dry_run }) => prune::prune(&config, name.as_deref(), dry_run),
+        Some(Command::Stats) => stats::show_stats(),
+        Some(Command::Index { output }) => index::index(&config, output.as_deref()),
+        Some(Command::Dedupe { link }) => dedupe::dedupe(link).await,
+        Some(Command::Mark {
+            mark,
+            episodes,
+            show,
+        }) => mark::mark(mark, &episodes, show.as_deref()),
+        Some(Command::Play { n, latest }) => play::play(&config, n, latest),
+        Some(Command::Transcribe {
+            target,
+            latest,
+            language,
+            format,
+            force,
+        }) => transcribe::transcribe(&config, target, latest, &language, &format, force).await,
+        Some(Command::Stream {
+            feed,
+            n,
+            latest,
+            episode,
+        }) => {
+            let selector = if latest {
+                Some(Selector::Latest)
+            } else {
+                episode.map(Selector::Episode)
+            };
+            stream::stream(&client, &config, feed, n, selector).await
+        }
+        Some(Command::Update {
+            download,
+            refresh_changed,
+        }) => update::update(&client, &config, download, refresh_changed).await,
+        Some(Command::Watch {
+            interval,
+            once,
+            now,
+            metrics,
+        }) => watch::watch(&client, &config, interval, once, now, metrics).await,
+        Some(Command::Tui) => tui::run(&client, &config).await,
+        Some(Command::Sync(SyncCommand::Gpodder {
+            server,
+            api,
+            device,
+        })) => sync::gpodder(&client, &config, server, api, device).await,
+        Some(Command::Queue(QueueCommand::Add(mut download))) => {
+            download.queue = true;
+            run_download(&client, &config, *download).await
+        }
+        Some(Command::Queue(QueueCommand::List)) => queue::list(),
+        Some(Command::Queue(QueueCommand::Remove { ids, all })) => queue::remove(&ids, all),
+        Some(Command::Queue(QueueCommand::Pause { ids })) => queue::pause(&ids, true),
+        Some(Command::Queue(QueueCommand::Resume { ids })) => queue::pause(&ids, false),
+        Some(Command::Queue(QueueCommand::Start { now })) => {
+            queue::start(&client, &config, now).await
+        }
+        Some(Command::Serve { listen }) => serve::serve(&client, &config, listen).await,
+        Some(Command::ServeFeed { listen, base_url }) => {
+            serve_feed::serve_feed(listen, base_url).await
+        }
+        Some(Command::Auth(AuthCommand::Add { url, user, token })) => {
+            auth::add(&url, user, token).await
+        }
+        Some(Command::Auth(AuthCommand::Remove { url })) => auth::remove(&url).await,
+        Some(Command::Auth(AuthCommand::List)) => auth::list(),
+        Some(Command::Config(_) | Command::Man { .. }) => unreachable!("handled above"),
+        None => run_download(&client, &config, args.download).await,
     }
 }
@@ -0,0 +1,120 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters kept since pdl started, for `/metrics` in `pdl serve` and
+/// `pdl watch --metrics`
+static FEEDS_REFRESHED: AtomicU64 = AtomicU64::new(0);
+static FEED_ERRORS: AtomicU64 = AtomicU64::new(0);
+static EPISODES_DOWNLOADED: AtomicU64 = AtomicU64::new(0);
+static DOWNLOAD_ERRORS: AtomicU64 = AtomicU64::new(0);
+static BYTES_DOWNLOADED: AtomicU64 = AtomicU64::new(0);
+
+/// A feed was fetched (unchanged ones included)
+pub fn feed_refreshed() {
+    FEEDS_REFRESHED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A feed couldn't be fetched or parsed
+pub fn feed_failed() {
+    FEED_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// An episode was downloaded
+pub fn episode_downloaded() {
+    EPISODES_DOWNLOADED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// An episode's download failed
+pub fn download_failed() {
+    DOWNLOAD_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// `bytes` more of some episode were received
+pub fn add_bytes(bytes: u64) {
+    BYTES_DOWNLOADED.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Gauges read when the metrics are asked for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gauges {
+    /// Episodes waiting in the download queue
+    pub queue_depth: usize,
+    /// Downloads running now
+    pub active_downloads: usize,
+}
+
+/// The counters and `gauges` in the Prometheus text format
+pub fn render(gauges: Gauges) -> String {
+    let counter = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, u64)]| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+    metric(
+        "pdl_feeds_refreshed_total",
+        "counter",
+        "Feeds fetched.",
+        &[("", counter(&FEEDS_REFRESHED))],
+    );
+    metric(
+        "pdl_episodes_downloaded_total",
+        "counter",
+        "Episodes downloaded.",
+        &[("", counter(&EPISODES_DOWNLOADED))],
+    );
+    metric(
+        "pdl_downloaded_bytes_total",
+        "counter",
+        "Bytes of episodes received.",
+        &[("", counter(&BYTES_DOWNLOADED))],
+    );
+    metric(
+        "pdl_errors_total",
+        "counter",
+        "Feeds that failed to refresh and episodes that failed to download.",
+        &[
+            ("{kind=\"feed\"}", counter(&FEED_ERRORS)),
+            ("{kind=\"download\"}", counter(&DOWNLOAD_ERRORS)),
+        ],
+    );
+    metric(
+        "pdl_queue_depth",
+        "gauge",
+        "Episodes waiting in the download queue.",
+        &[("", gauges.queue_depth as u64)],
+    );
+    metric(
+        "pdl_active_downloads",
+        "gauge",
+        "Downloads in progress.",
+        &[("", gauges.active_downloads as u64)],
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        download_failed();
+        let out = render(Gauges {
+            queue_depth: 3,
+            active_downloads: 1,
+        });
+        assert!(out.contains("# TYPE pdl_feeds_refreshed_total counter\n"));
+        assert!(out.contains("\npdl_queue_depth 3\n"));
+        assert!(out.contains("\npdl_active_downloads 1\n"));
+        // Other tests may count too, so just check this one did
+        let errors = out
+            .lines()
+            .find_map(|line| line.strip_prefix("pdl_errors_total{kind=\"download\"} "))
+            .unwrap();
+        assert!(errors.parse::<u64>().unwrap() >= 1);
+    }
+}
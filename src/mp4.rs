@@ -0,0 +1,376 @@
+//! Minimal MP4 (m4a/m4b) metadata writer: replaces items in `moov/udta/meta/ilst`
+//! and fixes up chunk offsets when the `moov` atom grows in front of `mdat`.
+
+use crate::tag::Artwork;
+use anyhow::{Context, Result, bail};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Atom types pdl descends into; everything else is kept as opaque bytes
+const CONTAINERS: [&[u8; 4]; 8] = [
+    b"moov", b"trak", b"mdia", b"minf", b"stbl", b"udta", b"meta", b"ilst",
+];
+
+/// Value of an `ilst` item
+#[derive(Debug, Clone, PartialEq)]
+pub enum IlstValue {
+    Text(String),
+    /// `trkn`: track number
+    Track(u32),
+    /// `disk`: disc number
+    Disc(u32),
+    /// One-byte integer (`stik`, `pcst`)
+    Byte(u8),
+    /// `covr`: cover art
+    Image(Artwork),
+}
+
+impl IlstValue {
+    /// Payload of the item's `data` atom: type indicator, locale, value
+    fn data(&self) -> Vec<u8> {
+        let (kind, value): (u32, Vec<u8>) = match self {
+            Self::Text(text) => (1, text.as_bytes().to_vec()),
+            Self::Track(n) => (0, pair(*n, 8)),
+            Self::Disc(n) => (0, pair(*n, 6)),
+            Self::Byte(b) => (21, vec![*b]),
+            Self::Image(artwork) => (if artwork.png { 14 } else { 13 }, artwork.data.clone()),
+        };
+        let mut data = kind.to_be_bytes().to_vec();
+        data.extend_from_slice(&[0; 4]);
+        data.extend(value);
+        data
+    }
+}
+
+/// `trkn`/`disk` layout: 2 reserved bytes, number, total (0), padding
+fn pair(n: u32, len: usize) -> Vec<u8> {
+    let mut value = vec![0; len];
+    value[2..4].copy_from_slice(&(n.min(u16::MAX as u32) as u16).to_be_bytes());
+    value
+}
+
+#[derive(Debug, Clone)]
+struct Atom {
+    kind: [u8; 4],
+    body: Body,
+}
+
+#[derive(Debug, Clone)]
+enum Body {
+    Data(Vec<u8>),
+    /// `prefix` holds the version/flags of full boxes such as ISO `meta`
+    Children {
+        prefix: Vec<u8>,
+        children: Vec<Atom>,
+    },
+}
+
+impl Atom {
+    fn container(kind: &[u8; 4], prefix: Vec<u8>, children: Vec<Atom>) -> Self {
+        Self {
+            kind: *kind,
+            body: Body::Children { prefix, children },
+        }
+    }
+
+    fn data(kind: [u8; 4], data: Vec<u8>) -> Self {
+        Self {
+            kind,
+            body: Body::Data(data),
+        }
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<Atom>> {
+        match &mut self.body {
+            Body::Children { children, .. } => Some(children),
+            Body::Data(_) => None,
+        }
+    }
+
+    /// The child container of type `kind`, created by `make` if missing
+    fn child_or_insert(&mut self, kind: &[u8; 4], make: impl FnOnce() -> Atom) -> &mut Atom {
+        let children = self.children_mut().expect("container atom");
+        let index = match children.iter().position(|a| &a.kind == kind) {
+            Some(index) => index,
+            None => {
+                children.push(make());
+                children.len() - 1
+            }
+        };
+        &mut children[index]
+    }
+
+    fn to_bytes(&self, out: &mut Vec<u8>) {
+        let mut body = Vec::new();
+        match &self.body {
+            Body::Data(data) => body.extend_from_slice(data),
+            Body::Children { prefix, children } => {
+                body.extend_from_slice(prefix);
+                for child in children {
+                    child.to_bytes(&mut body);
+                }
+            }
+        }
+        let size = body.len() as u64 + 8;
+        if size <= u32::MAX as u64 {
+            out.extend_from_slice(&(size as u32).to_be_bytes());
+            out.extend_from_slice(&self.kind);
+        } else {
+            out.extend_from_slice(&1u32.to_be_bytes());
+            out.extend_from_slice(&self.kind);
+            out.extend_from_slice(&(size + 8).to_be_bytes());
+        }
+        out.extend(body);
+    }
+
+    /// Add `delta` to every chunk offset at or past `from`
+    fn shift_chunk_offsets(&mut self, from: u64, delta: i64) {
+        match &mut self.body {
+            Body::Children { children, .. } => {
+                for child in children {
+                    child.shift_chunk_offsets(from, delta);
+                }
+            }
+            Body::Data(data) if &self.kind == b"stco" => {
+                for entry in data.get_mut(8..).unwrap_or_default().chunks_exact_mut(4) {
+                    let offset = u32::from_be_bytes(entry.try_into().unwrap()) as u64;
+                    if offset >= from {
+                        let shifted = offset.saturating_add_signed(delta) as u32;
+                        entry.copy_from_slice(&shifted.to_be_bytes());
+                    }
+                }
+            }
+            Body::Data(data) if &self.kind == b"co64" => {
+                for entry in data.get_mut(8..).unwrap_or_default().chunks_exact_mut(8) {
+                    let offset = u64::from_be_bytes(entry.try_into().unwrap());
+                    if offset >= from {
+                        entry.copy_from_slice(&offset.saturating_add_signed(delta).to_be_bytes());
+                    }
+                }
+            }
+            Body::Data(_) => {}
+        }
+    }
+}
+
+/// Split `buf` into atoms, descending into known containers
+fn parse_atoms(mut buf: &[u8]) -> Result<Vec<Atom>> {
+    let mut atoms = Vec::new();
+    while buf.len() >= 8 {
+        let size = u32::from_be_bytes(buf[0..4].try_into().unwrap()) as u64;
+        let kind: [u8; 4] = buf[4..8].try_into().unwrap();
+        let (header, size) = match size {
+            0 => (8, buf.len() as u64),
+            1 if buf.len() >= 16 => (16, u64::from_be_bytes(buf[8..16].try_into().unwrap())),
+            _ => (8, size),
+        };
+        if size < header as u64 || size > buf.len() as u64 {
+            bail!("Malformed MP4 atom {}", String::from_utf8_lossy(&kind));
+        }
+        let payload = &buf[header..size as usize];
+        atoms.push(parse_atom(kind, payload)?);
+        buf = &buf[size as usize..];
+    }
+    Ok(atoms)
+}
+
+fn parse_atom(kind: [u8; 4], payload: &[u8]) -> Result<Atom> {
+    if !CONTAINERS.contains(&&kind) {
+        return Ok(Atom::data(kind, payload.to_vec()));
+    }
+    // ISO `meta` is a full box (4 bytes of version/flags); QuickTime's isn't
+    let prefix_len = if &kind == b"meta" && payload.get(4..8) != Some(b"hdlr") {
+        4
+    } else {
+        0
+    };
+    let (prefix, rest) = payload.split_at(prefix_len.min(payload.len()));
+    Ok(Atom::container(&kind, prefix.to_vec(), parse_atoms(rest)?))
+}
+
+/// Handler that marks `meta` as iTunes-style metadata
+fn mdir_handler() -> Atom {
+    let mut data = vec![0; 8];
+    data.extend_from_slice(b"mdirappl");
+    data.extend_from_slice(&[0; 10]);
+    Atom::data(*b"hdlr", data)
+}
+
+/// Set `items` in the `ilst` of `moov`, replacing existing items of the same type
+fn set_items(moov: &mut Atom, items: &[([u8; 4], IlstValue)]) {
+    let udta = moov.child_or_insert(b"udta", || Atom::container(b"udta", Vec::new(), Vec::new()));
+    let meta = udta.child_or_insert(b"meta", || {
+        Atom::container(b"meta", vec![0; 4], vec![mdir_handler()])
+    });
+    let ilst = meta.child_or_insert(b"ilst", || Atom::container(b"ilst", Vec::new(), Vec::new()));
+    let children = ilst.children_mut().expect("container atom");
+
+    for (kind, value) in items {
+        let mut item = Vec::new();
+        Atom::data(*b"data", value.data()).to_bytes(&mut item);
+        let item = Atom::data(*kind, item);
+        match children.iter_mut().find(|a| a.kind == *kind) {
+            Some(existing) => *existing = item,
+            None => children.push(item),
+        }
+    }
+}
+
+/// Offset, header-inclusive size and type of each top-level atom
+fn top_level_atoms(file: &mut File) -> Result<Vec<(u64, u64, [u8; 4])>> {
+    let len = file.metadata()?.len();
+    let mut atoms = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= len {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0; 16];
+        file.read_exact(&mut header[..8])?;
+        let kind: [u8; 4] = header[4..8].try_into().unwrap();
+        let size = match u32::from_be_bytes(header[0..4].try_into().unwrap()) as u64 {
+            0 => len - offset,
+            1 => {
+                file.read_exact(&mut header[8..16])?;
+                u64::from_be_bytes(header[8..16].try_into().unwrap())
+            }
+            size => size,
+        };
+        if size < 8 || offset + size > len {
+            bail!("Malformed MP4 atom {}", String::from_utf8_lossy(&kind));
+        }
+        atoms.push((offset, size, kind));
+        offset += size;
+    }
+    Ok(atoms)
+}
+
+/// Write `items` into the MP4 file at `path`, rewriting it through a temp file
+pub fn write_tags(path: &Path, items: &[([u8; 4], IlstValue)]) -> Result<()> {
+    let mut file = File::open(path).context("Failed to open MP4 file")?;
+    let atoms = top_level_atoms(&mut file).context("Failed to read MP4 structure")?;
+    if !atoms.iter().any(|(_, _, kind)| kind == b"ftyp") {
+        bail!("Not an MP4 file: {}", path.display());
+    }
+    let &(moov_offset, moov_size, _) = atoms
+        .iter()
+        .find(|(_, _, kind)| kind == b"moov")
+        .context("MP4 file has no moov atom")?;
+
+    let mut raw = vec![0; moov_size as usize];
+    file.seek(SeekFrom::Start(moov_offset))?;
+    file.read_exact(&mut raw)?;
+    let mut moov = parse_atoms(&raw)?
+        .pop()
+        .context("Failed to parse moov atom")?;
+
+    set_items(&mut moov, items);
+    let mut new_moov = Vec::new();
+    moov.to_bytes(&mut new_moov);
+
+    // Media stored after moov moves by however much moov grew or shrank
+    let delta = new_moov.len() as i64 - moov_size as i64;
+    if delta != 0 {
+        moov.shift_chunk_offsets(moov_offset + moov_size, delta);
+        new_moov.clear();
+        moov.to_bytes(&mut new_moov);
+    }
+
+    let tmp_path = path.with_extension("tagging");
+    let result = (|| -> io::Result<()> {
+        let mut out = File::create(&tmp_path)?;
+        file.seek(SeekFrom::Start(0))?;
+        io::copy(&mut (&mut file).take(moov_offset), &mut out)?;
+        out.write_all(&new_moov)?;
+        file.seek(SeekFrom::Start(moov_offset + moov_size))?;
+        io::copy(&mut file, &mut out)?;
+        out.sync_all()
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e).context("Failed to write tagged MP4 file");
+    }
+    fs::rename(&tmp_path, path).context("Failed to replace MP4 file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    /// ftyp + moov (one stco entry) + mdat, with the chunk offset pointing at the media
+    fn sample_file() -> Vec<u8> {
+        let ftyp = atom(b"ftyp", b"M4A \0\0\0\0");
+        let stco_len = 8 + 12;
+        let moov_len = 8 + 8 + 8 + 8 + 8 + stco_len;
+        let media_offset = (ftyp.len() + moov_len + 8) as u32;
+        let mut stco = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stco.extend_from_slice(&media_offset.to_be_bytes());
+        let stbl = atom(b"stbl", &atom(b"stco", &stco));
+        let moov = atom(
+            b"moov",
+            &atom(b"trak", &atom(b"mdia", &atom(b"minf", &stbl))),
+        );
+        assert_eq!(moov.len(), moov_len);
+        let mut file = ftyp;
+        file.extend(moov);
+        file.extend(atom(b"mdat", b"AUDIO"));
+        file
+    }
+
+    fn chunk_offset(file: &[u8]) -> u32 {
+        let pos = file.windows(4).position(|w| w == b"stco").unwrap();
+        u32::from_be_bytes(file[pos + 12..pos + 16].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_write_tags_adds_ilst_and_shifts_chunk_offsets() {
+        let path = std::env::temp_dir().join(format!("pdl-mp4-{}.m4a", std::process::id()));
+        let original = sample_file();
+        let offset = chunk_offset(&original) as usize;
+        assert_eq!(&original[offset..offset + 5], b"AUDIO");
+        std::fs::write(&path, &original).unwrap();
+
+        write_tags(
+            &path,
+            &[
+                (*b"\xa9nam", IlstValue::Text("Episode 1".to_string())),
+                (*b"trkn", IlstValue::Track(7)),
+            ],
+        )
+        .unwrap();
+        let tagged = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let offset = chunk_offset(&tagged) as usize;
+        assert_eq!(&tagged[offset..offset + 5], b"AUDIO");
+        assert!(tagged.windows(9).any(|w| w == b"Episode 1"));
+
+        let atoms = parse_atoms(&tagged).unwrap();
+        let kinds: Vec<_> = atoms.iter().map(|a| &a.kind).collect();
+        assert_eq!(kinds, [b"ftyp", b"moov", b"mdat"]);
+    }
+
+    #[test]
+    fn test_set_items_replaces_existing_values() {
+        let mut moov = Atom::container(b"moov", Vec::new(), Vec::new());
+        set_items(
+            &mut moov,
+            &[(*b"\xa9nam", IlstValue::Text("Old".to_string()))],
+        );
+        set_items(
+            &mut moov,
+            &[(*b"\xa9nam", IlstValue::Text("New".to_string()))],
+        );
+
+        let mut bytes = Vec::new();
+        moov.to_bytes(&mut bytes);
+        assert!(bytes.windows(3).any(|w| w == b"New"));
+        assert!(!bytes.windows(3).any(|w| w == b"Old"));
+    }
+}
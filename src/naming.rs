@@ -0,0 +1,334 @@
+use crate::download::FilenameMode;
+use crate::feed::Episode;
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{LazyLock, Mutex};
+
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{(\w+)\}").unwrap());
+
+/// Placeholders a filename template may use
+const PLACEHOLDERS: [&str; 6] = ["title", "date", "number", "index", "season", "guid"];
+
+/// Digits `{index}` is zero-padded to, so files sort in order in file managers
+const INDEX_WIDTH: usize = 3;
+
+/// What downloaded episodes' files are named after
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum NameScheme {
+    /// The filename template, `{title}` unless set
+    #[default]
+    Title,
+    /// The episode's GUID (its URL if it has none), which stays put when
+    /// the title is edited and is never shared by two episodes
+    Guid,
+    /// The SHA-256 of the file's content, so the same file always gets the
+    /// same name
+    Hash,
+}
+
+/// Bytes of a GUID kept in front of its hash when the GUID isn't usable
+/// as a file name as it is
+const GUID_PREFIX_BYTES: usize = 64;
+
+/// Hex digits of a GUID's hash added to a GUID that had to be changed to
+/// make a file name, so two GUIDs never end up with the same one
+const GUID_HASH_DIGITS: usize = 12;
+
+/// File name (without extension) for a downloaded episode, e.g. `{date} {title}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilenameTemplate {
+    template: String,
+    /// How strictly the rendered name is cleaned up
+    mode: FilenameMode,
+    scheme: NameScheme,
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        Self {
+            template: "{title}".to_string(),
+            mode: FilenameMode::default(),
+            scheme: NameScheme::default(),
+        }
+    }
+}
+
+impl FromStr for FilenameTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(template: &str) -> Result<Self> {
+        for captures in PLACEHOLDER.captures_iter(template) {
+            let name = &captures[1];
+            if !PLACEHOLDERS.contains(&name) {
+                bail!(
+                    "Unknown placeholder {{{}}} in filename template (expected one of: {})",
+                    name,
+                    PLACEHOLDERS.join(", ")
+                );
+            }
+        }
+        if !template.contains('{') {
+            bail!("Filename template needs at least one placeholder, e.g. {{title}}");
+        }
+        Ok(Self {
+            template: template.to_string(),
+            ..Default::default()
+        })
+    }
+}
+
+impl fmt::Display for FilenameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+impl FilenameTemplate {
+    /// This template with `{date}` and/or `{index}` put in front, for
+    /// `--date-prefix` and `--number-prefix`
+    pub fn with_prefixes(&self, date: bool, number: bool) -> Self {
+        let mut template = self.template.clone();
+        if number {
+            template = format!("{{index}} {}", template);
+        }
+        if date {
+            template = format!("{{date}} {}", template);
+        }
+        Self {
+            template,
+            ..self.clone()
+        }
+    }
+
+    /// This template, naming files by `scheme`; the template itself is only
+    /// used by [`NameScheme::Title`]
+    pub fn with_scheme(mut self, scheme: NameScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn scheme(&self) -> NameScheme {
+        self.scheme
+    }
+
+    /// This template, cleaning up names according to `mode`
+    pub fn with_mode(mut self, mode: FilenameMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// File name for `episode`, without extension. Falls back to the title if
+    /// the template renders to nothing. With [`NameScheme::Hash`] this is
+    /// the GUID's name, which the file has until its content is hashed.
+    pub fn render(&self, episode: &Episode) -> String {
+        if self.scheme != NameScheme::Title {
+            return self.guid_name(episode);
+        }
+        let name = PLACEHOLDER.replace_all(&self.template, |captures: &regex::Captures| {
+            let value = match &captures[1] {
+                "title" => Some(episode.title.clone()),
+                "date" => episode.published.map(|d| d.format("%Y-%m-%d").to_string()),
+                "number" => episode.number.map(|n| n.to_string()),
+                "index" => episode
+                    .number
+                    .or(episode.position)
+                    .map(|n| format!("{:0width$}", n, width = INDEX_WIDTH)),
+                "season" => episode.season.map(|n| n.to_string()),
+                "guid" => episode.guid.clone(),
+                _ => None,
+            };
+            value.unwrap_or_default()
+        });
+        let name = self.mode.sanitize(&name);
+        if name.is_empty() {
+            self.mode.sanitize(&episode.title)
+        } else {
+            name
+        }
+    }
+
+    /// `episode`'s GUID as a file name: as it is if it can be, else made
+    /// safe and followed by part of its hash
+    fn guid_name(&self, episode: &Episode) -> String {
+        let guid = episode.guid.as_deref().unwrap_or(&episode.url);
+        let name = self.mode.sanitize(guid);
+        if name == guid && !name.is_empty() && name.len() <= GUID_PREFIX_BYTES {
+            return name;
+        }
+        let mut end = name.len().min(GUID_PREFIX_BYTES);
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        let hash = format!("{:x}", Sha256::digest(guid));
+        match name[..end].trim_end() {
+            "" => hash[..GUID_HASH_DIGITS].to_string(),
+            prefix => format!("{}-{}", prefix, &hash[..GUID_HASH_DIGITS]),
+        }
+    }
+}
+
+/// File in a folder of downloads named by GUID or hash, mapping each file
+/// back to the episode it holds
+pub const NAMES_FILE: &str = "names.json";
+
+/// An episode as [`NAMES_FILE`] records it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedEpisode {
+    pub title: String,
+    pub guid: Option<String>,
+    pub url: String,
+    /// RFC 3339
+    pub published: Option<String>,
+}
+
+impl From<&Episode> for NamedEpisode {
+    fn from(episode: &Episode) -> Self {
+        Self {
+            title: episode.title.clone(),
+            guid: episode.guid.clone(),
+            url: episode.url.clone(),
+            published: episode.published.map(|d| d.to_rfc3339()),
+        }
+    }
+}
+
+/// Keeps parallel downloads from losing each other's updates to a names file
+static NAMES_LOCK: Mutex<()> = Mutex::new(());
+
+/// The names file of `dir`, by file name; empty if there is none
+pub fn read_names(dir: &Path) -> BTreeMap<String, NamedEpisode> {
+    fs::read_to_string(dir.join(NAMES_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Note in the names file next to `path` that it holds `episode`
+pub fn record_name(path: &Path, episode: &Episode) -> Result<()> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(());
+    };
+    let _lock = NAMES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut names = read_names(dir);
+    names.insert(name.to_string_lossy().into_owned(), episode.into());
+    let mut content = serde_json::to_string_pretty(&names)?;
+    content.push('\n');
+    let file = dir.join(NAMES_FILE);
+    fs::write(&file, content).with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// The file in `dir` that the names file says holds `episode`, if it is
+/// still there
+pub fn recorded_file(dir: &Path, episode: &Episode) -> Option<PathBuf> {
+    let guid = episode.guid.as_deref();
+    read_names(dir)
+        .into_iter()
+        .filter(|(_, named)| match guid {
+            Some(guid) => named.guid.as_deref() == Some(guid),
+            None => named.guid.is_none() && named.url == episode.url,
+        })
+        .map(|(name, _)| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn episode() -> Episode {
+        Episode {
+            title: "Pilot: Part 1".to_string(),
+            published: DateTime::parse_from_rfc3339("2024-01-02T10:00:00Z").ok(),
+            number: Some(7),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_fills_placeholders() {
+        let template: FilenameTemplate = "{date} {number} - {title}".parse().unwrap();
+        assert_eq!(template.render(&episode()), "2024-01-02 7 - Pilot- Part 1");
+        assert_eq!(
+            FilenameTemplate::default().render(&episode()),
+            "Pilot- Part 1"
+        );
+    }
+
+    #[test]
+    fn test_prefixes() {
+        let template = FilenameTemplate::default().with_prefixes(true, true);
+        assert_eq!(template.to_string(), "{date} {index} {title}");
+        assert_eq!(template.render(&episode()), "2024-01-02 007 Pilot- Part 1");
+
+        // Feeds without episode numbers fall back to the position in the feed
+        let unnumbered = Episode {
+            number: None,
+            position: Some(42),
+            published: None,
+            ..episode()
+        };
+        assert_eq!(template.render(&unnumbered), "042 Pilot- Part 1");
+        assert_eq!(
+            FilenameTemplate::default()
+                .with_prefixes(false, false)
+                .render(&unnumbered),
+            "Pilot- Part 1"
+        );
+    }
+
+    #[test]
+    fn test_render_missing_values() {
+        let template: FilenameTemplate = "{season}".parse().unwrap();
+        assert_eq!(template.render(&episode()), "Pilot- Part 1");
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_placeholders() {
+        assert!("{title} {author}".parse::<FilenameTemplate>().is_err());
+        assert!("episode".parse::<FilenameTemplate>().is_err());
+    }
+
+    #[test]
+    fn test_guid_names_and_names_file() {
+        let template = FilenameTemplate::default().with_scheme(NameScheme::Guid);
+        let mut episode = Episode {
+            guid: Some("ep-0007".to_string()),
+            url: "https://example.com/7.mp3".to_string(),
+            ..episode()
+        };
+        assert_eq!(template.render(&episode), "ep-0007");
+
+        // GUIDs that had to change keep a hash, so they stay apart
+        episode.guid = Some("https://example.com/?p=7".to_string());
+        let name = template.render(&episode);
+        assert!(name.starts_with("https---example.com--p=7-"), "{}", name);
+        assert_eq!(
+            name.len(),
+            "https---example.com--p=7-".len() + GUID_HASH_DIGITS
+        );
+        episode.guid = Some("https://example.com/?p=7/".to_string());
+        assert_ne!(template.render(&episode), name);
+
+        let dir = std::env::temp_dir().join(format!("pdl-names-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(format!("{}.mp3", name));
+        fs::write(&file, b"audio").unwrap();
+        assert_eq!(recorded_file(&dir, &episode), None);
+        record_name(&file, &episode).unwrap();
+        assert_eq!(recorded_file(&dir, &episode), Some(file.clone()));
+        assert_eq!(
+            read_names(&dir)[&format!("{}.mp3", name)].title,
+            "Pilot: Part 1"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use crate::feed::Episode;
+use anyhow::{Context, Result};
+use quick_xml::escape::escape;
+use std::path::{Path, PathBuf};
+
+/// Format of the show notes sidecar written by `--save-notes`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum NotesFormat {
+    #[default]
+    #[value(name = "md")]
+    Markdown,
+    Html,
+}
+
+impl NotesFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// Sidecar path for `audio`: same name, notes extension
+pub fn notes_path(audio: &Path, format: NotesFormat) -> PathBuf {
+    audio.with_extension(format.extension())
+}
+
+/// The episode's show notes as a document, or `None` if the feed has none
+pub fn render(episode: &Episode, format: NotesFormat) -> Option<String> {
+    let description = episode.description.as_deref()?;
+    let published = episode.published.map(|d| d.format("%Y-%m-%d").to_string());
+
+    Some(match format {
+        NotesFormat::Markdown => {
+            let mut doc = format!("# {}\n\n", episode.title);
+            if let Some(date) = published {
+                doc.push_str(&format!("*Published {}*\n\n", date));
+            }
+            doc.push_str(html2md::parse_html(description).trim());
+            doc.push('\n');
+            doc
+        }
+        NotesFormat::Html => {
+            let title = escape(episode.title.as_str());
+            let mut doc = format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n",
+                title, title
+            );
+            if let Some(date) = published {
+                doc.push_str(&format!("<p><em>Published {}</em></p>\n", date));
+            }
+            doc.push_str(description);
+            doc.push_str("\n</body>\n</html>\n");
+            doc
+        }
+    })
+}
+
+/// Write the episode's notes next to `audio`, returning the sidecar path if
+/// there were notes to write
+pub async fn save_notes(
+    audio: &Path,
+    episode: &Episode,
+    format: NotesFormat,
+) -> Result<Option<PathBuf>> {
+    let Some(doc) = render(episode, format) else {
+        return Ok(None);
+    };
+    let path = notes_path(audio, format);
+    tokio::fs::write(&path, doc)
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn episode() -> Episode {
+        Episode {
+            title: "Q&A".to_string(),
+            published: DateTime::parse_from_rfc3339("2024-01-02T10:00:00Z").ok(),
+            description: Some(
+                "<p>Links: <a href=\"https://example.com\">site</a></p><ul><li>one</li></ul>"
+                    .to_string(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_notes_path_matches_audio_name() {
+        assert_eq!(
+            notes_path(Path::new("Show/Ep 1.mp3"), NotesFormat::Markdown),
+            PathBuf::from("Show/Ep 1.md")
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_converts_html() {
+        let doc = render(&episode(), NotesFormat::Markdown).unwrap();
+
+        assert!(doc.starts_with("# Q&A\n\n*Published 2024-01-02*\n\n"));
+        assert!(doc.contains("[site](https://example.com)"));
+        assert!(doc.contains("one"));
+    }
+
+    #[test]
+    fn test_render_html_escapes_title() {
+        let doc = render(&episode(), NotesFormat::Html).unwrap();
+
+        assert!(doc.contains("<h1>Q&amp;A</h1>"));
+        assert!(doc.contains("<a href=\"https://example.com\">site</a>"));
+        assert_eq!(render(&Episode::default(), NotesFormat::Html), None);
+    }
+}
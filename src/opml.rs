@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+
+/// A feed listed in an OPML document
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpmlFeed {
+    pub title: String,
+    pub url: String,
+}
+
+/// Collect every `<outline>` carrying an `xmlUrl`, including nested ones
+/// (podcast apps often group feeds into folders)
+pub fn parse(xml: &str) -> Result<Vec<OpmlFeed>> {
+    let mut reader = Reader::from_str(xml);
+    let mut feeds = Vec::new();
+
+    loop {
+        match reader.read_event().context("Failed to parse OPML")? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"outline" => {
+                let mut url = None;
+                let mut text = None;
+                let mut title = None;
+                for attr in e.attributes() {
+                    let attr = attr.context("Invalid OPML attribute")?;
+                    let value = attr
+                        .decode_and_unescape_value(reader.decoder())
+                        .context("Invalid OPML attribute value")?
+                        .trim()
+                        .to_string();
+                    match attr.key.local_name().as_ref() {
+                        b"xmlUrl" | b"xmlurl" => url = Some(value),
+                        b"text" => text = Some(value),
+                        b"title" => title = Some(value),
+                        _ => {}
+                    }
+                }
+
+                if let Some(url) = url.filter(|u| !u.is_empty()) {
+                    let title = title
+                        .or(text)
+                        .filter(|t| !t.is_empty())
+                        .unwrap_or_else(|| url.clone());
+                    feeds.push(OpmlFeed { title, url });
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(feeds)
+}
+
+/// Render `feeds` as an OPML 2.0 document importable by other podcast apps
+pub fn write(feeds: &[OpmlFeed]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <opml version=\"2.0\">\n  \
+         <head>\n    <title>pdl subscriptions</title>\n  </head>\n  \
+         <body>\n",
+    );
+    for feed in feeds {
+        let title = escape(feed.title.as_str());
+        out.push_str(&format!(
+            "    <outline type=\"rss\" text=\"{}\" title=\"{}\" xmlUrl=\"{}\"/>\n",
+            title,
+            title,
+            escape(feed.url.as_str())
+        ));
+    }
+    out.push_str("  </body>\n</opml>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested_outlines() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head><title>Subscriptions</title></head>
+  <body>
+    <outline text="feeds">
+      <outline type="rss" text="First &amp; Best" xmlUrl="https://example.com/1.rss"/>
+      <outline type="rss" text="ignored" title="Second" xmlUrl="https://example.com/2.rss"></outline>
+    </outline>
+    <outline type="rss" xmlUrl="https://example.com/3.rss"/>
+  </body>
+</opml>"#;
+
+        let feeds = parse(xml).unwrap();
+        assert_eq!(
+            feeds,
+            vec![
+                OpmlFeed {
+                    title: "First & Best".to_string(),
+                    url: "https://example.com/1.rss".to_string(),
+                },
+                OpmlFeed {
+                    title: "Second".to_string(),
+                    url: "https://example.com/2.rss".to_string(),
+                },
+                OpmlFeed {
+                    title: "https://example.com/3.rss".to_string(),
+                    url: "https://example.com/3.rss".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_roundtrips_through_parse() {
+        let feeds = vec![
+            OpmlFeed {
+                title: "Tom & Jerry's \"Show\"".to_string(),
+                url: "https://example.com/feed.rss?a=1&b=2".to_string(),
+            },
+            OpmlFeed {
+                title: "한글 팟캐스트".to_string(),
+                url: "https://example.com/ko.rss".to_string(),
+            },
+        ];
+
+        let xml = write(&feeds);
+        assert!(xml.contains("&amp;b=2"));
+        assert_eq!(parse(&xml).unwrap(), feeds);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_xml() {
+        assert!(parse("<opml><body><outline xmlUrl=\"x></body>").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::{Context, Result, bail};
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Players tried, in order, when none is configured
+const KNOWN_PLAYERS: [&str; 6] = ["mpv", "vlc", "afplay", "ffplay", "xdg-open", "open"];
+
+/// Where a player command's argument goes (appended if absent)
+const PLACEHOLDER: &str = "{}";
+
+/// An external program that plays files, e.g. `mpv --no-video`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Player {
+    program: String,
+    args: Vec<String>,
+}
+
+impl Player {
+    /// Parse a player command line. `{}` marks where the files go, as in
+    /// `vlc {} --play-and-exit`; without it they go at the end.
+    pub fn from_command(command: &str) -> Result<Self> {
+        let mut words = command.split_whitespace().map(str::to_string);
+        let Some(program) = words.next() else {
+            bail!("The player command is empty");
+        };
+        Ok(Self {
+            program,
+            args: words.collect(),
+        })
+    }
+
+    /// The configured player, else the first known one installed
+    pub fn find(command: Option<&str>) -> Result<Self> {
+        if let Some(command) = command {
+            return Self::from_command(command);
+        }
+        KNOWN_PLAYERS
+            .iter()
+            .find(|name| on_path(name).is_some())
+            .map(|name| Self::from_command(name))
+            .transpose()?
+            .with_context(|| {
+                format!(
+                    "No player found (tried {}); set one with `pdl config set player mpv`",
+                    KNOWN_PLAYERS.join(", ")
+                )
+            })
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// The command that plays `targets` (files or URLs) one after another
+    pub fn command<S: AsRef<std::ffi::OsStr>>(&self, targets: &[S]) -> Command {
+        let targets: Vec<OsString> = targets.iter().map(|t| t.as_ref().to_owned()).collect();
+        let mut command = Command::new(&self.program);
+        let mut placed = false;
+        for arg in &self.args {
+            if arg == PLACEHOLDER {
+                command.args(&targets);
+                placed = true;
+            } else {
+                command.arg(arg);
+            }
+        }
+        if !placed {
+            command.args(&targets);
+        }
+        command
+    }
+
+    /// Play `targets`, waiting until the player exits
+    pub fn play<S: AsRef<std::ffi::OsStr>>(&self, targets: &[S]) -> Result<()> {
+        let status = self
+            .command(targets)
+            .status()
+            .with_context(|| format!("Failed to start player {:?}", self.program))?;
+        if !status.success() {
+            bail!("Player exited with {}", status);
+        }
+        Ok(())
+    }
+}
+
+/// Full path of the program `name` if it is on `PATH`
+pub(crate) fn on_path(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path)
+        .flat_map(|dir| executable_names(name).map(move |name| dir.join(name)))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn executable_names(name: &str) -> impl Iterator<Item = String> + '_ {
+    let extensions: &[&str] = if cfg!(windows) {
+        &[".exe", ".com", ".bat"]
+    } else {
+        &[""]
+    };
+    extensions.iter().map(move |ext| format!("{}{}", name, ext))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_player_command() {
+        let player = Player::from_command("mpv  --no-video").unwrap();
+        let command = player.command(&["a.mp3", "b.mp3"]);
+        assert_eq!(command.get_program(), "mpv");
+        assert_eq!(args(&command), ["--no-video", "a.mp3", "b.mp3"]);
+
+        let player = Player::from_command("vlc {} --play-and-exit").unwrap();
+        assert_eq!(
+            args(&player.command(&["a.mp3"])),
+            ["a.mp3", "--play-and-exit"]
+        );
+
+        assert!(Player::from_command("  ").is_err());
+    }
+}
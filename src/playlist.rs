@@ -0,0 +1,175 @@
+use crate::download::show_dir;
+use crate::feed::Episode;
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Playlist of recently downloaded episodes across shows, in the download root
+pub const NEW_EPISODES_PLAYLIST: &str = "new-episodes.m3u8";
+
+/// Comment line carrying an entry's publish date, which M3U has no tag for;
+/// players skip it like any other comment
+const PUBLISHED_PREFIX: &str = "# published ";
+
+/// The playlist for `show`'s episodes in `dir`, named after the show
+pub fn show_playlist_path(dir: &Path, show: &str) -> PathBuf {
+    let name = show_dir(Path::new(""), show);
+    dir.join(format!("{}.m3u8", name.display()))
+}
+
+/// An episode file in a playlist
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaylistEntry {
+    pub path: PathBuf,
+    pub title: String,
+    pub duration: Option<Duration>,
+    pub published: Option<DateTime<FixedOffset>>,
+}
+
+impl PlaylistEntry {
+    pub fn new(episode: &Episode, path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            title: episode.title.clone(),
+            duration: episode.duration,
+            published: episode.published,
+        }
+    }
+}
+
+/// An extended M3U playlist (`.m3u8`, UTF-8) of episode files, oldest
+/// first. Paths inside the playlist's folder are written relative to it, so
+/// the folder can be moved or shared as a whole.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Playlist {
+    path: PathBuf,
+    entries: Vec<PlaylistEntry>,
+}
+
+impl Playlist {
+    /// Read the playlist at `path`, empty if there is none yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let entries = match fs::read_to_string(path) {
+            Ok(content) => parse(&content, base_dir(path)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn entries(&self) -> &[PlaylistEntry] {
+        &self.entries
+    }
+
+    /// Add `entry`, replacing any entry for the same file
+    pub fn add(&mut self, entry: PlaylistEntry) {
+        self.entries.retain(|existing| existing.path != entry.path);
+        self.entries.push(entry);
+    }
+
+    /// Write the playlist, dropping files that no longer exist and ordering
+    /// the rest by publish date (undated ones last)
+    pub fn save(&mut self) -> Result<()> {
+        self.entries.retain(|entry| entry.path.exists());
+        self.entries
+            .sort_by_key(|entry| (entry.published.is_none(), entry.published));
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&self.path, render(&self.entries, base_dir(&self.path)))
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+fn base_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+fn render(entries: &[PlaylistEntry], base: &Path) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for entry in entries {
+        if let Some(published) = entry.published {
+            out.push_str(&format!("{}{}\n", PUBLISHED_PREFIX, published.to_rfc3339()));
+        }
+        let seconds = entry.duration.map_or(-1, |d| d.as_secs() as i64);
+        // A comma ends the duration, but the title runs to the end of the line
+        let title = entry.title.replace(['\r', '\n'], " ");
+        out.push_str(&format!("#EXTINF:{},{}\n", seconds, title));
+        let path = entry.path.strip_prefix(base).unwrap_or(&entry.path);
+        out.push_str(&format!("{}\n", path.display()));
+    }
+    out
+}
+
+fn parse(content: &str, base: &Path) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut title = None;
+    let mut duration = None;
+    let mut published = None;
+    for line in content.lines().map(str::trim) {
+        if let Some(date) = line.strip_prefix(PUBLISHED_PREFIX) {
+            published = DateTime::parse_from_rfc3339(date.trim()).ok();
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (seconds, name) = info.split_once(',').unwrap_or((info, ""));
+            duration = seconds.trim().parse::<u64>().ok().map(Duration::from_secs);
+            title = Some(name.to_string());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let path = base.join(line);
+            entries.push(PlaylistEntry {
+                title: title.take().unwrap_or_else(|| {
+                    path.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                }),
+                path,
+                duration: duration.take(),
+                published: published.take(),
+            });
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_and_parse() {
+        let base = Path::new("/podcasts/My Show");
+        let date = |s: &str| DateTime::parse_from_rfc3339(s).ok();
+        let entries = vec![
+            PlaylistEntry {
+                path: base.join("One, the first.mp3"),
+                title: "One, the first".to_string(),
+                duration: Some(Duration::from_secs(1800)),
+                published: date("2024-01-01T10:00:00+00:00"),
+            },
+            PlaylistEntry {
+                path: PathBuf::from("/elsewhere/Two.mp3"),
+                title: "Two".to_string(),
+                duration: None,
+                published: None,
+            },
+        ];
+
+        let content = render(&entries, base);
+        assert_eq!(
+            content,
+            "#EXTM3U\n# published 2024-01-01T10:00:00+00:00\n#EXTINF:1800,One, the first\nOne, the first.mp3\n#EXTINF:-1,Two\n/elsewhere/Two.mp3\n"
+        );
+        assert_eq!(parse(&content, base), entries);
+
+        // Plain M3U files from elsewhere are read too
+        let plain = parse("Three.mp3\n", base);
+        assert_eq!(plain[0].title, "Three");
+        assert_eq!(plain[0].path, base.join("Three.mp3"));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::history::HistoryEntry;
+use anyhow::{Context, Result};
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sidecar files written next to an episode (show notes, chapters,
+/// transcripts), removed along with it
+const SIDECAR_SUFFIXES: [&str; 7] = [
+    ".md",
+    ".html",
+    ".chapters.json",
+    ".json",
+    ".srt",
+    ".vtt",
+    ".txt",
+];
+
+const GIGABYTE: f64 = 1_000_000_000.0;
+
+/// How many of a feed's downloads to keep on disk. Every limit that is set
+/// applies; an episode goes once it falls outside any of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Retention {
+    /// Keep only this many of the most recent downloads
+    pub keep_latest: Option<usize>,
+    /// Delete downloads older than this
+    pub max_age_days: Option<u64>,
+    /// Keep the most recent downloads that fit together in this many
+    /// gigabytes
+    pub max_size_gb: Option<f64>,
+}
+
+impl Retention {
+    /// Whether no limit is set, so nothing is ever pruned
+    pub fn is_empty(&self) -> bool {
+        self.keep_latest.is_none() && self.max_age_days.is_none() && self.max_size_gb.is_none()
+    }
+}
+
+/// A downloaded episode file still on disk
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Download {
+    pub title: String,
+    pub path: PathBuf,
+    /// UTC timestamp (`YYYY-MM-DD HH:MM:SS`)
+    pub downloaded_at: String,
+    pub size: u64,
+}
+
+/// The files of a feed's downloads that are still there, newest first, one
+/// per file even if it was downloaded more than once
+pub fn downloads(entries: Vec<HistoryEntry>) -> Vec<Download> {
+    let mut seen = HashSet::new();
+    let mut downloads: Vec<Download> = entries
+        .into_iter()
+        .filter(|entry| seen.insert(entry.path.clone()))
+        .filter_map(|entry| {
+            let size = fs::metadata(&entry.path).ok()?.len();
+            Some(Download {
+                title: entry.title,
+                path: entry.path,
+                downloaded_at: entry.downloaded_at,
+                size,
+            })
+        })
+        .collect();
+    downloads.sort_by(|a, b| b.downloaded_at.cmp(&a.downloaded_at));
+    downloads
+}
+
+/// The `downloads` (newest first) that fall outside `retention` as of `now`
+pub fn select(downloads: &[Download], retention: &Retention, now: NaiveDateTime) -> Vec<Download> {
+    let max_age = retention
+        .max_age_days
+        .and_then(|days| TimeDelta::try_days(days as i64));
+    let max_size = retention.max_size_gb.map(|gb| (gb * GIGABYTE) as u64);
+    let mut kept = 0;
+    let mut kept_size = 0u64;
+    // Once one download doesn't fit, older ones go too
+    let mut full = false;
+    let mut pruned = Vec::new();
+    for download in downloads {
+        let too_many = retention.keep_latest.is_some_and(|n| kept >= n);
+        let too_old = max_age.is_some_and(|max_age| {
+            NaiveDateTime::parse_from_str(&download.downloaded_at, "%Y-%m-%d %H:%M:%S")
+                .is_ok_and(|at| now - at > max_age)
+        });
+        full = full || max_size.is_some_and(|max| kept_size + download.size > max);
+        if too_many || too_old || full {
+            pruned.push(download.clone());
+        } else {
+            kept += 1;
+            kept_size += download.size;
+        }
+    }
+    pruned
+}
+
+/// Delete `audio` and the sidecar files saved with it
+pub fn remove(audio: &Path) -> Result<()> {
+    fs::remove_file(audio).with_context(|| format!("Failed to delete {}", audio.display()))?;
+    for sidecar in sidecars(audio) {
+        fs::remove_file(&sidecar)
+            .with_context(|| format!("Failed to delete {}", sidecar.display()))?;
+    }
+    Ok(())
+}
+
+/// The sidecar files saved next to `audio` that exist
+pub fn sidecars(audio: &Path) -> Vec<PathBuf> {
+    let stem = audio.with_extension("").into_os_string();
+    SIDECAR_SUFFIXES
+        .iter()
+        .map(|suffix| {
+            let mut sidecar = stem.clone();
+            sidecar.push(suffix);
+            PathBuf::from(sidecar)
+        })
+        .filter(|sidecar| sidecar.is_file())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn download(name: &str, downloaded_at: &str, size: u64) -> Download {
+        Download {
+            title: name.to_string(),
+            path: PathBuf::from(format!("{}.mp3", name)),
+            downloaded_at: downloaded_at.to_string(),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_select_by_retention() {
+        let downloads = [
+            download("new", "2024-03-01 00:00:00", 600_000_000),
+            download("mid", "2024-02-01 00:00:00", 600_000_000),
+            download("old", "2024-01-01 00:00:00", 100_000_000),
+        ];
+        let now =
+            NaiveDateTime::parse_from_str("2024-03-10 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let titles = |retention: Retention| -> Vec<String> {
+            select(&downloads, &retention, now)
+                .into_iter()
+                .map(|d| d.title)
+                .collect()
+        };
+
+        assert!(titles(Retention::default()).is_empty());
+        let latest = Retention {
+            keep_latest: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(titles(latest), ["mid", "old"]);
+        let age = Retention {
+            max_age_days: Some(45),
+            ..Default::default()
+        };
+        assert_eq!(titles(age), ["old"]);
+        // Older downloads don't take the place of a newer one that didn't fit
+        let size = Retention {
+            max_size_gb: Some(1.0),
+            ..Default::default()
+        };
+        assert_eq!(titles(size), ["mid", "old"]);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::feed::Episode;
+use chrono::DateTime;
+use rusqlite::{Connection, params, params_from_iter};
+use std::time::Duration;
+
+/// An episode waiting in the download queue
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueItem {
+    pub id: i64,
+    pub feed_url: String,
+    pub show: String,
+    pub episode: Episode,
+    /// UTC timestamp (`YYYY-MM-DD HH:MM:SS`)
+    pub added_at: String,
+    /// Left alone by `pdl queue start` until resumed
+    pub paused: bool,
+    /// Why the last attempt to download it failed, if it did
+    pub error: Option<String>,
+}
+
+/// Create the queue table. Episodes are kept whole, so the queue can be
+/// worked through without fetching their feeds again.
+pub(crate) fn init(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS queue (
+            id INTEGER PRIMARY KEY,
+            feed_url TEXT NOT NULL,
+            key TEXT NOT NULL,
+            show TEXT NOT NULL,
+            title TEXT NOT NULL,
+            url TEXT NOT NULL,
+            guid TEXT,
+            published TEXT,
+            description TEXT,
+            number INTEGER,
+            season INTEGER,
+            image TEXT,
+            chapters_url TEXT,
+            mime_type TEXT,
+            duration INTEGER,
+            size INTEGER,
+            position INTEGER,
+            added_at TEXT NOT NULL DEFAULT (datetime('now')),
+            paused INTEGER NOT NULL DEFAULT 0,
+            error TEXT,
+            UNIQUE (feed_url, key)
+        );",
+    )
+}
+
+/// Put `episode` of `show` at the end of the queue, returning whether it
+/// wasn't there already
+pub(crate) fn add(
+    conn: &Connection,
+    feed_url: &str,
+    show: &str,
+    episode: &Episode,
+) -> rusqlite::Result<bool> {
+    let added = conn.execute(
+        "INSERT OR IGNORE INTO queue (feed_url, key, show, title, url, guid, published,
+            description, number, season, image, chapters_url, mime_type, duration, size, position)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        params![
+            feed_url,
+            episode.key(),
+            show,
+            episode.title,
+            episode.url,
+            episode.guid,
+            episode.published.map(|d| d.to_rfc3339()),
+            episode.description,
+            episode.number,
+            episode.season,
+            episode.image,
+            episode.chapters_url,
+            episode.mime_type,
+            episode.duration.map(|d| d.as_secs() as i64),
+            episode.size.map(|s| s as i64),
+            episode.position,
+        ],
+    )?;
+    Ok(added > 0)
+}
+
+/// Everything in the queue, in the order it was added
+pub(crate) fn items(conn: &Connection) -> rusqlite::Result<Vec<QueueItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, feed_url, show, title, url, guid, published, description, number, season,
+            image, chapters_url, mime_type, duration, size, position, added_at, paused, error
+         FROM queue ORDER BY id",
+    )?;
+    stmt.query_map([], |row| {
+        let published: Option<String> = row.get(6)?;
+        let duration: Option<i64> = row.get(13)?;
+        let size: Option<i64> = row.get(14)?;
+        Ok(QueueItem {
+            id: row.get(0)?,
+            feed_url: row.get(1)?,
+            show: row.get(2)?,
+            episode: Episode {
+                title: row.get(3)?,
+                url: row.get(4)?,
+                guid: row.get(5)?,
+                published: published.and_then(|d| DateTime::parse_from_rfc3339(&d).ok()),
+                description: row.get(7)?,
+                number: row.get(8)?,
+                season: row.get(9)?,
+                image: row.get(10)?,
+                chapters_url: row.get(11)?,
+                mime_type: row.get(12)?,
+                duration: duration.map(|d| Duration::from_secs(d as u64)),
+                size: size.map(|s| s as u64),
+                position: row.get(15)?,
+                ..Episode::default()
+            },
+            added_at: row.get(16)?,
+            paused: row.get(17)?,
+            error: row.get(18)?,
+        })
+    })?
+    .collect()
+}
+
+/// Take the items with these ids out of the queue, returning how many there were
+pub(crate) fn remove(conn: &Connection, ids: &[i64]) -> rusqlite::Result<usize> {
+    conn.execute(
+        &format!(
+            "DELETE FROM queue WHERE id IN ({})",
+            placeholders(ids.len())
+        ),
+        params_from_iter(ids),
+    )
+}
+
+/// Take `episode` of the feed at `feed_url` out of the queue, if it's there
+pub(crate) fn remove_episode(
+    conn: &Connection,
+    feed_url: &str,
+    episode: &Episode,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM queue WHERE feed_url = ?1 AND key = ?2",
+        params![feed_url, episode.key()],
+    )?;
+    Ok(())
+}
+
+/// Pause or resume the items with these ids, or every item if `ids` is
+/// empty, returning how many there were
+pub(crate) fn set_paused(conn: &Connection, ids: &[i64], paused: bool) -> rusqlite::Result<usize> {
+    if ids.is_empty() {
+        return conn.execute("UPDATE queue SET paused = ?1", [paused]);
+    }
+    conn.execute(
+        &format!(
+            "UPDATE queue SET paused = {} WHERE id IN ({})",
+            i64::from(paused),
+            placeholders(ids.len())
+        ),
+        params_from_iter(ids),
+    )
+}
+
+/// Note why downloading `episode` of the feed at `feed_url` failed, if
+/// it's queued
+pub(crate) fn set_error(
+    conn: &Connection,
+    feed_url: &str,
+    episode: &Episode,
+    error: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE queue SET error = ?3 WHERE feed_url = ?1 AND key = ?2",
+        params![feed_url, episode.key(), error],
+    )?;
+    Ok(())
+}
+
+/// `?, ?, ?` for `n` values
+fn placeholders(n: usize) -> String {
+    vec!["?"; n].join(", ")
+}
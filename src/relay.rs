@@ -0,0 +1,186 @@
+use crate::client::Http;
+use crate::retry::{RetryPolicy, check_response};
+use anyhow::{Context, Result, bail};
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Longest request head a player may send
+const MAX_REQUEST_HEAD: usize = 16 * 1024;
+
+/// Response headers passed on from the server to the player
+const FORWARDED_HEADERS: [reqwest::header::HeaderName; 4] =
+    [CONTENT_TYPE, CONTENT_LENGTH, CONTENT_RANGE, ACCEPT_RANGES];
+
+/// Serves one episode's media on a local port, fetching it through pdl's
+/// HTTP client (with its credentials, headers and proxy) as a player reads
+/// it. Nothing is written to disk. Range requests are passed on, so players
+/// can seek. Stops serving when dropped.
+#[derive(Debug)]
+pub struct Relay {
+    url: String,
+    task: JoinHandle<()>,
+}
+
+impl Relay {
+    pub(crate) async fn start(http: Http, retry: RetryPolicy, url: &str) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .context("Failed to open a local port for streaming")?;
+        let addr: SocketAddr = listener.local_addr()?;
+        let upstream = url.to_string();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (http, upstream) = (http.clone(), upstream.clone());
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream, &http, &retry, &upstream).await {
+                        debug!("Relay connection failed: {:#}", e);
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            url: format!("http://{}/episode", addr),
+            task,
+        })
+    }
+
+    /// Local URL to hand to the player
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// What a player asked the relay for
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Request {
+    head_only: bool,
+    range: Option<String>,
+}
+
+async fn serve(mut stream: TcpStream, http: &Http, retry: &RetryPolicy, url: &str) -> Result<()> {
+    let request = read_request(&mut stream).await?;
+    let response = retry
+        .run(|| async {
+            let mut upstream = http.get(url);
+            if let Some(range) = &request.range {
+                upstream = upstream.header(RANGE, range);
+            }
+            check_response(upstream.send().await.context("Failed to start stream")?)
+        })
+        .await;
+    let mut response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            let _ = write_head(&mut stream, "502 Bad Gateway", &[]).await;
+            return Err(e);
+        }
+    };
+
+    let status = response.status();
+    let headers: Vec<(&str, String)> = FORWARDED_HEADERS
+        .iter()
+        .filter_map(|name| {
+            let value = response.headers().get(name)?.to_str().ok()?;
+            Some((name.as_str(), value.to_string()))
+        })
+        .collect();
+    write_head(&mut stream, &status.to_string(), &headers).await?;
+    if request.head_only {
+        return Ok(());
+    }
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .context("Failed to read stream chunk")?
+    {
+        // The player hanging up (e.g. to seek elsewhere) ends this connection
+        stream.write_all(&chunk).await?;
+    }
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_HEAD {
+            bail!("Request head too long");
+        }
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            bail!("Connection closed before the request was complete");
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    parse_request(&String::from_utf8_lossy(&head))
+}
+
+fn parse_request(head: &str) -> Result<Request> {
+    let mut lines = head.lines();
+    let method = lines
+        .next()
+        .and_then(|line| line.split_whitespace().next())
+        .context("Empty request")?;
+    let head_only = match method {
+        "GET" => false,
+        "HEAD" => true,
+        other => bail!("Unsupported method {}", other),
+    };
+    let range = lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("range")
+            .then(|| value.trim().to_string())
+    });
+    Ok(Request { head_only, range })
+}
+
+async fn write_head(
+    stream: &mut (impl AsyncWrite + Unpin),
+    status: &str,
+    headers: &[(&str, String)],
+) -> Result<()> {
+    let mut head = format!("HTTP/1.1 {}\r\n", status);
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request =
+            parse_request("GET /episode HTTP/1.1\r\nHost: 127.0.0.1\r\nrange: bytes=100-\r\n\r\n")
+                .unwrap();
+        assert_eq!(
+            request,
+            Request {
+                head_only: false,
+                range: Some("bytes=100-".to_string()),
+            }
+        );
+        assert!(
+            parse_request("HEAD /episode HTTP/1.1\r\n\r\n")
+                .unwrap()
+                .head_only
+        );
+        assert!(parse_request("POST /episode HTTP/1.1\r\n\r\n").is_err());
+    }
+}
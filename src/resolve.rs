@@ -0,0 +1,134 @@
+use crate::client::Http;
+use crate::discover::discover_feeds;
+use crate::retry::check_response;
+use crate::search;
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use reqwest::Url;
+use std::sync::LazyLock;
+use tracing::debug;
+
+/// iTunes IDs in links to Apple Podcasts (`/id123`) or Overcast (`/itunes123`)
+static ITUNES_ID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:(?:podcasts|itunes)\.apple\.com/[^\s\x22'<>]*?/id|overcast\.fm/itunes|\x22/itunes)(\d+)")
+        .unwrap()
+});
+
+/// A link copied from a podcast app's share sheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareLink {
+    /// A show on Apple Podcasts, or an Overcast show link, by iTunes ID
+    Itunes(u64),
+    /// A page that names the show without a feed URL (Overcast episode
+    /// links, Pocket Casts); the feed is found by reading the page
+    Page,
+    /// Spotify, which doesn't publish RSS feeds
+    Spotify,
+}
+
+impl ShareLink {
+    /// Recognise a podcast app link, `None` for anything else
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_ascii_lowercase();
+        let host = host.strip_prefix("www.").unwrap_or(&host);
+        let segments: Vec<&str> = url.path_segments()?.collect();
+        let id_after = |prefix: &str| {
+            segments
+                .iter()
+                .find_map(|segment| segment.strip_prefix(prefix)?.parse().ok())
+        };
+        match host {
+            "podcasts.apple.com" | "itunes.apple.com" => id_after("id").map(ShareLink::Itunes),
+            "overcast.fm" => Some(id_after("itunes").map_or(ShareLink::Page, ShareLink::Itunes)),
+            "pca.st" | "pocketcasts.com" | "play.pocketcasts.com" => Some(ShareLink::Page),
+            "open.spotify.com" | "spotify.link" => Some(ShareLink::Spotify),
+            _ => None,
+        }
+    }
+}
+
+/// The RSS feed behind a podcast app link, or `None` if `url` isn't one
+pub(crate) async fn resolve(client: &Http, url: &str) -> Result<Option<String>> {
+    let id = match ShareLink::parse(url) {
+        None => return Ok(None),
+        Some(ShareLink::Spotify) => {
+            bail!("Spotify doesn't publish RSS feeds; find the show with `pdl search` instead")
+        }
+        Some(ShareLink::Itunes(id)) => id,
+        Some(ShareLink::Page) => {
+            let response = client
+                .get(url)
+                .send()
+                .await
+                .context("Failed to fetch page")?;
+            let response = check_response(response).context("Failed to fetch page")?;
+            let page_url = response.url().to_string();
+            let html = response.text().await.context("Failed to read page")?;
+            if let Some(feed) = discover_feeds(&html, &page_url).into_iter().next() {
+                return Ok(Some(feed.url));
+            }
+            match itunes_id_in_page(&html) {
+                Some(id) => id,
+                None => bail!("Couldn't find the podcast feed behind {}", url),
+            }
+        }
+    };
+
+    debug!("Looking up iTunes ID {} for {}", id, url);
+    let podcast = search::lookup_itunes(&client.client, id).await?;
+    match podcast {
+        Some(podcast) => Ok(Some(podcast.feed_url)),
+        None => bail!("Apple Podcasts has no public RSS feed for {}", url),
+    }
+}
+
+/// The first iTunes ID a share page links to
+fn itunes_id_in_page(html: &str) -> Option<u64> {
+    ITUNES_ID
+        .captures_iter(html)
+        .find_map(|captures| captures[1].parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_share_link() {
+        assert_eq!(
+            ShareLink::parse("https://podcasts.apple.com/us/podcast/cozy-up/id1234567890?i=1000"),
+            Some(ShareLink::Itunes(1234567890))
+        );
+        assert_eq!(
+            ShareLink::parse("https://overcast.fm/itunes1234567890/cozy-up"),
+            Some(ShareLink::Itunes(1234567890))
+        );
+        assert_eq!(
+            ShareLink::parse("https://overcast.fm/+AbCdEf"),
+            Some(ShareLink::Page)
+        );
+        assert_eq!(
+            ShareLink::parse("https://pca.st/x1y2"),
+            Some(ShareLink::Page)
+        );
+        assert_eq!(
+            ShareLink::parse("https://open.spotify.com/show/abc"),
+            Some(ShareLink::Spotify)
+        );
+        assert_eq!(
+            ShareLink::parse("https://podcasts.apple.com/us/browse"),
+            None
+        );
+        assert_eq!(ShareLink::parse("https://example.com/id123/feed.rss"), None);
+    }
+
+    #[test]
+    fn test_itunes_id_in_page() {
+        let overcast = r#"<a href="/itunes1234567890/cozy-up" class="ordinarylink">Cozy Up</a>"#;
+        assert_eq!(itunes_id_in_page(overcast), Some(1234567890));
+        let apple = r#"<a href="https://podcasts.apple.com/us/podcast/cozy-up/id987654">Apple</a>"#;
+        assert_eq!(itunes_id_in_page(apple), Some(987654));
+        assert_eq!(itunes_id_in_page("<p>id123</p>"), None);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::checksum::ChecksumMismatch;
+use crate::download::SizeMismatch;
+use crate::interrupt;
+use anyhow::Result;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Response, StatusCode};
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+/// Longest pause between two attempts, however many retries have happened
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Longest `Retry-After` pdl is willing to wait before giving up
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(15 * 60);
+
+/// The server asked us to slow down (HTTP 429, or 503 with `Retry-After`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimited {
+    pub status: StatusCode,
+    /// How long the server asked us to wait, if it said
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rate limited by server (HTTP {})", self.status.as_u16())?;
+        if let Some(wait) = self.retry_after {
+            write!(f, "; asked to retry after {}s", wait.as_secs())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// Turn rate-limit responses into [`RateLimited`] and other error statuses
+/// into errors, so their bodies never get treated as content
+pub fn check_response(response: Response) -> Result<Response> {
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_retry_after(v, SystemTime::now()));
+
+    if status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::SERVICE_UNAVAILABLE && retry_after.is_some())
+    {
+        return Err(RateLimited {
+            status,
+            retry_after,
+        }
+        .into());
+    }
+    Ok(response.error_for_status()?)
+}
+
+/// Parse a `Retry-After` value: delay in seconds or an HTTP date
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+/// How often and how patiently to retry transient network failures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (0 disables retrying)
+    pub retries: u32,
+    /// Delay before the first retry; doubled for each one after
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            base_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub fn none() -> Self {
+        Self {
+            retries: 0,
+            base_delay: Duration::ZERO,
+        }
+    }
+
+    /// Run `op`, retrying with exponential backoff while it fails transiently
+    pub async fn run<T, F, Fut>(&self, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match op().await {
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    // Honor the server's own schedule when it gave one
+                    let delay = match rate_limit(&e).and_then(|r| r.retry_after) {
+                        Some(wait) if wait > MAX_RETRY_AFTER => return Err(e),
+                        Some(wait) => wait,
+                        None => self.delay(attempt),
+                    };
+                    debug!(
+                        "Retrying in {:.1}s (attempt {} of {}): {:#}",
+                        delay.as_secs_f64(),
+                        attempt + 1,
+                        self.retries,
+                        e
+                    );
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = interrupt::interrupted() => return Err(e),
+                    }
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Backoff before retry number `attempt` (0-based), with ±50% jitter so
+    /// parallel downloads don't hammer a struggling server in lockstep
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_DELAY);
+        backoff.mul_f64(0.5 + fastrand::f64())
+    }
+}
+
+fn rate_limit(error: &anyhow::Error) -> Option<&RateLimited> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<RateLimited>())
+}
+
+/// Whether an error is worth retrying: timeouts, dropped connections, rate
+/// limiting, 5xx responses and downloads that arrived truncated or corrupted
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    if rate_limit(error).is_some() {
+        return true;
+    }
+    error.chain().any(|cause| {
+        if cause.is::<SizeMismatch>() || cause.is::<ChecksumMismatch>() {
+            return true;
+        }
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return e.is_timeout()
+                || e.is_connect()
+                || e.is_body()
+                || e.status().is_some_and(|s| s.is_server_error());
+        }
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            return matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::UnexpectedEof
+            );
+        }
+        false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+    use std::cell::Cell;
+
+    #[test]
+    fn test_delay_grows_exponentially_within_jitter() {
+        let policy = RetryPolicy {
+            retries: 5,
+            base_delay: Duration::from_secs(1),
+        };
+        for attempt in 0..4 {
+            let expected = Duration::from_secs(1 << attempt);
+            let delay = policy.delay(attempt);
+            assert!(delay >= expected / 2 && delay <= expected * 3 / 2);
+        }
+        assert!(policy.delay(20) <= MAX_DELAY * 3 / 2);
+    }
+
+    #[test]
+    fn test_is_transient_walks_error_chain() {
+        let reset = Err::<(), _>(io::Error::from(io::ErrorKind::ConnectionReset))
+            .context("Failed to read download chunk")
+            .unwrap_err();
+        assert!(is_transient(&reset));
+
+        let not_found = Err::<(), _>(io::Error::from(io::ErrorKind::NotFound))
+            .context("Failed to open file")
+            .unwrap_err();
+        assert!(!is_transient(&not_found));
+        assert!(!is_transient(&anyhow!("Failed to parse feed")));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = httpdate::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        // Dates in the past mean "now"
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_rate_limited_is_transient() {
+        let error = anyhow::Error::from(RateLimited {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            retry_after: Some(Duration::from_secs(5)),
+        })
+        .context("Download request failed");
+
+        assert!(is_transient(&error));
+        assert_eq!(
+            format!("{:#}", error),
+            "Download request failed: Rate limited by server (HTTP 429); asked to retry after 5s"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_retries_transient_errors_only() {
+        let policy = RetryPolicy {
+            retries: 2,
+            base_delay: Duration::ZERO,
+        };
+
+        let calls = Cell::new(0);
+        let result: Result<()> = policy
+            .run(|| async {
+                calls.set(calls.get() + 1);
+                Err(io::Error::from(io::ErrorKind::TimedOut).into())
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let result: Result<()> = policy
+            .run(|| async {
+                calls.set(calls.get() + 1);
+                Err(anyhow!("not found"))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::{Context, Result, bail};
+use chrono::{Local, NaiveTime, Timelike};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+const DAY_SECS: u32 = 24 * 60 * 60;
+
+/// Hours of the day (local time) when `pdl watch` and `pdl queue start` may
+/// download, e.g. `01:00-06:00` for a connection that's unmetered at night.
+/// A window whose end is before its start runs past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl DownloadWindow {
+    /// Whether downloads may run right now
+    pub fn is_open(&self) -> bool {
+        self.contains(Local::now().time())
+    }
+
+    /// How long until the window next opens: zero while it's open
+    pub fn until_open(&self) -> Duration {
+        self.wait_from(Local::now().time())
+    }
+
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    fn wait_from(&self, time: NaiveTime) -> Duration {
+        if self.contains(time) {
+            return Duration::ZERO;
+        }
+        let now = time.num_seconds_from_midnight();
+        let start = self.start.num_seconds_from_midnight();
+        Duration::from_secs(u64::from((start + DAY_SECS - now) % DAY_SECS))
+    }
+}
+
+impl FromStr for DownloadWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let Some((start, end)) = value.split_once('-') else {
+            bail!("Expected hours like 01:00-06:00, not {:?}", value);
+        };
+        let time = |s: &str| {
+            NaiveTime::parse_from_str(s.trim(), "%H:%M")
+                .with_context(|| format!("Invalid time {:?}", s.trim()))
+        };
+        let window = Self {
+            start: time(start)?,
+            end: time(end)?,
+        };
+        if window.start == window.end {
+            bail!("{:?} starts and ends at the same time", value);
+        }
+        Ok(window)
+    }
+}
+
+impl fmt::Display for DownloadWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_download_window() {
+        let night: DownloadWindow = "01:00-06:00".parse().unwrap();
+        assert_eq!(night.to_string(), "01:00-06:00");
+        assert!(night.contains(at("01:00")));
+        assert!(night.contains(at("05:59")));
+        assert!(!night.contains(at("06:00")));
+        assert_eq!(night.wait_from(at("03:00")), Duration::ZERO);
+        assert_eq!(night.wait_from(at("00:30")), Duration::from_secs(30 * 60));
+        assert_eq!(night.wait_from(at("23:00")), Duration::from_secs(2 * 3600));
+
+        // Past midnight
+        let late: DownloadWindow = "22:30 - 2:00".parse().unwrap();
+        assert_eq!(late.to_string(), "22:30-02:00");
+        assert!(late.contains(at("23:00")));
+        assert!(late.contains(at("01:00")));
+        assert!(!late.contains(at("12:00")));
+        assert_eq!(late.wait_from(at("22:00")), Duration::from_secs(30 * 60));
+
+        assert!("01:00".parse::<DownloadWindow>().is_err());
+        assert!("01:00-25:00".parse::<DownloadWindow>().is_err());
+        assert!("06:00-06:00".parse::<DownloadWindow>().is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ITUNES_SEARCH_URL: &str = "https://itunes.apple.com/search";
+const ITUNES_LOOKUP_URL: &str = "https://itunes.apple.com/lookup";
+const PODCAST_INDEX_SEARCH_URL: &str = "https://api.podcastindex.org/api/1.0/search/byterm";
+
+/// Podcast directory used by `pdl search`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchBackend {
+    /// Apple's iTunes Search API (no account needed)
+    #[default]
+    Itunes,
+    /// podcastindex.org (requires a free API key and secret)
+    #[value(name = "podcastindex")]
+    #[serde(rename = "podcastindex")]
+    PodcastIndex,
+}
+
+/// API key pair for podcastindex.org
+#[derive(Debug, Clone, PartialEq)]
+pub struct PodcastIndexCredentials {
+    pub key: String,
+    pub secret: String,
+}
+
+/// A podcast found by a directory search
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    pub title: String,
+    pub author: Option<String>,
+    pub feed_url: String,
+    pub episode_count: Option<u32>,
+    pub artwork_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItunesResponse {
+    #[serde(default)]
+    results: Vec<ItunesPodcast>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItunesPodcast {
+    collection_name: Option<String>,
+    artist_name: Option<String>,
+    feed_url: Option<String>,
+    track_count: Option<u32>,
+    artwork_url600: Option<String>,
+    artwork_url100: Option<String>,
+}
+
+/// Search the iTunes directory for podcasts matching `terms`
+pub async fn search_itunes(
+    client: &Client,
+    terms: &str,
+    limit: usize,
+) -> Result<Vec<SearchResult>> {
+    let limit = limit.to_string();
+    let body = client
+        .get(ITUNES_SEARCH_URL)
+        .query(&[
+            ("media", "podcast"),
+            ("entity", "podcast"),
+            ("term", terms),
+            ("limit", &limit),
+        ])
+        .send()
+        .await
+        .context("Failed to query iTunes search")?
+        .error_for_status()
+        .context("Failed to query iTunes search")?
+        .bytes()
+        .await
+        .context("Failed to read iTunes search response")?;
+
+    parse_itunes(&body)
+}
+
+/// Look up a podcast by its iTunes ID (the `id123` in Apple Podcasts links).
+/// `None` when Apple has no public feed for it.
+pub async fn lookup_itunes(client: &Client, id: u64) -> Result<Option<SearchResult>> {
+    let id = id.to_string();
+    let body = client
+        .get(ITUNES_LOOKUP_URL)
+        .query(&[("id", id.as_str()), ("entity", "podcast")])
+        .send()
+        .await
+        .context("Failed to query iTunes lookup")?
+        .error_for_status()
+        .context("Failed to query iTunes lookup")?
+        .bytes()
+        .await
+        .context("Failed to read iTunes lookup response")?;
+
+    Ok(parse_itunes(&body)?.into_iter().next())
+}
+
+#[derive(Debug, Deserialize)]
+struct PodcastIndexResponse {
+    #[serde(default)]
+    feeds: Vec<PodcastIndexFeed>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PodcastIndexFeed {
+    title: Option<String>,
+    url: Option<String>,
+    author: Option<String>,
+    episode_count: Option<u32>,
+    artwork: Option<String>,
+    image: Option<String>,
+}
+
+/// Search podcastindex.org for podcasts matching `terms`
+pub async fn search_podcast_index(
+    client: &Client,
+    credentials: &PodcastIndexCredentials,
+    terms: &str,
+    limit: usize,
+) -> Result<Vec<SearchResult>> {
+    let date = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System clock is before 1970")?
+        .as_secs()
+        .to_string();
+    let max = limit.to_string();
+
+    let body = client
+        .get(PODCAST_INDEX_SEARCH_URL)
+        .query(&[("q", terms), ("max", &max)])
+        .header("User-Agent", concat!("pdl/", env!("CARGO_PKG_VERSION")))
+        .header("X-Auth-Key", &credentials.key)
+        .header("X-Auth-Date", &date)
+        .header(
+            "Authorization",
+            podcast_index_authorization(credentials, &date),
+        )
+        .send()
+        .await
+        .context("Failed to query Podcast Index")?
+        .error_for_status()
+        .context("Failed to query Podcast Index")?
+        .bytes()
+        .await
+        .context("Failed to read Podcast Index response")?;
+
+    parse_podcast_index(&body)
+}
+
+/// Podcast Index request signature: hex SHA-1 of key + secret + date
+fn podcast_index_authorization(credentials: &PodcastIndexCredentials, date: &str) -> String {
+    let mut hasher = sha1_smol::Sha1::new();
+    hasher.update(credentials.key.as_bytes());
+    hasher.update(credentials.secret.as_bytes());
+    hasher.update(date.as_bytes());
+    hasher.digest().to_string()
+}
+
+fn parse_podcast_index(body: &[u8]) -> Result<Vec<SearchResult>> {
+    let response: PodcastIndexResponse =
+        serde_json::from_slice(body).context("Failed to parse Podcast Index response")?;
+
+    Ok(response
+        .feeds
+        .into_iter()
+        .filter_map(|f| {
+            let feed_url = f.url.filter(|u| !u.is_empty())?;
+            Some(SearchResult {
+                title: f.title.unwrap_or_else(|| feed_url.clone()),
+                author: f.author.filter(|a| !a.is_empty()),
+                feed_url,
+                episode_count: f.episode_count,
+                artwork_url: f
+                    .artwork
+                    .filter(|a| !a.is_empty())
+                    .or(f.image.filter(|i| !i.is_empty())),
+            })
+        })
+        .collect())
+}
+
+/// Parse an iTunes search/lookup response, dropping entries without a feed URL
+pub(crate) fn parse_itunes(body: &[u8]) -> Result<Vec<SearchResult>> {
+    let response: ItunesResponse =
+        serde_json::from_slice(body).context("Failed to parse iTunes search response")?;
+
+    Ok(response
+        .results
+        .into_iter()
+        .filter_map(|p| {
+            let feed_url = p.feed_url?;
+            Some(SearchResult {
+                title: p.collection_name.unwrap_or_else(|| feed_url.clone()),
+                author: p.artist_name,
+                feed_url,
+                episode_count: p.track_count,
+                artwork_url: p.artwork_url600.or(p.artwork_url100),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_itunes_skips_results_without_feed() {
+        let body = br#"{
+            "resultCount": 2,
+            "results": [
+                {
+                    "collectionName": "Cozy Up",
+                    "artistName": "Omny",
+                    "feedUrl": "https://omny.fm/shows/cozy-up/podcast.rss",
+                    "trackCount": 120,
+                    "artworkUrl100": "https://example.com/100.jpg",
+                    "artworkUrl600": "https://example.com/600.jpg"
+                },
+                {"collectionName": "Apple exclusive"}
+            ]
+        }"#;
+
+        let results = parse_itunes(body).unwrap();
+        assert_eq!(
+            results,
+            vec![SearchResult {
+                title: "Cozy Up".to_string(),
+                author: Some("Omny".to_string()),
+                feed_url: "https://omny.fm/shows/cozy-up/podcast.rss".to_string(),
+                episode_count: Some(120),
+                artwork_url: Some("https://example.com/600.jpg".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_podcast_index() {
+        let body = br#"{
+            "status": "true",
+            "feeds": [
+                {
+                    "id": 1,
+                    "title": "Indie Show",
+                    "url": "https://example.com/indie.rss",
+                    "author": "",
+                    "artwork": "",
+                    "image": "https://example.com/image.jpg",
+                    "episodeCount": 42
+                },
+                {"id": 2, "title": "Broken", "url": ""}
+            ],
+            "count": 2
+        }"#;
+
+        let results = parse_podcast_index(body).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].feed_url, "https://example.com/indie.rss");
+        assert_eq!(results[0].author, None);
+        assert_eq!(results[0].episode_count, Some(42));
+        // An empty artwork field falls back to `image`
+        assert_eq!(
+            results[0].artwork_url.as_deref(),
+            Some("https://example.com/image.jpg")
+        );
+    }
+
+    #[test]
+    fn test_podcast_index_authorization() {
+        let credentials = PodcastIndexCredentials {
+            key: "key".to_string(),
+            secret: "secret".to_string(),
+        };
+        assert_eq!(
+            podcast_index_authorization(&credentials, "1700000000"),
+            "abaf71c02050c31e4d4e6b08c1625173af0445ba"
+        );
+    }
+
+    #[test]
+    fn test_parse_itunes_empty() {
+        assert!(
+            parse_itunes(br#"{"resultCount": 0, "results": []}"#)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use crate::feed::Episode;
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use regex::Regex;
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Picks episodes without prompting, for scripted runs
+#[derive(Debug, Clone)]
+pub enum Selector {
+    /// The newest episode (first in the feed)
+    Latest,
+    /// A 1-based position in the episode list, or an episode GUID
+    Episode(String),
+    /// Every episode whose title matches the pattern
+    Match(Regex),
+}
+
+impl Selector {
+    /// Episodes in `episodes` picked by this selector, in feed order
+    pub fn select<'a>(&self, episodes: &'a [Episode]) -> Vec<&'a Episode> {
+        match self {
+            Selector::Latest => episodes.iter().take(1).collect(),
+            Selector::Episode(key) => {
+                let by_index = key
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| i.checked_sub(1))
+                    .and_then(|i| episodes.get(i));
+                let by_guid = || episodes.iter().find(|ep| ep.guid.as_deref() == Some(key));
+                by_index.or_else(by_guid).into_iter().collect()
+            }
+            Selector::Match(pattern) => episodes
+                .iter()
+                .filter(|ep| pattern.is_match(&ep.title))
+                .collect(),
+        }
+    }
+}
+
+/// Narrows a feed's episodes before they are listed or selected
+#[derive(Debug, Clone, Default)]
+pub struct EpisodeFilter {
+    /// Keep titles matching this pattern
+    pub title: Option<Regex>,
+    /// Keep episodes published on or after this day
+    pub since: Option<NaiveDate>,
+    /// Keep episodes published on or before this day
+    pub until: Option<NaiveDate>,
+    /// Keep episodes of this `<itunes:season>`
+    pub season: Option<u32>,
+    /// Keep episodes whose `<itunes:episode>` number is in these ranges
+    pub numbers: Option<NumberRanges>,
+    /// Keep played (`true`) or unplayed (`false`) episodes, going by
+    /// `played_keys`
+    pub played: Option<bool>,
+    /// [`Episode::key`]s of the feed's played episodes, filled in from the
+    /// history once the feed is known
+    pub played_keys: HashSet<String>,
+}
+
+impl EpisodeFilter {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.since.is_none()
+            && self.until.is_none()
+            && self.played.is_none()
+            && !self.by_number()
+    }
+
+    /// Whether the filter picks episodes by season or episode number
+    pub fn by_number(&self) -> bool {
+        self.season.is_some() || self.numbers.is_some()
+    }
+
+    /// Whether `episode` passes every condition. Episodes without a date or
+    /// number fail any condition on it.
+    pub fn matches(&self, episode: &Episode) -> bool {
+        let date = episode.published.map(|d| d.date_naive());
+        let title = |re: &Regex| re.is_match(&episode.title);
+        let number = |ranges: &NumberRanges| episode.number.is_some_and(|n| ranges.contains(n));
+        self.title.as_ref().is_none_or(title)
+            && self
+                .since
+                .is_none_or(|since| date.is_some_and(|d| d >= since))
+            && self
+                .until
+                .is_none_or(|until| date.is_some_and(|d| d <= until))
+            && self
+                .season
+                .is_none_or(|season| episode.season == Some(season))
+            && self.numbers.as_ref().is_none_or(number)
+            && self
+                .played
+                .is_none_or(|played| self.played_keys.contains(episode.key()) == played)
+    }
+
+    /// Drop the episodes that don't match
+    pub fn apply(&self, episodes: &mut Vec<Episode>) {
+        episodes.retain(|episode| self.matches(episode));
+    }
+}
+
+/// Episode numbers such as `10-20` or `1,4,7-9`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberRanges(Vec<RangeInclusive<u32>>);
+
+impl NumberRanges {
+    pub fn contains(&self, n: u32) -> bool {
+        self.0.iter().any(|range| range.contains(&n))
+    }
+}
+
+impl FromStr for NumberRanges {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<u32>()
+                .with_context(|| format!("Invalid episode number {:?}", n.trim()))
+        };
+        let mut ranges = Vec::new();
+        for part in s.split(',') {
+            let range = match part.split_once('-') {
+                Some((start, end)) => parse(start)?..=parse(end)?,
+                None => {
+                    let n = parse(part)?;
+                    n..=n
+                }
+            };
+            if range.is_empty() {
+                bail!("Episode range {:?} is backwards", part.trim());
+            }
+            ranges.push(range);
+        }
+        Ok(Self(ranges))
+    }
+}
+
+/// Parse a `YYYY-MM-DD` date argument
+pub fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Expected a date like 2024-01-31, got {:?}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episodes() -> Vec<Episode> {
+        ["Interview: Alice", "Weekly news", "Interview: Bob"]
+            .iter()
+            .enumerate()
+            .map(|(i, title)| Episode {
+                title: title.to_string(),
+                url: format!("https://example.com/{}.mp3", i),
+                guid: Some(format!("guid-{}", i)),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_latest_picks_first() {
+        let episodes = episodes();
+        let selected = Selector::Latest.select(&episodes);
+        assert_eq!(selected, vec![&episodes[0]]);
+        assert!(Selector::Latest.select(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_episode_by_index_or_guid() {
+        let episodes = episodes();
+        let select = |key: &str| Selector::Episode(key.to_string()).select(&episodes);
+
+        assert_eq!(select("2"), vec![&episodes[1]]);
+        assert_eq!(select("guid-2"), vec![&episodes[2]]);
+        assert!(select("0").is_empty());
+        assert!(select("4").is_empty());
+        assert!(select("missing").is_empty());
+    }
+
+    #[test]
+    fn test_filter_by_title_and_dates() {
+        let mut episodes = episodes();
+        for (episode, date) in episodes.iter_mut().zip(["2024-03-01", "2024-06-01"]) {
+            episode.published =
+                chrono::DateTime::parse_from_rfc3339(&format!("{}T23:00:00-05:00", date)).ok();
+        }
+        let filter = EpisodeFilter {
+            since: Some(parse_date("2024-03-01").unwrap()),
+            until: Some(parse_date("2024-12-31").unwrap()),
+            ..Default::default()
+        };
+        // The third episode has no date
+        assert_eq!(episodes.iter().filter(|e| filter.matches(e)).count(), 2);
+
+        let filter = EpisodeFilter {
+            title: Some(Regex::new("Interview").unwrap()),
+            until: Some(parse_date("2024-05-31").unwrap()),
+            ..Default::default()
+        };
+        filter.apply(&mut episodes);
+        assert_eq!(episodes.len(), 1);
+        assert_eq!(episodes[0].title, "Interview: Alice");
+        assert!(parse_date("March 1").is_err());
+    }
+
+    #[test]
+    fn test_filter_by_season_and_number() {
+        let mut episodes = episodes();
+        for (i, episode) in episodes.iter_mut().enumerate() {
+            episode.season = Some(if i == 0 { 1 } else { 2 });
+            episode.number = Some(10 + i as u32);
+        }
+        let numbers = |s: &str| Some(s.parse::<NumberRanges>().unwrap());
+
+        let season = EpisodeFilter {
+            season: Some(2),
+            ..Default::default()
+        };
+        let range = EpisodeFilter {
+            numbers: numbers("10-11"),
+            ..Default::default()
+        };
+        let list = EpisodeFilter {
+            numbers: numbers("10, 12"),
+            ..Default::default()
+        };
+        let pick = |filter: &EpisodeFilter| {
+            episodes
+                .iter()
+                .filter(|e| filter.matches(e))
+                .map(|e| e.number.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(pick(&season), vec![11, 12]);
+        assert_eq!(pick(&range), vec![10, 11]);
+        assert_eq!(pick(&list), vec![10, 12]);
+        assert!(EpisodeFilter::default().matches(&Episode::default()));
+        assert!(!range.matches(&Episode::default()));
+        assert!("20-10".parse::<NumberRanges>().is_err());
+        assert!("ten".parse::<NumberRanges>().is_err());
+    }
+
+    #[test]
+    fn test_match_filters_titles() {
+        let episodes = episodes();
+        let selector = Selector::Match(Regex::new("^Interview").unwrap());
+        assert_eq!(selector.select(&episodes), vec![&episodes[0], &episodes[2]]);
+    }
+}
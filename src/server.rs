@@ -0,0 +1,494 @@
+use anyhow::{Context, Result, bail};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::io::SeekFrom;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+use std::{error, fmt};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+/// Longest request head a client may send
+const MAX_REQUEST_HEAD: usize = 16 * 1024;
+
+/// Largest request body accepted, plenty for the JSON `pdl serve` takes
+const MAX_REQUEST_BODY: usize = 1024 * 1024;
+
+/// How long a client gets to send its whole request, so one that connects
+/// and goes quiet doesn't hold a connection open forever
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Characters left alone in a path segment of a link
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// A request to `pdl serve`'s HTTP server. It speaks just enough HTTP/1.1
+/// for scripts, dashboards and podcast apps: one request per connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// As sent, without the query string
+    pub path: String,
+    /// Percent-decoded query parameters, in order
+    pub query: Vec<(String, String)>,
+    /// Header names (lowercased) and values, in order
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of query parameter `name`, if given
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value of header `name` (lowercase), if sent
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// A response refusing this request if a web page could have had the
+    /// browser send it to a server listening on `local`: it names another
+    /// host (DNS rebinding), comes from another site's page, or sends a body
+    /// that isn't JSON, which pages can post anywhere without asking
+    pub fn refuse_cross_site(&self, local: SocketAddr) -> Option<Response> {
+        if let Some(host) = self.header("host")
+            && !names_local(host, local)
+        {
+            return Some(Response::error(
+                StatusCode::FORBIDDEN,
+                &format!("Unknown host {:?}", host),
+            ));
+        }
+        if let Some(origin) = self.header("origin")
+            && !origin
+                .strip_prefix("http://")
+                .is_some_and(|host| names_local(host, local))
+        {
+            return Some(Response::error(
+                StatusCode::FORBIDDEN,
+                "Requests from other sites aren't allowed",
+            ));
+        }
+        let json = self.header("content-type").is_some_and(|content_type| {
+            let media_type = content_type.split(';').next().unwrap_or_default();
+            media_type.trim().eq_ignore_ascii_case("application/json")
+        });
+        if !matches!(self.method.as_str(), "GET" | "HEAD") && !json {
+            return Some(Response::error(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Send the request body as application/json",
+            ));
+        }
+        None
+    }
+
+    /// The path's percent-decoded segments, e.g. `["feeds", "My Show"]` for
+    /// `/feeds/My%20Show`
+    pub fn segments(&self) -> Vec<String> {
+        self.path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(decode)
+            .collect()
+    }
+}
+
+/// What to send back for a [`Request`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: StatusCode,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// `value` as JSON, with status 200
+    pub fn json(value: &impl Serialize) -> Result<Self> {
+        Ok(Self {
+            status: StatusCode::OK,
+            content_type: "application/json",
+            body: serde_json::to_vec(value).context("Failed to serialize response")?,
+        })
+    }
+
+    /// A JSON `{"error": ...}` body with `status`
+    pub fn error(status: StatusCode, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::json!({ "error": message })
+                .to_string()
+                .into_bytes(),
+        }
+    }
+
+    /// The answer to a request [`read_request`] couldn't read: 408 when it
+    /// took too long, 400 otherwise
+    pub fn unreadable(e: &anyhow::Error) -> Self {
+        let status = if e.is::<RequestTimeout>() {
+            StatusCode::REQUEST_TIMEOUT
+        } else {
+            StatusCode::BAD_REQUEST
+        };
+        Self::error(status, &format!("{:#}", e))
+    }
+
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+/// The client didn't send its whole request within [`REQUEST_TIMEOUT`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestTimeout;
+
+impl fmt::Display for RequestTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Timed out waiting for the request")
+    }
+}
+
+impl error::Error for RequestTimeout {}
+
+/// Read one request from `stream`, giving up with [`RequestTimeout`] if it
+/// takes longer than [`REQUEST_TIMEOUT`]
+pub async fn read_request(stream: &mut (impl AsyncRead + Unpin)) -> Result<Request> {
+    tokio::time::timeout(REQUEST_TIMEOUT, read_request_untimed(stream))
+        .await
+        .map_err(|_| RequestTimeout)?
+}
+
+async fn read_request_untimed(stream: &mut (impl AsyncRead + Unpin)) -> Result<Request> {
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    let head_end = loop {
+        if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if data.len() > MAX_REQUEST_HEAD {
+            bail!("Request head too long");
+        }
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            bail!("Connection closed before the request was complete");
+        }
+        data.extend_from_slice(&buf[..n]);
+    };
+    let (mut request, length) = parse_head(&String::from_utf8_lossy(&data[..head_end]))?;
+    if length > MAX_REQUEST_BODY {
+        bail!("Request body too large");
+    }
+    let mut body = data.split_off(head_end);
+    body.truncate(length);
+    if body.len() < length {
+        let start = body.len();
+        body.resize(length, 0);
+        stream
+            .read_exact(&mut body[start..])
+            .await
+            .context("Connection closed before the request body was complete")?;
+    }
+    request.body = body;
+    Ok(request)
+}
+
+/// The request line and headers, and the body's length
+fn parse_head(head: &str) -> Result<(Request, usize)> {
+    let mut lines = head.lines();
+    let mut words = lines.next().context("Empty request")?.split_whitespace();
+    let (Some(method), Some(target)) = (words.next(), words.next()) else {
+        bail!("Malformed request line");
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        })
+        .collect();
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .map(|(_, value)| value.parse::<usize>())
+        .transpose()
+        .context("Invalid Content-Length")?
+        .unwrap_or(0);
+    let request = Request {
+        method: method.to_ascii_uppercase(),
+        path: path.to_string(),
+        query: query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(key), decode(&value.replace('+', " ")))
+            })
+            .collect(),
+        headers,
+        body: Vec::new(),
+    };
+    Ok((request, length))
+}
+
+/// Whether `authority` (`host[:port]`, as in a `Host` header) names a
+/// server listening on `local`: `localhost` or its address, with its port.
+/// A server listening on every address answers to any of them.
+fn names_local(authority: &str, local: SocketAddr) -> bool {
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse::<u16>().ok()),
+        _ => (authority, Some(80)),
+    };
+    if port != Some(local.port()) {
+        return false;
+    }
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    host.parse::<IpAddr>()
+        .is_ok_and(|ip| ip == local.ip() || local.ip().is_unspecified())
+}
+
+fn decode(s: &str) -> String {
+    percent_decode_str(s).decode_utf8_lossy().into_owned()
+}
+
+/// `s` percent-encoded for use as one segment of a URL path
+pub fn encode_segment(s: &str) -> String {
+    utf8_percent_encode(s, SEGMENT).to_string()
+}
+
+/// Send `response` on `stream` and close it
+pub async fn write_response(
+    stream: &mut (impl AsyncWrite + Unpin),
+    response: &Response,
+    head_only: bool,
+) -> Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status.as_u16(),
+        response.status.canonical_reason().unwrap_or_default(),
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    if !head_only {
+        stream.write_all(&response.body).await?;
+    }
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Send the file at `path` on `stream` and close it, or the part a `Range`
+/// header asks for, so players can seek
+pub async fn write_file(
+    stream: &mut (impl AsyncWrite + Unpin),
+    path: &Path,
+    content_type: &str,
+    range: Option<&str>,
+    head_only: bool,
+) -> Result<()> {
+    let mut file = File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let len = file.metadata().await?.len();
+    let (status, start, end) = match range.map(|range| parse_range(range, len)) {
+        None | Some(Ok(None)) => (StatusCode::OK, 0, len),
+        Some(Ok(Some((start, end)))) => (StatusCode::PARTIAL_CONTENT, start, end),
+        Some(Err(_)) => {
+            let head = format!(
+                "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                len
+            );
+            stream.write_all(head.as_bytes()).await?;
+            stream.flush().await?;
+            return Ok(());
+        }
+    };
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default(),
+        content_type,
+        end - start
+    );
+    if status == StatusCode::PARTIAL_CONTENT {
+        head.push_str(&format!(
+            "Content-Range: bytes {}-{}/{}\r\n",
+            start,
+            end - 1,
+            len
+        ));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    if !head_only {
+        file.seek(SeekFrom::Start(start)).await?;
+        tokio::io::copy(&mut file.take(end - start), stream).await?;
+    }
+    stream.flush().await?;
+    Ok(())
+}
+
+/// The bytes `start..end` of a `len`-byte file a `Range` header value asks
+/// for; `None` for ranges this server ignores (several at once, or not
+/// bytes), which get the whole file
+fn parse_range(range: &str, len: u64) -> Result<Option<(u64, u64)>> {
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let (first, last) = spec.split_once('-').context("Malformed range")?;
+    let (first, last) = (first.trim(), last.trim());
+    let (start, end) = if first.is_empty() {
+        // The last `last` bytes
+        let suffix: u64 = last.parse().context("Malformed range")?;
+        (len.saturating_sub(suffix), len)
+    } else {
+        let start: u64 = first.parse().context("Malformed range")?;
+        let end = match last {
+            "" => len,
+            last => last
+                .parse::<u64>()
+                .context("Malformed range")?
+                .saturating_add(1)
+                .min(len),
+        };
+        (start, end)
+    };
+    if start >= end {
+        bail!("Range outside the file");
+    }
+    Ok(Some((start, end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_request() {
+        let raw = b"POST /feeds/AC%2FDC%20Live/episodes?n=5&q=a+b%21 HTTP/1.1\r\nHost: x\r\ncontent-length: 12\r\n\r\n{\"latest\":1}";
+        let request = read_request(&mut &raw[..]).await.unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.segments(), ["feeds", "AC/DC Live", "episodes"]);
+        assert_eq!(request.param("n"), Some("5"));
+        assert_eq!(request.param("q"), Some("a b!"));
+        assert_eq!(request.param("x"), None);
+        assert_eq!(request.header("host"), Some("x"));
+        assert_eq!(request.body, b"{\"latest\":1}");
+
+        let request = read_request(&mut &b"GET / HTTP/1.1\r\n\r\n"[..])
+            .await
+            .unwrap();
+        assert_eq!(request.path, "/");
+        assert!(request.body.is_empty());
+
+        assert!(read_request(&mut &b"GET / HTTP/1.1\r\n"[..]).await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_read_request_timeout() {
+        // A client that sends half a request and goes quiet
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        let e = read_request(&mut server).await.unwrap_err();
+        assert!(e.is::<RequestTimeout>());
+        assert_eq!(Response::unreadable(&e).status, StatusCode::REQUEST_TIMEOUT);
+
+        let e = read_request(&mut &b"GET\r\n\r\n"[..]).await.unwrap_err();
+        assert_eq!(Response::unreadable(&e).status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_refuse_cross_site() {
+        let local: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let request = |method: &str, headers: &[(&str, &str)]| Request {
+            method: method.to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        let refused = |request: Request| request.refuse_cross_site(local).map(|r| r.status);
+
+        let json = ("content-type", "application/json; charset=utf-8");
+        assert_eq!(refused(request("GET", &[("host", "127.0.0.1:8080")])), None);
+        assert_eq!(refused(request("GET", &[("host", "localhost:8080")])), None);
+        assert_eq!(refused(request("POST", &[json])), None);
+        assert_eq!(
+            refused(request(
+                "POST",
+                &[("origin", "http://localhost:8080"), json]
+            )),
+            None
+        );
+
+        // DNS rebinding: another name for this address
+        assert_eq!(
+            refused(request("GET", &[("host", "evil.example:8080")])),
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            refused(request("GET", &[("host", "127.0.0.1:9090")])),
+            Some(StatusCode::FORBIDDEN)
+        );
+        // A page on another site
+        assert_eq!(
+            refused(request("POST", &[("origin", "https://evil.example"), json])),
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            refused(request("POST", &[("origin", "null"), json])),
+            Some(StatusCode::FORBIDDEN)
+        );
+        // A form or text/plain post, which needs no permission to send
+        assert_eq!(
+            refused(request("POST", &[("content-type", "text/plain")])),
+            Some(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        );
+        assert_eq!(
+            refused(request("POST", &[])),
+            Some(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        );
+
+        assert!(names_local("[::1]:8080", "[::1]:8080".parse().unwrap()));
+        assert!(names_local(
+            "192.168.1.5:8080",
+            "0.0.0.0:8080".parse().unwrap()
+        ));
+        assert!(!names_local(
+            "nas.local:8080",
+            "0.0.0.0:8080".parse().unwrap()
+        ));
+        assert!(names_local("localhost", "127.0.0.1:80".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-", 100).unwrap(), Some((0, 100)));
+        assert_eq!(parse_range("bytes=10-19", 100).unwrap(), Some((10, 20)));
+        assert_eq!(parse_range("bytes=90-200", 100).unwrap(), Some((90, 100)));
+        assert_eq!(parse_range("bytes=-30", 100).unwrap(), Some((70, 100)));
+        assert_eq!(parse_range("bytes=0-1,5-9", 100).unwrap(), None);
+        assert_eq!(parse_range("items=0-1", 100).unwrap(), None);
+        assert!(parse_range("bytes=100-", 100).is_err());
+        assert!(parse_range("bytes=x-", 100).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::download::show_dir;
+use crate::library::{LibraryEpisode, ShowFiles, on_disk_by_show};
+use crate::listing::format_duration;
+use crate::server::encode_segment;
+use anyhow::{Context, Result};
+use quick_xml::escape::escape;
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// Page name in the top folder and in each show's folder
+pub const INDEX_PAGE: &str = "index.html";
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:50em;margin:2em auto;padding:0 1em;line-height:1.5}\
+img.cover{width:8em;height:8em;object-fit:cover;float:right;margin-left:1em}\
+ul.shows img{width:3em;height:3em;object-fit:cover;vertical-align:middle;margin-right:.5em}\
+ul.shows{list-style:none;padding:0}article{clear:both;border-top:1px solid #ccc;padding:.5em 0}\
+.meta{color:#666}audio{width:100%}details p{white-space:pre-wrap}";
+
+/// What [`write`] produced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Site {
+    pub shows: usize,
+    pub episodes: usize,
+}
+
+/// Write a static HTML site for browsing downloaded `episodes` into `dir`:
+/// an index of shows, and a page per show (in the show's folder, as
+/// downloads are laid out) with a player and the show notes of each episode.
+/// Files are linked relative to the pages, so the site can be served or
+/// opened from disk; episodes whose file is gone are left out.
+pub fn write(dir: &Path, episodes: &[LibraryEpisode]) -> Result<Site> {
+    let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let shows = on_disk_by_show(episodes);
+
+    let mut index = page("Podcasts", None);
+    index.push_str("<h1>Podcasts</h1>\n<ul class=\"shows\">\n");
+    let mut count = 0;
+    for ShowFiles { show, files } in &shows {
+        let folder = show_dir(&dir, show);
+        fs::create_dir_all(&folder)
+            .with_context(|| format!("Failed to create {}", folder.display()))?;
+        // Artwork is saved next to the episodes, which may not be here
+        let cover = files.iter().find_map(|(_, path)| cover(path.parent()?));
+        let page_path = folder.join(INDEX_PAGE);
+        fs::write(
+            &page_path,
+            show_page(show, files, cover.as_deref(), &folder, &dir),
+        )
+        .with_context(|| format!("Failed to write {}", page_path.display()))?;
+
+        index.push_str("<li>");
+        if let Some(cover) = &cover {
+            index.push_str(&format!(
+                "<img src=\"{}\" alt=\"\">",
+                relative_url(cover, &dir)
+            ));
+        }
+        index.push_str(&format!(
+            "<a href=\"{}\">{}</a> <span class=\"meta\">{} episode(s)</span></li>\n",
+            relative_url(&page_path, &dir),
+            escape(*show),
+            files.len()
+        ));
+        count += files.len();
+    }
+    index.push_str("</ul>\n</body>\n</html>\n");
+    let index_path = dir.join(INDEX_PAGE);
+    fs::write(&index_path, index)
+        .with_context(|| format!("Failed to write {}", index_path.display()))?;
+    Ok(Site {
+        shows: shows.len(),
+        episodes: count,
+    })
+}
+
+fn show_page(
+    show: &str,
+    files: &[(&LibraryEpisode, &Path)],
+    cover: Option<&Path>,
+    folder: &Path,
+    root: &Path,
+) -> String {
+    let mut html = page(show, Some(&relative_url(&root.join(INDEX_PAGE), folder)));
+    if let Some(cover) = cover {
+        html.push_str(&format!(
+            "<img class=\"cover\" src=\"{}\" alt=\"\">\n",
+            relative_url(cover, folder)
+        ));
+    }
+    html.push_str(&format!("<h1>{}</h1>\n", escape(show)));
+    for (episode, path) in files {
+        html.push_str(&format!("<article>\n<h2>{}</h2>\n", escape(&episode.title)));
+        let date = episode
+            .published
+            .as_deref()
+            .map(|d| d.get(..10).unwrap_or(d));
+        let duration = episode
+            .duration
+            .map(|secs| format_duration(Duration::from_secs(secs)));
+        let meta: Vec<&str> = [date, duration.as_deref()].into_iter().flatten().collect();
+        if !meta.is_empty() {
+            html.push_str(&format!("<p class=\"meta\">{}</p>\n", meta.join(" · ")));
+        }
+        let src = relative_url(path, folder);
+        html.push_str(&format!(
+            "<audio controls preload=\"none\" src=\"{}\"></audio>\n<p><a href=\"{}\">Download</a></p>\n",
+            src, src
+        ));
+        if let Some(notes) = episode.description.as_deref().filter(|n| !n.is_empty()) {
+            html.push_str(&format!(
+                "<details><summary>Show notes</summary><p>{}</p></details>\n",
+                escape(notes)
+            ));
+        }
+        html.push_str("</article>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// The start of a page, up to and including `<body>`, with a link back to
+/// `up` if given
+fn page(title: &str, up: Option<&str>) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        escape(title),
+        STYLE
+    );
+    if let Some(up) = up {
+        html.push_str(&format!("<p><a href=\"{}\">← All shows</a></p>\n", up));
+    }
+    html
+}
+
+/// The show artwork `--save-cover` saved in `folder`, if there is one
+pub fn cover(folder: &Path) -> Option<PathBuf> {
+    ["jpg", "png"]
+        .iter()
+        .map(|ext| folder.join(format!("cover.{}", ext)))
+        .find(|path| path.exists())
+}
+
+/// A link from a page in `from` to `target` (both absolute): relative where
+/// they share a root, else a `file://` URL
+pub fn relative_url(target: &Path, from: &Path) -> String {
+    let target: Vec<Component> = target.components().collect();
+    let from: Vec<Component> = from.components().collect();
+    let common = target.iter().zip(&from).take_while(|(a, b)| a == b).count();
+    let encode = |c: &Component| encode_segment(&c.as_os_str().to_string_lossy());
+    if common == 0 {
+        // On another drive (Windows)
+        let parts: Vec<String> = target
+            .iter()
+            .filter(|c| !matches!(c, Component::RootDir))
+            .map(encode)
+            .collect();
+        return format!("file:///{}", parts.join("/"));
+    }
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(target[common..].iter().map(encode));
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_url() {
+        let root = Path::new("/home/me/Podcasts");
+        assert_eq!(
+            relative_url(Path::new("/home/me/Podcasts/My Show/Ep #1.mp3"), root),
+            "My%20Show/Ep%20%231.mp3"
+        );
+        assert_eq!(
+            relative_url(
+                Path::new("/home/me/Podcasts/index.html"),
+                &root.join("My Show")
+            ),
+            "../index.html"
+        );
+        assert_eq!(
+            relative_url(Path::new("/mnt/usb/Show/1.mp3"), root),
+            "../../../mnt/usb/Show/1.mp3"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use crate::cache::url_key;
+use crate::config;
+use crate::feed::{Episode, Feed, FeedFormat, detect_format};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Snapshots of every feed pdl fetches, in a folder per feed URL
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The store under the data directory (`<data dir>/pdl/snapshots`)
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(config::data_dir()?.join("snapshots")))
+    }
+
+    /// Snapshots of the feed at `url`
+    pub fn for_feed(&self, url: &str) -> Snapshots {
+        Snapshots::new(self.root.join(url_key(url)))
+    }
+}
+
+/// Copies of a feed document, one for each time it was seen to change, named
+/// by when they were taken (`feed-20240101T120000Z.xml`). Files are only ever
+/// added, so the folder is a history of the feed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshots {
+    dir: PathBuf,
+}
+
+impl Snapshots {
+    /// Keep snapshots in `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Every snapshot, oldest first; empty if none were taken yet
+    pub async fn list(&self) -> Result<Vec<PathBuf>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.dir.display()));
+            }
+        };
+        let mut snapshots = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .with_context(|| format!("Failed to read {}", self.dir.display()))?
+        {
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with("feed-") {
+                snapshots.push(entry.path());
+            }
+        }
+        // The timestamps in the names sort chronologically
+        snapshots.sort();
+        Ok(snapshots)
+    }
+
+    /// Save `content` as taken at `at`, unless it is identical to the latest
+    /// snapshot. Returns the file written, if any.
+    pub async fn save(&self, content: &[u8], at: DateTime<Utc>) -> Result<Option<PathBuf>> {
+        if let Some(latest) = self.list().await?.pop()
+            && tokio::fs::read(&latest).await.ok().as_deref() == Some(content)
+        {
+            return Ok(None);
+        }
+
+        let extension = match detect_format(content) {
+            Some(FeedFormat::Json) => "json",
+            _ => "xml",
+        };
+        let path = self.dir.join(format!(
+            "feed-{}.{}",
+            at.format("%Y%m%dT%H%M%SZ"),
+            extension
+        ));
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        tokio::fs::write(&path, content)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(Some(path))
+    }
+
+    /// Parse the snapshot at `path`
+    pub async fn read(path: &Path) -> Result<Feed> {
+        let content = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Feed::parse(&content, usize::MAX)
+            .with_context(|| format!("Failed to parse snapshot {}", path.display()))
+    }
+}
+
+/// How the episodes of a feed differ between two copies of it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeedDiff {
+    /// Episodes only in the newer copy
+    pub added: Vec<Episode>,
+    /// Episodes only in the older copy
+    pub removed: Vec<Episode>,
+    pub edited: Vec<EpisodeEdit>,
+}
+
+/// An episode in both copies whose details changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpisodeEdit {
+    pub before: Episode,
+    pub after: Episode,
+    /// Names of the fields that differ, e.g. `title`, `url`, `description`
+    pub fields: Vec<&'static str>,
+}
+
+impl FeedDiff {
+    /// Compare two copies of a feed, matching episodes by [`Episode::key`].
+    /// Episodes keep the order of the copy they come from.
+    pub fn between(old: &Feed, new: &Feed) -> Self {
+        let old_by_key: HashMap<&str, &Episode> =
+            old.episodes.iter().map(|e| (e.key(), e)).collect();
+        let new_by_key: HashMap<&str, &Episode> =
+            new.episodes.iter().map(|e| (e.key(), e)).collect();
+
+        let mut diff = Self::default();
+        for episode in &new.episodes {
+            match old_by_key.get(episode.key()) {
+                None => diff.added.push(episode.clone()),
+                Some(before) => {
+                    let fields = changed_fields(before, episode);
+                    if !fields.is_empty() {
+                        diff.edited.push(EpisodeEdit {
+                            before: (*before).clone(),
+                            after: episode.clone(),
+                            fields,
+                        });
+                    }
+                }
+            }
+        }
+        diff.removed = old
+            .episodes
+            .iter()
+            .filter(|e| !new_by_key.contains_key(e.key()))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.edited.is_empty()
+    }
+}
+
+/// Fields a publisher might edit after release. The position is left out:
+/// it moves whenever an episode is added.
+fn changed_fields(before: &Episode, after: &Episode) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    let mut check = |name, changed: bool| {
+        if changed {
+            fields.push(name);
+        }
+    };
+    check("title", before.title != after.title);
+    check("url", before.url != after.url);
+    check("published", before.published != after.published);
+    check("description", before.description != after.description);
+    check("duration", before.duration != after.duration);
+    check("size", before.size != after.size);
+    check("type", before.mime_type != after.mime_type);
+    check("number", before.number != after.number);
+    check("season", before.season != after.season);
+    check("image", before.image != after.image);
+    check("chapters", before.chapters_url != after.chapters_url);
+    check("transcripts", before.transcripts != after.transcripts);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_save_only_changes() {
+        let dir = std::env::temp_dir().join(format!("pdl-snapshots-{}", std::process::id()));
+        let snapshots = Snapshots::new(&dir);
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+
+        let first = snapshots
+            .save(b"<rss>1</rss>", at("2024-01-01T12:00:00Z"))
+            .await
+            .unwrap();
+        let same = snapshots
+            .save(b"<rss>1</rss>", at("2024-01-02T12:00:00Z"))
+            .await
+            .unwrap();
+        let changed = snapshots
+            .save(b"<rss>2</rss>", at("2024-01-03T12:00:00Z"))
+            .await
+            .unwrap();
+        let listed = snapshots.list().await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first, Some(dir.join("feed-20240101T120000Z.xml")));
+        assert_eq!(same, None);
+        assert_eq!(changed, Some(dir.join("feed-20240103T120000Z.xml")));
+        assert_eq!(listed, [first.unwrap(), changed.unwrap()]);
+    }
+
+    #[test]
+    fn test_diff() {
+        let feed = |items: &str| {
+            let content = format!(
+                r#"<rss version="2.0"><channel><title>Show</title>{items}</channel></rss>"#
+            );
+            Feed::parse(content.as_bytes(), usize::MAX).unwrap()
+        };
+        let old = feed(
+            r#"<item><title>Two</title><guid>2</guid><enclosure url="https://example.com/2.mp3" length="1" type="audio/mpeg"/></item>
+            <item><title>One</title><guid>1</guid><description>Notes</description><enclosure url="https://example.com/1.mp3" length="1" type="audio/mpeg"/></item>
+            <item><title>Zero</title><guid>0</guid><enclosure url="https://example.com/0.mp3" length="1" type="audio/mpeg"/></item>"#,
+        );
+        let new = feed(
+            r#"<item><title>Three</title><guid>3</guid><enclosure url="https://example.com/3.mp3" length="1" type="audio/mpeg"/></item>
+            <item><title>Two</title><guid>2</guid><enclosure url="https://example.com/2.mp3" length="1" type="audio/mpeg"/></item>
+            <item><title>One (edited)</title><guid>1</guid><description>New notes</description><enclosure url="https://example.com/1.mp3" length="1" type="audio/mpeg"/></item>"#,
+        );
+
+        let diff = FeedDiff::between(&old, &new);
+        let titles =
+            |episodes: &[Episode]| episodes.iter().map(|e| e.title.clone()).collect::<Vec<_>>();
+        assert_eq!(titles(&diff.added), ["Three"]);
+        assert_eq!(titles(&diff.removed), ["Zero"]);
+        assert_eq!(diff.edited.len(), 1);
+        assert_eq!(diff.edited[0].after.title, "One (edited)");
+        assert_eq!(diff.edited[0].fields, ["title", "description"]);
+        assert!(FeedDiff::between(&new, &new).is_empty());
+    }
+}
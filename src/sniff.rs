@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Bytes read from the start of a file to identify it
+pub const HEADER_LEN: usize = 64;
+
+/// What a file's first bytes say it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sniffed {
+    /// Audio or video; the extensions that suit it, preferred one first
+    Media(&'static [&'static str]),
+    /// A web page, e.g. an error or login page served in place of the media
+    Html,
+}
+
+impl Sniffed {
+    /// Extension to save the file with instead of `current`, if `current`
+    /// doesn't suit what the file is
+    pub fn better_extension(&self, current: &str) -> Option<&'static str> {
+        match self {
+            Sniffed::Media(extensions) if !extensions.contains(&current) => {
+                extensions.first().copied()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Identify a file from its header (ID3/MPEG frame sync, `ftyp`, `OggS`, ...)
+pub fn sniff(header: &[u8]) -> Option<Sniffed> {
+    let media = |extensions| Some(Sniffed::Media(extensions));
+    match header {
+        [b'I', b'D', b'3', ..] => media(&["mp3"]),
+        // ADTS AAC: frame sync with layer bits 00
+        [0xFF, b, ..] if b & 0xF6 == 0xF0 => media(&["aac"]),
+        // MPEG audio frame sync
+        [0xFF, b, ..] if b & 0xE0 == 0xE0 => media(&["mp3"]),
+        [_, _, _, _, b'f', b't', b'y', b'p', brand @ ..] => match brand.get(..4) {
+            Some(b"M4A ") => media(&["m4a", "mp4", "m4b"]),
+            Some(b"M4B ") => media(&["m4b", "m4a", "mp4"]),
+            Some(b"qt  ") => media(&["mov", "mp4"]),
+            _ => media(&["mp4", "m4a", "m4b", "m4v"]),
+        },
+        [b'O', b'g', b'g', b'S', ..] => media(&["ogg", "opus", "oga"]),
+        [b'f', b'L', b'a', b'C', ..] => media(&["flac"]),
+        _ if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WAVE") => media(&["wav"]),
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => media(&["webm", "mka", "mkv"]),
+        _ if looks_like_html(header) => Some(Sniffed::Html),
+        _ => None,
+    }
+}
+
+fn looks_like_html(header: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(header.trim_ascii_start()).to_ascii_lowercase();
+    text.starts_with("<!doctype html") || text.starts_with("<html") || text.starts_with("<head")
+}
+
+/// Sniff the file at `path`
+pub async fn sniff_file(path: &Path) -> Result<Option<Sniffed>> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    (&mut file)
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(sniff(&header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_media() {
+        assert_eq!(sniff(b"ID3\x04\x00"), Some(Sniffed::Media(&["mp3"])));
+        assert_eq!(sniff(&[0xFF, 0xFB, 0x90]), Some(Sniffed::Media(&["mp3"])));
+        assert_eq!(sniff(&[0xFF, 0xF1, 0x50]), Some(Sniffed::Media(&["aac"])));
+        assert_eq!(
+            sniff(b"\x00\x00\x00\x20ftypM4A \x00\x00"),
+            Some(Sniffed::Media(&["m4a", "mp4", "m4b"]))
+        );
+        assert_eq!(
+            sniff(b"OggS\x00\x02"),
+            Some(Sniffed::Media(&["ogg", "opus", "oga"]))
+        );
+        assert_eq!(
+            sniff(b"RIFF\x24\x00\x00\x00WAVEfmt "),
+            Some(Sniffed::Media(&["wav"]))
+        );
+        assert_eq!(sniff(b"\x00\x01\x02"), None);
+    }
+
+    #[test]
+    fn test_sniff_html() {
+        assert_eq!(sniff(b"\n  <!DOCTYPE html><html>"), Some(Sniffed::Html));
+        assert_eq!(sniff(b"<html lang=\"en\">"), Some(Sniffed::Html));
+        assert_eq!(sniff(b"<?xml version=\"1.0\"?><rss>"), None);
+    }
+
+    #[test]
+    fn test_better_extension() {
+        let m4a = sniff(b"\x00\x00\x00\x20ftypM4A ").unwrap();
+        assert_eq!(m4a.better_extension("mp3"), Some("m4a"));
+        assert_eq!(m4a.better_extension("m4b"), None);
+        assert_eq!(Sniffed::Html.better_extension("mp3"), None);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::client::Http;
+use anyhow::{Context, Result};
+use indicatif::HumanBytes;
+use reqwest::header::CONTENT_LENGTH;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+/// How long to wait for a server to answer a size check before going by
+/// the feed's figure instead
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Space to leave free on top of the downloads, so the disk isn't filled to
+/// the last byte
+pub const HEADROOM: u64 = 64 * 1024 * 1024;
+
+/// A batch of downloads that would not fit on the disk
+#[derive(Debug)]
+pub struct InsufficientSpace {
+    pub dir: PathBuf,
+    pub needed: u64,
+    pub available: u64,
+}
+
+impl fmt::Display for InsufficientSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Not enough disk space in {}: the downloads need {} but only {} is free",
+            self.dir.display(),
+            HumanBytes(self.needed),
+            HumanBytes(self.available)
+        )
+    }
+}
+
+impl std::error::Error for InsufficientSpace {}
+
+/// Free space on the filesystem `dir` is (or will be) on. `dir` need not
+/// exist yet; its nearest existing ancestor is checked.
+pub fn available_space(dir: &Path) -> Result<u64> {
+    let existing = dir
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("."));
+    fs4::available_space(existing)
+        .with_context(|| format!("Failed to check free space in {}", existing.display()))
+}
+
+/// Fail with [`InsufficientSpace`] if `needed` bytes, plus some headroom,
+/// won't fit in `dir`
+pub fn check(dir: &Path, needed: u64) -> Result<()> {
+    let available = available_space(dir)?;
+    debug!("{} needed, {} free in {}", needed, available, dir.display());
+    if needed.saturating_add(HEADROOM) > available {
+        return Err(InsufficientSpace {
+            dir: dir.to_path_buf(),
+            needed,
+            available,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// The size the server gives for `url` in answer to a `HEAD` request, if it
+/// gives one
+pub(crate) async fn content_length(client: &Http, url: &str) -> Option<u64> {
+    let response = match client.head(url).timeout(HEAD_TIMEOUT).send().await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            debug!("HEAD {} returned {}", url, response.status());
+            return None;
+        }
+        Err(e) => {
+            debug!("HEAD {} failed: {}", url, e);
+            return None;
+        }
+    };
+    // `Response::content_length` is the body's length, which is 0 for HEAD
+    response
+        .headers()
+        .get(CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_space() {
+        let dir = std::env::temp_dir().join("pdl-space-check/not/created/yet");
+        let available = available_space(&dir).unwrap();
+        assert!(check(&dir, 0).is_ok() || available < HEADROOM);
+
+        let error = check(&dir, u64::MAX).unwrap_err();
+        let error = error.downcast_ref::<InsufficientSpace>().unwrap();
+        assert_eq!(error.needed, u64::MAX);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::history::HistoryEntry;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// An episode at one end of a show's range
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EpisodeRef {
+    pub title: String,
+    /// RFC 3339
+    pub published: String,
+}
+
+/// What the library holds of one show
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShowStats {
+    pub show: String,
+    pub feed_url: String,
+    /// Episodes ever downloaded
+    pub downloaded: usize,
+    /// Of those, how many files are still there
+    pub on_disk: usize,
+    /// Size of the files still there
+    pub bytes: u64,
+    /// Running time of the episodes still there, in seconds, as far as the
+    /// feed gave it
+    pub listening_seconds: u64,
+    /// Earliest published episode still there
+    pub oldest: Option<EpisodeRef>,
+    /// Latest published episode still there
+    pub newest: Option<EpisodeRef>,
+    /// UTC timestamp (`YYYY-MM-DD HH:MM:SS`)
+    pub last_download: String,
+}
+
+/// Downloads made in one month
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MonthCount {
+    /// `YYYY-MM`
+    pub month: String,
+    pub downloads: usize,
+}
+
+/// Totals over every show
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Totals {
+    pub shows: usize,
+    pub downloaded: usize,
+    pub on_disk: usize,
+    pub bytes: u64,
+    pub listening_seconds: u64,
+}
+
+/// Statistics over the download history, as shown by `pdl stats`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LibraryStats {
+    /// Most listening time first
+    pub shows: Vec<ShowStats>,
+    pub total: Totals,
+    /// Downloads per month, oldest first, over the months downloads were made
+    pub monthly: Vec<MonthCount>,
+}
+
+/// Summarize `entries` (the download history). Shows are named by the title
+/// recorded with the download, else by `name_of(feed_url)` (e.g. the
+/// subscription's name), else by the folder the files are in. Each file is
+/// counted once, however often it was downloaded.
+pub fn compute(entries: &[HistoryEntry], name_of: impl Fn(&str) -> Option<String>) -> LibraryStats {
+    let mut by_feed: HashMap<&str, Vec<&HistoryEntry>> = HashMap::new();
+    let mut seen = HashSet::new();
+    let mut monthly: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in entries {
+        if let Some(month) = entry.downloaded_at.get(..7) {
+            *monthly.entry(month.to_string()).or_default() += 1;
+        }
+        if seen.insert(&entry.path) {
+            by_feed.entry(&entry.feed_url).or_default().push(entry);
+        }
+    }
+
+    let mut shows: Vec<ShowStats> = by_feed
+        .into_iter()
+        .map(|(feed_url, entries)| show_stats(feed_url, &entries, &name_of))
+        .collect();
+    shows.sort_by(|a, b| {
+        b.listening_seconds
+            .cmp(&a.listening_seconds)
+            .then(b.bytes.cmp(&a.bytes))
+            .then(a.show.cmp(&b.show))
+    });
+
+    let total = Totals {
+        shows: shows.len(),
+        downloaded: shows.iter().map(|s| s.downloaded).sum(),
+        on_disk: shows.iter().map(|s| s.on_disk).sum(),
+        bytes: shows.iter().map(|s| s.bytes).sum(),
+        listening_seconds: shows.iter().map(|s| s.listening_seconds).sum(),
+    };
+    let monthly = monthly
+        .into_iter()
+        .map(|(month, downloads)| MonthCount { month, downloads })
+        .collect();
+    LibraryStats {
+        shows,
+        total,
+        monthly,
+    }
+}
+
+fn show_stats(
+    feed_url: &str,
+    entries: &[&HistoryEntry],
+    name_of: impl Fn(&str) -> Option<String>,
+) -> ShowStats {
+    let show = entries
+        .iter()
+        .find_map(|e| e.show.clone())
+        .or_else(|| name_of(feed_url))
+        .or_else(|| folder_name(&entries[0].path))
+        .unwrap_or_else(|| feed_url.to_string());
+
+    let mut stats = ShowStats {
+        show,
+        feed_url: feed_url.to_string(),
+        downloaded: entries.len(),
+        on_disk: 0,
+        bytes: 0,
+        listening_seconds: 0,
+        oldest: None,
+        newest: None,
+        last_download: entries
+            .iter()
+            .map(|e| e.downloaded_at.clone())
+            .max()
+            .unwrap_or_default(),
+    };
+    for entry in entries {
+        let Ok(metadata) = fs::metadata(&entry.path) else {
+            continue;
+        };
+        stats.on_disk += 1;
+        stats.bytes += metadata.len();
+        stats.listening_seconds += entry.duration.unwrap_or(0);
+        if let Some(published) = &entry.published {
+            let episode = || EpisodeRef {
+                title: entry.title.clone(),
+                published: published.clone(),
+            };
+            if stats
+                .oldest
+                .as_ref()
+                .is_none_or(|o| *published < o.published)
+            {
+                stats.oldest = Some(episode());
+            }
+            if stats
+                .newest
+                .as_ref()
+                .is_none_or(|n| *published > n.published)
+            {
+                stats.newest = Some(episode());
+            }
+        }
+    }
+    stats
+}
+
+fn folder_name(path: &Path) -> Option<String> {
+    Some(path.parent()?.file_name()?.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(feed_url: &str, path: &Path, downloaded_at: &str) -> HistoryEntry {
+        HistoryEntry {
+            feed_url: feed_url.to_string(),
+            guid: None,
+            url: format!("{}/{}", feed_url, path.display()),
+            title: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            path: path.to_path_buf(),
+            downloaded_at: downloaded_at.to_string(),
+            show: None,
+            published: None,
+            duration: None,
+        }
+    }
+
+    #[test]
+    fn test_compute_stats() {
+        let dir = std::env::temp_dir()
+            .join("pdl-stats-test")
+            .join("Folder Show");
+        fs::create_dir_all(&dir).unwrap();
+        let kept = dir.join("kept.mp3");
+        fs::write(&kept, [0; 100]).unwrap();
+
+        let entries = [
+            HistoryEntry {
+                show: Some("My Show".to_string()),
+                published: Some("2024-02-01T00:00:00+00:00".to_string()),
+                duration: Some(600),
+                ..entry("a", &kept, "2024-02-03 10:00:00")
+            },
+            // Downloaded again: counted once
+            entry("a", &kept, "2024-01-05 10:00:00"),
+            entry("a", &dir.join("gone.mp3"), "2024-01-04 10:00:00"),
+            entry(
+                "b",
+                &PathBuf::from("/nowhere/Other/x.mp3"),
+                "2024-01-01 00:00:00",
+            ),
+        ];
+        let stats = compute(&entries, |_| None);
+
+        assert_eq!(stats.total.shows, 2);
+        assert_eq!(stats.total.downloaded, 3);
+        assert_eq!(stats.total.on_disk, 1);
+        assert_eq!(stats.total.bytes, 100);
+
+        let show = &stats.shows[0];
+        assert_eq!(show.show, "My Show");
+        assert_eq!(show.listening_seconds, 600);
+        assert_eq!(show.last_download, "2024-02-03 10:00:00");
+        assert_eq!(show.oldest, show.newest);
+        assert_eq!(stats.shows[1].show, "Other");
+        assert_eq!(
+            stats.monthly,
+            [
+                MonthCount {
+                    month: "2024-01".to_string(),
+                    downloads: 3
+                },
+                MonthCount {
+                    month: "2024-02".to_string(),
+                    downloads: 1
+                },
+            ]
+        );
+    }
+}
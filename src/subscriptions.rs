@@ -0,0 +1,399 @@
+use crate::auth::Credentials;
+use crate::config;
+use crate::prune::Retention;
+use crate::select::EpisodeFilter;
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A podcast feed the user has subscribed to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub name: String,
+    pub url: String,
+
+    /// How many of the newest episodes `pdl watch` keeps downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest: Option<usize>,
+
+    /// Title pattern an episode must match for `pdl watch` to download it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+
+    /// Command run on each episode downloaded from this feed, in place of
+    /// the configured `post_cmd`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_cmd: Option<String>,
+
+    /// Keep only this many of the feed's most recent downloads on disk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_latest: Option<usize>,
+
+    /// Delete the feed's downloads after this many days
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+
+    /// Keep the feed's most recent downloads within this many gigabytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_gb: Option<f64>,
+
+    /// Download directory for this feed, in place of the configured `download_dir`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_dir: Option<PathBuf>,
+
+    /// File name template for this feed, in place of the configured one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename_template: Option<String>,
+
+    /// Basic auth login for the feed, as `user:password`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<String>,
+
+    /// Bearer token for the feed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bearer: Option<String>,
+}
+
+/// Episodes `pdl watch` considers when a subscription doesn't set `latest`
+const DEFAULT_WATCH_LATEST: usize = 1;
+
+impl Subscription {
+    fn new(name: &str, url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            latest: None,
+            filter: None,
+            post_cmd: None,
+            keep_latest: None,
+            max_age_days: None,
+            max_size_gb: None,
+            download_dir: None,
+            filename_template: None,
+            auth: None,
+            bearer: None,
+        }
+    }
+
+    /// Number of newest episodes (after the filter) that `pdl watch` downloads
+    pub fn watch_latest(&self) -> usize {
+        self.latest.unwrap_or(DEFAULT_WATCH_LATEST)
+    }
+
+    /// How many of the feed's downloads `pdl prune` keeps
+    pub fn retention(&self) -> Retention {
+        Retention {
+            keep_latest: self.keep_latest,
+            max_age_days: self.max_age_days,
+            max_size_gb: self.max_size_gb,
+        }
+    }
+
+    /// The login the subscription gives for its feed, if any
+    pub fn credentials(&self) -> Result<Option<Credentials>> {
+        match (&self.auth, &self.bearer) {
+            (Some(_), Some(_)) => bail!("\"{}\" sets both auth and bearer", self.name),
+            (Some(auth), None) => Credentials::basic(auth)
+                .map(Some)
+                .with_context(|| format!("Invalid auth for \"{}\"", self.name)),
+            (None, Some(token)) => Ok(Some(Credentials::Bearer(token.clone()))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// The subscription's `filter` as an episode filter
+    pub fn watch_filter(&self) -> Result<EpisodeFilter> {
+        let title = match &self.filter {
+            Some(pattern) => Some(
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid filter for \"{}\"", self.name))?,
+            ),
+            None => None,
+        };
+        Ok(EpisodeFilter {
+            title,
+            ..Default::default()
+        })
+    }
+}
+
+/// Persistent list of subscribed feeds, stored as TOML in the user config dir
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Subscriptions {
+    #[serde(default)]
+    pub feeds: Vec<Subscription>,
+}
+
+/// Feeds suggested in the feed picker before the user has subscribed to
+/// anything
+const DEFAULT_FEEDS: &[(&str, &str)] = &[
+    (
+        "Cozy Up (Doctor)",
+        "https://omny.fm/shows/cozy-up/playlists/doctor.rss",
+    ),
+    (
+        "Cozy Up (Podcast)",
+        "https://omny.fm/shows/cozy-up/playlists/podcast.rss",
+    ),
+];
+
+impl Subscriptions {
+    /// Path of the subscriptions file (`<config dir>/pdl/subscriptions.toml`)
+    pub fn path() -> Result<PathBuf> {
+        Ok(config::config_dir()?.join("subscriptions.toml"))
+    }
+
+    /// Load subscriptions; none if no file exists yet
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::path()?)
+    }
+
+    /// Load subscriptions from the file at `path`; none if it doesn't exist
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_toml(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Write subscriptions back to disk, creating the config dir if needed
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::path()?)
+    }
+
+    /// Write subscriptions to the file at `path`. Feeds' logins are kept in
+    /// it, so on unix only the user may read it.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create config directory")?;
+        }
+
+        let content = toml::to_string_pretty(self).context("Failed to serialize subscriptions")?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        // The mode only applies to new files; older ones may be world-readable
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("Failed to restrict access to {}", path.display()))?;
+        }
+        file.write_all(content.as_bytes())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Feeds to suggest to someone with no subscriptions, who isn't
+    /// subscribed to them by picking one
+    pub fn suggestions() -> Vec<Subscription> {
+        DEFAULT_FEEDS
+            .iter()
+            .map(|(name, url)| Subscription::new(name, url))
+            .collect()
+    }
+
+    fn from_toml(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Add a feed, rejecting duplicate names or URLs
+    pub fn add(&mut self, name: &str, url: &str) -> Result<()> {
+        if let Some(existing) = self.feeds.iter().find(|f| f.url == url) {
+            bail!("Already subscribed to {} as \"{}\"", url, existing.name);
+        }
+        if self.feeds.iter().any(|f| f.name == name) {
+            bail!("A feed named \"{}\" already exists", name);
+        }
+
+        self.feeds.push(Subscription::new(name, url));
+        Ok(())
+    }
+
+    /// Whether a feed with this URL is already subscribed
+    pub fn contains_url(&self, url: &str) -> bool {
+        self.feeds.iter().any(|f| f.url == url)
+    }
+
+    /// The subscription for the feed at `url`, if any
+    pub fn find_url(&self, url: &str) -> Option<&Subscription> {
+        self.feeds.iter().find(|f| f.url == url)
+    }
+
+    /// `name`, or `name (2)`, `name (3)`, ... if it is already taken
+    pub fn unique_name(&self, name: &str) -> String {
+        let taken = |candidate: &str| self.feeds.iter().any(|f| f.name == candidate);
+        if !taken(name) {
+            return name.to_string();
+        }
+        (2..)
+            .map(|i| format!("{} ({})", name, i))
+            .find(|candidate| !taken(candidate))
+            .expect("unbounded range always yields a free name")
+    }
+
+    /// Remove a feed by name (case-insensitive), returning the removed entry
+    pub fn remove(&mut self, name: &str) -> Result<Subscription> {
+        let index = self
+            .feeds
+            .iter()
+            .position(|f| f.name.eq_ignore_ascii_case(name))
+            .with_context(|| format!("No subscription named \"{}\"", name))?;
+        Ok(self.feeds.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_rejects_duplicates() {
+        let mut subs = Subscriptions::default();
+        subs.add("Show", "https://example.com/feed.rss").unwrap();
+
+        assert!(subs.add("Other", "https://example.com/feed.rss").is_err());
+        assert!(subs.add("Show", "https://example.com/other.rss").is_err());
+        assert_eq!(subs.feeds.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_is_case_insensitive() {
+        let mut subs = Subscriptions::default();
+        subs.add("My Show", "https://example.com/feed.rss").unwrap();
+
+        let removed = subs.remove("my show").unwrap();
+        assert_eq!(removed.url, "https://example.com/feed.rss");
+        assert!(subs.feeds.is_empty());
+        assert!(subs.remove("my show").is_err());
+    }
+
+    #[test]
+    fn test_unique_name_appends_counter() {
+        let mut subs = Subscriptions::default();
+        assert_eq!(subs.unique_name("Show"), "Show");
+
+        subs.add("Show", "https://example.com/1.rss").unwrap();
+        subs.add("Show (2)", "https://example.com/2.rss").unwrap();
+        assert_eq!(subs.unique_name("Show"), "Show (3)");
+        assert!(subs.contains_url("https://example.com/2.rss"));
+    }
+
+    #[test]
+    fn test_load_without_file_has_no_feeds() {
+        let path =
+            std::env::temp_dir().join(format!("pdl-no-subscriptions-{}.toml", std::process::id()));
+        assert!(!path.exists());
+        assert!(Subscriptions::load_from(&path).unwrap().feeds.is_empty());
+        assert!(!Subscriptions::suggestions().is_empty());
+    }
+
+    #[test]
+    fn test_save_keeps_logins_private() {
+        let path =
+            std::env::temp_dir().join(format!("pdl-subscriptions-{}.toml", std::process::id()));
+        fs::write(&path, "").unwrap();
+        let mut subs = Subscriptions::default();
+        subs.add("Private", "https://example.com/feed.rss").unwrap();
+        subs.feeds[0].auth = Some("me:secret".to_string());
+        subs.save_to(&path).unwrap();
+
+        let loaded = Subscriptions::load_from(&path).unwrap();
+        assert_eq!(loaded.feeds[0].auth.as_deref(), Some("me:secret"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_toml_roundtrip() {
+        let mut subs = Subscriptions::default();
+        subs.add("한글 팟캐스트", "https://example.com/feed.rss")
+            .unwrap();
+
+        let content = toml::to_string_pretty(&subs).unwrap();
+        let parsed = Subscriptions::from_toml(&content).unwrap();
+        assert_eq!(parsed.feeds, subs.feeds);
+    }
+
+    #[test]
+    fn test_watch_rules() {
+        let parsed = Subscriptions::from_toml(
+            "[[feeds]]
+name = \"Show\"
+url = \"https://example.com/feed.rss\"
+latest = 3
+filter = \"^Interview\"
+
+[[feeds]]
+name = \"Other\"
+url = \"https://example.com/other.rss\"
+",
+        )
+        .unwrap();
+        let (show, other) = (&parsed.feeds[0], &parsed.feeds[1]);
+
+        assert_eq!(show.watch_latest(), 3);
+        assert!(
+            show.watch_filter()
+                .unwrap()
+                .title
+                .unwrap()
+                .is_match("Interview: Bob")
+        );
+        assert_eq!(other.watch_latest(), 1);
+        assert!(other.watch_filter().unwrap().is_empty());
+        // Unset rules stay out of the file
+        assert!(!toml::to_string(other).unwrap().contains("latest"));
+    }
+
+    #[test]
+    fn test_feed_overrides() {
+        let parsed = Subscriptions::from_toml(
+            "[[feeds]]
+name = \"Show\"
+url = \"https://example.com/feed.rss\"
+download_dir = \"~/Audiobooks\"
+filename_template = \"{number} {title}\"
+auth = \"me:secret\"
+
+[[feeds]]
+name = \"Other\"
+url = \"https://example.com/other.rss\"
+auth = \"me:secret\"
+bearer = \"token\"
+",
+        )
+        .unwrap();
+        let (show, other) = (&parsed.feeds[0], &parsed.feeds[1]);
+
+        assert_eq!(show.download_dir, Some(PathBuf::from("~/Audiobooks")));
+        assert_eq!(
+            show.credentials().unwrap(),
+            Some(Credentials::Basic {
+                username: "me".into(),
+                password: Some("secret".into()),
+            })
+        );
+        assert!(other.credentials().is_err());
+    }
+
+    #[test]
+    fn test_empty_file_has_no_feeds() {
+        let parsed = Subscriptions::from_toml("").unwrap();
+        assert!(parsed.feeds.is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::client::Http;
+use crate::feed::Episode;
+use crate::mp4::{self, IlstValue};
+use crate::retry::check_response;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Datelike, FixedOffset, Timelike};
+use id3::frame::{Comment, Picture, PictureType};
+use id3::{Tag, TagLike, Timestamp, Version};
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// Cover art image, JPEG or PNG
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artwork {
+    pub data: Vec<u8>,
+    pub png: bool,
+}
+
+impl Artwork {
+    /// Wrap downloaded image bytes, telling PNG from JPEG by their signature
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let png = data.starts_with(b"\x89PNG");
+        if !png && !data.starts_with(&[0xFF, 0xD8]) {
+            bail!("Artwork is not a JPEG or PNG image");
+        }
+        Ok(Self { data, png })
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        if self.png { "image/png" } else { "image/jpeg" }
+    }
+
+    /// File extension for saving the image (`jpg` or `png`)
+    pub fn extension(&self) -> &'static str {
+        if self.png { "png" } else { "jpg" }
+    }
+}
+
+pub(crate) async fn fetch_artwork(client: &Http, url: &str) -> Result<Artwork> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .context("Failed to fetch artwork")?;
+    let bytes = check_response(response)
+        .context("Failed to fetch artwork")?
+        .bytes()
+        .await
+        .context("Failed to read artwork")?;
+    Artwork::from_bytes(bytes.to_vec())
+}
+
+/// Write episode metadata, and `artwork` as the cover if given, into the
+/// audio file at `path`.
+/// Returns `false` if the file type can't be tagged (only mp3 and MP4 audio can).
+pub fn tag_file(
+    path: &Path,
+    episode: &Episode,
+    show: &str,
+    artwork: Option<&Artwork>,
+) -> Result<bool> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "mp3" => write_id3(path, episode, show, artwork)?,
+        "m4a" | "m4b" | "mp4" => mp4::write_tags(path, &mp4_items(episode, show, artwork))?,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn write_id3(path: &Path, episode: &Episode, show: &str, artwork: Option<&Artwork>) -> Result<()> {
+    let mut tag = id3::no_tag_ok(Tag::read_from_path(path))
+        .context("Failed to read ID3 tag")?
+        .unwrap_or_default();
+
+    tag.set_title(&episode.title);
+    tag.set_album(show);
+    tag.set_artist(show);
+    tag.set_album_artist(show);
+    tag.set_genre("Podcast");
+    if let Some(published) = episode.published {
+        tag.set_date_recorded(timestamp(published));
+        tag.set_date_released(timestamp(published));
+    }
+    if let Some(number) = episode.number {
+        tag.set_track(number);
+    }
+    if let Some(season) = episode.season {
+        tag.set_disc(season);
+    }
+    if let Some(description) = &episode.description {
+        tag.remove_comment(Some(""), None);
+        tag.add_frame(Comment {
+            lang: "eng".to_string(),
+            description: String::new(),
+            text: plain_text(description),
+        });
+    }
+
+    if let Some(artwork) = artwork {
+        tag.remove_picture_by_type(PictureType::CoverFront);
+        tag.add_frame(Picture {
+            mime_type: artwork.mime_type().to_string(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: artwork.data.clone(),
+        });
+    }
+
+    tag.write_to_path(path, Version::Id3v24)
+        .context("Failed to write ID3 tag")
+}
+
+fn timestamp(date: DateTime<FixedOffset>) -> Timestamp {
+    Timestamp {
+        year: date.year(),
+        month: Some(date.month() as u8),
+        day: Some(date.day() as u8),
+        hour: Some(date.hour() as u8),
+        minute: Some(date.minute() as u8),
+        second: Some(date.second() as u8),
+    }
+}
+
+fn mp4_items(
+    episode: &Episode,
+    show: &str,
+    artwork: Option<&Artwork>,
+) -> Vec<([u8; 4], IlstValue)> {
+    let text = |s: &str| IlstValue::Text(s.to_string());
+    let mut items = vec![
+        (*b"\xa9nam", text(&episode.title)),
+        (*b"\xa9alb", text(show)),
+        (*b"\xa9ART", text(show)),
+        (*b"aART", text(show)),
+        (*b"\xa9gen", text("Podcast")),
+        // Media kind "Podcast", so players file it with other episodes
+        (*b"stik", IlstValue::Byte(21)),
+        (*b"pcst", IlstValue::Byte(1)),
+    ];
+    if let Some(published) = episode.published {
+        items.push((*b"\xa9day", text(&published.to_rfc3339())));
+    }
+    if let Some(number) = episode.number {
+        items.push((*b"trkn", IlstValue::Track(number)));
+    }
+    if let Some(season) = episode.season {
+        items.push((*b"disk", IlstValue::Disc(season)));
+    }
+    if let Some(description) = &episode.description {
+        let description = plain_text(description);
+        items.push((*b"desc", text(&description)));
+        items.push((*b"ldes", IlstValue::Text(description)));
+    }
+    if let Some(artwork) = artwork {
+        items.push((*b"covr", IlstValue::Image(artwork.clone())));
+    }
+    items
+}
+
+/// Show notes without HTML markup, for tag fields that are displayed as plain text
+pub(crate) fn plain_text(html: &str) -> String {
+    let text = HTML_TAG.replace_all(html, "");
+    quick_xml::escape::unescape(&text)
+        .map(|t| t.into_owned())
+        .unwrap_or_else(|_| text.into_owned())
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episode() -> Episode {
+        Episode {
+            title: "Pilot".to_string(),
+            url: "https://example.com/1.mp3".to_string(),
+            published: DateTime::parse_from_rfc3339("2024-01-02T10:00:00Z").ok(),
+            description: Some("<p>Show &amp; tell</p>".to_string()),
+            number: Some(1),
+            season: Some(2),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_plain_text_strips_markup() {
+        assert_eq!(plain_text("<p>Show &amp; tell</p>\n"), "Show & tell");
+        assert_eq!(plain_text("Q&A <b>live</b>"), "Q&A live");
+    }
+
+    #[test]
+    fn test_tag_file_writes_id3() {
+        let path = std::env::temp_dir().join(format!("pdl-tag-{}.mp3", std::process::id()));
+        std::fs::write(&path, [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+
+        let artwork = Artwork::from_bytes(vec![0xFF, 0xD8, 0xFF, 0xE0]).unwrap();
+        assert!(tag_file(&path, &episode(), "My Show", Some(&artwork)).unwrap());
+        let tag = Tag::read_from_path(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tag.title(), Some("Pilot"));
+        assert_eq!(tag.album(), Some("My Show"));
+        assert_eq!(tag.track(), Some(1));
+        assert_eq!(tag.disc(), Some(2));
+        assert_eq!(tag.date_released().map(|d| d.year), Some(2024));
+        assert_eq!(
+            tag.comments().next().map(|c| c.text.as_str()),
+            Some("Show & tell")
+        );
+        let picture = tag.pictures().next().unwrap();
+        assert_eq!(picture.mime_type, "image/jpeg");
+        assert_eq!(picture.data, artwork.data);
+    }
+
+    #[test]
+    fn test_artwork_from_bytes_sniffs_format() {
+        assert!(Artwork::from_bytes(b"\x89PNG\r\n".to_vec()).unwrap().png);
+        assert_eq!(
+            Artwork::from_bytes(vec![0xFF, 0xD8, 0xFF])
+                .unwrap()
+                .extension(),
+            "jpg"
+        );
+        assert!(Artwork::from_bytes(b"<html>".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_tag_file_skips_unknown_formats() {
+        let path = Path::new("episode.ogg");
+        assert!(!tag_file(path, &episode(), "My Show", None).unwrap());
+    }
+}
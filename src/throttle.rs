@@ -0,0 +1,113 @@
+use anyhow::{Result, bail};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A download speed cap shared by every download using it, so parallel
+/// downloads together stay under the limit
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    bytes_per_sec: u64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes that may be read right away; negative while over the limit
+    available: f64,
+    refilled: Instant,
+}
+
+impl RateLimit {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            bucket: Arc::new(Mutex::new(Bucket {
+                available: 0.0,
+                refilled: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// Account for `bytes` just read, waiting as long as that puts the
+    /// downloads ahead of the limit
+    pub async fn consume(&self, bytes: usize) {
+        let wait = self.reserve(bytes, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Take `bytes` from the bucket at `now`, returning how long to pause
+    fn reserve(&self, bytes: usize, now: Instant) -> Duration {
+        let rate = self.bytes_per_sec as f64;
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+        // Idle time earns at most a second's worth of burst
+        bucket.available = (bucket.available + elapsed * rate).min(rate);
+        bucket.refilled = now;
+        bucket.available -= bytes as f64;
+        if bucket.available < 0.0 {
+            Duration::from_secs_f64(-bucket.available / rate)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// Parse a speed such as `500k`, `2M` or `1.5m` (bytes per second; k, m and
+/// g are multiples of 1024)
+pub fn parse_rate(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let lower = value.to_ascii_lowercase();
+    let number = lower.trim_end_matches(['b', '/', 's']);
+    let (digits, multiplier) = match number.chars().last() {
+        Some('k') => (&number[..number.len() - 1], 1024.0),
+        Some('m') => (&number[..number.len() - 1], 1024.0 * 1024.0),
+        Some('g') => (&number[..number.len() - 1], 1024.0 * 1024.0 * 1024.0),
+        _ => (number, 1.0),
+    };
+    let bytes = match digits.parse::<f64>() {
+        Ok(n) if n.is_finite() && n > 0.0 => n * multiplier,
+        _ => bail!("Invalid rate \"{}\" (expected e.g. 500k or 2M)", value),
+    };
+    if bytes < 1.0 {
+        bail!("Rate \"{}\" is below one byte per second", value);
+    }
+    Ok(bytes as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("500k").unwrap(), 500 * 1024);
+        assert_eq!(parse_rate("2M").unwrap(), 2 * 1024 * 1024);
+        assert_eq!(parse_rate("1.5m").unwrap(), 1536 * 1024);
+        assert_eq!(parse_rate("100KB/s").unwrap(), 100 * 1024);
+        assert_eq!(parse_rate("4096").unwrap(), 4096);
+        assert!(parse_rate("fast").is_err());
+        assert!(parse_rate("0k").is_err());
+        assert!(parse_rate("-1k").is_err());
+    }
+
+    #[test]
+    fn test_reserve_shares_the_limit() {
+        let limit = RateLimit::new(1000);
+        let start = limit.bucket.lock().unwrap().refilled;
+        // Two downloads reading at the same moment queue behind each other
+        let clone = limit.clone();
+        assert_eq!(limit.reserve(500, start), Duration::from_millis(500));
+        assert_eq!(clone.reserve(500, start), Duration::from_secs(1));
+
+        // After waiting out the debt, a second's worth is free again
+        let later = start + Duration::from_secs(3);
+        assert_eq!(limit.reserve(1000, later), Duration::ZERO);
+        assert_eq!(limit.reserve(100, later), Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::download::{self, DownloadOutcome, IfExists};
+use crate::feed::Episode;
+use crate::interrupt::{self, Interrupted};
+use crate::naming::FilenameTemplate;
+use crate::player::on_path;
+use anyhow::{Context, Result, bail};
+use indicatif::ProgressBar;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::fs;
+use tokio::process::Command;
+
+/// Placeholders a torrent command may use, and what they stand for
+const PLACEHOLDERS: [&str; 2] = ["{torrent}", "{dir}"];
+
+/// Used when no `torrent_cmd` is configured, if it's installed
+const DEFAULT_COMMAND: &str =
+    "aria2c --seed-time=0 --console-log-level=error --summary-interval=0 --download-result=hide";
+
+/// A BitTorrent client that fetches episodes offered as torrents, e.g.
+/// `transmission-cli -w {dir} {torrent}`. `{torrent}` is the magnet link or
+/// `.torrent` URL (appended if no argument mentions it) and `{dir}` the
+/// folder to save into, which is also the command's working directory. The
+/// command must exit once the download is complete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TorrentClient {
+    program: String,
+    args: Vec<String>,
+}
+
+impl FromStr for TorrentClient {
+    type Err = anyhow::Error;
+
+    fn from_str(command: &str) -> Result<Self> {
+        let mut words = command.split_whitespace().map(str::to_string);
+        let Some(program) = words.next() else {
+            bail!("The torrent command is empty");
+        };
+        Ok(Self {
+            program,
+            args: words.collect(),
+        })
+    }
+}
+
+impl TorrentClient {
+    /// The configured torrent command, else aria2c if it's installed
+    pub fn find(command: Option<&str>) -> Result<Self> {
+        if let Some(command) = command {
+            return command.parse();
+        }
+        if on_path("aria2c").is_none() {
+            bail!(
+                "No torrent client found; install aria2c or set one with `pdl config set torrent_cmd \"transmission-cli -w {{dir}}\"`"
+            );
+        }
+        DEFAULT_COMMAND.parse()
+    }
+
+    /// The arguments for fetching `torrent` into `dir`, with placeholders filled in
+    fn args_for(&self, torrent: &str, dir: &Path) -> Vec<String> {
+        let dir = dir.to_string_lossy();
+        let values = [torrent, dir.as_ref()];
+        let mut args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| {
+                PLACEHOLDERS
+                    .iter()
+                    .zip(values)
+                    .fold(arg.clone(), |arg, (placeholder, value)| {
+                        arg.replace(placeholder, value)
+                    })
+            })
+            .collect();
+        if !self.args.iter().any(|arg| arg.contains(PLACEHOLDERS[0])) {
+            args.push(torrent.to_string());
+        }
+        args
+    }
+
+    /// Download `episode`'s torrent into `dir`, named like any other
+    /// download. The client works in a hidden folder next to where the file
+    /// goes, which is kept if it's interrupted so the client can pick up
+    /// from there next time; the largest file it saves is the episode.
+    pub async fn download(
+        &self,
+        episode: &Episode,
+        dir: &Path,
+        template: &FilenameTemplate,
+        if_exists: IfExists,
+        pb: &ProgressBar,
+    ) -> Result<DownloadOutcome> {
+        let Some(torrent) = &episode.torrent else {
+            bail!("\"{}\" isn't offered as a torrent", episode.title);
+        };
+        if interrupt::is_interrupted() {
+            return Err(Interrupted.into());
+        }
+        let planned = download::planned_path(episode, dir, template);
+        if if_exists == IfExists::Skip && fs::try_exists(&planned).await.unwrap_or(false) {
+            return Ok(DownloadOutcome::AlreadyExists(planned));
+        }
+
+        let filename = template.render(episode);
+        let work = dir.join(format!(".{}.torrent", filename));
+        fs::create_dir_all(&work)
+            .await
+            .context("Failed to create torrent download directory")?;
+        pb.set_message("via BitTorrent");
+        pb.enable_steady_tick(Duration::from_millis(120));
+
+        let run = Command::new(&self.program)
+            .args(self.args_for(torrent, &work))
+            .current_dir(&work)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::select! {
+            output = run => output.with_context(|| format!("Failed to run torrent client {:?}", self.program))?,
+            _ = interrupt::interrupted() => {
+                pb.abandon();
+                return Err(Interrupted.into());
+            }
+        };
+        if !output.status.success() {
+            let message = [output.stderr, output.stdout]
+                .iter()
+                .map(|out| String::from_utf8_lossy(out).trim().to_string())
+                .find(|out| !out.is_empty())
+                .unwrap_or_default();
+            bail!(
+                "Torrent client {:?} exited with {}: {}",
+                self.program,
+                output.status,
+                message
+            );
+        }
+
+        let Some(media) = largest_file(&work)? else {
+            bail!("The torrent client saved nothing for \"{}\"", episode.title);
+        };
+        let extension = media
+            .extension()
+            .map(|e| e.to_string_lossy().into_owned())
+            .unwrap_or_else(|| download::DEFAULT_EXTENSION.to_string());
+        let path = dir.join(format!("{}.{}", filename, extension));
+        let path = match if_exists {
+            IfExists::Skip if fs::try_exists(&path).await.unwrap_or(false) => {
+                let _ = fs::remove_dir_all(&work).await;
+                return Ok(DownloadOutcome::AlreadyExists(path));
+            }
+            IfExists::Rename => download::free_path(path).await,
+            _ => path,
+        };
+        fs::rename(&media, &path)
+            .await
+            .context("Failed to move completed download into place")?;
+        let _ = fs::remove_dir_all(&work).await;
+        // For the size and speed reported afterwards
+        let size = fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+        pb.set_length(size);
+        pb.set_position(size);
+        pb.finish_with_message("Download complete");
+        Ok(DownloadOutcome::Downloaded(path))
+    }
+}
+
+/// The biggest file anywhere under `dir`, skipping clients' own bookkeeping
+fn largest_file(dir: &Path) -> Result<Option<PathBuf>> {
+    let mut largest: Option<(u64, PathBuf)> = None;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            std::fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(path);
+            } else if !path
+                .extension()
+                .is_some_and(|e| e == "aria2" || e == "torrent" || e == "part")
+                && largest
+                    .as_ref()
+                    .is_none_or(|(size, _)| metadata.len() > *size)
+            {
+                largest = Some((metadata.len(), path));
+            }
+        }
+    }
+    Ok(largest.map(|(_, path)| path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_torrent_client_args() {
+        let client: TorrentClient = "transmission-cli -w {dir}".parse().unwrap();
+        assert_eq!(
+            client.args_for("magnet:?xt=urn:btih:123", Path::new("/tmp/x")),
+            ["-w", "/tmp/x", "magnet:?xt=urn:btih:123"]
+        );
+
+        let client: TorrentClient = "my-bt --get {torrent} --to {dir}".parse().unwrap();
+        assert_eq!(
+            client.args_for("https://example.com/1.torrent", Path::new("d")),
+            ["--get", "https://example.com/1.torrent", "--to", "d"]
+        );
+
+        assert!(" ".parse::<TorrentClient>().is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use anyhow::{Context, Result, bail};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::str::FromStr;
+use tokio::fs;
+use tokio::process::Command;
+
+/// Audio formats pdl can transcode to with ffmpeg
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Opus,
+    Mp3,
+    Aac,
+    Vorbis,
+    Flac,
+}
+
+impl Codec {
+    const ALL: [Codec; 5] = [
+        Codec::Opus,
+        Codec::Mp3,
+        Codec::Aac,
+        Codec::Vorbis,
+        Codec::Flac,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Codec::Opus => "opus",
+            Codec::Mp3 => "mp3",
+            Codec::Aac => "aac",
+            Codec::Vorbis => "vorbis",
+            Codec::Flac => "flac",
+        }
+    }
+
+    /// ffmpeg encoder
+    fn encoder(self) -> &'static str {
+        match self {
+            Codec::Opus => "libopus",
+            Codec::Mp3 => "libmp3lame",
+            Codec::Aac => "aac",
+            Codec::Vorbis => "libvorbis",
+            Codec::Flac => "flac",
+        }
+    }
+
+    /// ffmpeg container format, given explicitly since the output is
+    /// written under a temporary name
+    fn container(self) -> &'static str {
+        match self {
+            Codec::Opus => "opus",
+            Codec::Mp3 => "mp3",
+            Codec::Aac => "ipod",
+            Codec::Vorbis => "ogg",
+            Codec::Flac => "flac",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Codec::Opus => "opus",
+            Codec::Mp3 => "mp3",
+            Codec::Aac => "m4a",
+            Codec::Vorbis => "ogg",
+            Codec::Flac => "flac",
+        }
+    }
+
+    /// Bitrate used when none is given; plenty for speech. Lossless FLAC
+    /// has none.
+    fn default_bitrate(self) -> Option<&'static str> {
+        match self {
+            Codec::Opus => Some("64k"),
+            Codec::Mp3 => Some("128k"),
+            Codec::Aac | Codec::Vorbis => Some("96k"),
+            Codec::Flac => None,
+        }
+    }
+}
+
+/// What to transcode downloads to: a codec and optional bitrate, written
+/// `opus:64k`, `mp3:96k`, `aac` or `flac`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcode {
+    codec: Codec,
+    bitrate: Option<String>,
+}
+
+impl FromStr for Transcode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, bitrate) = match s.split_once(':') {
+            Some((name, bitrate)) => (name, Some(bitrate)),
+            None => (s, None),
+        };
+        let Some(codec) = Codec::ALL
+            .into_iter()
+            .find(|codec| codec.name().eq_ignore_ascii_case(name.trim()))
+        else {
+            let names: Vec<&str> = Codec::ALL.iter().map(|c| c.name()).collect();
+            bail!(
+                "Unknown codec {:?} (expected one of: {})",
+                name,
+                names.join(", ")
+            );
+        };
+        let bitrate = match bitrate.map(str::trim) {
+            Some(bitrate) => {
+                let digits = bitrate.strip_suffix(['k', 'K']).unwrap_or(bitrate);
+                if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                    bail!("Invalid bitrate {:?} (expected e.g. 64k)", bitrate);
+                }
+                if codec == Codec::Flac {
+                    bail!("FLAC is lossless and takes no bitrate");
+                }
+                Some(bitrate.to_lowercase())
+            }
+            None => codec.default_bitrate().map(str::to_string),
+        };
+        Ok(Self { codec, bitrate })
+    }
+}
+
+impl fmt::Display for Transcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.bitrate {
+            Some(bitrate) => write!(f, "{}:{}", self.codec.name(), bitrate),
+            None => write!(f, "{}", self.codec.name()),
+        }
+    }
+}
+
+/// Fail early, with a hint, if ffmpeg isn't installed; `option` is what
+/// needs it, e.g. `--transcode`
+pub async fn check_ffmpeg(option: &str) -> Result<()> {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .with_context(|| {
+            format!(
+                "ffmpeg is needed for {}; install it and make sure it is on PATH",
+                option
+            )
+        })?;
+    Ok(())
+}
+
+impl Transcode {
+    /// Where the transcoded copy of `input` goes: next to it with the new
+    /// extension, or with the bitrate added if that would be `input` itself
+    pub fn output_path(&self, input: &Path) -> PathBuf {
+        let output = input.with_extension(self.codec.extension());
+        if output != input {
+            return output;
+        }
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let suffix = self.bitrate.as_deref().unwrap_or("transcoded");
+        input.with_file_name(format!("{}.{}.{}", stem, suffix, self.codec.extension()))
+    }
+
+    fn ffmpeg_args(&self, input: &Path, output: &Path) -> Vec<std::ffi::OsString> {
+        let mut args: Vec<std::ffi::OsString> = ["-hide_banner", "-loglevel", "error", "-y", "-i"]
+            .map(Into::into)
+            .into();
+        args.push(input.into());
+        // Keep the tags, drop embedded cover art that most audio containers can't hold
+        args.extend(["-vn", "-map_metadata", "0", "-c:a", self.codec.encoder()].map(Into::into));
+        if let Some(bitrate) = &self.bitrate {
+            args.extend(["-b:a".into(), bitrate.into()]);
+        }
+        args.extend(["-f", self.codec.container()].map(Into::into));
+        args.push(output.into());
+        args
+    }
+
+    /// Transcode `input` with ffmpeg, returning the new file. The original
+    /// is deleted unless `keep_original` is set; if transcoding fails it is
+    /// left untouched.
+    pub async fn run(&self, input: &Path, keep_original: bool) -> Result<PathBuf> {
+        let output = self.output_path(input);
+        let mut temp = output.clone().into_os_string();
+        temp.push(".transcoding");
+        let temp = PathBuf::from(temp);
+
+        let result = Command::new("ffmpeg")
+            .args(self.ffmpeg_args(input, &temp))
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .context("Failed to run ffmpeg")?;
+        if !result.status.success() {
+            let _ = fs::remove_file(&temp).await;
+            bail!(
+                "ffmpeg failed to transcode {} to {}: {}",
+                input.display(),
+                self,
+                String::from_utf8_lossy(&result.stderr).trim()
+            );
+        }
+        fs::rename(&temp, &output)
+            .await
+            .with_context(|| format!("Failed to save {}", output.display()))?;
+        if !keep_original {
+            fs::remove_file(input)
+                .await
+                .with_context(|| format!("Failed to remove {}", input.display()))?;
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_output_path() {
+        let opus: Transcode = "opus:64k".parse().unwrap();
+        assert_eq!(opus.to_string(), "opus:64k");
+        assert_eq!(
+            opus.output_path(Path::new("Show/Ep 1.mp3")),
+            Path::new("Show/Ep 1.opus")
+        );
+
+        let mp3: Transcode = "MP3".parse().unwrap();
+        assert_eq!(mp3.to_string(), "mp3:128k");
+        assert_eq!(
+            mp3.output_path(Path::new("Show/Ep 1.mp3")),
+            Path::new("Show/Ep 1.128k.mp3")
+        );
+
+        assert_eq!("flac".parse::<Transcode>().unwrap().to_string(), "flac");
+        assert!("flac:500k".parse::<Transcode>().is_err());
+        assert!("opus:fast".parse::<Transcode>().is_err());
+        assert!("wav".parse::<Transcode>().is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::player::on_path;
+use crate::transcode::check_ffmpeg;
+use anyhow::{Context, Result, bail};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::fs;
+use tokio::process::Command;
+
+/// whisper.cpp command line programs tried, in order, when none is configured
+const KNOWN_PROGRAMS: [&str; 3] = ["whisper-cli", "whisper-cpp", "whisper"];
+
+/// Transcript formats whisper.cpp can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TranscriptFormat {
+    Srt,
+    Vtt,
+    Txt,
+}
+
+impl TranscriptFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+            Self::Txt => "txt",
+        }
+    }
+
+    /// whisper.cpp flag asking for this format
+    fn flag(self) -> &'static str {
+        match self {
+            Self::Srt => "-osrt",
+            Self::Vtt => "-ovtt",
+            Self::Txt => "-otxt",
+        }
+    }
+}
+
+/// Where the `format` transcript of `audio` goes: next to it, like the
+/// transcripts `--extras` fetches (`Ep 1.srt`)
+pub fn transcript_path(audio: &Path, format: TranscriptFormat) -> PathBuf {
+    audio.with_extension(format.extension())
+}
+
+/// A local whisper.cpp install: its command line program and a ggml model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Whisper {
+    program: PathBuf,
+    model: PathBuf,
+}
+
+impl Whisper {
+    /// The configured program (else the first whisper.cpp program on
+    /// `PATH`) with `model`
+    pub fn find(program: Option<&str>, model: Option<&Path>) -> Result<Self> {
+        let Some(model) = model else {
+            bail!(
+                "No whisper model set; download a ggml model (e.g. ggml-base.en.bin) and run `pdl config set whisper_model <path>`"
+            );
+        };
+        if !model.is_file() {
+            bail!("Whisper model not found: {}", model.display());
+        }
+        let program = match program {
+            Some(program) => PathBuf::from(program),
+            None => KNOWN_PROGRAMS
+                .iter()
+                .find_map(|name| on_path(name))
+                .with_context(|| {
+                    format!(
+                        "whisper.cpp not found (tried {}); install it or set `whisper_cmd`",
+                        KNOWN_PROGRAMS.join(", ")
+                    )
+                })?,
+        };
+        Ok(Self {
+            program,
+            model: model.to_path_buf(),
+        })
+    }
+
+    fn args(
+        &self,
+        wav: &Path,
+        output_base: &Path,
+        language: &str,
+        formats: &[TranscriptFormat],
+    ) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![
+            "-m".into(),
+            self.model.clone().into(),
+            "-f".into(),
+            wav.into(),
+            "-l".into(),
+            language.into(),
+            "-of".into(),
+            output_base.into(),
+        ];
+        args.extend(formats.iter().map(|format| format.flag().into()));
+        args
+    }
+
+    /// Transcribe `audio` into the given formats next to it, in `language`
+    /// (or `auto`). The audio is converted to the 16 kHz WAV whisper.cpp
+    /// reads with ffmpeg first. Returns the transcripts written.
+    pub async fn transcribe(
+        &self,
+        audio: &Path,
+        language: &str,
+        formats: &[TranscriptFormat],
+    ) -> Result<Vec<PathBuf>> {
+        check_ffmpeg("transcribing").await?;
+        let output_base = audio.with_extension("");
+        let mut wav = output_base.clone().into_os_string();
+        wav.push(".transcribing.wav");
+        let wav = PathBuf::from(wav);
+
+        let result = self.run(audio, &wav, &output_base, language, formats).await;
+        let _ = fs::remove_file(&wav).await;
+        result?;
+        Ok(formats
+            .iter()
+            .map(|&format| transcript_path(audio, format))
+            .collect())
+    }
+
+    async fn run(
+        &self,
+        audio: &Path,
+        wav: &Path,
+        output_base: &Path,
+        language: &str,
+        formats: &[TranscriptFormat],
+    ) -> Result<()> {
+        let converted = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+            .arg(audio)
+            .args(["-vn", "-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le"])
+            .arg(wav)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .context("Failed to run ffmpeg")?;
+        if !converted.status.success() {
+            bail!(
+                "ffmpeg failed to convert {}: {}",
+                audio.display(),
+                String::from_utf8_lossy(&converted.stderr).trim()
+            );
+        }
+
+        // whisper.cpp prints its progress on stderr; let it show
+        let status = Command::new(&self.program)
+            .args(self.args(wav, output_base, language, formats))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .status()
+            .await
+            .with_context(|| format!("Failed to run {}", self.program.display()))?;
+        if !status.success() {
+            bail!("{} exited with {}", self.program.display(), status);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whisper_args() {
+        let whisper = Whisper {
+            program: PathBuf::from("whisper-cli"),
+            model: PathBuf::from("/models/ggml-base.en.bin"),
+        };
+        let args = whisper.args(
+            Path::new("Show/Ep 1.transcribing.wav"),
+            Path::new("Show/Ep 1"),
+            "auto",
+            &[TranscriptFormat::Srt, TranscriptFormat::Txt],
+        );
+        assert_eq!(
+            args,
+            [
+                "-m",
+                "/models/ggml-base.en.bin",
+                "-f",
+                "Show/Ep 1.transcribing.wav",
+                "-l",
+                "auto",
+                "-of",
+                "Show/Ep 1",
+                "-osrt",
+                "-otxt"
+            ]
+            .map(OsString::from)
+        );
+        assert_eq!(
+            transcript_path(Path::new("Show/Ep 1.mp3"), TranscriptFormat::Vtt),
+            Path::new("Show/Ep 1.vtt")
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use crate::player::on_path;
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::Stdio;
+use std::str::FromStr;
+use tokio::process::Command;
+
+/// Where finished downloads are copied off the machine: `s3://bucket/prefix`
+/// with the AWS CLI, or an rclone remote such as `b2:podcasts` or
+/// `gdrive:Archive/Podcasts` with rclone. Each file lands under it in a
+/// folder named like the one it was saved in, e.g.
+/// `s3://bucket/prefix/My Show/Episode 1.mp3`, using whatever credentials
+/// the tool is configured with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadTarget {
+    S3(String),
+    Rclone(String),
+}
+
+impl FromStr for UploadTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(target: &str) -> Result<Self> {
+        let target = target.trim();
+        if let Some(path) = target.strip_prefix("s3://") {
+            let path = path.trim_end_matches('/');
+            if path.is_empty() {
+                bail!("The upload target {:?} has no bucket", target);
+            }
+            return Ok(Self::S3(format!("s3://{}", path)));
+        }
+        let target = target.trim_end_matches('/');
+        match target.split_once(':') {
+            // A remote's name can't contain `/` like a local path, nor be a
+            // drive letter
+            Some((remote, _)) if remote.len() > 1 && !remote.contains(['/', '\\']) => {
+                Ok(Self::Rclone(target.to_string()))
+            }
+            _ => bail!(
+                "Unknown upload target {:?}; use s3://bucket/prefix or an rclone remote like remote:path",
+                target
+            ),
+        }
+    }
+}
+
+impl UploadTarget {
+    /// The program that does the uploading
+    fn program(&self) -> &'static str {
+        match self {
+            Self::S3(_) => "aws",
+            Self::Rclone(_) => "rclone",
+        }
+    }
+
+    /// Fail unless the program for this target is installed
+    pub fn check(&self) -> Result<()> {
+        if on_path(self.program()).is_none() {
+            bail!(
+                "{} is needed to upload to {}; install it and make sure it is on PATH",
+                self.program(),
+                self.location()
+            );
+        }
+        Ok(())
+    }
+
+    fn location(&self) -> &str {
+        match self {
+            Self::S3(location) | Self::Rclone(location) => location,
+        }
+    }
+
+    /// Where `file` goes: under the target, in a folder named like its own
+    pub fn remote_path(&self, file: &Path) -> String {
+        let name = |path: Option<&Path>| {
+            path.and_then(Path::file_name)
+                .map(|name| name.to_string_lossy().into_owned())
+        };
+        let base = self.location();
+        // rclone remotes with no path (`remote:`) need no separator
+        let separator = if base.ends_with(':') { "" } else { "/" };
+        match (name(file.parent()), name(Some(file))) {
+            (Some(folder), Some(file)) => format!("{}{}{}/{}", base, separator, folder, file),
+            (_, file) => format!("{}{}{}", base, separator, file.unwrap_or_default()),
+        }
+    }
+
+    /// Copy `file` to the target, replacing any copy already there, and
+    /// return where it went. Fails with the tool's error output if it does.
+    pub async fn upload(&self, file: &Path) -> Result<String> {
+        let remote = self.remote_path(file);
+        let mut command = Command::new(self.program());
+        match self {
+            Self::S3(_) => command.args(["s3", "cp", "--only-show-errors"]),
+            Self::Rclone(_) => command.args(["copyto", "--quiet"]),
+        };
+        let output = command
+            .arg(file)
+            .arg(&remote)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .with_context(|| format!("Failed to run {}", self.program()))?;
+        if !output.status.success() {
+            bail!(
+                "Failed to upload {} to {}: {}",
+                file.display(),
+                remote,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(remote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_remote_path() {
+        let file = Path::new("/home/me/Podcasts/My Show/Ep 1.mp3");
+        let s3: UploadTarget = "s3://bucket/podcasts/".parse().unwrap();
+        assert_eq!(s3, UploadTarget::S3("s3://bucket/podcasts".to_string()));
+        assert_eq!(
+            s3.remote_path(file),
+            "s3://bucket/podcasts/My Show/Ep 1.mp3"
+        );
+
+        let rclone: UploadTarget = "b2:".parse().unwrap();
+        assert_eq!(rclone, UploadTarget::Rclone("b2:".to_string()));
+        assert_eq!(rclone.remote_path(file), "b2:My Show/Ep 1.mp3");
+        assert_eq!(
+            "gdrive:Archive"
+                .parse::<UploadTarget>()
+                .unwrap()
+                .remote_path(file),
+            "gdrive:Archive/My Show/Ep 1.mp3"
+        );
+
+        assert!("s3://".parse::<UploadTarget>().is_err());
+        assert!("/mnt/backup".parse::<UploadTarget>().is_err());
+        assert!("./a:b".parse::<UploadTarget>().is_err());
+        assert!("C:\\Backup".parse::<UploadTarget>().is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+
+/// Shape of the JSON body posted to a webhook
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// The [`WebhookEvent`] itself
+    #[default]
+    Json,
+    /// A Slack incoming webhook message (`{"text": ...}`)
+    Slack,
+    /// A Discord webhook message (`{"content": ...}`)
+    Discord,
+}
+
+/// Where to post download events, and in what shape
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub url: String,
+    pub format: WebhookFormat,
+}
+
+/// What happened to an episode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEventKind {
+    Downloaded,
+    Failed,
+}
+
+/// An episode that was downloaded, or failed to, as posted to a webhook
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WebhookEvent {
+    pub event: WebhookEventKind,
+    pub show: String,
+    pub feed_url: String,
+    pub title: String,
+    pub guid: Option<String>,
+    /// Where the episode was saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Why the download failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl WebhookEvent {
+    /// One line describing the event, for chat messages
+    pub fn message(&self) -> String {
+        match self.event {
+            WebhookEventKind::Downloaded => {
+                format!("New episode of {}: {}", self.show, self.title)
+            }
+            WebhookEventKind::Failed => format!(
+                "Failed to download {} ({}): {}",
+                self.title,
+                self.show,
+                self.error.as_deref().unwrap_or("unknown error")
+            ),
+        }
+    }
+}
+
+impl Webhook {
+    /// The JSON body to post for `event`
+    pub fn payload(&self, event: &WebhookEvent) -> Value {
+        match self.format {
+            WebhookFormat::Json => json!(event),
+            WebhookFormat::Slack => json!({ "text": event.message() }),
+            WebhookFormat::Discord => json!({ "content": event.message() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payloads() {
+        let event = WebhookEvent {
+            event: WebhookEventKind::Downloaded,
+            show: "My Show".to_string(),
+            feed_url: "https://example.com/feed.rss".to_string(),
+            title: "Episode 1".to_string(),
+            guid: Some("ep-1".to_string()),
+            path: Some(PathBuf::from("/podcasts/My Show/Episode 1.mp3")),
+            error: None,
+        };
+        let webhook = |format| Webhook {
+            url: "https://hooks.example.com/x".to_string(),
+            format,
+        };
+
+        let body = webhook(WebhookFormat::Json).payload(&event);
+        assert_eq!(body["event"], "downloaded");
+        assert_eq!(body["guid"], "ep-1");
+        assert!(body.get("error").is_none());
+        assert_eq!(
+            webhook(WebhookFormat::Slack).payload(&event),
+            json!({ "text": "New episode of My Show: Episode 1" })
+        );
+
+        let failed = WebhookEvent {
+            event: WebhookEventKind::Failed,
+            path: None,
+            error: Some("HTTP 404".to_string()),
+            ..event
+        };
+        assert_eq!(
+            webhook(WebhookFormat::Discord).payload(&failed),
+            json!({ "content": "Failed to download Episode 1 (My Show): HTTP 404" })
+        );
+    }
+}